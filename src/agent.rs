@@ -0,0 +1,570 @@
+use crate::bagit::{
+    bag_directory, validate_bag, BagReport, ChecksumAlgorithm, Duplicates, Finding, FindingKind,
+    Mismatch, Progress, SampleSummary, ValidationMode, ValidationReport, RULE_BAG_DECLARATION,
+    RULE_BAG_METADATA, RULE_COMPLETENESS, RULE_FETCH, RULE_PAYLOAD_MANIFEST, RULE_PROFILE,
+    RULE_SERIALIZATION, RULE_SIGNATURE, RULE_TAG_MANIFEST,
+};
+use crate::settings::Settings;
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::PathBuf;
+use std::sync::mpsc::{channel, Sender};
+use std::thread;
+use std::time::Duration;
+
+/// Port the agent listens on unless told otherwise.
+pub const DEFAULT_AGENT_ADDRESS: &str = "127.0.0.1:7878";
+
+/// Longest line the agent reads from a client, so one that never ends can't fill
+/// its memory.
+const MAX_LINE: u64 = 64 * 1024;
+
+/// How long the agent waits for a client that has stopped sending.
+const READ_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// Work the GUI can hand to an agent. Paths are as the agent's machine sees them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RemoteJob {
+    Bag(String),
+    Validate(String),
+}
+
+// The protocol is one tab-separated record per line. The client sends `HELLO <token>`
+// and one job line; the agent answers with the job's progress, ending in `DONE`,
+// `VALIDATED` or `FAILED`. Tabs, newlines and backslashes in fields are escaped.
+
+fn escape(field: &str) -> String {
+    field
+        .replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+}
+
+fn unescape(field: &str) -> String {
+    let mut out = String::new();
+    let mut chars = field.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('t') => out.push('\t'),
+            Some('n') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out
+}
+
+fn record(fields: &[&str]) -> String {
+    fields
+        .iter()
+        .map(|f| escape(f))
+        .collect::<Vec<_>>()
+        .join("\t")
+}
+
+fn fields(line: &str) -> Vec<String> {
+    line.trim_end_matches(['\r', '\n'])
+        .split('\t')
+        .map(unescape)
+        .collect()
+}
+
+/// Reads one line of at most `limit` bytes; a longer one is an `InvalidData` error
+/// rather than being read whole.
+pub(crate) fn read_line_limited(reader: &mut impl BufRead, limit: u64) -> io::Result<String> {
+    let mut line = String::new();
+    reader.by_ref().take(limit).read_line(&mut line)?;
+    if line.len() as u64 == limit && !line.ends_with('\n') {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            "the line is too long",
+        ));
+    }
+    Ok(line)
+}
+
+/// Finding rules travel as text; this maps them back to baggie's own.
+fn rule(text: &str) -> &'static str {
+    [
+        RULE_BAG_DECLARATION,
+        RULE_PAYLOAD_MANIFEST,
+        RULE_TAG_MANIFEST,
+        RULE_BAG_METADATA,
+        RULE_FETCH,
+        RULE_PROFILE,
+        RULE_COMPLETENESS,
+        RULE_SERIALIZATION,
+        RULE_SIGNATURE,
+    ]
+    .into_iter()
+    .find(|r| *r == text)
+    .unwrap_or("reported by the agent")
+}
+
+fn encode(progress: &Progress) -> Vec<String> {
+    match progress {
+        Progress::Scanning { found } => vec![record(&["SCANNING", &found.to_string()])],
+        Progress::Started { total_files } => vec![record(&["STARTED", &total_files.to_string()])],
+        Progress::Moving { current, filename } => {
+            vec![record(&["MOVING", &current.to_string(), filename])]
+        }
+        Progress::Copying { current, filename } => {
+            vec![record(&["COPYING", &current.to_string(), filename])]
+        }
+        Progress::Downloading { current, filename } => {
+            vec![record(&["DOWNLOADING", &current.to_string(), filename])]
+        }
+        Progress::Checksumming { current, filename } => {
+            vec![record(&["CHECKSUMMING", &current.to_string(), filename])]
+        }
+        Progress::Verifying { current, filename } => {
+            vec![record(&["VERIFYING", &current.to_string(), filename])]
+        }
+        Progress::FileChecked { filename, passed } => {
+            let outcome = if *passed { "pass" } else { "fail" };
+            vec![record(&["CHECKED", filename, outcome])]
+        }
+        Progress::Done { path, report } => {
+            let mut lines = Vec::new();
+            for skipped in &report.skipped {
+                lines.push(record(&["SKIPPED", &skipped.to_string_lossy()]));
+            }
+            for vanished in &report.vanished {
+                lines.push(record(&["VANISHED", &vanished.to_string_lossy()]));
+            }
+            for warning in &report.warnings {
+                lines.push(record(&["WARNING", warning]));
+            }
+            for duplicates in &report.duplicates {
+                let (size, copies) = (duplicates.size.to_string(), duplicates.copies.to_string());
+                let mut fields = vec!["DUPLICATES", &size, &copies];
+                fields.extend(duplicates.files.iter().map(String::as_str));
+                lines.push(record(&fields));
+            }
+            lines.push(record(&["DONE", &path.to_string_lossy()]));
+            lines
+        }
+        Progress::Validated { report } => {
+            let mut lines = vec![record(&["FILES", &report.files_checked.to_string()])];
+            for (tag, findings) in [("ERROR", &report.errors), ("NOTE", &report.warnings)] {
+                for finding in findings {
+                    let file = finding.file.as_deref().unwrap_or_default();
+                    let kind = finding.kind.name();
+                    lines.push(record(&[tag, finding.rule, &finding.message, kind, file]));
+                }
+            }
+            for m in &report.mismatches {
+                lines.push(record(&[
+                    "MISMATCH",
+                    &m.file,
+                    &m.expected,
+                    &m.actual,
+                    m.algorithm.name(),
+                ]));
+            }
+            if let Some(sample) = &report.sample {
+                lines.push(record(&[
+                    "SAMPLE",
+                    &sample.percent.to_string(),
+                    &sample.seed.to_string(),
+                    &sample.hashed.to_string(),
+                    &sample.population.to_string(),
+                    &sample.failed.to_string(),
+                ]));
+            }
+            if let Some(signer) = &report.signed_by {
+                lines.push(record(&["SIGNED", signer]));
+            }
+            let mode = match report.mode {
+                ValidationMode::Strict => "strict",
+                ValidationMode::Lenient => "lenient",
+            };
+            lines.push(record(&["VALIDATED", mode, &report.path.to_string_lossy()]));
+            lines
+        }
+        Progress::Error { message } => vec![record(&["FAILED", message])],
+        // Comparing and serializing bags aren't remote jobs
+        Progress::Compared { .. } | Progress::Serialized { .. } | Progress::Reassembled { .. } => {
+            Vec::new()
+        }
+    }
+}
+
+/// Rebuilds progress from the agent's lines; reports arrive a piece at a time.
+#[derive(Default)]
+struct Decoder {
+    report: BagReport,
+    validation: ValidationReport,
+}
+
+impl Decoder {
+    fn decode(&mut self, line: &str) -> Option<Progress> {
+        let fields = fields(line);
+        let field = |i: usize| fields.get(i).cloned().unwrap_or_default();
+        let number = |i: usize| field(i).parse().unwrap_or(0);
+        let malformed = || {
+            Some(Progress::Error {
+                message: format!("The agent sent a line baggie can't read: {}", line.trim()),
+            })
+        };
+        let finding = || {
+            let kind = FindingKind::parse(&field(3)).filter(|_| fields.len() == 5)?;
+            let mut finding = Finding::new(rule(&field(1)), field(2));
+            finding.kind = kind;
+            finding.file = Some(field(4)).filter(|f| !f.is_empty());
+            Some(finding)
+        };
+        match field(0).as_str() {
+            "SCANNING" => Some(Progress::Scanning { found: number(1) }),
+            "STARTED" => Some(Progress::Started {
+                total_files: number(1),
+            }),
+            "MOVING" => Some(Progress::Moving {
+                current: number(1),
+                filename: field(2),
+            }),
+            "COPYING" => Some(Progress::Copying {
+                current: number(1),
+                filename: field(2),
+            }),
+            "DOWNLOADING" => Some(Progress::Downloading {
+                current: number(1),
+                filename: field(2),
+            }),
+            "CHECKSUMMING" => Some(Progress::Checksumming {
+                current: number(1),
+                filename: field(2),
+            }),
+            "VERIFYING" => Some(Progress::Verifying {
+                current: number(1),
+                filename: field(2),
+            }),
+            "CHECKED" => Some(Progress::FileChecked {
+                filename: field(1),
+                passed: field(2) == "pass",
+            }),
+            "SKIPPED" => {
+                self.report.skipped.push(PathBuf::from(field(1)));
+                None
+            }
+            "VANISHED" => {
+                self.report.vanished.push(PathBuf::from(field(1)));
+                None
+            }
+            "WARNING" => {
+                self.report.warnings.push(field(1));
+                None
+            }
+            "DUPLICATES" => {
+                self.report.duplicates.push(Duplicates {
+                    size: field(1).parse().unwrap_or(0),
+                    copies: number(2),
+                    files: fields.iter().skip(3).cloned().collect(),
+                });
+                None
+            }
+            "DONE" => Some(Progress::Done {
+                path: PathBuf::from(field(1)),
+                report: Box::new(std::mem::take(&mut self.report)),
+            }),
+            "FILES" => {
+                self.validation.files_checked = number(1);
+                None
+            }
+            "ERROR" | "NOTE" => {
+                let Some(finding) = finding() else {
+                    return malformed();
+                };
+                match field(0) == "ERROR" {
+                    true => self.validation.errors.push(finding),
+                    false => self.validation.warnings.push(finding),
+                }
+                None
+            }
+            "MISMATCH" => {
+                let Some(algorithm) = ChecksumAlgorithm::from_name(&field(4)) else {
+                    return malformed();
+                };
+                self.validation.mismatches.push(Mismatch {
+                    file: field(1),
+                    algorithm,
+                    expected: field(2),
+                    actual: field(3),
+                });
+                None
+            }
+            "SAMPLE" => {
+                self.validation.sample = Some(SampleSummary {
+                    percent: field(1).parse().unwrap_or(0.0),
+                    seed: field(2).parse().unwrap_or(0),
+                    hashed: number(3),
+                    population: number(4),
+                    failed: number(5),
+                });
+                None
+            }
+            "SIGNED" => {
+                self.validation.signed_by = Some(field(1));
+                None
+            }
+            "VALIDATED" => {
+                let mut report = std::mem::take(&mut self.validation);
+                report.mode = match field(1).as_str() {
+                    "strict" => ValidationMode::Strict,
+                    _ => ValidationMode::Lenient,
+                };
+                report.path = PathBuf::from(field(2));
+                Some(Progress::Validated {
+                    report: Box::new(report),
+                })
+            }
+            "FAILED" => Some(Progress::Error { message: field(1) }),
+            _ => None,
+        }
+    }
+}
+
+/// Runs the agent: each connection is one job, bagged or validated on this machine
+/// with this machine's settings. Only returns if the listener fails.
+pub fn serve(listener: TcpListener, settings: Settings, token: String) -> io::Result<()> {
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let settings = settings.clone();
+        let token = token.clone();
+        thread::spawn(move || {
+            let _ = handle_connection(stream, &settings, &token);
+        });
+    }
+    Ok(())
+}
+
+fn handle_connection(stream: TcpStream, settings: &Settings, token: &str) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let mut writer = stream;
+    let mut send = |progress: &Progress| -> io::Result<()> {
+        for line in encode(progress) {
+            writeln!(writer, "{}", line)?;
+        }
+        writer.flush()
+    };
+
+    // Both lines are read before answering, so a refusal never meets a half-sent job.
+    let lines = read_line_limited(&mut reader, MAX_LINE)
+        .and_then(|hello| Ok((hello, read_line_limited(&mut reader, MAX_LINE)?)));
+    let (hello, line) = match lines {
+        Ok(lines) => lines,
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            return send(&Progress::Error {
+                message: "The agent refused the connection (a line was too long)".to_string(),
+            })
+        }
+        Err(e) => return Err(e),
+    };
+    if fields(&hello) != ["HELLO", token] {
+        return send(&Progress::Error {
+            message: "The agent refused the connection (wrong token)".to_string(),
+        });
+    }
+
+    let (tx, rx) = channel();
+    match fields(&line).as_slice() {
+        [command, path] if command == "BAG" => {
+            let path = PathBuf::from(path);
+            let options = settings.bag_options(true);
+            thread::spawn(move || {
+                if let Err(e) = bag_directory(&path, &options, Some(tx.clone())) {
+                    let _ = tx.send(Progress::Error {
+                        message: e.to_string(),
+                    });
+                }
+            });
+        }
+        [command, path] if command == "VALIDATE" => {
+            let path = PathBuf::from(path);
+            let options = settings.validation_options();
+            thread::spawn(move || {
+                if let Err(e) = validate_bag(&path, &options, Some(tx.clone())) {
+                    let _ = tx.send(Progress::Error {
+                        message: e.to_string(),
+                    });
+                }
+            });
+        }
+        _ => {
+            return send(&Progress::Error {
+                message: format!("The agent doesn't understand the job: {}", line.trim()),
+            })
+        }
+    }
+    for progress in rx {
+        send(&progress)?;
+    }
+    Ok(())
+}
+
+/// Hands `job` to the agent at `address` and forwards its progress to `progress_tx`
+/// until it finishes.
+pub fn submit(
+    address: &str,
+    token: &str,
+    job: &RemoteJob,
+    progress_tx: &Sender<Progress>,
+) -> io::Result<()> {
+    let stream = TcpStream::connect(address)?;
+    let job_line = match job {
+        RemoteJob::Bag(path) => record(&["BAG", path]),
+        RemoteJob::Validate(path) => record(&["VALIDATE", path]),
+    };
+    (&stream).write_all(format!("{}\n{}\n", record(&["HELLO", token]), job_line).as_bytes())?;
+
+    let mut decoder = Decoder::default();
+    for line in BufReader::new(stream).lines() {
+        if let Some(progress) = decoder.decode(&line?) {
+            let finished = matches!(
+                progress,
+                Progress::Done { .. } | Progress::Validated { .. } | Progress::Error { .. }
+            );
+            let _ = progress_tx.send(progress);
+            if finished {
+                return Ok(());
+            }
+        }
+    }
+    Err(io::Error::new(
+        io::ErrorKind::UnexpectedEof,
+        "the agent closed the connection before the job finished",
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_progress_round_trip() {
+        let mut report = ValidationReport {
+            path: PathBuf::from("/srv/bags/a\tb"),
+            mode: ValidationMode::Strict,
+            files_checked: 3,
+            signed_by: Some("Archivist <archivist@example.org>".to_string()),
+            ..ValidationReport::default()
+        };
+        report.warnings.push(Finding::new(
+            RULE_SIGNATURE,
+            "Key 1234 isn't in your keyring",
+        ));
+        report.errors.push(Finding::about(
+            FindingKind::ChecksumMismatch,
+            "data/x",
+            RULE_PAYLOAD_MANIFEST,
+            "Checksum mismatch:\ndata/x",
+        ));
+        report.mismatches.push(Mismatch {
+            file: "data/x".to_string(),
+            algorithm: ChecksumAlgorithm::Md5,
+            expected: "aa".to_string(),
+            actual: "bb".to_string(),
+        });
+
+        let mut decoder = Decoder::default();
+        let decoded: Vec<Progress> = encode(&Progress::Validated {
+            report: Box::new(report),
+        })
+        .iter()
+        .filter_map(|line| decoder.decode(line))
+        .collect();
+        let [Progress::Validated { report }] = decoded.as_slice() else {
+            panic!("expected one validation result, got {:?}", decoded);
+        };
+        assert_eq!(report.path, PathBuf::from("/srv/bags/a\tb"));
+        assert_eq!(report.mode, ValidationMode::Strict);
+        assert_eq!(report.files_checked, 3);
+        assert_eq!(report.errors[0].rule, RULE_PAYLOAD_MANIFEST);
+        assert_eq!(report.errors[0].message, "Checksum mismatch:\ndata/x");
+        assert_eq!(report.files(FindingKind::ChecksumMismatch), vec!["data/x"]);
+        assert_eq!(report.mismatches[0].actual, "bb");
+        assert_eq!(report.mismatches[0].algorithm, ChecksumAlgorithm::Md5);
+        assert_eq!(report.warnings[0].rule, RULE_SIGNATURE);
+        assert_eq!(
+            report.signed_by.as_deref(),
+            Some("Archivist <archivist@example.org>")
+        );
+
+        // A finding without its kind and file isn't guessed at
+        let mut decoder = Decoder::default();
+        let decoded = decoder.decode(&record(&["ERROR", RULE_PAYLOAD_MANIFEST, "Missing"]));
+        assert!(
+            matches!(decoded, Some(Progress::Error { message }) if message.contains("can't read"))
+        );
+        let decoded = decoder.decode(&record(&["MISMATCH", "data/x", "aa", "bb"]));
+        assert!(matches!(decoded, Some(Progress::Error { .. })));
+    }
+
+    #[test]
+    fn test_agent_bags_and_validates() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_agent");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        thread::spawn(move || serve(listener, Settings::default(), "secret".to_string()));
+
+        let path = temp_dir.to_string_lossy().to_string();
+        let (tx, rx) = channel();
+        submit(&address, "secret", &RemoteJob::Bag(path.clone()), &tx).unwrap();
+        submit(&address, "secret", &RemoteJob::Validate(path.clone()), &tx).unwrap();
+        submit(&address, "wrong", &RemoteJob::Validate(path), &tx).unwrap();
+        drop(tx);
+        let progress: Vec<Progress> = rx.into_iter().collect();
+
+        assert!(matches!(
+            progress.first(),
+            Some(Progress::Started { total_files: 1 })
+        ));
+        assert!(progress.iter().any(|p| matches!(p, Progress::Done { .. })));
+        assert!(progress
+            .iter()
+            .any(|p| matches!(p, Progress::Validated { report } if report.is_valid())));
+        assert!(
+            matches!(progress.last(), Some(Progress::Error { message }) if message.contains("token"))
+        );
+        assert!(temp_dir.join("data/file.txt").exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_read_line_limited() {
+        let mut reader = io::Cursor::new("HELLO\tsecret\nBAG\t/srv\n");
+        assert_eq!(
+            read_line_limited(&mut reader, 16).unwrap(),
+            "HELLO\tsecret\n"
+        );
+        assert_eq!(read_line_limited(&mut reader, 16).unwrap(), "BAG\t/srv\n");
+        assert_eq!(read_line_limited(&mut reader, 16).unwrap(), "");
+        let mut endless = io::Cursor::new("x".repeat(100));
+        let error = read_line_limited(&mut endless, 16).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // A duplicate set's size isn't cut down to the platform's word
+        let mut decoder = Decoder::default();
+        decoder.decode(&record(&[
+            "DUPLICATES",
+            "6000000000",
+            "2",
+            "data/a",
+            "data/b",
+        ]));
+        assert_eq!(decoder.report.duplicates[0].size, 6_000_000_000);
+    }
+}
@@ -1,8 +1,37 @@
-use crate::bagit::{bag_directory, Progress};
+use crate::agent::{submit, RemoteJob, DEFAULT_AGENT_ADDRESS};
+use crate::archive::{serialize_bag, validate_archive, SerialFormat};
+use crate::bagit::{
+    bag_directory, bag_into, bag_like_reason, bag_name, bag_sources, bag_volume,
+    default_skip_names, payload_sources, plan_volumes, unbag_directory, update_bag, validate_bag,
+    BagItVersion, BagLikeReason, BagReport, BagSummary, CaseCollisionPolicy, ChecksumAlgorithm,
+    DateMode, DedupMode, EmptyDirPolicy, EmptySourcePolicy, ErrorPolicy, IdScheme, Mismatch,
+    PayloadSource, Progress, Sample, ValidationMode, ValidationOptions, ValidationReport,
+    VolumePlan, DEFAULT_NAME_TEMPLATE,
+};
+use crate::clone::clone_bag;
+use crate::dedup::FETCH_FILE;
+use crate::diff::{compare_bags, BagDiff};
+use crate::download::{bag_from_urls, complete_bag, parse_url_list, DEFAULT_DOWNLOADS};
+use crate::encrypt::EncryptionTool;
+use crate::filelist::{bag_from_list, parse_file_list};
+use crate::inventory::{AuditRecord, FixityStatus, Inventory, InventoryStats, Tally};
+use crate::locale::Locale;
+use crate::plugin::Plugin;
+use crate::profile::{remove_profile, save_profile, saved_profiles, Profile};
+use crate::receipt::receipt_html;
+use crate::settings::Settings;
+use crate::signature::SigningTool;
+use crate::split::{join_volumes, DEFAULT_VOLUME_SIZE, VOLUME_LIST_EXTENSION};
+use crate::triage::{compare_with_copy, rehash, restore, CopyComparison};
 use eframe::egui;
+use qrcode::QrCode;
 use std::path::PathBuf;
-use std::sync::mpsc::{channel, Receiver};
+use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
 use std::thread;
+use std::time::{Duration, Instant};
+
+/// How often the app looks for bags due a background fixity check.
+const FIXITY_SCAN_INTERVAL: Duration = Duration::from_secs(60);
 
 #[derive(Default)]
 enum AppState {
@@ -17,32 +46,238 @@ enum AppState {
     Done {
         path: PathBuf,
         file_count: usize,
+        report: Box<BagReport>,
+        summary: Option<BagSummary>,
+        qr: Option<QrCode>,
+    },
+    Validated {
+        report: ValidationReport,
+    },
+    Compared {
+        diff: Box<BagDiff>,
+    },
+    Serialized {
+        path: PathBuf,
+    },
+    Reassembled {
+        path: PathBuf,
     },
     Error {
         message: String,
     },
+    /// The dropped folder can't be bagged in place; the user picks what to do instead.
+    LooksLikeABag {
+        path: PathBuf,
+        reason: BagLikeReason,
+    },
+    /// A sub-bag of a split payload is done; waiting for the next volume.
+    NextVolume,
+    Inventory {
+        stats: InventoryStats,
+    },
+    Fixity,
+    /// Working through checksum mismatches; the details live in `BagItApp::triage`.
+    Triage,
+    Settings,
+    Metadata,
+    /// Choosing folders to bag together, and the `data/` subfolder each goes in; the
+    /// list lives in `BagItApp::combining`.
+    Combine,
+    /// Sending a job to an agent on another machine.
+    Remote,
+}
+
+/// Checksum mismatches from a validation run, being dealt with one at a time.
+struct Triage {
+    bag: PathBuf,
+    mismatches: Vec<Mismatch>,
+    selected: usize,
+    note: String,
+    /// What the last action found, shown under the buttons.
+    outcome: Option<String>,
+}
+
+/// A payload being bagged across removable volumes, one sub-bag per volume.
+struct VolumeRun {
+    source: PathBuf,
+    volumes: Vec<VolumePlan>,
+    /// Index of the volume to write next.
+    next: usize,
+}
+
+impl VolumeRun {
+    fn name(&self) -> String {
+        self.source
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "bag".to_string())
+    }
 }
 
 pub struct BagItApp {
     state: AppState,
     progress_rx: Option<Receiver<Progress>>,
+    settings: Settings,
+    /// Per-run override: unticking bags system files too, without touching the saved list.
+    skip_system_files: bool,
+    /// Skip list being edited on the settings screen, one name per line.
+    skip_names_text: String,
+    /// Plugins being edited on the settings screen, one `stage: command` per line.
+    plugins_text: String,
+    /// Encryption recipients being edited on the settings screen, one per line.
+    recipients_text: String,
+    volume_run: Option<VolumeRun>,
+    /// Path on the agent's machine, being typed on the remote screen.
+    remote_path: String,
+    inventory: Inventory,
+    /// Results of fixity checks running in the background.
+    fixity_tx: Sender<(PathBuf, Result<ValidationReport, String>)>,
+    fixity_rx: Receiver<(PathBuf, Result<ValidationReport, String>)>,
+    /// Bags being checked, and whether the scheduler (rather than the user) started each.
+    fixity_checking: Vec<(PathBuf, bool)>,
+    next_fixity_scan: Instant,
+    /// Problem found by a scheduled check, shown until dismissed.
+    fixity_alert: Option<String>,
+    /// Bag whose audit history is shown on the fixity screen.
+    fixity_selected: Option<PathBuf>,
+    triage: Option<Triage>,
+    /// Latest announcement for screen readers, and whether it should interrupt.
+    announcement: (String, bool),
+    /// Whether the running job has announced reaching halfway.
+    announced_half: bool,
+    /// Files that failed their checksum so far in the running validation.
+    failed_checks: usize,
+    /// Folder being bagged in place, kept if it fails so it can be bagged again.
+    bagging: Option<PathBuf>,
+    /// Folders being gathered into one bag on the combine screen.
+    combining: Vec<PayloadSource>,
+    /// The profile new bags are made under, as picked on the main screen.
+    profile: Option<Profile>,
+    saved_profiles: Vec<String>,
+    /// URL of a profile to save, being typed on the metadata screen.
+    profile_url: String,
+    /// A profile being fetched and saved in the background: its name, or what went wrong.
+    adding_profile: Option<Receiver<Result<String, String>>>,
 }
 
 impl Default for BagItApp {
     fn default() -> Self {
+        let (fixity_tx, fixity_rx) = channel();
         Self {
             state: AppState::Idle,
             progress_rx: None,
+            settings: Settings::default(),
+            skip_system_files: true,
+            skip_names_text: String::new(),
+            plugins_text: String::new(),
+            recipients_text: String::new(),
+            volume_run: None,
+            remote_path: String::new(),
+            inventory: Inventory::default(),
+            fixity_tx,
+            fixity_rx,
+            fixity_checking: Vec::new(),
+            next_fixity_scan: Instant::now(),
+            fixity_alert: None,
+            fixity_selected: None,
+            triage: None,
+            announcement: (String::new(), false),
+            announced_half: false,
+            failed_checks: 0,
+            bagging: None,
+            combining: Vec::new(),
+            profile: None,
+            saved_profiles: Vec::new(),
+            profile_url: String::new(),
+            adding_profile: None,
         }
     }
 }
 
 impl BagItApp {
     pub fn new(_cc: &eframe::CreationContext<'_>) -> Self {
-        Self::default()
+        let mut settings = Settings::load();
+        // A profile removed since, or no longer readable, is dropped rather than
+        // bagged without
+        let profile = Profile::named(&settings.bag_profile).ok();
+        if profile.is_none() {
+            settings.bag_profile.clear();
+        }
+        Self {
+            settings,
+            inventory: Inventory::load(),
+            profile,
+            saved_profiles: saved_profiles(),
+            ..Self::default()
+        }
+    }
+
+    /// Makes new bags be made under the saved profile `name`, or none if it's empty,
+    /// adding the bag-info fields it requires to the metadata. If any are left blank,
+    /// the metadata screen is shown to fill them in.
+    fn choose_profile(&mut self, name: String) {
+        self.profile = None;
+        if !name.is_empty() {
+            match Profile::named(&name) {
+                Ok(profile) => {
+                    if !profile.prefill(&mut self.settings.metadata).is_empty() {
+                        self.state = AppState::Metadata;
+                    }
+                    self.profile = Some(profile);
+                }
+                Err(e) => {
+                    self.state = AppState::Error {
+                        message: e.to_string(),
+                    };
+                    return;
+                }
+            }
+        }
+        self.settings.bag_profile = name;
+        let _ = self.settings.save();
+    }
+
+    /// Saves the profile at `source` (a file or URL) for picking on the main screen.
+    /// It's fetched in the background, as a URL may be slow to answer.
+    fn add_profile(&mut self, source: &str) {
+        let (tx, rx) = channel();
+        self.adding_profile = Some(rx);
+        let source = source.to_string();
+        thread::spawn(move || {
+            let _ = tx.send(save_profile(&source).map_err(|e| e.to_string()));
+        });
+    }
+
+    /// Picks up the profile `add_profile` saved, once it's done.
+    fn poll_adding_profile(&mut self) {
+        let Some(rx) = &self.adding_profile else {
+            return;
+        };
+        let result = match rx.try_recv() {
+            Ok(result) => result,
+            Err(TryRecvError::Empty) => return,
+            Err(TryRecvError::Disconnected) => Err("Saving the profile stopped".to_string()),
+        };
+        self.adding_profile = None;
+        match result {
+            Ok(_) => self.saved_profiles = saved_profiles(),
+            Err(message) => self.state = AppState::Error { message },
+        }
     }
 
     fn start_bagging(&mut self, path: PathBuf) {
+        // A copy can be made of anything, bag-like or not
+        if self.settings.bag_copy {
+            let options = self.settings.bag_options(self.skip_system_files);
+            let dest = self.settings.copy_destination(&path, &options);
+            self.start_bagging_into(path, dest);
+            return;
+        }
+        if let Some(reason) = bag_like_reason(&path) {
+            self.state = AppState::LooksLikeABag { path, reason };
+            return;
+        }
+
         let (tx, rx) = channel();
         self.progress_rx = Some(rx);
         self.state = AppState::Processing {
@@ -52,8 +287,10 @@ impl BagItApp {
             stage: "Starting...".to_string(),
         };
 
+        let options = self.settings.bag_options(self.skip_system_files);
+        self.bagging = Some(path.clone());
         thread::spawn(move || {
-            if let Err(e) = bag_directory(&path, Some(tx.clone())) {
+            if let Err(e) = bag_directory(&path, &options, Some(tx.clone())) {
                 let _ = tx.send(Progress::Error {
                     message: e.to_string(),
                 });
@@ -61,123 +298,1993 @@ impl BagItApp {
         });
     }
 
-    fn process_progress(&mut self) {
-        let mut clear_rx = false;
+    fn start_update(&mut self, path: PathBuf) {
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Starting...".to_string(),
+        };
 
-        if let Some(ref rx) = self.progress_rx {
-            while let Ok(progress) = rx.try_recv() {
-                match progress {
-                    Progress::Started { total_files } => {
-                        self.state = AppState::Processing {
-                            total_files,
-                            current: 0,
-                            current_file: String::new(),
-                            stage: "Preparing...".to_string(),
-                        };
-                    }
-                    Progress::Moving { current, filename } => {
-                        if let AppState::Processing {
-                            total_files,
-                            current_file,
-                            stage,
-                            ..
-                        } = &mut self.state
-                        {
-                            *current_file = filename;
-                            *stage = format!("Moving files ({}/{})", current, *total_files);
-                        }
-                    }
-                    Progress::Checksumming { current, filename } => {
-                        if let AppState::Processing {
-                            total_files,
-                            current: curr,
-                            current_file,
-                            stage,
-                        } = &mut self.state
-                        {
-                            *curr = current;
-                            *current_file = filename;
-                            *stage = format!("Checksumming ({}/{})", current, *total_files);
-                        }
-                    }
-                    Progress::Done { path } => {
-                        let file_count = if let AppState::Processing { total_files, .. } = &self.state {
-                            *total_files
-                        } else {
-                            0
-                        };
-                        self.state = AppState::Done { path, file_count };
-                        clear_rx = true;
-                    }
-                    Progress::Error { message } => {
-                        self.state = AppState::Error { message };
-                        clear_rx = true;
-                    }
-                }
+        let options = self.settings.bag_options(self.skip_system_files);
+        thread::spawn(move || {
+            if let Err(e) = update_bag(&path, &options, Some(tx.clone())) {
+                let _ = tx.send(Progress::Error {
+                    message: e.to_string(),
+                });
+            }
+        });
+    }
+
+    /// Fetches the files the bag's fetch.txt lists, making it a complete bag.
+    fn start_complete(&mut self, path: PathBuf) {
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Starting...".to_string(),
+        };
+
+        thread::spawn(move || {
+            if let Err(e) = complete_bag(&path, DEFAULT_DOWNLOADS, Some(tx.clone())) {
+                let _ = tx.send(Progress::Error {
+                    message: e.to_string(),
+                });
+            }
+        });
+    }
+
+    fn start_bagging_into(&mut self, source: PathBuf, dest: PathBuf) {
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Starting...".to_string(),
+        };
+
+        let options = self.settings.bag_options(self.skip_system_files);
+        thread::spawn(move || {
+            if let Err(e) = bag_into(&source, &dest, &options, Some(tx.clone())) {
+                let _ = tx.send(Progress::Error {
+                    message: e.to_string(),
+                });
+            }
+        });
+    }
+
+    /// Adds `paths` to the folders on the combine screen. Names already there are kept;
+    /// only the new folders get ones of their own.
+    fn add_to_combining(&mut self, paths: Vec<PathBuf>) {
+        if paths.is_empty() {
+            return;
+        }
+        let names: Vec<String> = self.combining.iter().map(|s| s.name.clone()).collect();
+        let mut all: Vec<PathBuf> = self.combining.drain(..).map(|s| s.path).collect();
+        all.extend(paths);
+        self.combining = payload_sources(all);
+        for (source, name) in self.combining.iter_mut().zip(names) {
+            source.name = name;
+        }
+    }
+
+    /// Bags copies of the folders on the combine screen into one bag at `dest`.
+    fn start_bagging_sources(&mut self, dest: PathBuf) {
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Starting...".to_string(),
+        };
+
+        let sources = std::mem::take(&mut self.combining);
+        let options = self.settings.bag_options(self.skip_system_files);
+        thread::spawn(move || {
+            if let Err(e) = bag_sources(&sources, &dest, &options, Some(tx.clone())) {
+                let _ = tx.send(Progress::Error {
+                    message: e.to_string(),
+                });
+            }
+        });
+    }
+
+    /// Copies the bag at `source` to `dest` under a new identifier, checking the
+    /// payload as it goes.
+    fn start_clone(&mut self, source: PathBuf, dest: PathBuf) {
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Starting...".to_string(),
+        };
+
+        let options = self.settings.bag_options(self.skip_system_files);
+        thread::spawn(move || {
+            if let Err(e) = clone_bag(&source, &dest, &options, true, Some(tx.clone())) {
+                let _ = tx.send(Progress::Error {
+                    message: e.to_string(),
+                });
+            }
+        });
+    }
+
+    /// Bags `source` onto the media at `dest`, splitting it into sub-bags when it
+    /// doesn't fit in the free space there.
+    fn start_removable(&mut self, source: PathBuf, dest: PathBuf) {
+        let options = self.settings.bag_options(self.skip_system_files);
+        let plan = fs2::available_space(&dest)
+            .map_err(|e| e.to_string())
+            .and_then(|space| plan_volumes(&source, &options, space).map_err(|e| e.to_string()));
+        let volumes = match plan {
+            Ok((volumes, _)) => volumes,
+            Err(message) => {
+                self.state = AppState::Error { message };
+                return;
+            }
+        };
+
+        let run = VolumeRun {
+            source,
+            volumes,
+            next: 0,
+        };
+        if run.volumes.len() == 1 {
+            let dest = dest.join(bag_name(
+                &self.settings.name_template,
+                &run.source,
+                &options,
+            ));
+            self.start_bagging_into(run.source, dest);
+        } else {
+            self.volume_run = Some(run);
+            self.start_next_volume(dest);
+        }
+    }
+
+    fn start_next_volume(&mut self, dest: PathBuf) {
+        let Some(run) = &self.volume_run else {
+            return;
+        };
+        let volume = &run.volumes[run.next];
+        if let Ok(space) = fs2::available_space(&dest) {
+            if space < volume.bytes {
+                self.state = AppState::Error {
+                    message: format!(
+                        "Volume {} needs {} bytes but only {} are free there",
+                        run.next + 1,
+                        volume.bytes,
+                        space
+                    ),
+                };
+                self.volume_run = None;
+                return;
+            }
+        }
+
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Starting...".to_string(),
+        };
+
+        let options = self.settings.bag_options(self.skip_system_files);
+        let name = run.name();
+        let dest = dest.join(format!(
+            "{}_{}_of_{}",
+            name,
+            run.next + 1,
+            run.volumes.len()
+        ));
+        let source = run.source.clone();
+        let volumes = run.volumes.clone();
+        let index = run.next;
+        thread::spawn(move || {
+            if let Err(e) = bag_volume(
+                &source,
+                &dest,
+                &volumes,
+                index,
+                &name,
+                &options,
+                Some(tx.clone()),
+            ) {
+                let _ = tx.send(Progress::Error {
+                    message: e.to_string(),
+                });
+            }
+        });
+    }
+
+    fn start_fixity_check(&mut self, path: PathBuf, scheduled: bool) {
+        let tx = self.fixity_tx.clone();
+        self.fixity_checking.push((path.clone(), scheduled));
+
+        let mut options = self.settings.validation_options();
+        let percent = self.settings.audit_sample_percent;
+        if scheduled && percent > 0 {
+            options.sample = Some(Sample::new(percent as f64));
+        }
+        thread::spawn(move || {
+            let result = validate_bag(&path, &options, None).map_err(|e| e.to_string());
+            let _ = tx.send((path, result));
+        });
+    }
+
+    /// Collects finished fixity checks and, every so often, starts the ones due. Scheduled
+    /// checks only fill the job slots left over by bagging and validation the user started.
+    fn poll_fixity(&mut self) {
+        while let Ok((path, result)) = self.fixity_rx.try_recv() {
+            let Some(index) = self.fixity_checking.iter().position(|(p, _)| *p == path) else {
+                continue;
+            };
+            let (_, scheduled) = self.fixity_checking.remove(index);
+            let audit = AuditRecord::new(&result, scheduled);
+            if scheduled && audit.status == FixityStatus::Failed {
+                let problem = match result {
+                    Ok(report) => format!("{} errors", report.errors.len()),
+                    Err(message) => message,
+                };
+                let alert = format!(
+                    "Scheduled fixity check failed for {}: {}",
+                    path.display(),
+                    problem
+                );
+                self.announcement = (alert.clone(), true);
+                self.fixity_alert = Some(alert);
+            }
+            self.inventory.record_check(&path, audit);
+            let _ = self.inventory.save();
+        }
+
+        if Instant::now() < self.next_fixity_scan {
+            return;
+        }
+        self.next_fixity_scan = Instant::now() + FIXITY_SCAN_INTERVAL;
+        let running = self.fixity_checking.len() + usize::from(self.progress_rx.is_some());
+        let free = self.settings.max_jobs.saturating_sub(running);
+        let today = chrono::Local::now().date_naive();
+        let due: Vec<PathBuf> = self
+            .inventory
+            .due(today, self.settings.audit_interval_days)
+            .into_iter()
+            .filter(|e| !self.fixity_checking.iter().any(|(p, _)| *p == e.path))
+            .take(free)
+            .map(|e| e.path.clone())
+            .collect();
+        for path in due {
+            self.start_fixity_check(path, true);
+        }
+    }
+
+    fn start_validation(&mut self, path: PathBuf) {
+        let options = self.settings.validation_options();
+        self.start_checking(path, options);
+    }
+
+    /// Checks only that the bag at `path` is complete, without checksumming.
+    fn start_completeness_check(&mut self, path: PathBuf) {
+        let options = ValidationOptions {
+            completeness_only: true,
+            ..self.settings.validation_options()
+        };
+        self.start_checking(path, options);
+    }
+
+    fn start_checking(&mut self, path: PathBuf, options: ValidationOptions) {
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Reading manifests...".to_string(),
+        };
+
+        thread::spawn(move || {
+            let result = if path.is_file() {
+                validate_archive(&path, &options, Some(tx.clone()))
+            } else {
+                validate_bag(&path, &options, Some(tx.clone()))
+            };
+            if let Err(e) = result {
+                let _ = tx.send(Progress::Error {
+                    message: e.to_string(),
+                });
             }
+        });
+    }
+
+    fn start_comparison(&mut self, original: PathBuf, copy: PathBuf) {
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Reading manifests...".to_string(),
+        };
+
+        thread::spawn(
+            move || match compare_bags(&original, &copy, Some(tx.clone())) {
+                Ok(diff) => {
+                    let _ = tx.send(Progress::Compared {
+                        diff: Box::new(diff),
+                    });
+                }
+                Err(e) => {
+                    let _ = tx.send(Progress::Error {
+                        message: e.to_string(),
+                    });
+                }
+            },
+        );
+    }
+
+    /// Writes the bag at `bag` into `dest` as one file, in the format set in Settings.
+    fn start_serializing(&mut self, bag: PathBuf, dest: PathBuf) {
+        let options = self.settings.serial_options();
+        let problem = self
+            .profile
+            .as_ref()
+            .and_then(|p| p.check_serialization(Some(options.format.mime_type())));
+        if let Some(problem) = problem {
+            self.state = AppState::Error {
+                message: format!("The bag's BagIt Profile says {}", problem),
+            };
+            return;
         }
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Serializing...".to_string(),
+        };
+
+        thread::spawn(
+            move || match serialize_bag(&bag, &dest, &options, Some(tx.clone())) {
+                Ok(path) => {
+                    let _ = tx.send(Progress::Serialized { path });
+                }
+                Err(e) => {
+                    let _ = tx.send(Progress::Error {
+                        message: e.to_string(),
+                    });
+                }
+            },
+        );
+    }
+
+    /// Joins the volumes listed in `list` back into the serialized bag beside them, then
+    /// validates it, unless it's encrypted and so can't be read.
+    fn start_reassembly(&mut self, list: PathBuf) {
+        let options = self.settings.validation_options();
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Reassembling volumes...".to_string(),
+        };
+
+        thread::spawn(move || {
+            let result = join_volumes(&list, None).and_then(|path| {
+                let encrypted = path.extension().is_some_and(|e| e == "age" || e == "gpg");
+                match encrypted {
+                    true => {
+                        let _ = tx.send(Progress::Reassembled { path });
+                        Ok(())
+                    }
+                    false => validate_archive(&path, &options, Some(tx.clone())).map(|_| ()),
+                }
+            });
+            if let Err(e) = result {
+                let _ = tx.send(Progress::Error {
+                    message: e.to_string(),
+                });
+            }
+        });
+    }
+
+    /// Downloads the URLs listed in the file at `list` into a new bag at `dest`.
+    fn start_download(&mut self, list: PathBuf, dest: PathBuf) {
+        let entries = std::fs::read_to_string(&list)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_url_list(&text).map_err(|e| e.to_string()));
+        let entries = match entries {
+            Ok(entries) => entries,
+            Err(message) => {
+                self.state = AppState::Error { message };
+                return;
+            }
+        };
+
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Starting...".to_string(),
+        };
+
+        let options = self.settings.bag_options(self.skip_system_files);
+        thread::spawn(move || {
+            if let Err(e) = bag_from_urls(
+                &entries,
+                &dest,
+                &options,
+                DEFAULT_DOWNLOADS,
+                Some(tx.clone()),
+            ) {
+                let _ = tx.send(Progress::Error {
+                    message: e.to_string(),
+                });
+            }
+        });
+    }
+
+    /// Bags copies of the files `list` names into `dest`.
+    fn start_file_list(&mut self, list: PathBuf, dest: PathBuf) {
+        let csv = list
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+        let entries = std::fs::read_to_string(&list)
+            .map_err(|e| e.to_string())
+            .and_then(|text| parse_file_list(&text, csv).map_err(|e| e.to_string()));
+        let entries = match entries {
+            Ok(entries) => entries,
+            Err(message) => {
+                self.state = AppState::Error { message };
+                return;
+            }
+        };
+
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Starting...".to_string(),
+        };
+
+        let options = self.settings.bag_options(self.skip_system_files);
+        thread::spawn(move || {
+            if let Err(e) = bag_from_list(&entries, &dest, &options, Some(tx.clone())) {
+                let _ = tx.send(Progress::Error {
+                    message: e.to_string(),
+                });
+            }
+        });
+    }
+
+    /// Runs `job` on the agent set in the settings, showing its progress as if it
+    /// were local.
+    fn start_remote(&mut self, job: RemoteJob) {
+        let (tx, rx) = channel();
+        self.progress_rx = Some(rx);
+        self.state = AppState::Processing {
+            total_files: 0,
+            current: 0,
+            current_file: String::new(),
+            stage: "Contacting the agent...".to_string(),
+        };
+        let _ = self.settings.save();
+
+        let address = self.settings.agent_address.clone();
+        let token = self.settings.agent_token.clone();
+        thread::spawn(move || {
+            if let Err(e) = submit(&address, &token, &job, &tx) {
+                let _ = tx.send(Progress::Error {
+                    message: format!("Agent at {}: {}", address, e),
+                });
+            }
+        });
+    }
+
+    /// Fills the settings screen's text boxes from the settings.
+    fn load_settings_text(&mut self) {
+        self.skip_names_text = self.settings.skip_names.join("\n");
+        self.plugins_text = self
+            .settings
+            .plugins
+            .iter()
+            .map(Plugin::to_line)
+            .collect::<Vec<_>>()
+            .join("\n");
+        self.recipients_text = self.settings.encryption_recipients.join("\n");
+    }
+
+    /// Copies the lists being edited on the settings screen into the settings.
+    fn apply_settings_text(&mut self) {
+        self.settings.skip_names = self
+            .skip_names_text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(String::from)
+            .collect();
+        self.settings.plugins = self
+            .plugins_text
+            .lines()
+            .filter_map(Plugin::parse)
+            .collect();
+        self.settings.encryption_recipients = self
+            .recipients_text
+            .lines()
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(String::from)
+            .collect();
+    }
+
+    fn process_progress(&mut self) {
+        let mut clear_rx = false;
+        let locale = self.settings.locale.resolve();
+
+        if let Some(ref rx) = self.progress_rx {
+            while let Ok(progress) = rx.try_recv() {
+                match progress {
+                    Progress::Started { total_files } => {
+                        self.announced_half = false;
+                        self.failed_checks = 0;
+                        self.announcement = (
+                            format!("Job started: {} files", locale.count(total_files as u64)),
+                            false,
+                        );
+                        self.state = AppState::Processing {
+                            total_files,
+                            current: 0,
+                            current_file: String::new(),
+                            stage: "Preparing...".to_string(),
+                        };
+                    }
+                    Progress::Scanning { found } => {
+                        if let AppState::Processing { stage, .. } = &mut self.state {
+                            *stage =
+                                format!("Scanning ({} files found)", locale.count(found as u64));
+                        }
+                    }
+                    Progress::Moving { current, filename } => {
+                        if let AppState::Processing {
+                            total_files,
+                            current_file,
+                            stage,
+                            ..
+                        } = &mut self.state
+                        {
+                            *current_file = filename;
+                            *stage = format!(
+                                "Moving files ({}/{})",
+                                locale.count(current as u64),
+                                locale.count(*total_files as u64)
+                            );
+                        }
+                    }
+                    Progress::Copying { current, filename } => {
+                        if let AppState::Processing {
+                            total_files,
+                            current_file,
+                            stage,
+                            ..
+                        } = &mut self.state
+                        {
+                            *current_file = filename;
+                            *stage = format!(
+                                "Copying files ({}/{})",
+                                locale.count(current as u64),
+                                locale.count(*total_files as u64)
+                            );
+                        }
+                    }
+                    Progress::Downloading { current, filename } => {
+                        if let AppState::Processing {
+                            total_files,
+                            current_file,
+                            stage,
+                            ..
+                        } = &mut self.state
+                        {
+                            *current_file = filename;
+                            *stage = format!(
+                                "Downloading ({}/{})",
+                                locale.count(current as u64),
+                                locale.count(*total_files as u64)
+                            );
+                        }
+                    }
+                    Progress::Checksumming { current, filename } => {
+                        if let AppState::Processing {
+                            total_files,
+                            current: curr,
+                            current_file,
+                            stage,
+                        } = &mut self.state
+                        {
+                            *curr = current;
+                            *current_file = filename;
+                            *stage = format!(
+                                "Checksumming ({}/{})",
+                                locale.count(current as u64),
+                                locale.count(*total_files as u64)
+                            );
+                            if !self.announced_half
+                                && *total_files > 0
+                                && current * 2 >= *total_files
+                            {
+                                self.announced_half = true;
+                                self.announcement = ("50% complete".to_string(), false);
+                            }
+                        }
+                    }
+                    Progress::Verifying { current, filename } => {
+                        if let AppState::Processing {
+                            total_files,
+                            current: curr,
+                            current_file,
+                            stage,
+                        } = &mut self.state
+                        {
+                            *curr = current;
+                            *current_file = filename;
+                            *stage = format!(
+                                "Reading back ({}/{})",
+                                locale.count(current as u64),
+                                locale.count(*total_files as u64)
+                            );
+                        }
+                    }
+                    Progress::FileChecked { passed, .. } => {
+                        if !passed {
+                            self.failed_checks += 1;
+                        }
+                    }
+                    Progress::Done { path, report } => {
+                        self.bagging = None;
+                        let file_count = report.bag.manifest.len();
+                        if self.inventory.register(&path).is_ok() {
+                            let _ = self.inventory.save();
+                        }
+                        self.announcement = (
+                            format!(
+                                "Bag created: {} files, {} warnings",
+                                locale.count(file_count as u64),
+                                locale.count(report.warnings.len() as u64)
+                            ),
+                            false,
+                        );
+                        let summary = BagSummary::read(&path).ok();
+                        let qr = summary
+                            .as_ref()
+                            .and_then(|s| QrCode::new(s.to_label_text()).ok());
+                        self.state = AppState::Done {
+                            path,
+                            file_count,
+                            report,
+                            summary,
+                            qr,
+                        };
+                        if let Some(run) = &mut self.volume_run {
+                            run.next += 1;
+                            if run.next < run.volumes.len() {
+                                self.state = AppState::NextVolume;
+                            } else {
+                                self.volume_run = None;
+                            }
+                        }
+                        clear_rx = true;
+                    }
+                    Progress::Validated { report } => {
+                        let text = match (report.completeness_only, report.is_valid()) {
+                            (true, true) => "Quick check finished: the bag is complete".to_string(),
+                            (true, false) => {
+                                format!("Quick check finished: {} errors", report.errors.len())
+                            }
+                            (false, true) => "Validation finished: the bag is valid".to_string(),
+                            (false, false) => {
+                                format!("Validation finished: {} errors", report.errors.len())
+                            }
+                        };
+                        self.announcement = (text, false);
+                        self.state = AppState::Validated { report: *report };
+                        clear_rx = true;
+                    }
+                    Progress::Compared { diff } => {
+                        let text = match diff.is_identical() {
+                            true => "Comparison finished: the bags match".to_string(),
+                            false => "Comparison finished: the bags differ".to_string(),
+                        };
+                        self.announcement = (text, false);
+                        self.state = AppState::Compared { diff };
+                        clear_rx = true;
+                    }
+                    Progress::Serialized { path } => {
+                        self.announcement = ("Serializing finished".to_string(), false);
+                        self.state = AppState::Serialized { path };
+                        clear_rx = true;
+                    }
+                    Progress::Reassembled { path } => {
+                        self.announcement = ("Volumes reassembled".to_string(), false);
+                        self.state = AppState::Reassembled { path };
+                        clear_rx = true;
+                    }
+                    Progress::Error { message } => {
+                        // Errors interrupt whatever the screen reader is saying.
+                        self.announcement = (format!("Error: {}", message), true);
+                        self.state = AppState::Error { message };
+                        self.volume_run = None;
+                        clear_rx = true;
+                    }
+                }
+            }
+        }
+
+        if clear_rx {
+            self.progress_rx = None;
+        }
+    }
+}
+
+impl eframe::App for BagItApp {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        // Process any pending progress updates
+        self.process_progress();
+
+        self.poll_fixity();
+        self.poll_adding_profile();
+
+        // Request repaint while processing
+        if self.progress_rx.is_some()
+            || !self.fixity_checking.is_empty()
+            || self.adding_profile.is_some()
+        {
+            ctx.request_repaint();
+        } else {
+            ctx.request_repaint_after(FIXITY_SCAN_INTERVAL);
+        }
+
+        // Handle dropped files
+        let dropped_files: Vec<PathBuf> = ctx
+            .input(|i| {
+                i.raw
+                    .dropped_files
+                    .iter()
+                    .filter_map(|f| f.path.clone())
+                    .collect()
+            });
+
+        let folders: Vec<PathBuf> = dropped_files
+            .iter()
+            .filter(|p| p.is_dir())
+            .cloned()
+            .collect();
+        if matches!(self.state, AppState::Combine) {
+            // Dropped onto the combine screen, folders join the list
+            self.add_to_combining(folders);
+        } else if folders.len() > 1 {
+            if matches!(
+                self.state,
+                AppState::Idle
+                    | AppState::Done { .. }
+                    | AppState::Validated { .. }
+                    | AppState::Error { .. }
+                    | AppState::LooksLikeABag { .. }
+            ) {
+                self.combining = payload_sources(folders);
+                self.state = AppState::Combine;
+            }
+        } else if let Some(path) = dropped_files.into_iter().next() {
+            if matches!(
+                self.state,
+                AppState::Idle
+                    | AppState::Done { .. }
+                    | AppState::Validated { .. }
+                    | AppState::Error { .. }
+                    | AppState::LooksLikeABag { .. }
+            ) {
+                if path.is_dir() {
+                    self.start_bagging(path);
+                } else if SerialFormat::of(&path).is_some() {
+                    self.start_validation(path);
+                }
+            }
+        }
+
+        let locale = self.settings.locale.resolve();
+
+        // Live region: screen readers read out changes to this line on their own, so
+        // nobody has to keep polling the window during a long run.
+        egui::TopBottomPanel::bottom("announcements").show(ctx, |ui| {
+            let (text, urgent) = &self.announcement;
+            let response = ui.small(text.as_str());
+            ctx.accesskit_node_builder(response.id, |node| {
+                node.set_live(if *urgent {
+                    egui::accesskit::Live::Assertive
+                } else {
+                    egui::accesskit::Live::Polite
+                });
+            });
+        });
+
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ui.vertical_centered(|ui| {
+                // ui.add_space(40.0);
+
+                if let Some(alert) = &self.fixity_alert {
+                    let mut dismiss = false;
+                    ui.horizontal_wrapped(|ui| {
+                        ui.colored_label(egui::Color32::RED, format!("⚠ {}", alert));
+                        dismiss = ui.small_button("✖").clicked();
+                    });
+                    if dismiss {
+                        self.fixity_alert = None;
+                    }
+                }
+
+                match &self.state {
+                    AppState::Idle => {
+                        // ui.heading("Baggie");
+                        // ui.add_space(30.0);
+
+                        // Drop zone
+                        let drop_zone = egui::Frame::none()
+                            .stroke(egui::Stroke::new(2.0, egui::Color32::GRAY))
+                            .rounding(10.0)
+                            .inner_margin(40.0);
+
+                        drop_zone.show(ui, |ui| {
+                            ui.vertical_centered(|ui| {
+                                ui.label(egui::RichText::new("📁").size(48.0));
+                                ui.add_space(10.0);
+                                ui.label(egui::RichText::new("Drop folder here").size(20.0));
+                                ui.label("to create a bag");
+                                ui.label(
+                                    egui::RichText::new("or a .zip, .tar.gz or other serialized bag to validate it without unpacking")
+                                        .small()
+                                        .color(egui::Color32::GRAY),
+                                );
+                                ui.add_space(20.0);
+
+                                if ui.button("Browse...").clicked() {
+                                    if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                                        self.start_bagging(path);
+                                    }
+                                }
+                            });
+                        });
+
+                        ui.add_space(10.0);
+                        ui.checkbox(&mut self.skip_system_files, "Skip system files")
+                            .on_hover_text(self.settings.skip_names.join(", "));
+                        if ui
+                            .checkbox(&mut self.settings.bag_copy, "Bag a copy, leaving the original untouched")
+                            .on_hover_text("The bag is named and placed as set in Settings; by default next to the folder, as <name>_bag")
+                            .changed()
+                        {
+                            let _ = self.settings.save();
+                        }
+                        if !self.saved_profiles.is_empty() {
+                            let mut chosen = self.settings.bag_profile.clone();
+                            ui.horizontal(|ui| {
+                                ui.label("BagIt Profile");
+                                egui::ComboBox::from_id_salt("bag_profile")
+                                    .selected_text(match chosen.is_empty() {
+                                        true => "None",
+                                        false => chosen.as_str(),
+                                    })
+                                    .show_ui(ui, |ui| {
+                                        ui.selectable_value(&mut chosen, String::new(), "None");
+                                        for name in &self.saved_profiles {
+                                            ui.selectable_value(&mut chosen, name.clone(), name);
+                                        }
+                                    })
+                                    .response
+                                    .on_hover_text("New bags get the fields, algorithms and version it asks for, and aren't made if they wouldn't conform");
+                            });
+                            if chosen != self.settings.bag_profile {
+                                self.choose_profile(chosen);
+                            }
+                        }
+                        ui.horizontal_wrapped(|ui| {
+                            if ui.small_button("✔ Validate a bag...").clicked() {
+                                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                                    self.start_validation(path);
+                                }
+                            }
+                            if ui
+                                .small_button("⚡ Quick check a bag...")
+                                .on_hover_text("Check every listed file is there and Payload-Oxum matches, e.g. after copying a bag to a new drive. Not a full validation: nothing is checksummed")
+                                .clicked()
+                            {
+                                if let Some(path) = rfd::FileDialog::new().pick_folder() {
+                                    self.start_completeness_check(path);
+                                }
+                            }
+                            if ui
+                                .small_button("🗜 Validate a serialized bag...")
+                                .on_hover_text("Check a serialized bag without unpacking it")
+                                .clicked()
+                            {
+                                if let Some(path) = rfd::FileDialog::new()
+                                    .add_filter("Serialized bag", &["zip", "tar", "gz", "tgz", "zst", "tzst", "7z"])
+                                    .pick_file()
+                                {
+                                    self.start_validation(path);
+                                }
+                            }
+                            if ui
+                                .small_button("🧩 Reassemble volumes...")
+                                .on_hover_text("Join a serialized bag split into volumes, checking each one, then validate it")
+                                .clicked()
+                            {
+                                if let Some(list) = rfd::FileDialog::new()
+                                    .set_title("Choose the list of volumes")
+                                    .add_filter("List of volumes", &[VOLUME_LIST_EXTENSION])
+                                    .pick_file()
+                                {
+                                    self.start_reassembly(list);
+                                }
+                            }
+                            if ui
+                                .small_button("⎘ Clone a bag...")
+                                .on_hover_text("Copy a bag under a new identifier, e.g. for a second repository")
+                                .clicked()
+                            {
+                                let source = rfd::FileDialog::new()
+                                    .set_title("Choose the bag to clone")
+                                    .pick_folder();
+                                let dest = source.as_ref().and_then(|_| {
+                                    rfd::FileDialog::new()
+                                        .set_title("Choose an empty folder for the clone")
+                                        .pick_folder()
+                                });
+                                if let (Some(source), Some(dest)) = (source, dest) {
+                                    self.start_clone(source, dest);
+                                }
+                            }
+                            if ui
+                                .small_button("⇄ Compare bags...")
+                                .on_hover_text("List the files added, removed or changed between a bag and a copy of it")
+                                .clicked()
+                            {
+                                let original = rfd::FileDialog::new()
+                                    .set_title("Choose the original bag")
+                                    .pick_folder();
+                                let copy = original.as_ref().and_then(|_| {
+                                    rfd::FileDialog::new()
+                                        .set_title("Choose the bag to compare it with")
+                                        .pick_folder()
+                                });
+                                if let (Some(original), Some(copy)) = (original, copy) {
+                                    self.start_comparison(original, copy);
+                                }
+                            }
+                            if ui
+                                .small_button("⊕ Combine folders...")
+                                .on_hover_text("Bag copies of several folders together, each in its own folder under data/")
+                                .clicked()
+                            {
+                                let paths = rfd::FileDialog::new()
+                                    .set_title("Choose the folders to bag together")
+                                    .pick_folders()
+                                    .unwrap_or_default();
+                                self.combining = payload_sources(paths);
+                                self.state = AppState::Combine;
+                            }
+                            if ui
+                                .small_button("💾 Removable media...")
+                                .on_hover_text(
+                                    "Copy onto a drive or disc, split across volumes if it doesn't fit",
+                                )
+                                .clicked()
+                            {
+                                let source = rfd::FileDialog::new()
+                                    .set_title("Choose the folder to bag")
+                                    .pick_folder();
+                                let dest = source.as_ref().and_then(|_| {
+                                    rfd::FileDialog::new()
+                                        .set_title("Choose the removable drive")
+                                        .pick_folder()
+                                });
+                                if let (Some(source), Some(dest)) = (source, dest) {
+                                    self.start_removable(source, dest);
+                                }
+                            }
+                            if ui
+                                .small_button("🌐 From URLs...")
+                                .on_hover_text("Download each URL in a list into a new bag")
+                                .clicked()
+                            {
+                                let list = rfd::FileDialog::new()
+                                    .set_title("Choose the URL list")
+                                    .pick_file();
+                                let dest = list.as_ref().and_then(|_| {
+                                    rfd::FileDialog::new()
+                                        .set_title("Choose an empty folder for the new bag")
+                                        .pick_folder()
+                                });
+                                if let (Some(list), Some(dest)) = (list, dest) {
+                                    self.start_download(list, dest);
+                                }
+                            }
+                            if ui
+                                .small_button("📋 From a file list...")
+                                .on_hover_text("Bag exactly the files a text or CSV list names, wherever they are")
+                                .clicked()
+                            {
+                                let list = rfd::FileDialog::new()
+                                    .set_title("Choose the file list")
+                                    .add_filter("File list", &["txt", "csv", "tsv"])
+                                    .pick_file();
+                                let dest = list.as_ref().and_then(|_| {
+                                    rfd::FileDialog::new()
+                                        .set_title("Choose an empty folder for the new bag")
+                                        .pick_folder()
+                                });
+                                if let (Some(list), Some(dest)) = (list, dest) {
+                                    self.start_file_list(list, dest);
+                                }
+                            }
+                            if ui.small_button("📊 Inventory").clicked() {
+                                self.state = AppState::Inventory {
+                                    stats: self.inventory.stats(),
+                                };
+                            }
+                            if ui.small_button("🛡 Fixity").clicked() {
+                                self.state = AppState::Fixity;
+                            }
+                            if ui.small_button("📝 Metadata").clicked() {
+                                self.state = AppState::Metadata;
+                            }
+                            if ui
+                                .small_button("🖧 Remote")
+                                .on_hover_text("Bag or validate on a storage server running `baggie agent`")
+                                .clicked()
+                            {
+                                if self.settings.agent_address.is_empty() {
+                                    self.settings.agent_address = DEFAULT_AGENT_ADDRESS.to_string();
+                                }
+                                self.state = AppState::Remote;
+                            }
+                            if ui.small_button("⚙ Settings").clicked() {
+                                self.load_settings_text();
+                                self.state = AppState::Settings;
+                            }
+                        });
+                    }
+
+                    AppState::Remote => {
+                        ui.heading("Remote agent");
+                        ui.label(
+                            egui::RichText::new(
+                                "The agent hashes on its own machine, so nothing crosses the network but progress.",
+                            )
+                            .small(),
+                        );
+                        ui.add_space(10.0);
+
+                        egui::Grid::new("remote").num_columns(2).show(ui, |ui| {
+                            ui.label("Agent");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.settings.agent_address)
+                                    .hint_text(DEFAULT_AGENT_ADDRESS),
+                            );
+                            ui.end_row();
+                            ui.label("Token");
+                            ui.add(egui::TextEdit::singleline(&mut self.settings.agent_token).password(true));
+                            ui.end_row();
+                            ui.label("Folder");
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.remote_path)
+                                    .hint_text("Path as the agent's machine sees it"),
+                            );
+                            ui.end_row();
+                        });
+
+                        ui.add_space(20.0);
+                        let ready = !self.settings.agent_address.trim().is_empty()
+                            && !self.remote_path.trim().is_empty();
+                        ui.horizontal(|ui| {
+                            if ui.add_enabled(ready, egui::Button::new("Bag on agent")).clicked() {
+                                self.start_remote(RemoteJob::Bag(self.remote_path.trim().to_string()));
+                            } else if ui.add_enabled(ready, egui::Button::new("Validate on agent")).clicked() {
+                                self.start_remote(RemoteJob::Validate(self.remote_path.trim().to_string()));
+                            } else if ui.button("Cancel").clicked() {
+                                self.state = AppState::Idle;
+                            }
+                        });
+                    }
+
+                    AppState::Combine => {
+                        ui.heading("Combine folders");
+                        ui.label(
+                            egui::RichText::new(
+                                "Each folder is copied into data/ under the name beside it. Drop more folders here to add them.",
+                            )
+                            .small(),
+                        );
+                        ui.add_space(10.0);
+
+                        let mut remove = None;
+                        egui::ScrollArea::vertical().max_height(160.0).show(ui, |ui| {
+                            for (i, source) in self.combining.iter_mut().enumerate() {
+                                ui.horizontal(|ui| {
+                                    ui.label("data/");
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut source.name)
+                                            .hint_text("Folder name")
+                                            .desired_width(110.0),
+                                    );
+                                    ui.label(egui::RichText::new(source.path.display().to_string()).small());
+                                    if ui.small_button("🗑").clicked() {
+                                        remove = Some(i);
+                                    }
+                                });
+                            }
+                        });
+                        if let Some(i) = remove {
+                            self.combining.remove(i);
+                        }
+                        if ui.small_button("+ Add folders").clicked() {
+                            if let Some(paths) = rfd::FileDialog::new().pick_folders() {
+                                self.add_to_combining(paths);
+                            }
+                        }
+
+                        ui.add_space(20.0);
+                        ui.horizontal(|ui| {
+                            let ready = !self.combining.is_empty();
+                            if ui.add_enabled(ready, egui::Button::new("Bag together...")).clicked() {
+                                if let Some(dest) = rfd::FileDialog::new()
+                                    .set_title("Choose an empty folder for the bag")
+                                    .pick_folder()
+                                {
+                                    self.start_bagging_sources(dest);
+                                }
+                            } else if ui.button("Cancel").clicked() {
+                                self.combining.clear();
+                                self.state = AppState::Idle;
+                            }
+                        });
+                    }
+
+                    AppState::Metadata => {
+                        ui.heading("Bag metadata");
+                        ui.label(
+                            egui::RichText::new(
+                                "Added to bag-info.txt of every bag. Repeat a label (e.g. Contact-Email) to record several values.",
+                            )
+                            .small(),
+                        );
+                        if let Some(profile) = &self.profile {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "New bags are made under the profile {}; the fields it requires are marked *.",
+                                    self.settings.bag_profile
+                                ))
+                                .small(),
+                            )
+                            .on_hover_text(&profile.identifier);
+                        }
+                        ui.add_space(10.0);
+
+                        let mut remove = None;
+                        let profile = self.profile.as_ref();
+                        egui::ScrollArea::vertical().max_height(140.0).show(ui, |ui| {
+                            for (i, (label, value)) in
+                                self.settings.metadata.fields_mut().iter_mut().enumerate()
+                            {
+                                ui.horizontal(|ui| {
+                                    ui.add(
+                                        egui::TextEdit::singleline(label)
+                                            .hint_text("Label")
+                                            .desired_width(110.0),
+                                    );
+                                    ui.add(
+                                        egui::TextEdit::singleline(value)
+                                            .hint_text("Value")
+                                            .desired_width(150.0),
+                                    );
+                                    if ui.small_button("🗑").clicked() {
+                                        remove = Some(i);
+                                    }
+                                    let rule = profile.and_then(|p| {
+                                        p.bag_info.iter().find(|r| r.label.eq_ignore_ascii_case(label))
+                                    });
+                                    if let Some(rule) = rule.filter(|r| r.required) {
+                                        let mut hint = rule.description.clone();
+                                        if !rule.values.is_empty() {
+                                            hint.push_str(&format!("\nOne of: {}", rule.values.join(", ")));
+                                        }
+                                        ui.label("*").on_hover_text(hint.trim());
+                                    }
+                                });
+                            }
+                        });
+                        if let Some(i) = remove {
+                            self.settings.metadata.fields_mut().remove(i);
+                        }
+                        if ui.small_button("+ Add field").clicked() {
+                            self.settings.metadata.push("", "");
+                        }
+
+                        ui.add_space(20.0);
+                        ui.label("BagIt Profiles to pick from for new bags");
+                        let mut removed = None;
+                        for name in &self.saved_profiles {
+                            ui.horizontal(|ui| {
+                                ui.label(name);
+                                if ui.small_button("🗑").clicked() {
+                                    removed = Some(name.clone());
+                                }
+                            });
+                        }
+                        if let Some(name) = removed {
+                            let _ = remove_profile(&name);
+                            self.saved_profiles = saved_profiles();
+                            if self.settings.bag_profile == name {
+                                self.choose_profile(String::new());
+                            }
+                        }
+                        let adding = self.adding_profile.is_some();
+                        ui.horizontal(|ui| {
+                            let from_file = egui::Button::new("Add from file...").small();
+                            if ui.add_enabled(!adding, from_file).clicked() {
+                                if let Some(file) = rfd::FileDialog::new()
+                                    .add_filter("BagIt Profile", &["json"])
+                                    .pick_file()
+                                {
+                                    self.add_profile(&file.to_string_lossy());
+                                }
+                            }
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.profile_url)
+                                    .hint_text("https://...")
+                                    .desired_width(180.0),
+                            );
+                            let url = self.profile_url.trim().to_string();
+                            let add = egui::Button::new("Add").small();
+                            if ui.add_enabled(!adding && !url.is_empty(), add).clicked() {
+                                self.add_profile(&url);
+                                self.profile_url.clear();
+                            }
+                            if adding {
+                                ui.spinner();
+                            }
+                        });
+
+                        ui.add_space(20.0);
+                        if ui.button("Done").clicked() {
+                            self.settings
+                                .metadata
+                                .fields_mut()
+                                .retain(|(label, _)| !label.trim().is_empty());
+                            let _ = self.settings.save();
+                            self.state = AppState::Idle;
+                        }
+                    }
+
+                    AppState::Validated { report } => {
+                        let (valid, invalid) = match report.completeness_only {
+                            true => ("Quick check passed: bag is complete", "Quick check failed: bag is incomplete"),
+                            false => ("Bag is valid", "Bag is invalid"),
+                        };
+                        if report.is_valid() {
+                            ui.label(egui::RichText::new("✅").size(48.0));
+                            ui.add_space(10.0);
+                            ui.heading(valid);
+                        } else {
+                            ui.label(egui::RichText::new("❌").size(48.0));
+                            ui.add_space(10.0);
+                            ui.heading(invalid);
+                        }
+                        if let Some(signer) = &report.signed_by {
+                            ui.label(format!("🔏 Tag manifest signed by {}", signer));
+                        }
+                        if report.completeness_only {
+                            ui.label(
+                                egui::RichText::new(
+                                    "Not a full validation: files were counted and sized, not checksummed, so changed contents go unnoticed.",
+                                )
+                                .color(egui::Color32::YELLOW),
+                            );
+                        }
+                        ui.add_space(10.0);
+
+                        let mode = match report.mode {
+                            ValidationMode::Strict => "strict",
+                            ValidationMode::Lenient => "lenient",
+                        };
+                        ui.label(format!(
+                            "{} files checked ({} mode)",
+                            locale.count(report.files_checked as u64),
+                            mode
+                        ));
+                        ui.label(
+                            egui::RichText::new(report.path.to_string_lossy())
+                                .small()
+                                .color(egui::Color32::GRAY),
+                        );
+
+                        egui::ScrollArea::vertical().max_height(120.0).show(ui, |ui| {
+                            for (kind, count) in report.error_counts() {
+                                ui.label(
+                                    egui::RichText::new(format!(
+                                        "{} ({})",
+                                        kind.label(),
+                                        locale.count(count as u64)
+                                    ))
+                                    .small()
+                                    .strong()
+                                    .color(egui::Color32::RED),
+                                );
+                                for error in report.errors_of(kind) {
+                                    ui.label(
+                                        egui::RichText::new(error.to_string())
+                                            .small()
+                                            .color(egui::Color32::RED),
+                                    );
+                                }
+                            }
+                            for warning in &report.warnings {
+                                ui.label(
+                                    egui::RichText::new(format!("⚠ {}", warning))
+                                        .small()
+                                        .color(egui::Color32::YELLOW),
+                                );
+                            }
+                        });
+
+                        ui.add_space(20.0);
+                        if !report.is_valid()
+                            && ui
+                                .button("📋 Copy the problem files")
+                                .on_hover_text("Each kind of problem, with the files it's about")
+                                .clicked()
+                        {
+                            let mut text = String::new();
+                            for (kind, _) in report.error_counts() {
+                                let files = report.files(kind);
+                                if !files.is_empty() {
+                                    text.push_str(&format!("{}:\n", kind.label()));
+                                    for file in files {
+                                        text.push_str(&format!("  {}\n", file));
+                                    }
+                                }
+                            }
+                            ui.ctx().copy_text(text);
+                        }
+                        if ui
+                            .small_button("Save JSON report...")
+                            .on_hover_text("The full report, for ingest scripts")
+                            .clicked()
+                        {
+                            if let Some(file) = rfd::FileDialog::new()
+                                .add_filter("JSON", &["json"])
+                                .set_file_name("validation.json")
+                                .save_file()
+                            {
+                                let _ = std::fs::write(file, report.to_json());
+                            }
+                        }
+                        let full_check = report.completeness_only.then(|| report.path.clone());
+                        if !report.mismatches.is_empty()
+                            && ui
+                                .button(format!("🔧 Triage {} mismatches", report.mismatches.len()))
+                                .clicked()
+                        {
+                            self.triage = Some(Triage {
+                                bag: report.path.clone(),
+                                mismatches: report.mismatches.clone(),
+                                selected: 0,
+                                note: String::new(),
+                                outcome: None,
+                            });
+                            self.state = AppState::Triage;
+                        }
+                        if let Some(path) = full_check {
+                            if ui
+                                .button("Validate it fully")
+                                .on_hover_text("Compare every file's checksum too")
+                                .clicked()
+                            {
+                                self.start_validation(path);
+                            }
+                        }
+                        if ui.button("Done").clicked() {
+                            self.state = AppState::Idle;
+                        }
+                    }
+
+                    AppState::Triage => {
+                        let Some(triage) = &mut self.triage else {
+                            self.state = AppState::Idle;
+                            return;
+                        };
+                        let mismatch = triage.mismatches[triage.selected].clone();
+                        ui.heading(format!(
+                            "Mismatch {} of {}",
+                            triage.selected + 1,
+                            triage.mismatches.len()
+                        ));
+                        ui.label(mismatch.file.as_str());
+                        ui.label(
+                            egui::RichText::new(format!(
+                                "expected {}\nactual   {}",
+                                mismatch.expected, mismatch.actual
+                            ))
+                            .small()
+                            .monospace(),
+                        );
+                        ui.add_space(10.0);
+
+                        ui.horizontal_wrapped(|ui| {
+                            if ui.small_button("Re-hash").clicked() {
+                                triage.outcome = Some(match rehash(&triage.bag, &mismatch) {
+                                    Ok(true) => "Matches now: most likely a transient read error".to_string(),
+                                    Ok(false) => "Still doesn't match".to_string(),
+                                    Err(e) => format!("Could not read it: {}", e),
+                                });
+                            }
+                            if ui.small_button("Compare with a copy...").clicked() {
+                                if let Some(copy) = rfd::FileDialog::new()
+                                    .set_title("Choose a copy of the bag or of the original folder")
+                                    .pick_folder()
+                                {
+                                    triage.outcome = Some(match compare_with_copy(&copy, &mismatch) {
+                                        Ok(CopyComparison::Matches(path)) => format!(
+                                            "The copy at {} is intact; restore from it below",
+                                            path.display()
+                                        ),
+                                        Ok(CopyComparison::Differs { path, actual }) => format!(
+                                            "The copy at {} doesn't match either ({})",
+                                            path.display(),
+                                            actual
+                                        ),
+                                        Ok(CopyComparison::Missing) => {
+                                            "The copy doesn't have this file".to_string()
+                                        }
+                                        Err(e) => format!("Could not read the copy: {}", e),
+                                    });
+                                }
+                            }
+                            if ui.small_button("Restore from file...").clicked() {
+                                if let Some(replacement) = rfd::FileDialog::new().pick_file() {
+                                    let restored = restore(&triage.bag, &mismatch, &replacement);
+                                    triage.outcome = Some(match restored {
+                                        Ok(()) => {
+                                            self.inventory.clear_damaged(&triage.bag, &mismatch.file);
+                                            let _ = self.inventory.save();
+                                            "Restored; the file matches its manifest entry again".to_string()
+                                        }
+                                        Err(e) => e.to_string(),
+                                    });
+                                }
+                            }
+                        });
+
+                        ui.add_space(10.0);
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut triage.note)
+                                    .hint_text("Note, e.g. where the damage came from")
+                                    .desired_width(200.0),
+                            );
+                            if ui.small_button("Mark as known damaged").clicked() {
+                                self.inventory
+                                    .mark_damaged(&triage.bag, &mismatch.file, &triage.note);
+                                let _ = self.inventory.save();
+                                triage.outcome = Some("Recorded as known damaged".to_string());
+                            }
+                        });
+
+                        if let Some(outcome) = &triage.outcome {
+                            ui.add_space(10.0);
+                            ui.label(egui::RichText::new(outcome).small());
+                        }
+
+                        ui.add_space(10.0);
+                        let mut done = false;
+                        ui.horizontal(|ui| {
+                            if ui
+                                .add_enabled(triage.selected > 0, egui::Button::new("◀ Previous"))
+                                .clicked()
+                            {
+                                triage.selected -= 1;
+                                triage.note.clear();
+                                triage.outcome = None;
+                            }
+                            if ui
+                                .add_enabled(
+                                    triage.selected + 1 < triage.mismatches.len(),
+                                    egui::Button::new("Next ▶"),
+                                )
+                                .clicked()
+                            {
+                                triage.selected += 1;
+                                triage.note.clear();
+                                triage.outcome = None;
+                            }
+                            done = ui.button("Done").clicked();
+                        });
+                        if done {
+                            self.triage = None;
+                            self.state = AppState::Idle;
+                        }
+                    }
+
+                    AppState::Settings => {
+                        ui.heading("Settings");
+                        ui.add_space(10.0);
+
+                        egui::ScrollArea::vertical().show(ui, |ui| {
+                            ui.label("Bagging-Date clock");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(&mut self.settings.date_mode, DateMode::Local, "Local time");
+                                ui.radio_value(&mut self.settings.date_mode, DateMode::Utc, "UTC");
+                            });
+                            ui.checkbox(
+                                &mut self.settings.include_bagging_time,
+                                "Also write Bagging-Time with UTC offset",
+                            );
+
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label("Name copied bags");
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut self.settings.name_template)
+                                        .hint_text(DEFAULT_NAME_TEMPLATE)
+                                        .desired_width(160.0),
+                                )
+                                .on_hover_text("{folder}, {date}, {time} and {algorithm} are filled in, e.g. {folder}-{date}-{algorithm}");
+                            });
+                            ui.horizontal(|ui| {
+                                let folder = match &self.settings.output_dir {
+                                    Some(dir) => dir.display().to_string(),
+                                    None => "next to the folder".to_string(),
+                                };
+                                ui.label(format!("Put copied bags {}", folder));
+                                if ui.small_button("Choose...").clicked() {
+                                    if let Some(dir) = rfd::FileDialog::new().pick_folder() {
+                                        self.settings.output_dir = Some(dir);
+                                    }
+                                }
+                                if self.settings.output_dir.is_some() && ui.small_button("Next to the folder").clicked() {
+                                    self.settings.output_dir = None;
+                                }
+                            });
+                            ui.checkbox(
+                                &mut self.settings.record_provenance,
+                                "Record where each file came from (provenance.csv)",
+                            )
+                            .on_hover_text("Original path, size, modification time and owner of every payload file");
+                            ui.checkbox(
+                                &mut self.settings.record_file_times,
+                                "Record each file's original timestamps (file-metadata.tsv)",
+                            )
+                            .on_hover_text("Modification, access and creation times of every payload file, to the nanosecond");
+                            ui.checkbox(
+                                &mut self.settings.record_permissions,
+                                "Record each file's permissions and owner (file-permissions.tsv)",
+                            )
+                            .on_hover_text("Unix mode, user ID and group ID, put back when the bag is undone or by `baggie restore-permissions`");
+                            ui.checkbox(
+                                &mut self.settings.record_xattrs,
+                                "Record extended attributes and Finder tags (extended-attributes.tsv)",
+                            )
+                            .on_hover_text("Every extended attribute of every payload file, which a bag otherwise loses");
+                            ui.checkbox(
+                                &mut self.settings.event_log,
+                                "Keep a log of what was done to the bag (events.csv)",
+                            )
+                            .on_hover_text("Timestamped events for walking, moving, copying, checksumming, serializing and transfers");
 
-        if clear_rx {
-            self.progress_rx = None;
-        }
-    }
-}
+                            ui.add_space(10.0);
+                            ui.label("BagIt version for new bags");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(&mut self.settings.bagit_version, BagItVersion::V1_0, "1.0 (RFC 8493)");
+                                ui.radio_value(&mut self.settings.bagit_version, BagItVersion::V0_97, "0.97, for older tools");
+                            });
+                            ui.add_space(10.0);
+                            ui.label("Checksum algorithms for new bags (a manifest each)");
+                            ui.horizontal(|ui| {
+                                for algorithm in ChecksumAlgorithm::ALL {
+                                    let mut on = self.settings.algorithms.contains(&algorithm);
+                                    // The last one left stays on
+                                    let last = on && self.settings.algorithms.len() == 1;
+                                    if ui.add_enabled(!last, egui::Checkbox::new(&mut on, algorithm.label())).changed() {
+                                        if on {
+                                            self.settings.algorithms.push(algorithm);
+                                        } else {
+                                            self.settings.algorithms.retain(|a| *a != algorithm);
+                                        }
+                                    }
+                                }
+                            });
 
-impl eframe::App for BagItApp {
-    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
-        // Process any pending progress updates
-        self.process_progress();
+                            ui.add_space(10.0);
+                            ui.label("Format for serialized bags");
+                            ui.horizontal(|ui| {
+                                for (format, label) in [
+                                    (SerialFormat::Tar, "tar"),
+                                    (SerialFormat::TarGz, "tar.gz"),
+                                    (SerialFormat::TarZst, "tar.zst"),
+                                    (SerialFormat::Zip, "zip"),
+                                    (SerialFormat::SevenZip, "7z"),
+                                ] {
+                                    ui.radio_value(&mut self.settings.serial_format, format, label);
+                                }
+                            });
+                            ui.checkbox(&mut self.settings.reproducible_archives, "Reproducible archives")
+                                .on_hover_text("Serializing the same bag twice gives identical files: fixed dates, no packing event, and zstd on one thread");
+                            if self.settings.serial_format == SerialFormat::TarZst {
+                                ui.horizontal(|ui| {
+                                    ui.label("zstd level");
+                                    ui.add(egui::DragValue::new(&mut self.settings.zstd_level).range(1..=22))
+                                        .on_hover_text("1 is fastest, 22 smallest; 3 is a good balance, and past 19 takes a lot of memory");
+                                    ui.label("Compression threads");
+                                    ui.add(egui::DragValue::new(&mut self.settings.compression_threads).range(1..=256))
+                                        .on_hover_text("Compresses parts of the archive side by side, so a fast disk isn't left waiting");
+                                });
+                            }
+                            ui.horizontal(|ui| {
+                                let mut split = self.settings.volume_size.is_some();
+                                let toggled = ui
+                                    .checkbox(&mut split, "Split into volumes of")
+                                    .on_hover_text("For upload services that cap the size of one file. Volumes are numbered .001, .002 and on, with a .sha256 list to check and reassemble them by")
+                                    .changed();
+                                let mut megabytes = self.settings.volume_size.unwrap_or(DEFAULT_VOLUME_SIZE) / 1_000_000;
+                                let resized = ui
+                                    .add_enabled(
+                                        split,
+                                        egui::DragValue::new(&mut megabytes).range(1..=u64::MAX).suffix(" MB"),
+                                    )
+                                    .changed();
+                                if toggled || resized {
+                                    self.settings.volume_size = split.then_some(megabytes.max(1) * 1_000_000);
+                                }
+                            });
+                            ui.horizontal(|ui| {
+                                ui.label("Encrypt serialized bags with");
+                                ui.radio_value(&mut self.settings.encryption_tool, None, "nothing");
+                                ui.radio_value(&mut self.settings.encryption_tool, Some(EncryptionTool::Age), "age");
+                                ui.radio_value(&mut self.settings.encryption_tool, Some(EncryptionTool::Gpg), "gpg");
+                            })
+                            .response
+                            .on_hover_text("Only the encrypted file is kept, and bag-info.txt records who it's for. The age or gpg program must be installed");
+                            if let Some(tool) = self.settings.encryption_tool {
+                                ui.label("Recipients (one per line; any of them can decrypt)");
+                                ui.add(
+                                    egui::TextEdit::multiline(&mut self.recipients_text)
+                                        .desired_rows(2)
+                                        .hint_text(match tool {
+                                            EncryptionTool::Age => "age1...",
+                                            EncryptionTool::Gpg => "key ID, fingerprint or email address",
+                                        }),
+                                );
+                                if self.recipients_text.trim().is_empty() {
+                                    ui.colored_label(
+                                        egui::Color32::YELLOW,
+                                        "With no recipients, serializing will fail rather than write an unencrypted bag",
+                                    );
+                                }
+                            }
+                            ui.horizontal(|ui| {
+                                ui.label("Sign new bags' tag manifests with");
+                                ui.radio_value(&mut self.settings.signing_tool, None, "nothing");
+                                ui.radio_value(&mut self.settings.signing_tool, Some(SigningTool::Gpg), "gpg");
+                                ui.radio_value(&mut self.settings.signing_tool, Some(SigningTool::Minisign), "minisign");
+                            })
+                            .response
+                            .on_hover_text("A detached signature over tagmanifest-sha256.txt, which covers the whole bag. The gpg or minisign program must be installed");
+                            if let Some(tool) = self.settings.signing_tool {
+                                ui.horizontal(|ui| {
+                                    ui.label("Key");
+                                    ui.add(
+                                        egui::TextEdit::singleline(&mut self.settings.signing_key)
+                                            .hint_text(match tool {
+                                                SigningTool::Gpg => "key ID or email address; blank for your default key",
+                                                SigningTool::Minisign => "secret key file, made with minisign -G -W",
+                                            })
+                                            .desired_width(280.0),
+                                    );
+                                    if tool == SigningTool::Minisign && ui.small_button("Choose...").clicked() {
+                                        if let Some(file) = rfd::FileDialog::new().pick_file() {
+                                            self.settings.signing_key = file.display().to_string();
+                                        }
+                                    }
+                                });
+                            }
+                            ui.horizontal(|ui| {
+                                let key = match &self.settings.minisign_public_key {
+                                    Some(file) => file.display().to_string(),
+                                    None => "none".to_string(),
+                                };
+                                ui.label(format!("Check minisign signatures against {}", key));
+                                if ui.small_button("Choose...").clicked() {
+                                    if let Some(file) = rfd::FileDialog::new().pick_file() {
+                                        self.settings.minisign_public_key = Some(file);
+                                    }
+                                }
+                                if self.settings.minisign_public_key.is_some() && ui.small_button("None").clicked() {
+                                    self.settings.minisign_public_key = None;
+                                }
+                            })
+                            .response
+                            .on_hover_text("GnuPG signatures are checked against your keyring");
 
-        // Request repaint while processing
-        if self.progress_rx.is_some() {
-            ctx.request_repaint();
-        }
+                            ui.add_space(10.0);
+                            ui.label("Files and folders to skip (one per line; * and ? are wildcards)");
+                            ui.add(
+                                egui::TextEdit::multiline(&mut self.skip_names_text)
+                                    .desired_rows(6),
+                            );
+                            if ui.small_button("Reset to defaults").clicked() {
+                                self.skip_names_text = default_skip_names().join("\n");
+                            }
+                            ui.checkbox(
+                                &mut self.settings.skip_appledouble,
+                                "Skip AppleDouble files (._*) by their content, whatever the list says",
+                            )
+                            .on_hover_text("The shadow files macOS leaves beside files on FAT and exFAT drives");
+                            ui.checkbox(
+                                &mut self.settings.strip_quarantine,
+                                "Remove the macOS quarantine flag from copied files",
+                            );
+                            ui.checkbox(
+                                &mut self.settings.clone_files,
+                                "Clone files instead of copying them where the disk can",
+                            )
+                            .on_hover_text("APFS, Btrfs and XFS share the original's blocks, so a copy takes no time or space");
+                            ui.checkbox(
+                                &mut self.settings.follow_symlinks,
+                                "Follow symlinks when bagging a copy",
+                            )
+                            .on_hover_text("Bags what the links point at; a link back into its own folder stops bagging");
+                            ui.horizontal(|ui| {
+                                ui.label("Stop at folders nested deeper than");
+                                ui.add(egui::DragValue::new(&mut self.settings.max_depth).range(1..=4096));
+                            });
 
-        // Handle dropped files
-        let dropped_files: Vec<PathBuf> = ctx
-            .input(|i| {
-                i.raw
-                    .dropped_files
-                    .iter()
-                    .filter_map(|f| f.path.clone())
-                    .collect()
-            });
+                            ui.add_space(10.0);
+                            ui.label("When a file can't be read or vanishes mid-run");
+                            error_policy_radios(ui, &mut self.settings.error_policy);
 
-        if let Some(path) = dropped_files.into_iter().next() {
-            if path.is_dir() && matches!(self.state, AppState::Idle | AppState::Done { .. } | AppState::Error { .. }) {
-                self.start_bagging(path);
-            }
-        }
+                            ui.add_space(10.0);
+                            ui.label("When the folder has no files");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(
+                                    &mut self.settings.empty_source,
+                                    EmptySourcePolicy::Refuse,
+                                    "Refuse",
+                                );
+                                ui.radio_value(
+                                    &mut self.settings.empty_source,
+                                    EmptySourcePolicy::Allow,
+                                    "Make an empty bag",
+                                );
+                            });
 
-        egui::CentralPanel::default().show(ctx, |ui| {
-            ui.vertical_centered(|ui| {
-                // ui.add_space(40.0);
+                            ui.add_space(10.0);
+                            ui.label("Empty folders, which manifests can't list");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(&mut self.settings.empty_dirs, EmptyDirPolicy::Warn, "Warn");
+                                ui.radio_value(
+                                    &mut self.settings.empty_dirs,
+                                    EmptyDirPolicy::Placeholder,
+                                    "Add a .keep file",
+                                );
+                                ui.radio_value(
+                                    &mut self.settings.empty_dirs,
+                                    EmptyDirPolicy::Record,
+                                    "List in empty-folders.txt",
+                                );
+                            });
 
-                match &self.state {
-                    AppState::Idle => {
-                        // ui.heading("Baggie");
-                        // ui.add_space(30.0);
+                            ui.add_space(10.0);
+                            ui.label("Names that differ only in case")
+                                .on_hover_text("Like Report.pdf and report.pdf, which collide on Windows and macOS");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(
+                                    &mut self.settings.case_collisions,
+                                    CaseCollisionPolicy::Warn,
+                                    "Warn",
+                                );
+                                ui.radio_value(
+                                    &mut self.settings.case_collisions,
+                                    CaseCollisionPolicy::Refuse,
+                                    "Refuse",
+                                );
+                            });
 
-                        // Drop zone
-                        let drop_zone = egui::Frame::none()
-                            .stroke(egui::Stroke::new(2.0, egui::Color32::GRAY))
-                            .rounding(10.0)
-                            .inner_margin(40.0);
+                            ui.add_space(10.0);
+                            ui.label("Internal-Sender-Identifier for new bags");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(&mut self.settings.id_scheme, IdScheme::Uuid, "UUID");
+                                ui.radio_value(&mut self.settings.id_scheme, IdScheme::Ulid, "ULID")
+                                    .on_hover_text("Sorts by the time the bag was made");
+                                let sequence = matches!(self.settings.id_scheme, IdScheme::Sequence(_));
+                                if ui.radio(sequence, "Prefix and number").clicked() && !sequence {
+                                    self.settings.id_scheme = IdScheme::Sequence(String::new());
+                                }
+                                if let IdScheme::Sequence(prefix) = &mut self.settings.id_scheme {
+                                    ui.add(
+                                        egui::TextEdit::singleline(prefix)
+                                            .hint_text("ACC-")
+                                            .desired_width(80.0),
+                                    )
+                                    .on_hover_text("Numbering carries on from the highest one in the inventory");
+                                }
+                            });
 
-                        drop_zone.show(ui, |ui| {
-                            ui.vertical_centered(|ui| {
-                                ui.label(egui::RichText::new("📁").size(48.0));
-                                ui.add_space(10.0);
-                                ui.label(egui::RichText::new("Drop folder here").size(20.0));
-                                ui.label("to create a bag");
-                                ui.add_space(20.0);
+                            ui.add_space(10.0);
+                            ui.label("Files already stored in an inventoried bag");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(&mut self.settings.dedup, DedupMode::Off, "Store");
+                                ui.radio_value(
+                                    &mut self.settings.dedup,
+                                    DedupMode::Report,
+                                    "Store and report",
+                                );
+                                ui.radio_value(
+                                    &mut self.settings.dedup,
+                                    DedupMode::Fetch,
+                                    "Reference in fetch.txt",
+                                )
+                                .on_hover_text("The new bag points to the existing copy instead of holding a second one");
+                            });
 
-                                if ui.button("Browse...").clicked() {
-                                    if let Some(path) = rfd::FileDialog::new().pick_folder() {
-                                        self.start_bagging(path);
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label("Re-check inventoried bags every");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.settings.audit_interval_days)
+                                        .range(0..=3650)
+                                        .suffix(" days"),
+                                )
+                                .on_hover_text("0 turns background fixity checks off");
+                                ui.label("hashing");
+                                ui.add(
+                                    egui::DragValue::new(&mut self.settings.audit_sample_percent)
+                                        .range(0..=100)
+                                        .suffix("%"),
+                                )
+                                .on_hover_text("A random share of each bag's payload files; 0 hashes them all. Every file is still checked to be there");
+                            });
+
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label("Max parallel jobs");
+                                ui.add(egui::DragValue::new(&mut self.settings.max_jobs).range(1..=32))
+                                    .on_hover_text("Bagging, validation and background fixity checks together");
+                                ui.label("Hashing threads per job");
+                                ui.add(egui::DragValue::new(&mut self.settings.hash_threads).range(1..=64))
+                                    .on_hover_text("More threads help on SSDs; 1 is kindest to spinning disks");
+                            });
+                            ui.checkbox(
+                                &mut self.settings.verify_after,
+                                "Read every file back after bagging and check it against the manifest",
+                            )
+                            .on_hover_text("Catches copies that went wrong without an error, as on flaky USB drives; takes as long again");
+
+                            ui.add_space(10.0);
+                            ui.horizontal(|ui| {
+                                ui.label("Numbers and dates");
+                                egui::ComboBox::from_id_salt("locale")
+                                    .selected_text(self.settings.locale.label())
+                                    .show_ui(ui, |ui| {
+                                        for option in Locale::ALL {
+                                            ui.selectable_value(
+                                                &mut self.settings.locale,
+                                                option,
+                                                option.label(),
+                                            );
+                                        }
+                                    });
+                            })
+                            .response
+                            .on_hover_text("Only changes what Baggie shows; tag files keep the spec's formats");
+
+                            ui.add_space(10.0);
+                            ui.label("Validation");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(
+                                    &mut self.settings.validation_mode,
+                                    ValidationMode::Lenient,
+                                    "Lenient",
+                                )
+                                .on_hover_text("Formatting quirks from other tools are warnings");
+                                ui.radio_value(
+                                    &mut self.settings.validation_mode,
+                                    ValidationMode::Strict,
+                                    "Strict",
+                                )
+                                .on_hover_text("Anything short of the exact spec is an error");
+                            });
+
+                            ui.add_space(10.0);
+                            ui.label("Plugins (one `stage: command` per line)").on_hover_text(
+                                "Stages: after-enumeration, after-hashing, before-sealing. \
+                                 See the README for what a plugin receives and may do.",
+                            );
+                            ui.add(
+                                egui::TextEdit::multiline(&mut self.plugins_text)
+                                    .desired_rows(3)
+                                    .hint_text("before-sealing: /usr/local/bin/format-report"),
+                            );
+                            let unreadable = self
+                                .plugins_text
+                                .lines()
+                                .filter(|l| !l.trim().is_empty() && Plugin::parse(l).is_none())
+                                .count();
+                            if unreadable > 0 {
+                                ui.colored_label(
+                                    egui::Color32::YELLOW,
+                                    format!("{} lines aren't `stage: command` and will be dropped", unreadable),
+                                );
+                            }
+                        });
+
+                        ui.add_space(20.0);
+                        ui.horizontal(|ui| {
+                            if ui
+                                .button("Export settings...")
+                                .on_hover_text("One file to set up other workstations the same way")
+                                .clicked()
+                            {
+                                self.apply_settings_text();
+                                if let Some(file) = rfd::FileDialog::new()
+                                    .set_file_name("baggie-settings.txt")
+                                    .save_file()
+                                {
+                                    if let Err(e) = std::fs::write(&file, self.settings.to_bundle()) {
+                                        self.state = AppState::Error {
+                                            message: format!("Could not export settings: {}", e),
+                                        };
                                     }
                                 }
-                            });
+                            }
+                            if ui.button("Import settings...").clicked() {
+                                if let Some(file) = rfd::FileDialog::new().pick_file() {
+                                    let imported = std::fs::read_to_string(&file)
+                                        .ok()
+                                        .and_then(|bundle| self.settings.with_bundle(&bundle));
+                                    match imported {
+                                        Some(settings) => {
+                                            self.settings = settings;
+                                            self.load_settings_text();
+                                        }
+                                        None => {
+                                            self.state = AppState::Error {
+                                                message: format!(
+                                                    "{} is not a Baggie settings bundle",
+                                                    file.display()
+                                                ),
+                                            };
+                                        }
+                                    }
+                                }
+                            }
+                            if ui.button("Done").clicked() {
+                                self.apply_settings_text();
+                                let _ = self.settings.save();
+                                self.state = AppState::Idle;
+                            }
                         });
                     }
 
@@ -195,7 +2302,7 @@ impl eframe::App for BagItApp {
 
                         if *total_files > 0 {
                             let progress = *current as f32 / *total_files as f32;
-                            ui.add(egui::ProgressBar::new(progress).show_percentage());
+                            ui.add(egui::ProgressBar::new(progress).text(locale.percent(progress)));
                         } else {
                             ui.spinner();
                         }
@@ -209,15 +2316,51 @@ impl eframe::App for BagItApp {
                                     .color(egui::Color32::GRAY),
                             );
                         }
+                        if self.failed_checks > 0 {
+                            ui.add_space(10.0);
+                            ui.colored_label(
+                                egui::Color32::RED,
+                                format!(
+                                    "✗ {} files failed so far",
+                                    locale.count(self.failed_checks as u64)
+                                ),
+                            );
+                        }
                     }
 
-                    AppState::Done { path, file_count } => {
+                    AppState::Done {
+                        path,
+                        file_count,
+                        report,
+                        summary,
+                        qr,
+                    } => {
                         ui.label(egui::RichText::new("✅").size(48.0));
                         ui.add_space(10.0);
                         ui.heading("Bag Created!");
                         ui.add_space(20.0);
 
-                        ui.label(format!("{} files bagged", file_count));
+                        if let Some(summary) = summary.as_ref().filter(|s| !s.sender_id.is_empty()) {
+                            ui.horizontal(|ui| {
+                                ui.label("ID");
+                                ui.label(
+                                    egui::RichText::new(&summary.sender_id)
+                                        .monospace()
+                                        .size(18.0)
+                                        .strong(),
+                                );
+                                if ui
+                                    .small_button("📋")
+                                    .on_hover_text("Copy the Internal-Sender-Identifier")
+                                    .clicked()
+                                {
+                                    ui.ctx().copy_text(summary.sender_id.clone());
+                                }
+                            });
+                            ui.add_space(10.0);
+                        }
+
+                        ui.label(format!("{} files bagged", locale.count(*file_count as u64)));
                         ui.add_space(10.0);
 
                         ui.label(
@@ -226,13 +2369,559 @@ impl eframe::App for BagItApp {
                                 .color(egui::Color32::GRAY),
                         );
 
-                        ui.add_space(30.0);
+                        for warning in &report.warnings {
+                            ui.add_space(10.0);
+                            ui.label(
+                                egui::RichText::new(format!("⚠ {}", warning))
+                                    .small()
+                                    .color(egui::Color32::YELLOW),
+                            );
+                        }
+
+                        if !report.skipped.is_empty() {
+                            ui.add_space(10.0);
+                            ui.collapsing(
+                                format!("{} system files skipped", report.skipped.len()),
+                                |ui| {
+                                    for skipped in &report.skipped {
+                                        ui.label(
+                                            egui::RichText::new(skipped.to_string_lossy()).small(),
+                                        );
+                                    }
+                                },
+                            );
+                        }
+
+                        if !report.vanished.is_empty() {
+                            ui.add_space(10.0);
+                            ui.collapsing(
+                                format!("⚠ {} files vanished during bagging", report.vanished.len()),
+                                |ui| {
+                                    for vanished in &report.vanished {
+                                        ui.label(
+                                            egui::RichText::new(vanished.to_string_lossy()).small(),
+                                        );
+                                    }
+                                },
+                            );
+                        }
+
+                        if !report.duplicates.is_empty() {
+                            let wasted = report.duplicates.iter().map(|d| d.wasted()).sum();
+                            ui.add_space(10.0);
+                            ui.collapsing(
+                                format!(
+                                    "⧉ {} sets of identical files ({} could be saved)",
+                                    locale.count(report.duplicates.len() as u64),
+                                    locale.bytes(wasted)
+                                ),
+                                |ui| {
+                                    for duplicates in &report.duplicates {
+                                        let note = match duplicates.copies {
+                                            1 => "hardlinks, stored once".to_string(),
+                                            _ => locale.bytes(duplicates.size) + " each",
+                                        };
+                                        ui.label(
+                                            egui::RichText::new(format!(
+                                                "{} ({})",
+                                                duplicates.files.join(", "),
+                                                note
+                                            ))
+                                            .small(),
+                                        );
+                                    }
+                                },
+                            );
+                        }
+
+                        if let (Some(summary), Some(qr)) = (summary, qr) {
+                            ui.add_space(10.0);
+                            ui.collapsing("▦ QR code", |ui| {
+                                draw_qr(ui, qr, 4.0);
+                                ui.label(
+                                    egui::RichText::new(summary.to_label_text())
+                                        .small()
+                                        .monospace(),
+                                );
+                                if ui.small_button("Save as SVG...").clicked() {
+                                    if let Some(file) = rfd::FileDialog::new()
+                                        .add_filter("SVG", &["svg"])
+                                        .set_file_name(format!("{}.svg", summary.identifier))
+                                        .save_file()
+                                    {
+                                        let _ = std::fs::write(file, qr_svg(qr));
+                                    }
+                                }
+                            });
+                        }
+
+                        ui.add_space(10.0);
+                        if ui
+                            .small_button("🖨 Save receipt...")
+                            .on_hover_text("A printable summary to sign when the media changes hands")
+                            .clicked()
+                        {
+                            let name = summary
+                                .as_ref()
+                                .map(|s| s.identifier.clone())
+                                .unwrap_or_else(|| "bag".to_string());
+                            if let Some(file) = rfd::FileDialog::new()
+                                .add_filter("HTML", &["html"])
+                                .set_file_name(format!("{}-receipt.html", name))
+                                .save_file()
+                            {
+                                let result = receipt_html(path, report)
+                                    .and_then(|html| Ok(std::fs::write(file, html)?));
+                                if let Err(e) = result {
+                                    self.state = AppState::Error {
+                                        message: e.to_string(),
+                                    };
+                                    return;
+                                }
+                            }
+                        }
+
+                        if ui
+                            .small_button("📦 Serialize...")
+                            .on_hover_text(format!(
+                                "Write the bag into one .{} file, as set in Settings",
+                                self.settings.serial_format.extension()
+                            ))
+                            .clicked()
+                        {
+                            if let Some(dest) = rfd::FileDialog::new()
+                                .set_title("Choose where to put the serialized bag")
+                                .pick_folder()
+                            {
+                                let path = path.clone();
+                                self.start_serializing(path, dest);
+                                return;
+                            }
+                        }
+
+                        if ui
+                            .small_button("↶ Undo")
+                            .on_hover_text("Move the files out of data/ and remove the tag files, as before bagging")
+                            .clicked()
+                        {
+                            let path = path.clone();
+                            match unbag_directory(&path) {
+                                Ok(()) => {
+                                    self.inventory.forget(&path);
+                                    let _ = self.inventory.save();
+                                    self.state = AppState::Idle;
+                                }
+                                Err(e) => {
+                                    self.state = AppState::Error {
+                                        message: e.to_string(),
+                                    };
+                                }
+                            }
+                            return;
+                        }
+
+                        ui.add_space(20.0);
 
                         if ui.button("Bag Another Folder").clicked() {
                             self.state = AppState::Idle;
                         }
                     }
 
+                    AppState::Inventory { stats } => {
+                        ui.heading("Inventory");
+                        ui.add_space(10.0);
+                        ui.label(format!(
+                            "{} bags, {} files, {}",
+                            locale.count(stats.total.bags),
+                            locale.count(stats.total.files),
+                            locale.bytes(stats.total.bytes)
+                        ));
+                        ui.add_space(10.0);
+
+                        egui::ScrollArea::vertical().max_height(120.0).show(ui, |ui| {
+                            ui.collapsing("Growth by month", |ui| {
+                                let months: Vec<(String, Tally)> = stats
+                                    .cumulative_by_month()
+                                    .into_iter()
+                                    .map(|(month, tally)| (locale.date(&month), tally))
+                                    .collect();
+                                tally_grid(ui, "growth", &months, "Month", locale);
+                            });
+                            ui.collapsing("By profile", |ui| {
+                                tally_grid(ui, "profile", &stats.by_profile, "Profile", locale);
+                            });
+                            ui.collapsing("By project", |ui| {
+                                tally_grid(ui, "project", &stats.by_project, "Project", locale);
+                            });
+                        });
+
+                        ui.add_space(10.0);
+                        if ui.small_button("Export CSV...").clicked() {
+                            if let Some(file) = rfd::FileDialog::new()
+                                .add_filter("CSV", &["csv"])
+                                .set_file_name("baggie-inventory.csv")
+                                .save_file()
+                            {
+                                let _ = std::fs::write(file, stats.to_csv());
+                            }
+                        }
+                        ui.add_space(10.0);
+                        if ui.button("Done").clicked() {
+                            self.state = AppState::Idle;
+                        }
+                    }
+
+                    AppState::Fixity => {
+                        ui.heading("Fixity");
+                        ui.label(
+                            egui::RichText::new(match self.settings.audit_interval_days {
+                                0 => "Background checks are off (see Settings)".to_string(),
+                                days => format!(
+                                    "Each bag is re-checked every {} days while Baggie is open",
+                                    days
+                                ),
+                            })
+                            .small(),
+                        );
+                        ui.add_space(10.0);
+
+                        let today = chrono::Local::now().date_naive();
+                        let checking: Vec<PathBuf> =
+                            self.fixity_checking.iter().map(|(p, _)| p.clone()).collect();
+                        let slot_free = checking.len() < self.settings.max_jobs;
+                        let mut check_now = None;
+                        let mut select = None;
+                        egui::ScrollArea::vertical().max_height(130.0).show(ui, |ui| {
+                            egui::Grid::new("fixity").striped(true).num_columns(4).show(ui, |ui| {
+                                ui.strong("Bag");
+                                ui.strong("Last checked");
+                                ui.strong("Status");
+                                ui.end_row();
+                                for entry in &self.inventory.entries {
+                                    let selected = self.fixity_selected.as_ref() == Some(&entry.path);
+                                    if ui
+                                        .selectable_label(selected, entry.identifier.as_str())
+                                        .on_hover_text(entry.path.to_string_lossy())
+                                        .clicked()
+                                    {
+                                        select = Some(entry.path.clone());
+                                    }
+                                    ui.label(if entry.last_checked.is_empty() {
+                                        "never".to_string()
+                                    } else {
+                                        locale.date(&entry.last_checked)
+                                    });
+                                    let overdue =
+                                        entry.is_overdue(today, self.settings.audit_interval_days);
+                                    let (text, color) = match entry.status {
+                                        FixityStatus::Failed => ("Failed", egui::Color32::RED),
+                                        _ if overdue => ("Overdue", egui::Color32::YELLOW),
+                                        FixityStatus::Ok => ("OK", egui::Color32::GREEN),
+                                        FixityStatus::Unchecked => ("Not checked", egui::Color32::GRAY),
+                                    };
+                                    ui.colored_label(color, text);
+                                    if checking.contains(&entry.path) {
+                                        ui.spinner();
+                                    } else if ui
+                                        .add_enabled(
+                                            slot_free,
+                                            egui::Button::new("Check now").small(),
+                                        )
+                                        .clicked()
+                                    {
+                                        check_now = Some(entry.path.clone());
+                                    }
+                                    ui.end_row();
+                                }
+                            });
+                        });
+                        if let Some(path) = check_now {
+                            self.start_fixity_check(path, false);
+                        }
+                        if select.is_some() {
+                            self.fixity_selected = select;
+                        }
+
+                        let selected = self
+                            .fixity_selected
+                            .as_ref()
+                            .and_then(|p| self.inventory.entries.iter().find(|e| e.path == *p));
+                        if let Some(entry) = selected {
+                            ui.add_space(10.0);
+                            ui.strong(format!("History of {}", entry.identifier));
+                            if entry.audits.is_empty() {
+                                ui.label(egui::RichText::new("No checks recorded yet").small());
+                            }
+                            egui::ScrollArea::vertical()
+                                .id_salt("audits")
+                                .max_height(110.0)
+                                .show(ui, |ui| {
+                                    for audit in entry.audits.iter().rev() {
+                                        let run = if audit.scheduled { "scheduled" } else { "manual" };
+                                        let when = format!(
+                                            "{} {}",
+                                            locale.date(audit.date()),
+                                            audit.time.get(11..).unwrap_or_default()
+                                        );
+                                        match audit.status {
+                                            FixityStatus::Failed => {
+                                                ui.colored_label(
+                                                    egui::Color32::RED,
+                                                    format!(
+                                                        "{}  failed ({}, {} problems)",
+                                                        when,
+                                                        run,
+                                                        audit.failures.len()
+                                                    ),
+                                                );
+                                                for failure in &audit.failures {
+                                                    ui.label(
+                                                        egui::RichText::new(format!("    {}", failure))
+                                                            .small(),
+                                                    );
+                                                }
+                                            }
+                                            _ => {
+                                                ui.label(format!(
+                                                    "{}  OK ({}, {} files)",
+                                                    when,
+                                                    run,
+                                                    locale.count(audit.files_checked as u64)
+                                                ));
+                                            }
+                                        }
+                                    }
+                                });
+                        }
+
+                        ui.add_space(10.0);
+                        ui.horizontal(|ui| {
+                            if ui.small_button("➕ Add a bag...").clicked() {
+                                if let Some(folder) = rfd::FileDialog::new().pick_folder() {
+                                    match self.inventory.register(&folder) {
+                                        Ok(()) => {
+                                            let _ = self.inventory.save();
+                                        }
+                                        Err(e) => {
+                                            self.announcement = (
+                                                format!("{} can't be added: {}", folder.display(), e),
+                                                true,
+                                            );
+                                        }
+                                    }
+                                }
+                            }
+                            if ui.small_button("Export history...").clicked() {
+                                if let Some(file) = rfd::FileDialog::new()
+                                    .add_filter("CSV", &["csv"])
+                                    .set_file_name("baggie-audits.csv")
+                                    .save_file()
+                                {
+                                    let _ = std::fs::write(file, self.inventory.audits_csv());
+                                }
+                            }
+                        });
+
+                        ui.add_space(10.0);
+                        if ui.button("Done").clicked() {
+                            self.state = AppState::Idle;
+                        }
+                    }
+
+                    AppState::NextVolume => {
+                        let (done, total) = self
+                            .volume_run
+                            .as_ref()
+                            .map(|run| (run.next, run.volumes.len()))
+                            .unwrap_or_default();
+                        ui.label(egui::RichText::new("💾").size(48.0));
+                        ui.add_space(10.0);
+                        ui.heading(format!("Volume {} of {} written", done, total));
+                        ui.add_space(10.0);
+                        ui.label(format!(
+                            "Insert the next disk, then choose where to put volume {}.",
+                            done + 1
+                        ));
+                        ui.add_space(20.0);
+
+                        if ui.button(format!("Choose folder for volume {}...", done + 1)).clicked() {
+                            if let Some(dest) = rfd::FileDialog::new()
+                                .set_title("Choose the removable drive")
+                                .pick_folder()
+                            {
+                                self.start_next_volume(dest);
+                            }
+                        }
+                        ui.add_space(10.0);
+                        if ui.small_button("Cancel").clicked() {
+                            self.volume_run = None;
+                            self.state = AppState::Idle;
+                        }
+                    }
+
+                    AppState::LooksLikeABag { path, reason } => {
+                        let path = path.clone();
+                        ui.label(egui::RichText::new("⚠").size(48.0));
+                        ui.add_space(10.0);
+                        ui.heading("This folder looks like a bag");
+                        ui.add_space(10.0);
+
+                        ui.label(match reason {
+                            BagLikeReason::Declaration => {
+                                "It has a bagit.txt, so it was probably bagged before."
+                            }
+                            BagLikeReason::DataFolder => {
+                                "It has a data/ folder, which bagging in place would mix into the payload."
+                            }
+                        });
+                        ui.label(
+                            egui::RichText::new(path.to_string_lossy())
+                                .small()
+                                .color(egui::Color32::GRAY),
+                        );
+                        ui.add_space(20.0);
+
+                        if *reason == BagLikeReason::Declaration {
+                            if ui.button("Validate it").clicked() {
+                                self.start_validation(path.clone());
+                            }
+                            if ui
+                                .button("Quick check")
+                                .on_hover_text(
+                                    "Check every listed file is there and Payload-Oxum matches, in seconds. Not a full validation: nothing is checksummed",
+                                )
+                                .clicked()
+                            {
+                                self.start_completeness_check(path.clone());
+                            }
+                            if ui
+                                .button("Update it")
+                                .on_hover_text(
+                                    "Re-checksum data/ and rewrite the tag files, keeping bag-info metadata",
+                                )
+                                .clicked()
+                            {
+                                self.start_update(path.clone());
+                            }
+                            if path.join(FETCH_FILE).is_file()
+                                && ui
+                                    .button("Complete it")
+                                    .on_hover_text("Download the files fetch.txt lists into data/")
+                                    .clicked()
+                            {
+                                self.start_complete(path.clone());
+                            }
+                        }
+                        if ui
+                            .button("Bag a copy into a new folder...")
+                            .on_hover_text("The original folder is left untouched")
+                            .clicked()
+                        {
+                            if let Some(parent) = rfd::FileDialog::new()
+                                .set_title("Choose where to put the new bag")
+                                .pick_folder()
+                            {
+                                let options = self.settings.bag_options(self.skip_system_files);
+                                let name = bag_name(&self.settings.name_template, &path, &options);
+                                let dest = parent.join(name);
+                                self.start_bagging_into(path.clone(), dest);
+                            }
+                        }
+
+                        ui.add_space(10.0);
+                        if ui.small_button("Cancel").clicked() {
+                            self.state = AppState::Idle;
+                        }
+                    }
+
+                    AppState::Compared { diff } => {
+                        if diff.is_identical() {
+                            ui.label(egui::RichText::new("✅").size(48.0));
+                            ui.add_space(10.0);
+                            ui.heading("The bags match");
+                        } else {
+                            ui.label(egui::RichText::new("⇄").size(48.0));
+                            ui.add_space(10.0);
+                            ui.heading("The bags differ");
+                        }
+                        ui.add_space(10.0);
+                        ui.label(format!(
+                            "{} added, {} removed, {} changed, {} the same ({} manifests)",
+                            locale.count(diff.added.len() as u64),
+                            locale.count(diff.removed.len() as u64),
+                            locale.count(diff.changed.len() as u64),
+                            locale.count(diff.unchanged as u64),
+                            diff.algorithm.label()
+                        ));
+                        if diff.hashed > 0 {
+                            ui.label(
+                                egui::RichText::new(format!(
+                                    "The bags share no algorithm, so {} files of the second were hashed",
+                                    locale.count(diff.hashed as u64)
+                                ))
+                                .small(),
+                            );
+                        }
+                        ui.label(
+                            egui::RichText::new("Only the manifests are compared; validate each bag to check its files match them.")
+                                .small(),
+                        );
+                        ui.add_space(10.0);
+                        egui::ScrollArea::vertical().max_height(200.0).show(ui, |ui| {
+                            for (heading, files) in [
+                                ("Added", &diff.added),
+                                ("Removed", &diff.removed),
+                                ("Changed", &diff.changed),
+                            ] {
+                                if !files.is_empty() {
+                                    ui.collapsing(format!("{} ({})", heading, files.len()), |ui| {
+                                        for file in files {
+                                            ui.label(file);
+                                        }
+                                    });
+                                }
+                            }
+                        });
+
+                        ui.add_space(20.0);
+                        if ui.button("Done").clicked() {
+                            self.state = AppState::Idle;
+                        }
+                    }
+
+                    AppState::Serialized { path } => {
+                        ui.label(egui::RichText::new("📦").size(48.0));
+                        ui.add_space(10.0);
+                        ui.heading("Bag serialized");
+                        ui.add_space(10.0);
+                        ui.label(
+                            egui::RichText::new(path.to_string_lossy())
+                                .small()
+                                .color(egui::Color32::GRAY),
+                        );
+                        ui.add_space(20.0);
+                        if ui.button("Done").clicked() {
+                            self.state = AppState::Idle;
+                        }
+                    }
+
+                    AppState::Reassembled { path } => {
+                        ui.label(egui::RichText::new("🧩").size(48.0));
+                        ui.add_space(10.0);
+                        ui.heading("Volumes reassembled");
+                        ui.add_space(10.0);
+                        ui.label("Every volume matched its checksum. The bag is encrypted, so decrypt it to validate it.");
+                        ui.label(
+                            egui::RichText::new(path.to_string_lossy())
+                                .small()
+                                .color(egui::Color32::GRAY),
+                        );
+                        ui.add_space(20.0);
+                        if ui.button("Done").clicked() {
+                            self.state = AppState::Idle;
+                        }
+                    }
+
                     AppState::Error { message } => {
                         ui.label(egui::RichText::new("❌").size(48.0));
                         ui.add_space(10.0);
@@ -243,7 +2932,19 @@ impl eframe::App for BagItApp {
 
                         ui.add_space(30.0);
 
+                        if let Some(path) = self.bagging.clone() {
+                            ui.label("When a file can't be read or vanishes mid-run");
+                            error_policy_radios(ui, &mut self.settings.error_policy);
+                            ui.add_space(10.0);
+                            if ui.button("Bag It Again").clicked() {
+                                let _ = self.settings.save();
+                                self.start_bagging(path);
+                                return;
+                            }
+                        }
+
                         if ui.button("Try Again").clicked() {
+                            self.bagging = None;
                             self.state = AppState::Idle;
                         }
                     }
@@ -252,3 +2953,81 @@ impl eframe::App for BagItApp {
         });
     }
 }
+
+/// Choices for a file that can't be read or vanishes mid-run.
+fn error_policy_radios(ui: &mut egui::Ui, policy: &mut ErrorPolicy) {
+    ui.horizontal(|ui| {
+        ui.radio_value(policy, ErrorPolicy::Abort, "Stop");
+        ui.radio_value(policy, ErrorPolicy::Retry, "Retry, then stop");
+        ui.radio_value(policy, ErrorPolicy::Skip, "Skip and report");
+    });
+}
+
+/// A table of bags, files and bytes per key; an empty key reads as "(none)".
+fn tally_grid(
+    ui: &mut egui::Ui,
+    id: &str,
+    rows: &[(String, Tally)],
+    heading: &str,
+    locale: Locale,
+) {
+    egui::Grid::new(id)
+        .striped(true)
+        .num_columns(4)
+        .show(ui, |ui| {
+            ui.strong(heading);
+            ui.strong("Bags");
+            ui.strong("Files");
+            ui.strong("Size");
+            ui.end_row();
+            for (key, tally) in rows {
+                let key = if key.is_empty() {
+                    "(none)"
+                } else {
+                    key.as_str()
+                };
+                ui.label(key);
+                ui.label(locale.count(tally.bags));
+                ui.label(locale.count(tally.files));
+                ui.label(locale.bytes(tally.bytes));
+                ui.end_row();
+            }
+        });
+}
+
+/// Draws a QR code with `module` points per module and the standard 4-module quiet zone.
+fn draw_qr(ui: &mut egui::Ui, qr: &QrCode, module: f32) {
+    let width = qr.width();
+    let side = (width + 8) as f32 * module;
+    let (response, painter) = ui.allocate_painter(egui::vec2(side, side), egui::Sense::hover());
+    let origin = response.rect.min;
+    painter.rect_filled(response.rect, 0.0, egui::Color32::WHITE);
+    for (i, color) in qr.to_colors().into_iter().enumerate() {
+        if color == qrcode::Color::Dark {
+            let x = (i % width + 4) as f32 * module;
+            let y = (i / width + 4) as f32 * module;
+            painter.rect_filled(
+                egui::Rect::from_min_size(origin + egui::vec2(x, y), egui::vec2(module, module)),
+                0.0,
+                egui::Color32::BLACK,
+            );
+        }
+    }
+}
+
+/// A QR code as a standalone SVG, for printing on labels and media sleeves.
+fn qr_svg(qr: &QrCode) -> String {
+    let width = qr.width();
+    let side = width + 8;
+    let mut svg = format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"0 0 {0} {0}\" shape-rendering=\"crispEdges\">\n<rect width=\"{0}\" height=\"{0}\" fill=\"#fff\"/>\n<path fill=\"#000\" d=\"",
+        side
+    );
+    for (i, color) in qr.to_colors().into_iter().enumerate() {
+        if color == qrcode::Color::Dark {
+            svg.push_str(&format!("M{},{}h1v1h-1z", i % width + 4, i / width + 4));
+        }
+    }
+    svg.push_str("\"/>\n</svg>\n");
+    svg
+}
@@ -0,0 +1,1312 @@
+use crate::bagit::{
+    check_free_space, validate_files, BagError, BagFiles, ChecksumAlgorithm, Digester, Progress,
+    ValidationOptions, ValidationReport,
+};
+use crate::encrypt::{clear_encryption, encrypt_file, record_encryption, Encryption};
+use crate::events::{append_events, Event, EventType, EVENT_LOG_FILE};
+use crate::signature::{is_signed, sign_bag, Signing, SigningTool};
+use crate::split::split_file;
+use crate::tape::{collect_members, tar_length, write_tar, Member as TarMember};
+use chrono::{Datelike, TimeZone, Timelike};
+use flate2::read::{DeflateDecoder, GzDecoder};
+use flate2::write::{DeflateEncoder, GzEncoder};
+use flate2::{Compression, Crc};
+use sevenz_rust::{Password, SevenZArchiveEntry, SevenZReader, SevenZWriter};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+/// Archive formats a bag can be serialized in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SerialFormat {
+    Zip,
+    Tar,
+    /// Gzip-compressed tar.
+    TarGz,
+    TarZst,
+    SevenZip,
+}
+
+impl SerialFormat {
+    /// File name endings, longest first so `.tar.zst` isn't taken for something else.
+    const EXTENSIONS: [(&'static str, SerialFormat); 7] = [
+        (".tar.zst", SerialFormat::TarZst),
+        (".tar.gz", SerialFormat::TarGz),
+        (".tzst", SerialFormat::TarZst),
+        (".tgz", SerialFormat::TarGz),
+        (".tar", SerialFormat::Tar),
+        (".zip", SerialFormat::Zip),
+        (".7z", SerialFormat::SevenZip),
+    ];
+
+    /// The format a file's name says it's in.
+    pub fn of(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_string_lossy().to_ascii_lowercase();
+        Self::EXTENSIONS
+            .iter()
+            .find(|(extension, _)| name.ends_with(extension))
+            .map(|(_, format)| *format)
+    }
+
+    /// Reads a `--format` value.
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "zip" => Some(SerialFormat::Zip),
+            "tar" => Some(SerialFormat::Tar),
+            "tar.gz" | "tgz" | "gz" => Some(SerialFormat::TarGz),
+            "tar.zst" | "zst" => Some(SerialFormat::TarZst),
+            "7z" => Some(SerialFormat::SevenZip),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            SerialFormat::Zip => "zip",
+            SerialFormat::Tar => "tar",
+            SerialFormat::TarGz => "tar.gz",
+            SerialFormat::TarZst => "tar.zst",
+            SerialFormat::SevenZip => "7z",
+        }
+    }
+
+    /// The MIME type BagIt Profiles name the format by.
+    pub fn mime_type(self) -> &'static str {
+        match self {
+            SerialFormat::Zip => "application/zip",
+            SerialFormat::Tar => "application/x-tar",
+            SerialFormat::TarGz => "application/gzip",
+            SerialFormat::TarZst => "application/zstd",
+            SerialFormat::SevenZip => "application/x-7z-compressed",
+        }
+    }
+}
+
+/// How a bag is serialized.
+#[derive(Debug, Clone)]
+pub struct SerialOptions {
+    pub format: SerialFormat,
+    /// zstd level for `.tar.zst`, from 1 (fastest) to 22 (smallest).
+    pub zstd_level: i32,
+    /// Threads compressing a `.tar.zst` at once; 1 compresses as the tar is written.
+    pub threads: u32,
+    /// Whether serializing the same bag again gives a byte-identical file: every
+    /// member dated 1 January 1970 (1980 in a zip, the earliest it can hold), no
+    /// packing event logged, and zstd kept to one thread, whose output doesn't
+    /// depend on how it's scheduled. Members are always in the same order.
+    pub reproducible: bool,
+    /// Encryption applied once the archive is written, leaving only the encrypted
+    /// copy. It's recorded in bag-info.txt first, so the bag inside says so too.
+    pub encryption: Option<Encryption>,
+    /// Signs the tag manifest again if recording the serialization changed it, or
+    /// signs it for the first time, so the archive holds a signed bag.
+    pub signing: Option<Signing>,
+    /// Splits the finished (and encrypted) archive into numbered volumes of at most
+    /// this many bytes, for services that cap the size of one upload.
+    pub volume_size: Option<u64>,
+}
+
+impl Default for SerialOptions {
+    fn default() -> Self {
+        Self {
+            format: SerialFormat::Tar,
+            zstd_level: zstd::DEFAULT_COMPRESSION_LEVEL,
+            threads: 1,
+            reproducible: false,
+            encryption: None,
+            signing: None,
+            volume_size: None,
+        }
+    }
+}
+
+fn archive_error(error: impl std::fmt::Display) -> BagError {
+    BagError::BadArchive(error.to_string())
+}
+
+/// Writes the bag at `bag` into `dest` as `<bag name>.<extension>`, everything inside
+/// a single folder named after the bag, and returns the file's path. An encrypted
+/// archive gets `.age` or `.gpg` added. A split archive's path is that of its list
+/// of volumes (see `split_file`).
+pub fn serialize_bag(
+    bag: &Path,
+    dest: &Path,
+    options: &SerialOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<PathBuf, BagError> {
+    if !bag.join("bagit.txt").is_file() {
+        return Err(BagError::NotABag);
+    }
+    let name = bag
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "bag".to_string());
+    fs::create_dir_all(dest)?;
+    let format = options.format;
+    let path = dest.join(format!("{}.{}", name, format.extension()));
+    // A compressed archive is rarely bigger than the plain tar, so it's the estimate
+    check_free_space(dest, tar_length(&collect_members(bag)?))?;
+    // What's recorded in the bag for the archive is put back as it was if the archive
+    // isn't made after all
+    let snapshot = TagSnapshot::take(bag)?;
+    let result = record_and_write(bag, &path, options, &progress_tx);
+    if result.is_err() {
+        snapshot.restore()?;
+    }
+    result
+}
+
+/// The tag files serializing may change, as they were before it: `None` for one
+/// that wasn't there.
+struct TagSnapshot {
+    bag: PathBuf,
+    files: Vec<(String, Option<Vec<u8>>)>,
+}
+
+impl TagSnapshot {
+    fn take(bag: &Path) -> io::Result<Self> {
+        let mut names = vec!["bag-info.txt".to_string(), EVENT_LOG_FILE.to_string()];
+        names.extend(ChecksumAlgorithm::ALL.iter().map(|a| a.tagmanifest_name()));
+        names.extend(SigningTool::ALL.iter().map(|t| t.signature_name()));
+        let mut files = Vec::new();
+        for name in names {
+            let content = match fs::read(bag.join(&name)) {
+                Ok(content) => Some(content),
+                Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+                Err(e) => return Err(e),
+            };
+            files.push((name, content));
+        }
+        Ok(Self {
+            bag: bag.to_path_buf(),
+            files,
+        })
+    }
+
+    fn restore(&self) -> io::Result<()> {
+        for (name, content) in &self.files {
+            let path = self.bag.join(name);
+            match content {
+                Some(content) => fs::write(&path, content)?,
+                None if path.is_file() => fs::remove_file(&path)?,
+                None => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Records the serialization in the bag at `bag`, then writes it to `path`, and
+/// encrypts and splits it as `options` say.
+fn record_and_write(
+    bag: &Path,
+    path: &Path,
+    options: &SerialOptions,
+    progress_tx: &Option<Sender<Progress>>,
+) -> Result<PathBuf, BagError> {
+    let encrypted = options.encryption.as_ref().map(|e| e.encrypted_path(path));
+    // A signed bag that won't be signed again is left as it is, as recording anything
+    // in it would rewrite the tag manifest and drop the signature
+    let keep_signature = options.signing.is_none() && is_signed(bag);
+    let mut unsigned = false;
+    if !keep_signature {
+        unsigned |= match &options.encryption {
+            Some(encryption) => record_encryption(bag, encryption)?,
+            None => clear_encryption(bag)?,
+        };
+    }
+    // Logged first, so the serialized bag carries its own record of it; left out of
+    // a reproducible archive, as it's dated
+    if !options.reproducible && !keep_signature {
+        let mut detail = format!(
+            "Serialized as {}",
+            encrypted.as_deref().unwrap_or(path).display()
+        );
+        if let Some(size) = options.volume_size {
+            detail.push_str(&format!(", split into volumes of {} bytes", size));
+        }
+        unsigned |= append_events(bag, &[Event::now(EventType::Packing, detail)])?;
+    }
+    if let Some(signing) = &options.signing {
+        if unsigned || !is_signed(bag) {
+            sign_bag(bag, signing)?;
+        }
+    }
+    let mut members = collect_members(bag)?;
+    if options.reproducible {
+        for member in &mut members {
+            member.mtime = 0;
+        }
+    }
+    write_archive(&members, path, options, progress_tx)?;
+    let path = match &options.encryption {
+        Some(encryption) => encrypt_file(path, encryption)?,
+        None => path.to_path_buf(),
+    };
+    match options.volume_size {
+        Some(size) => split_file(&path, size),
+        None => Ok(path),
+    }
+}
+
+/// Writes `members` to a new archive at `path` in the format `options` gives. If that
+/// fails partway, the partial archive is removed rather than left looking finished.
+fn write_archive(
+    members: &[TarMember],
+    path: &Path,
+    options: &SerialOptions,
+    progress_tx: &Option<Sender<Progress>>,
+) -> Result<(), BagError> {
+    let result = write_members(members, path, options, progress_tx);
+    if result.is_err() {
+        let _ = fs::remove_file(path);
+    }
+    result
+}
+
+fn write_members(
+    members: &[TarMember],
+    path: &Path,
+    options: &SerialOptions,
+    progress_tx: &Option<Sender<Progress>>,
+) -> Result<(), BagError> {
+    match options.format {
+        SerialFormat::Zip => {
+            let mut out = BufWriter::new(File::create(path)?);
+            write_zip(members, &mut out, progress_tx)?;
+            out.flush()?;
+        }
+        SerialFormat::Tar => {
+            let mut out = BufWriter::new(File::create(path)?);
+            write_tar(members, &mut out, progress_tx)?;
+            out.flush()?;
+        }
+        SerialFormat::TarGz => {
+            // The gzip header's own time is left at 0
+            let out = BufWriter::new(File::create(path)?);
+            let mut encoder = GzEncoder::new(out, Compression::default());
+            write_tar(members, &mut encoder, progress_tx)?;
+            encoder.finish()?.flush()?;
+        }
+        SerialFormat::TarZst => {
+            let out = BufWriter::new(File::create(path)?);
+            let mut encoder = zstd::stream::write::Encoder::new(out, options.zstd_level)?;
+            if options.threads > 1 && !options.reproducible {
+                encoder.multithread(options.threads)?;
+            }
+            write_tar(members, &mut encoder, progress_tx)?;
+            encoder.finish()?.flush()?;
+        }
+        SerialFormat::SevenZip => {
+            if let Some(ref tx) = progress_tx {
+                let _ = tx.send(Progress::Started {
+                    total_files: members.len(),
+                });
+            }
+            let mut writer = SevenZWriter::create(path).map_err(archive_error)?;
+            for (i, member) in members.iter().enumerate() {
+                if let Some(ref tx) = progress_tx {
+                    let _ = tx.send(Progress::Copying {
+                        current: i + 1,
+                        filename: member.name.clone(),
+                    });
+                }
+                let mut entry = SevenZArchiveEntry::from_path(&member.source, member.name.clone());
+                if options.reproducible {
+                    entry.has_creation_date = false;
+                    entry.has_last_modified_date = false;
+                    entry.has_access_date = false;
+                }
+                writer
+                    .push_archive_entry(entry, Some(File::open(&member.source)?))
+                    .map_err(archive_error)?;
+            }
+            writer.finish().map_err(archive_error)?;
+        }
+    }
+    Ok(())
+}
+
+/// Counts the bytes written through it, for the offsets a zip records.
+struct Counted<W> {
+    inner: W,
+    written: u64,
+}
+
+impl<W: Write> Write for Counted<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let written = self.inner.write(buf)?;
+        self.written += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+/// Members at least this big get zip64 sizes from the start, leaving room for
+/// deflate to make incompressible data slightly bigger.
+const ZIP64_MEMBER: u64 = 0xf000_0000;
+
+/// `mtime` as an MS-DOS (time, date) pair, in local time as zip tools expect.
+/// Times before 1980, which it can't hold, become 1 January 1980.
+fn dos_time(mtime: u64) -> (u16, u16) {
+    let time = chrono::Local
+        .timestamp_opt(mtime as i64, 0)
+        .single()
+        .filter(|t| t.year() >= 1980);
+    match time {
+        Some(t) => (
+            ((t.hour() << 11) | (t.minute() << 5) | (t.second() / 2)) as u16,
+            (((t.year() as u32 - 1980) << 9) | (t.month() << 5) | t.day()) as u16,
+        ),
+        None => (0, (1 << 5) | 1),
+    }
+}
+
+/// Writes `members`, from `collect_members`, to `out` as a zip, each file deflated as
+/// it's read. Sizes and checksums follow each file's data, so nothing is
+/// written twice or staged, and zip64 records are used where sizes, offsets or the
+/// file count outgrow the classic format.
+pub fn write_zip(
+    members: &[TarMember],
+    out: &mut impl Write,
+    progress_tx: &Option<Sender<Progress>>,
+) -> Result<(), BagError> {
+    // Zip gives a name's length 16 bits
+    if let Some(member) = members.iter().find(|m| m.name.len() > 0xffff) {
+        return Err(BagError::BadArchive(format!(
+            "{}... is longer than zip allows a path to be ({} bytes, at most 65535)",
+            member.name.chars().take(60).collect::<String>(),
+            member.name.len()
+        )));
+    }
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started {
+            total_files: members.len(),
+        });
+    }
+    let mut out = Counted {
+        inner: out,
+        written: 0,
+    };
+    let mut directory = Vec::new();
+    for (i, member) in members.iter().enumerate() {
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Copying {
+                current: i + 1,
+                filename: member.name.clone(),
+            });
+        }
+        let offset = out.written;
+        let zip64 = member.size >= ZIP64_MEMBER;
+        out.write_all(&zip_local_header(member, zip64))?;
+
+        let start = out.written;
+        let mut crc = Crc::new();
+        let mut encoder = DeflateEncoder::new(&mut out, Compression::default());
+        let mut source = File::open(&member.source)?.take(member.size);
+        let mut buffer = [0u8; 65536];
+        // Counted here, as `Crc::amount` wraps at 4 GiB
+        let mut read = 0u64;
+        loop {
+            let bytes_read = source.read(&mut buffer)?;
+            if bytes_read == 0 {
+                break;
+            }
+            crc.update(&buffer[..bytes_read]);
+            encoder.write_all(&buffer[..bytes_read])?;
+            read += bytes_read as u64;
+        }
+        encoder.finish()?;
+        if read != member.size {
+            return Err(BagError::FileVanished(member.source.clone()));
+        }
+        let compressed = out.written - start;
+
+        // The data descriptor, with 8-byte sizes for a zip64 member
+        let mut descriptor = vec![0x50, 0x4b, 0x07, 0x08];
+        descriptor.extend_from_slice(&crc.sum().to_le_bytes());
+        if zip64 {
+            descriptor.extend_from_slice(&compressed.to_le_bytes());
+            descriptor.extend_from_slice(&member.size.to_le_bytes());
+        } else {
+            if compressed >= 0xffff_ffff {
+                return Err(BagError::BadArchive(format!(
+                    "{} grew past 4 GiB when compressed",
+                    member.name
+                )));
+            }
+            descriptor.extend_from_slice(&(compressed as u32).to_le_bytes());
+            descriptor.extend_from_slice(&(member.size as u32).to_le_bytes());
+        }
+        out.write_all(&descriptor)?;
+        directory.extend(zip_directory_entry(member, crc.sum(), compressed, offset));
+    }
+
+    let start = out.written;
+    let length = directory.len() as u64;
+    out.write_all(&directory)?;
+    let count = members.len() as u64;
+    let zip64 = count >= 0xffff || start >= 0xffff_ffff || length >= 0xffff_ffff;
+    if zip64 {
+        // The zip64 end of central directory record, then its locator
+        let end = out.written;
+        let mut record = vec![0x50, 0x4b, 0x06, 0x06];
+        record.extend_from_slice(&44u64.to_le_bytes());
+        record.extend_from_slice(&[45, 0, 45, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+        for value in [count, count, length, start] {
+            record.extend_from_slice(&value.to_le_bytes());
+        }
+        record.extend_from_slice(&[0x50, 0x4b, 0x06, 0x07, 0, 0, 0, 0]);
+        record.extend_from_slice(&end.to_le_bytes());
+        record.extend_from_slice(&1u32.to_le_bytes());
+        out.write_all(&record)?;
+    }
+    let mut end = vec![0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0];
+    let short_count = count.min(0xffff) as u16;
+    end.extend_from_slice(&short_count.to_le_bytes());
+    end.extend_from_slice(&short_count.to_le_bytes());
+    end.extend_from_slice(&(length.min(0xffff_ffff) as u32).to_le_bytes());
+    end.extend_from_slice(&(start.min(0xffff_ffff) as u32).to_le_bytes());
+    end.extend_from_slice(&[0, 0]);
+    out.write_all(&end)?;
+    Ok(())
+}
+
+/// The fields local headers and central directory entries share, from "version
+/// needed" to the name length: deflated, UTF-8 names, sizes in a data descriptor.
+fn zip_common_fields(member: &TarMember, crc: u32, compressed: u32, size: u32) -> Vec<u8> {
+    let (time, date) = dos_time(member.mtime);
+    let mut fields = vec![45, 0];
+    fields.extend_from_slice(&((1u16 << 3) | (1 << 11)).to_le_bytes());
+    fields.extend_from_slice(&8u16.to_le_bytes());
+    fields.extend_from_slice(&time.to_le_bytes());
+    fields.extend_from_slice(&date.to_le_bytes());
+    fields.extend_from_slice(&crc.to_le_bytes());
+    fields.extend_from_slice(&compressed.to_le_bytes());
+    fields.extend_from_slice(&size.to_le_bytes());
+    fields.extend_from_slice(&(member.name.len() as u16).to_le_bytes());
+    fields
+}
+
+/// A local header, its checksum and sizes left to the data descriptor. A zip64
+/// member says so with a zip64 extra field.
+fn zip_local_header(member: &TarMember, zip64: bool) -> Vec<u8> {
+    let marker = if zip64 { 0xffff_ffff } else { 0 };
+    let mut header = vec![0x50, 0x4b, 0x03, 0x04];
+    header.extend(zip_common_fields(member, 0, marker, marker));
+    let extra_length: u16 = if zip64 { 20 } else { 0 };
+    header.extend_from_slice(&extra_length.to_le_bytes());
+    header.extend_from_slice(member.name.as_bytes());
+    if zip64 {
+        header.extend_from_slice(&[1, 0, 16, 0]);
+        header.extend_from_slice(&[0; 16]);
+    }
+    header
+}
+
+/// A central directory entry. Values that don't fit in 32 bits are marked
+/// 0xffffffff and given in a zip64 extra field, in the order the spec sets.
+fn zip_directory_entry(member: &TarMember, crc: u32, compressed: u64, offset: u64) -> Vec<u8> {
+    let large: Vec<u64> = [member.size, compressed, offset]
+        .into_iter()
+        .filter(|&value| value >= 0xffff_ffff)
+        .collect();
+    let short = |value: u64| value.min(0xffff_ffff) as u32;
+    let mut entry = vec![0x50, 0x4b, 0x01, 0x02, 45, 3];
+    entry.extend(zip_common_fields(
+        member,
+        crc,
+        short(compressed),
+        short(member.size),
+    ));
+    let extra_length = if large.is_empty() {
+        0
+    } else {
+        4 + 8 * large.len() as u16
+    };
+    entry.extend_from_slice(&extra_length.to_le_bytes());
+    // Comment length, disk number, internal attributes, then external ones: a
+    // regular file, rw-r--r--
+    entry.extend_from_slice(&[0, 0, 0, 0, 0, 0]);
+    entry.extend_from_slice(&(0o100644u32 << 16).to_le_bytes());
+    entry.extend_from_slice(&short(offset).to_le_bytes());
+    entry.extend_from_slice(member.name.as_bytes());
+    if !large.is_empty() {
+        entry.extend_from_slice(&1u16.to_le_bytes());
+        entry.extend_from_slice(&(8 * large.len() as u16).to_le_bytes());
+        for value in large {
+            entry.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+    entry
+}
+
+/// A bag read from a serialized file in one pass. Every member is hashed as it
+/// streams past; only the top-level tag files are kept.
+pub struct ArchiveBag {
+    /// Size and checksums of every file, by path in the bag.
+    files: HashMap<String, (u64, Digests)>,
+    tags: HashMap<String, Vec<u8>>,
+    /// Whether everything was inside one top-level folder, as RFC 8493 §4.2 asks.
+    in_one_folder: bool,
+    format: Option<SerialFormat>,
+}
+
+/// A member's checksum in each of `ChecksumAlgorithm::ALL`, in that order, or empty
+/// where it wasn't computed. A tar or 7z file is read once, before its manifests are
+/// known, so every algorithm is computed; a zip's tag files are read first, so its
+/// payload is hashed only in the algorithms of its manifests.
+type Digests = Vec<String>;
+
+/// One member as read: path in the archive, size, checksums, and contents if kept.
+type Member = (String, u64, Digests, Option<Vec<u8>>);
+
+impl ArchiveBag {
+    /// Reads the archive at `path`, picking the format from its name.
+    pub fn open(path: &Path, progress_tx: &Option<Sender<Progress>>) -> Result<Self, BagError> {
+        let format = SerialFormat::of(path);
+        let members = match format {
+            Some(SerialFormat::Zip) => read_zip(path, progress_tx)?,
+            Some(SerialFormat::Tar) => read_tar(BufReader::new(File::open(path)?), progress_tx)?,
+            Some(SerialFormat::TarGz) => read_tar(
+                GzDecoder::new(BufReader::new(File::open(path)?)),
+                progress_tx,
+            )?,
+            Some(SerialFormat::TarZst) => read_tar(
+                zstd::stream::read::Decoder::new(File::open(path)?)?,
+                progress_tx,
+            )?,
+            Some(SerialFormat::SevenZip) => read_7z(path, progress_tx)?,
+            None => {
+                return Err(BagError::BadArchive(
+                    "not a .zip, .tar, .tar.gz, .tar.zst or .7z file".to_string(),
+                ))
+            }
+        };
+        let mut bag = Self::from_members(members);
+        bag.format = format;
+        Ok(bag)
+    }
+
+    /// Drops the folder the bag was serialized in.
+    fn from_members(members: Vec<Member>) -> Self {
+        let top = members
+            .first()
+            .and_then(|m| m.0.split_once('/'))
+            .map(|(top, _)| top);
+        let strip = top.filter(|top| {
+            members
+                .iter()
+                .all(|m| m.0.split_once('/').is_some_and(|(t, _)| t == *top))
+        });
+        let strip = strip.map(|top| format!("{}/", top));
+
+        let mut bag = ArchiveBag {
+            files: HashMap::new(),
+            tags: HashMap::new(),
+            in_one_folder: strip.is_some(),
+            format: None,
+        };
+        for (name, size, digest, contents) in members {
+            let name = match &strip {
+                Some(top) => name[top.len()..].to_string(),
+                None => name,
+            };
+            if let Some(contents) = contents.filter(|_| !name.contains('/')) {
+                bag.tags.insert(name.clone(), contents);
+            }
+            bag.files.insert(name, (size, digest));
+        }
+        bag
+    }
+}
+
+impl BagFiles for ArchiveBag {
+    fn read(&self, name: &str) -> Option<io::Result<Vec<u8>>> {
+        self.tags.get(name).map(|contents| Ok(contents.clone()))
+    }
+
+    fn digest(&self, name: &str, algorithm: ChecksumAlgorithm) -> Option<io::Result<String>> {
+        self.files
+            .get(name)
+            .map(|(_, digests)| Ok(digests[algorithm as usize].clone()))
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        self.files.contains_key(name)
+    }
+
+    fn payload(&self) -> Vec<(String, u64)> {
+        self.files
+            .iter()
+            .filter(|(name, _)| name.starts_with("data/"))
+            .map(|(name, (size, _))| (name.clone(), *size))
+            .collect()
+    }
+
+    fn top_level(&self) -> Vec<String> {
+        self.files
+            .keys()
+            .filter(|name| !name.contains('/'))
+            .cloned()
+            .collect()
+    }
+
+    fn tag_files(&self) -> Vec<String> {
+        self.files
+            .keys()
+            .filter(|name| !name.starts_with("data/"))
+            .cloned()
+            .collect()
+    }
+
+    fn serialization_problem(&self) -> Option<String> {
+        (!self.in_one_folder)
+            .then(|| "The archive must unpack to a single folder holding the bag".to_string())
+    }
+
+    fn serialization(&self) -> Option<&'static str> {
+        self.format.map(SerialFormat::mime_type)
+    }
+}
+
+/// Checks the bag serialized in the file at `path` without extracting it.
+pub fn validate_archive(
+    path: &Path,
+    options: &ValidationOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<ValidationReport, BagError> {
+    let bag = ArchiveBag::open(path, &progress_tx)?;
+    validate_files(&bag, path, options, progress_tx)
+}
+
+/// Whether a member might be a top-level tag file, in or out of a bag folder.
+fn might_be_tag(name: &str) -> bool {
+    match name.split_once('/') {
+        None => true,
+        Some((top, rest)) => top != "data" && !rest.contains('/'),
+    }
+}
+
+/// Hashes all of `reader` in `algorithms`, keeping the bytes too if asked.
+fn hash_member(
+    reader: &mut dyn Read,
+    keep: bool,
+    algorithms: &[ChecksumAlgorithm],
+) -> io::Result<(u64, Digests, Option<Vec<u8>>)> {
+    let mut hashers: Vec<Option<Digester>> = ChecksumAlgorithm::ALL
+        .into_iter()
+        .map(|a| algorithms.contains(&a).then(|| Digester::new(a)))
+        .collect();
+    let mut kept = keep.then(Vec::new);
+    let mut buffer = [0u8; 65536];
+    let mut size = 0;
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        for hasher in hashers.iter_mut().flatten() {
+            hasher.update(&buffer[..bytes_read]);
+        }
+        if let Some(ref mut kept) = kept {
+            kept.extend_from_slice(&buffer[..bytes_read]);
+        }
+        size += bytes_read as u64;
+    }
+    let digests = hashers
+        .into_iter()
+        .map(|h| h.map(Digester::finish).unwrap_or_default())
+        .collect();
+    Ok((size, digests, kept))
+}
+
+/// The algorithms of the payload manifests among `members`, or all of them if there
+/// are none, so a bag without manifests still reads.
+fn manifest_algorithms(members: &[Member]) -> Vec<ChecksumAlgorithm> {
+    let algorithms: Vec<ChecksumAlgorithm> = members
+        .iter()
+        .filter_map(|m| {
+            let name = m.0.rsplit('/').next()?;
+            ChecksumAlgorithm::from_name(name.strip_prefix("manifest-")?.strip_suffix(".txt")?)
+        })
+        .collect();
+    match algorithms.is_empty() {
+        true => ChecksumAlgorithm::ALL.to_vec(),
+        false => algorithms,
+    }
+}
+
+fn send_progress(progress_tx: &Option<Sender<Progress>>, current: usize, name: &str) {
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Checksumming {
+            current,
+            filename: name.to_string(),
+        });
+    }
+}
+
+/// A NUL-terminated header field.
+fn tar_text(field: &[u8]) -> String {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    String::from_utf8_lossy(&field[..end]).to_string()
+}
+
+/// An octal number field, or a base-256 one when the high bit is set.
+fn tar_number(field: &[u8]) -> Option<u64> {
+    if field[0] & 0x80 != 0 {
+        return Some(field[1..].iter().fold(0, |n, &b| (n << 8) | b as u64));
+    }
+    let text = tar_text(field);
+    let text = text.trim_matches(|c: char| c == ' ' || c == '\0');
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+/// Reads pax records (`<length> <key>=<value>\n`) into key/value pairs.
+fn pax_records(bytes: &[u8]) -> HashMap<String, String> {
+    let text = String::from_utf8_lossy(bytes);
+    let mut rest = text.as_ref();
+    let mut records = HashMap::new();
+    while let Some((digits, _)) = rest.split_once(' ') {
+        let Ok(length) = digits.parse::<usize>() else {
+            break;
+        };
+        let Some(record) = rest.get(digits.len() + 1..length) else {
+            break;
+        };
+        if let Some((key, value)) = record.split_once('=') {
+            records.insert(key.to_string(), value.trim_end_matches('\n').to_string());
+        }
+        rest = &rest[length..];
+    }
+    records
+}
+
+fn skip(reader: &mut impl Read, length: u64) -> io::Result<()> {
+    io::copy(&mut reader.take(length), &mut io::sink())?;
+    Ok(())
+}
+
+/// Reads every regular file in a tar archive (ustar, with pax or GNU long names).
+fn read_tar(
+    mut reader: impl Read,
+    progress_tx: &Option<Sender<Progress>>,
+) -> Result<Vec<Member>, BagError> {
+    let bad = |message: &str| BagError::BadArchive(message.to_string());
+    let mut members = Vec::new();
+    let mut next_name = None;
+    let mut next_size = None;
+    let mut header = [0u8; 512];
+    loop {
+        if reader.read_exact(&mut header).is_err() {
+            return Err(bad("the tar file ends in the middle of a member"));
+        }
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+        let size = next_size
+            .take()
+            .or_else(|| tar_number(&header[124..136]))
+            .ok_or_else(|| bad("a tar header has an unreadable size"))?;
+        let padding = size.div_ceil(512) * 512 - size;
+        let kind = header[156];
+        match kind {
+            b'x' | b'L' => {
+                let mut contents = Vec::new();
+                (&mut reader).take(size).read_to_end(&mut contents)?;
+                if kind == b'L' {
+                    next_name = Some(tar_text(&contents));
+                } else {
+                    let records = pax_records(&contents);
+                    if let Some(path) = records.get("path") {
+                        next_name = Some(path.clone());
+                    }
+                    next_size = records.get("size").and_then(|s| s.parse().ok());
+                }
+            }
+            b'0' | 0 => {
+                let name = next_name.take().unwrap_or_else(|| {
+                    let name = tar_text(&header[..100]);
+                    let prefix = tar_text(&header[345..500]);
+                    if &header[257..262] == b"ustar" && !prefix.is_empty() {
+                        format!("{}/{}", prefix, name)
+                    } else {
+                        name
+                    }
+                });
+                let name = name.trim_start_matches("./").to_string();
+                send_progress(progress_tx, members.len() + 1, &name);
+                let mut data = (&mut reader).take(size);
+                let (read, digest, contents) =
+                    hash_member(&mut data, might_be_tag(&name), &ChecksumAlgorithm::ALL)?;
+                if read != size {
+                    return Err(bad("the tar file ends in the middle of a member"));
+                }
+                members.push((name, size, digest, contents));
+            }
+            _ => {
+                next_name = None;
+                skip(&mut reader, size)?;
+            }
+        }
+        skip(&mut reader, padding)?;
+    }
+    Ok(members)
+}
+
+/// Reads every file in a 7z archive, decompressing each as it goes.
+fn read_7z(path: &Path, progress_tx: &Option<Sender<Progress>>) -> Result<Vec<Member>, BagError> {
+    let mut reader = SevenZReader::open(path, Password::empty()).map_err(archive_error)?;
+    let mut members = Vec::new();
+    reader
+        .for_each_entries(|entry, data| {
+            if entry.is_directory() {
+                return Ok(true);
+            }
+            let name = entry.name().replace('\\', "/");
+            send_progress(progress_tx, members.len() + 1, &name);
+            let (size, digest, contents) =
+                hash_member(data, might_be_tag(&name), &ChecksumAlgorithm::ALL)?;
+            members.push((name, size, digest, contents));
+            Ok(true)
+        })
+        .map_err(archive_error)?;
+    Ok(members)
+}
+
+fn u16_at(bytes: &[u8], at: usize) -> u64 {
+    u16::from_le_bytes([bytes[at], bytes[at + 1]]) as u64
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u64 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().unwrap()) as u64
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().unwrap())
+}
+
+/// Finds the central directory: (entry count, offset), from the end of central
+/// directory record or its zip64 counterpart.
+fn zip_directory(file: &mut File) -> Result<(u64, u64), BagError> {
+    let bad = |message: &str| BagError::BadArchive(message.to_string());
+    let length = file.seek(SeekFrom::End(0))?;
+    let tail_start = length.saturating_sub(22 + 65535);
+    file.seek(SeekFrom::Start(tail_start))?;
+    let mut tail = Vec::new();
+    file.read_to_end(&mut tail)?;
+    let end = (0..tail.len().saturating_sub(21))
+        .rev()
+        .find(|&i| tail[i..i + 4] == [0x50, 0x4b, 0x05, 0x06])
+        .ok_or_else(|| bad("no zip central directory found"))?;
+    let count = u16_at(&tail, end + 10);
+    let offset = u32_at(&tail, end + 16);
+    if count != 0xffff && offset != 0xffff_ffff {
+        return Ok((count, offset));
+    }
+
+    // Zip64: a locator just before the record points to the zip64 record
+    let locator = end
+        .checked_sub(20)
+        .filter(|&at| tail[at..at + 4] == [0x50, 0x4b, 0x06, 0x07])
+        .ok_or_else(|| bad("the zip64 end of central directory is missing"))?;
+    file.seek(SeekFrom::Start(u64_at(&tail, locator + 8)))?;
+    let mut record = [0u8; 56];
+    file.read_exact(&mut record)?;
+    if record[..4] != [0x50, 0x4b, 0x06, 0x06] {
+        return Err(bad("the zip64 end of central directory is missing"));
+    }
+    Ok((u64_at(&record, 32), u64_at(&record, 48)))
+}
+
+/// Reads every file in a zip archive, stored or deflated.
+fn read_zip(path: &Path, progress_tx: &Option<Sender<Progress>>) -> Result<Vec<Member>, BagError> {
+    let bad = |message: String| BagError::BadArchive(message);
+    let mut file = File::open(path)?;
+    let (count, offset) = zip_directory(&mut file)?;
+
+    // (name, method, compressed size, size, local header offset), from the directory
+    let mut entries = Vec::new();
+    let mut directory = BufReader::new(file.try_clone()?);
+    directory.seek(SeekFrom::Start(offset))?;
+    for _ in 0..count {
+        let mut header = [0u8; 46];
+        directory.read_exact(&mut header)?;
+        if header[..4] != [0x50, 0x4b, 0x01, 0x02] {
+            return Err(bad("the zip central directory is damaged".to_string()));
+        }
+        let mut name = vec![0u8; u16_at(&header, 28) as usize];
+        let mut extra = vec![0u8; u16_at(&header, 30) as usize];
+        directory.read_exact(&mut name)?;
+        directory.read_exact(&mut extra)?;
+        skip(&mut directory, u16_at(&header, 32))?;
+        let name = String::from_utf8_lossy(&name).replace('\\', "/");
+        if u16_at(&header, 8) & 1 != 0 {
+            return Err(bad(format!("{} is encrypted", name)));
+        }
+
+        // Sizes and offset that don't fit in 32 bits are in the zip64 extra field,
+        // in this order, for just the ones marked 0xffffffff
+        let mut values = [
+            u32_at(&header, 24),
+            u32_at(&header, 20),
+            u32_at(&header, 42),
+        ];
+        let mut at = 0;
+        while at + 4 <= extra.len() {
+            let (id, length) = (u16_at(&extra, at), u16_at(&extra, at + 2) as usize);
+            if id == 1 {
+                let mut field = at + 4;
+                for value in values.iter_mut().filter(|v| **v == 0xffff_ffff) {
+                    if field + 8 <= (at + 4 + length).min(extra.len()) {
+                        *value = u64_at(&extra, field);
+                        field += 8;
+                    }
+                }
+            }
+            at += 4 + length;
+        }
+        if !name.ends_with('/') {
+            entries.push((name, u16_at(&header, 10), values[1], values[0], values[2]));
+        }
+    }
+
+    // Tag files first, so the payload is hashed only in the manifests' algorithms
+    entries.sort_by_key(|entry| !might_be_tag(&entry.0));
+    let mut algorithms = None;
+    let mut members = Vec::new();
+    for (name, method, compressed, size, local) in entries {
+        send_progress(progress_tx, members.len() + 1, &name);
+        let mut header = [0u8; 30];
+        file.seek(SeekFrom::Start(local))?;
+        file.read_exact(&mut header)?;
+        if header[..4] != [0x50, 0x4b, 0x03, 0x04] {
+            return Err(bad(format!("the zip entry for {} is damaged", name)));
+        }
+        let data = local + 30 + u16_at(&header, 26) + u16_at(&header, 28);
+        file.seek(SeekFrom::Start(data))?;
+        let mut raw = BufReader::new(&mut file).take(compressed);
+        let keep = might_be_tag(&name);
+        let wanted = match keep {
+            true => &ChecksumAlgorithm::ALL[..],
+            false => algorithms.get_or_insert_with(|| manifest_algorithms(&members)),
+        };
+        let (read, digest, contents) = match method {
+            0 => hash_member(&mut raw, keep, wanted)?,
+            8 => hash_member(&mut DeflateDecoder::new(raw), keep, wanted)?,
+            _ => {
+                return Err(bad(format!(
+                    "{} uses compression method {}, which isn't supported",
+                    name, method
+                )))
+            }
+        };
+        if read != size {
+            return Err(bad(format!("{} is shorter than the zip says", name)));
+        }
+        members.push((name, size, digest, contents));
+    }
+    Ok(members)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{bag_directory, BagOptions};
+    use crate::fixtures::{broken_bag, Breakage};
+    use crate::tape::{write_tape, TapeOptions};
+    use std::fs;
+    use std::path::PathBuf;
+
+    fn make_bag(temp_dir: &Path) -> PathBuf {
+        if temp_dir.exists() {
+            fs::remove_dir_all(temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("letters");
+        fs::create_dir_all(bag.join("1921")).unwrap();
+        fs::write(bag.join("1921/march.txt"), "Dear all,").unwrap();
+        fs::write(bag.join(format!("{}.txt", "long".repeat(40))), "x").unwrap();
+        bag_directory(&bag, &BagOptions::default(), None).unwrap();
+        bag
+    }
+
+    /// Writes the files under `bag`, named from `base`, into a zip with nothing
+    /// compressed.
+    fn write_stored_zip(bag: &Path, base: &Path, zip: &Path) {
+        let mut out = Vec::new();
+        let mut directory = Vec::new();
+        let mut count = 0u16;
+        for entry in walkdir::WalkDir::new(bag).sort_by_file_name() {
+            let entry = entry.unwrap();
+            if !entry.file_type().is_file() {
+                continue;
+            }
+            let relative = entry.path().strip_prefix(base).unwrap();
+            let name = relative.to_string_lossy().replace('\\', "/");
+            let data = fs::read(entry.path()).unwrap();
+            let offset = out.len() as u32;
+            let mut fields = Vec::new();
+            fields.extend_from_slice(&[20, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0]);
+            fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+            fields.extend_from_slice(&[0, 0]);
+
+            out.extend_from_slice(&[0x50, 0x4b, 0x03, 0x04]);
+            out.extend_from_slice(&fields);
+            out.extend_from_slice(name.as_bytes());
+            out.extend_from_slice(&data);
+
+            directory.extend_from_slice(&[0x50, 0x4b, 0x01, 0x02, 20, 0]);
+            directory.extend_from_slice(&fields);
+            directory.extend_from_slice(&[0; 10]);
+            directory.extend_from_slice(&offset.to_le_bytes());
+            directory.extend_from_slice(name.as_bytes());
+            count += 1;
+        }
+        let start = out.len() as u32;
+        out.extend_from_slice(&directory);
+        out.extend_from_slice(&[0x50, 0x4b, 0x05, 0x06, 0, 0, 0, 0]);
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&count.to_le_bytes());
+        out.extend_from_slice(&(directory.len() as u32).to_le_bytes());
+        out.extend_from_slice(&start.to_le_bytes());
+        out.extend_from_slice(&[0, 0]);
+        fs::write(zip, out).unwrap();
+    }
+
+    #[test]
+    fn test_validate_tar() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_archive_tar");
+        let bag = make_bag(&temp_dir);
+        let out = temp_dir.join("tape");
+        let segments = write_tape(&bag, &out, &TapeOptions::default(), None).unwrap();
+
+        let options = ValidationOptions::default();
+        let report = validate_archive(&segments[0], &options, None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // A changed payload byte shows up without unpacking anything
+        let mut bytes = fs::read(&segments[0]).unwrap();
+        let at = bytes.windows(9).position(|w| w == b"Dear all,").unwrap();
+        bytes[at] = b'B';
+        fs::write(&segments[0], bytes).unwrap();
+        let report = validate_archive(&segments[0], &options, None).unwrap();
+        assert!(!report.is_valid());
+        assert!(report
+            .errors
+            .iter()
+            .any(|e| e.message.contains("data/1921/march.txt")));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_zip() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_archive_zip");
+        let bag = make_bag(&temp_dir);
+        let zip = temp_dir.join("letters.zip");
+        write_stored_zip(&bag, &temp_dir, &zip);
+
+        let options = ValidationOptions::default();
+        let report = validate_archive(&zip, &options, None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // A payload file missing from the zip
+        fs::remove_file(bag.join("data/1921/march.txt")).unwrap();
+        write_stored_zip(&bag, &temp_dir, &zip);
+        let report = validate_archive(&zip, &options, None).unwrap();
+        assert!(!report.is_valid());
+
+        assert!(matches!(
+            validate_archive(&temp_dir.join("letters.rar"), &options, None),
+            Err(BagError::BadArchive(_))
+        ));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_serialize_formats() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_archive_serialize");
+        let bag = make_bag(&temp_dir);
+        let out = temp_dir.join("out");
+
+        let options = ValidationOptions::default();
+        for format in [
+            SerialFormat::Zip,
+            SerialFormat::Tar,
+            SerialFormat::TarGz,
+            SerialFormat::TarZst,
+            SerialFormat::SevenZip,
+        ] {
+            let serial = SerialOptions {
+                format,
+                ..SerialOptions::default()
+            };
+            let path = serialize_bag(&bag, &out, &serial, None).unwrap();
+            assert_eq!(SerialFormat::of(&path), Some(format));
+            let report = validate_archive(&path, &options, None).unwrap();
+            assert!(report.is_valid(), "{:?}: {:?}", format, report.errors);
+        }
+        // zstd at a high level on several threads still reads back
+        let zstd = SerialOptions {
+            format: SerialFormat::TarZst,
+            zstd_level: 19,
+            threads: 4,
+            ..SerialOptions::default()
+        };
+        let path = serialize_bag(&bag, &out, &zstd, None).unwrap();
+        assert!(validate_archive(&path, &options, None).unwrap().is_valid());
+        assert_eq!(
+            SerialFormat::of(Path::new("x.TZST")),
+            Some(SerialFormat::TarZst)
+        );
+        assert_eq!(
+            SerialFormat::of(Path::new("x.tgz")),
+            Some(SerialFormat::TarGz)
+        );
+
+        // A zip's payload is hashed only in the algorithm its manifest uses
+        let zip = out.join("letters.zip");
+        let archive = ArchiveBag::open(&zip, &None).unwrap();
+        let (_, digests) = &archive.files["data/1921/march.txt"];
+        assert!(!digests[ChecksumAlgorithm::default() as usize].is_empty());
+        assert_eq!(digests.iter().filter(|d| d.is_empty()).count(), 4);
+
+        // Damage survives serializing and is found in the archive
+        let broken = temp_dir.join("broken");
+        broken_bag(&broken, Breakage::ChangedPayload).unwrap();
+        let path = serialize_bag(&broken, &out, &zstd, None).unwrap();
+        assert!(!validate_archive(&path, &options, None).unwrap().is_valid());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_partial_archive_removed() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_archive_partial");
+        let bag = make_bag(&temp_dir);
+        let mut members = collect_members(&bag).unwrap();
+        // A payload file that's gone by the time it's written
+        let gone = members.last_mut().unwrap();
+        fs::remove_file(&gone.source).unwrap();
+        let zip = temp_dir.join("letters.zip");
+        let options = SerialOptions {
+            format: SerialFormat::Zip,
+            ..SerialOptions::default()
+        };
+        assert!(write_archive(&members, &zip, &options, &None).is_err());
+        assert!(!zip.exists());
+
+        // A path too long for zip fails rather than being cut short
+        members.pop();
+        members[0].name = "data/".to_string() + &"a/".repeat(40_000) + "letter.txt";
+        let result = write_zip(&members, &mut Vec::new(), &None);
+        assert!(
+            matches!(result, Err(BagError::BadArchive(_))),
+            "{:?}",
+            result
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    /// Deflates and hashes 4 GiB of zeros, so it's slow; run with
+    /// `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_zip_member_past_4_gib() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_archive_zip_4gib");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("big");
+        fs::create_dir_all(&bag).unwrap();
+        let size: u64 = 4 * 1024 * 1024 * 1024 + 1;
+        File::create(bag.join("big.bin"))
+            .unwrap()
+            .set_len(size)
+            .unwrap();
+        bag_directory(&bag, &BagOptions::default(), None).unwrap();
+
+        let zip = SerialOptions {
+            format: SerialFormat::Zip,
+            ..SerialOptions::default()
+        };
+        let path = serialize_bag(&bag, &temp_dir.join("out"), &zip, None).unwrap();
+        let report = validate_archive(&path, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert_eq!(report.files_checked, 4);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_reproducible_archives() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_archive_reproducible");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("letters");
+        fs::create_dir_all(&bag).unwrap();
+        fs::write(bag.join("march.txt"), "Dear all,").unwrap();
+        let logged = BagOptions {
+            event_log: true,
+            ..BagOptions::default()
+        };
+        bag_directory(&bag, &logged, None).unwrap();
+
+        for format in [
+            SerialFormat::Zip,
+            SerialFormat::Tar,
+            SerialFormat::TarGz,
+            SerialFormat::TarZst,
+            SerialFormat::SevenZip,
+        ] {
+            let options = SerialOptions {
+                format,
+                threads: 4,
+                reproducible: true,
+                ..SerialOptions::default()
+            };
+            let first = fs::read(serialize_bag(&bag, &temp_dir.join("a"), &options, None).unwrap());
+            // A later modification time makes no difference
+            let file = File::options()
+                .write(true)
+                .open(bag.join("data/march.txt"))
+                .unwrap();
+            file.set_modified(std::time::SystemTime::now() + std::time::Duration::from_secs(3600))
+                .unwrap();
+            let path = serialize_bag(&bag, &temp_dir.join("b"), &options, None).unwrap();
+            assert_eq!(first.unwrap(), fs::read(&path).unwrap(), "{:?}", format);
+            let report = validate_archive(&path, &ValidationOptions::default(), None).unwrap();
+            assert!(report.is_valid(), "{:?}: {:?}", format, report.errors);
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_single_top_level_folder() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_archive_top_level");
+        let bag = make_bag(&temp_dir);
+        let zip = temp_dir.join("letters.zip");
+
+        // The bag's files at the top of the zip, with no folder around them
+        write_stored_zip(&bag, &bag, &zip);
+        let report = validate_archive(&zip, &ValidationOptions::default(), None).unwrap();
+        assert_eq!(report.errors.len(), 1, "{:?}", report.errors);
+        assert_eq!(report.errors[0].rule, crate::bagit::RULE_SERIALIZATION);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,175 @@
+use crate::bagit::glob_match;
+use crate::tagfile::read_tag_file;
+use std::io;
+use std::path::Path;
+
+/// File in the source folder listing what to leave out of the payload, in gitignore
+/// syntax. It's never bagged itself.
+pub const BAGIGNORE_FILE: &str = ".bagignore";
+
+/// One pattern line of a .bagignore file.
+#[derive(Debug, Clone)]
+struct Rule {
+    /// The pattern split at `/`; a single part matches a name at any depth.
+    parts: Vec<String>,
+    /// Given with a `/` other than a trailing one, so it matches from the folder root.
+    anchored: bool,
+    /// Given with a trailing `/`, so it only matches folders.
+    dir_only: bool,
+    /// Given with a leading `!`, so it brings back what an earlier line left out.
+    negated: bool,
+}
+
+/// The rules of a folder's .bagignore. As with gitignore, the last line that matches
+/// a path decides it, and nothing inside a left-out folder can be brought back.
+/// Matching ignores ASCII case, like the skip list.
+#[derive(Debug, Clone, Default)]
+pub struct BagIgnore {
+    rules: Vec<Rule>,
+}
+
+impl BagIgnore {
+    /// Reads `folder`'s .bagignore; a folder without one ignores nothing.
+    pub fn load(folder: &Path) -> io::Result<Self> {
+        match read_tag_file(&folder.join(BAGIGNORE_FILE)) {
+            Ok((content, _)) => Ok(Self::parse(&content)),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Blank lines and `#` comments are skipped; a leading `\` escapes a `#` or `!`.
+    pub fn parse(content: &str) -> Self {
+        let rules = content
+            .lines()
+            .filter_map(|line| {
+                let line = line.trim_end();
+                if line.is_empty() || line.starts_with('#') {
+                    return None;
+                }
+                let (negated, line) = match line.strip_prefix('!') {
+                    Some(rest) => (true, rest),
+                    None => (false, line.strip_prefix('\\').unwrap_or(line)),
+                };
+                let (dir_only, line) = match line.strip_suffix('/') {
+                    Some(rest) => (true, rest),
+                    None => (false, line),
+                };
+                let anchored = line.contains('/');
+                let line = line.trim_start_matches('/');
+                (!line.is_empty()).then(|| Rule {
+                    parts: line.split('/').map(String::from).collect(),
+                    anchored,
+                    dir_only,
+                    negated,
+                })
+            })
+            .collect();
+        Self { rules }
+    }
+
+    /// Whether the entry at `relative` (to the folder holding the .bagignore) is left
+    /// out. The .bagignore itself always is.
+    pub fn is_ignored(&self, relative: &Path, is_dir: bool) -> bool {
+        let path: Vec<String> = relative
+            .iter()
+            .map(|part| part.to_string_lossy().to_string())
+            .collect();
+        if path.len() == 1 && path[0] == BAGIGNORE_FILE {
+            return true;
+        }
+        let mut ignored = false;
+        for rule in &self.rules {
+            if rule.dir_only && !is_dir {
+                continue;
+            }
+            let matched = match rule.anchored {
+                true => match_parts(&rule.parts, &path),
+                false => path
+                    .last()
+                    .is_some_and(|name| glob_match(&rule.parts[0], name)),
+            };
+            if matched {
+                ignored = !rule.negated;
+            }
+        }
+        ignored
+    }
+}
+
+/// Matches path parts against pattern parts, where a `**` part stands for any number
+/// of folders.
+fn match_parts(pattern: &[String], path: &[String]) -> bool {
+    match pattern.split_first() {
+        None => path.is_empty(),
+        Some((first, rest)) if first == "**" => {
+            (0..=path.len()).any(|skip| match_parts(rest, &path[skip..]))
+        }
+        Some((first, rest)) => path
+            .split_first()
+            .is_some_and(|(name, path)| glob_match(first, name) && match_parts(rest, path)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{bag_directory, validate_bag, BagOptions, ValidationOptions};
+    use std::fs;
+
+    #[test]
+    fn test_bagignore_rules() {
+        let ignore = BagIgnore::parse(
+            "# scratch files\n\
+             *.tmp\n\
+             !keep.tmp\n\
+             build/\n\
+             /notes.txt\n\
+             docs/**/draft-*\n\
+             \\#hash\n",
+        );
+        let ignored = |path: &str, is_dir| ignore.is_ignored(Path::new(path), is_dir);
+        assert!(ignored("a.tmp", false));
+        assert!(ignored("deep/down/b.TMP", false));
+        assert!(!ignored("keep.tmp", false));
+        assert!(ignored("src/build", true));
+        assert!(!ignored("build", false));
+        assert!(ignored("notes.txt", false));
+        assert!(!ignored("sub/notes.txt", false));
+        assert!(ignored("docs/draft-1.odt", false));
+        assert!(ignored("docs/2024/05/draft-2.odt", false));
+        assert!(!ignored("docs/final.odt", false));
+        assert!(ignored("#hash", false));
+        assert!(ignored(".bagignore", false));
+        assert!(!ignored("sub/.bagignore", false));
+    }
+
+    #[test]
+    fn test_bag_directory_honors_bagignore() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_bagignore");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(temp_dir.join("cache")).unwrap();
+        fs::write(temp_dir.join(BAGIGNORE_FILE), "cache/\n*.log\n").unwrap();
+        fs::write(temp_dir.join("cache/thumb.png"), "png").unwrap();
+        fs::write(temp_dir.join("run.log"), "log").unwrap();
+        fs::write(temp_dir.join("thesis.pdf"), "pdf").unwrap();
+
+        let report = bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+        let manifest = fs::read_to_string(temp_dir.join("manifest-sha256.txt")).unwrap();
+        assert_eq!(manifest.lines().count(), 1);
+        assert!(manifest.contains("data/thesis.pdf"));
+        assert_eq!(report.skipped.len(), 3);
+        // Left where they were, and not taken for tag files
+        assert!(temp_dir.join(BAGIGNORE_FILE).is_file());
+        assert!(temp_dir.join("cache/thumb.png").is_file());
+        let tagmanifest = fs::read_to_string(temp_dir.join("tagmanifest-sha256.txt")).unwrap();
+        assert!(!tagmanifest.contains("run.log"));
+        assert!(!tagmanifest.contains(BAGIGNORE_FILE));
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -1,15 +1,141 @@
-use sha2::{Digest, Sha256};
+use crate::bagignore::BagIgnore;
+use crate::clone::copy_hashing;
+use crate::dedup::{dedup_payload, parse_fetch_file, DEDUP_REPORT_FILE, FETCH_FILE};
+use crate::diff::BagDiff;
+use crate::download::SOURCE_URLS_FILE;
+use crate::events::{write_event_log, Event, EventType, EVENT_LOG_FILE};
+use crate::journal::{Journal, JOURNAL_FILE};
+use crate::json;
+use crate::linesort::LineSorter;
+use crate::plugin::{run_plugins, Plugin, PluginStage};
+use crate::profile::Profile;
+use crate::provenance::{
+    restore_permissions, write_records, ProvenanceLog, FILE_METADATA_FILE, FILE_PERMISSIONS_FILE,
+    PROVENANCE_FILE, XATTRS_FILE,
+};
+use crate::signature::{
+    check_signature, is_signed, remove_signatures, sign_bag, SignatureCheck, Signing,
+};
+use crate::tagfile::{
+    decode_tag_bytes, decode_tag_bytes_as, read_tag_file, tag_value, write_tag_file, BagInfo,
+    TagEncoding,
+};
+use md5::Md5;
+use sha1::Sha1;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs::{self, File};
+use std::hash::{BuildHasher, Hasher};
 use std::io::{self, BufReader, Read};
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicUsize, Ordering};
 use std::sync::mpsc::Sender;
+use std::sync::Mutex;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 use walkdir::WalkDir;
 
 #[derive(Debug)]
 pub enum BagError {
     NotADirectory,
     IoError(io::Error),
-    AlreadyABag,
+    AlreadyABag(BagLikeReason),
+    NotABag,
+    /// The destination for a copied bag already has something in it.
+    DestinationNotEmpty(PathBuf),
+    /// There was nothing to bag (after skipping) and `EmptySourcePolicy::Refuse` is set.
+    EmptySource,
+    /// A file offered as a replacement doesn't have the checksum the manifest expects.
+    ReplacementMismatch(PathBuf),
+    /// A single payload file is bigger than one volume can hold, so no split works.
+    FileTooLargeForVolume(PathBuf),
+    /// A file that was listed during the walk was gone by the time it was moved or hashed.
+    FileVanished(PathBuf),
+    /// A copy made for another medium doesn't match the bag's manifest.
+    CopyMismatch(PathBuf),
+    /// A tape block size that isn't a whole number of 512-byte tar records.
+    BadTapeBlockSize(u64),
+    /// A line of a URL list couldn't be read.
+    BadUrlList {
+        line: usize,
+        message: String,
+    },
+    /// A line of a file list couldn't be read.
+    BadFileList {
+        line: usize,
+        message: String,
+    },
+    /// A source location isn't `s3://bucket/prefix`.
+    BadS3Location(String),
+    /// A resource couldn't be downloaded intact, even after retrying.
+    DownloadFailed {
+        url: String,
+        message: String,
+    },
+    /// A plugin exited with an error or broke its contract.
+    PluginFailed {
+        command: String,
+        message: String,
+    },
+    /// A serialized bag that can't be read: not a zip or tar, damaged, or using an
+    /// unsupported feature.
+    BadArchive(String),
+    /// gpg or minisign couldn't sign the tag manifest.
+    SigningFailed {
+        tool: String,
+        message: String,
+    },
+    /// age or gpg couldn't encrypt a serialized bag, or wasn't given anyone to
+    /// encrypt it for.
+    EncryptionFailed {
+        tool: String,
+        message: String,
+    },
+    /// A serialized bag split into volumes can't be put back together: a volume or
+    /// its list is missing or damaged.
+    BadVolumes(String),
+    /// The bag has files only referenced in fetch.txt, so rewriting its manifests
+    /// would drop them.
+    HasFetchReferences,
+    /// A payload file would land on a file or folder already at the bag's top level
+    /// when the bag is undone.
+    UnbagConflict(PathBuf),
+    /// A file name has a line break, which a BagIt 0.97 manifest can't list.
+    UnlistablePath(PathBuf),
+    /// Two payload paths differ only in case and `CaseCollisionPolicy::Refuse` is set.
+    CaseCollision(PathBuf, PathBuf),
+    /// A tag directory isn't a folder, or has a name the bag's own files need.
+    BadTagDirectory(PathBuf),
+    /// A payload file couldn't be read (or moved), and the error policy doesn't skip it.
+    Unreadable(PathBuf, io::Error),
+    /// A payload file stayed open in another program through every retry, and the
+    /// error policy doesn't skip it.
+    FileLocked(PathBuf),
+    /// A symlink leads back to a folder that contains it, so following it would never
+    /// end.
+    CycleDetected(PathBuf),
+    /// The payload nests folders deeper than `BagOptions::max_depth`.
+    TooDeep {
+        path: PathBuf,
+        max_depth: usize,
+    },
+    /// A folder bagged alongside others was given a `data/` subfolder name that isn't
+    /// one folder name, or that another already has.
+    BadSourceName(String),
+    /// Bags given as a group don't make a whole one: a `Bag-Count` is missing or
+    /// repeated, the `Bag-Group-Identifier`s differ, or one of them isn't valid.
+    BadBagGroup(String),
+    /// Payload files read back after bagging don't match the manifest just written.
+    VerifyFailed(Vec<String>),
+    /// A BagIt Profile couldn't be read, or isn't one.
+    BadProfile(String),
+    /// The bag the options describe wouldn't conform to their BagIt Profile.
+    ProfileViolation(Vec<String>),
+    /// The volume a bag or archive would be written to hasn't the room for it.
+    InsufficientSpace {
+        path: PathBuf,
+        needed: u64,
+        available: u64,
+    },
 }
 
 impl std::fmt::Display for BagError {
@@ -17,7 +143,143 @@ impl std::fmt::Display for BagError {
         match self {
             BagError::NotADirectory => write!(f, "Path is not a directory"),
             BagError::IoError(e) => write!(f, "IO error: {}", e),
-            BagError::AlreadyABag => write!(f, "Directory appears to already be a bag"),
+            BagError::AlreadyABag(BagLikeReason::Declaration) => {
+                write!(f, "Directory appears to already be a bag (it has a bagit.txt)")
+            }
+            BagError::AlreadyABag(BagLikeReason::DataFolder) => write!(
+                f,
+                "Directory has a data/ folder, so bagging it in place would mix it into the payload"
+            ),
+            BagError::NotABag => write!(f, "Directory is not a bag (no bagit.txt)"),
+            BagError::DestinationNotEmpty(path) => {
+                write!(f, "Destination already exists and is not empty: {}", path.display())
+            }
+            BagError::FileTooLargeForVolume(path) => write!(
+                f,
+                "{} is larger than a whole volume, so the payload can't be split to fit",
+                path.display()
+            ),
+            BagError::ReplacementMismatch(path) => write!(
+                f,
+                "{} doesn't match the manifest checksum, so it wasn't used",
+                path.display()
+            ),
+            BagError::EmptySource => write!(
+                f,
+                "Folder has no files to bag (empty folders and skipped system files don't count)"
+            ),
+            BagError::FileVanished(path) => write!(
+                f,
+                "File vanished during bagging: {} (deleted or moved by another program after it was listed)",
+                path.display()
+            ),
+            BagError::CopyMismatch(path) => write!(
+                f,
+                "{} doesn't match the bag's manifest after copying; the source or the target may be failing",
+                path.display()
+            ),
+            BagError::BadTapeBlockSize(size) => {
+                write!(f, "Tape block size {} is not a multiple of 512 bytes", size)
+            }
+            BagError::BadUrlList { line, message } => {
+                write!(f, "URL list line {}: {}", line, message)
+            }
+            BagError::BadFileList { line, message } => {
+                write!(f, "File list line {}: {}", line, message)
+            }
+            BagError::BadS3Location(location) => {
+                write!(f, "Not an S3 location (expected s3://bucket/prefix): {}", location)
+            }
+            BagError::DownloadFailed { url, message } => {
+                write!(f, "Could not download {}: {}", url, message)
+            }
+            BagError::PluginFailed { command, message } => {
+                write!(f, "Plugin `{}` failed: {}", command, message)
+            }
+            BagError::BadArchive(message) => write!(f, "Can't read the serialized bag: {}", message),
+            BagError::SigningFailed { tool, message } => {
+                write!(f, "Signing with {} failed: {}", tool, message)
+            }
+            BagError::EncryptionFailed { tool, message } => {
+                write!(f, "Encrypting with {} failed: {}", tool, message)
+            }
+            BagError::BadVolumes(message) => write!(f, "Can't reassemble the volumes: {}", message),
+            BagError::HasFetchReferences => write!(
+                f,
+                "Bag has files referenced in fetch.txt; fetch them into data/ before updating or unbagging it"
+            ),
+            BagError::UnbagConflict(path) => write!(
+                f,
+                "{} is both in data/ and next to it, so the bag can't be undone without overwriting one",
+                path.display()
+            ),
+            BagError::UnlistablePath(path) => write!(
+                f,
+                "{} has a line break in its name, which only BagIt 1.0 manifests can list",
+                path.display()
+            ),
+            BagError::CaseCollision(first, second) => write!(
+                f,
+                "{} and {} differ only in case, so one would overwrite the other on Windows and macOS",
+                first.display(),
+                second.display()
+            ),
+            BagError::InsufficientSpace {
+                path,
+                needed,
+                available,
+            } => write!(
+                f,
+                "{} needs about {} of free space, but only {} is free there",
+                path.display(),
+                bag_size(*needed),
+                bag_size(*available)
+            ),
+            BagError::BadTagDirectory(path) => write!(
+                f,
+                "{} can't be a tag directory: it must be a folder, and not one named data",
+                path.display()
+            ),
+            BagError::FileLocked(path) => write!(
+                f,
+                "{} is open in another program; close it and bag again, or choose to skip files that can't be read",
+                path.display()
+            ),
+            BagError::BadSourceName(name) => write!(
+                f,
+                "\"{}\" can't name a folder in data/: each needs a different single folder name",
+                name
+            ),
+            BagError::VerifyFailed(files) => write!(
+                f,
+                "{} payload files didn't match the manifest when read back ({}); the disk or the copy may be failing, so the bag wasn't sealed",
+                files.len(),
+                files.iter().take(3).cloned().collect::<Vec<_>>().join(", ")
+            ),
+            BagError::BadBagGroup(message) => write!(f, "Not a whole bag group: {}", message),
+            BagError::BadProfile(message) => write!(f, "Not a usable BagIt Profile: {}", message),
+            BagError::ProfileViolation(problems) => write!(
+                f,
+                "The bag wouldn't conform to its BagIt Profile, so nothing was done: {}",
+                problems.join("; ")
+            ),
+            BagError::CycleDetected(path) => write!(
+                f,
+                "{} is a symlink to a folder it's inside, so following it would loop forever",
+                path.display()
+            ),
+            BagError::TooDeep { path, max_depth } => write!(
+                f,
+                "{} is nested more than {} folders deep; a symlink may be looping, or the limit can be raised in the settings",
+                path.display(),
+                max_depth
+            ),
+            BagError::Unreadable(path, e) => write!(
+                f,
+                "{} couldn't be read: {} (skipping unreadable files is in the settings)",
+                path.display(),
+                e
+            ),
         }
     }
 }
@@ -28,224 +290,6328 @@ impl From<io::Error> for BagError {
     }
 }
 
+/// Why a folder was taken for an existing bag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum BagLikeReason {
+    /// It has a `bagit.txt`, so it was almost certainly bagged before.
+    Declaration,
+    /// It has no `bagit.txt` but does have a `data/` folder, which would collide with
+    /// the payload directory.
+    DataFolder,
+}
+
+/// Returns why `path` can't be bagged in place, or `None` if it can. A folder whose
+/// bagging was interrupted can be, which resumes it.
+pub fn bag_like_reason(path: &Path) -> Option<BagLikeReason> {
+    if path.join(JOURNAL_FILE).is_file() {
+        None
+    } else if path.join("bagit.txt").exists() {
+        Some(BagLikeReason::Declaration)
+    } else if path.join("data").exists() {
+        Some(BagLikeReason::DataFolder)
+    } else {
+        None
+    }
+}
+
+/// Which clock `Bagging-Date` (and `Bagging-Time`) are taken from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DateMode {
+    #[default]
+    Local,
+    Utc,
+}
+
+/// The BagIt version new bags declare in bagit.txt.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum BagItVersion {
+    /// The last draft before the RFC, which many older tools still expect.
+    V0_97,
+    /// RFC 8493: manifest paths percent-encode CR, LF and `%`, and the tag manifest
+    /// covers every tag file.
+    #[default]
+    V1_0,
+}
+
+impl BagItVersion {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            BagItVersion::V0_97 => "0.97",
+            BagItVersion::V1_0 => "1.0",
+        }
+    }
+
+    pub fn parse(version: &str) -> Option<BagItVersion> {
+        match version.trim() {
+            "0.97" => Some(BagItVersion::V0_97),
+            "1.0" => Some(BagItVersion::V1_0),
+            _ => None,
+        }
+    }
+
+    /// `path` as manifests and fetch.txt of this version list it.
+    pub fn listed_path(self, path: &str) -> String {
+        match self {
+            BagItVersion::V0_97 => path.to_string(),
+            BagItVersion::V1_0 => encode_manifest_path(path),
+        }
+    }
+}
+
+/// Whether a bag declaring `version` percent-encodes manifest paths: from 1.0 on.
+fn encodes_paths(version: &str) -> bool {
+    !version.trim().starts_with("0.")
+}
+
+/// Whether the bag at `bag` percent-encodes the paths in its manifests and fetch.txt.
+pub fn bag_encodes_paths(bag: &Path) -> bool {
+    match read_tag_file(&bag.join("bagit.txt")) {
+        Ok((bagit, _)) => tag_value(&bagit, "BagIt-Version").is_some_and(encodes_paths),
+        Err(_) => false,
+    }
+}
+
+/// A path as a BagIt 1.0 manifest writes it (RFC 8493 §2.1.3).
+pub fn encode_manifest_path(path: &str) -> String {
+    path.replace('%', "%25")
+        .replace('\r', "%0D")
+        .replace('\n', "%0A")
+}
+
+/// Reverses `encode_manifest_path`, accepting either case of hex digit.
+pub fn decode_manifest_path(path: &str) -> String {
+    let mut decoded = String::with_capacity(path.len());
+    let mut rest = path;
+    while let Some(i) = rest.find('%') {
+        decoded.push_str(&rest[..i]);
+        let code = rest.get(i + 1..i + 3).map(|c| c.to_ascii_uppercase());
+        let (text, len) = match code.as_deref() {
+            Some("25") => ("%", 3),
+            Some("0D") => ("\r", 3),
+            Some("0A") => ("\n", 3),
+            _ => ("%", 1),
+        };
+        decoded.push_str(text);
+        rest = &rest[i + len..];
+    }
+    decoded.push_str(rest);
+    decoded
+}
+
+/// Files and folders macOS creates behind the user's back.
+pub const MACOS_SYSTEM_FILES: &[&str] = &[
+    ".DS_Store",
+    "._*",
+    ".Spotlight-V100",
+    ".Trashes",
+    ".fseventsd",
+    ".TemporaryItems",
+    ".DocumentRevisions-V100",
+];
+
+/// Files and folders Windows creates behind the user's back.
+pub const WINDOWS_SYSTEM_FILES: &[&str] = &[
+    "Thumbs.db",
+    "ehthumbs.db",
+    "desktop.ini",
+    "~$*",
+    "$RECYCLE.BIN",
+    "System Volume Information",
+];
+
+/// Files and folders Linux desktops create behind the user's back.
+pub const LINUX_SYSTEM_FILES: &[&str] = &[".directory", ".Trash-1000"];
+
+/// The built-in skip list: every OS's system files, since media moves between machines.
+pub fn default_skip_names() -> Vec<String> {
+    MACOS_SYSTEM_FILES
+        .iter()
+        .chain(WINDOWS_SYSTEM_FILES)
+        .chain(LINUX_SYSTEM_FILES)
+        .map(|s| s.to_string())
+        .collect()
+}
+
+/// What to do when a single file can't be read, or vanishes mid-run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ErrorPolicy {
+    /// Stop the whole run with a precise error.
+    #[default]
+    Abort,
+    /// Leave the file out of the bag and record it in the report.
+    Skip,
+    /// Try an unreadable file again after each of `RETRY_DELAYS`, as antivirus scans and
+    /// sync clients let go of files in time, then stop the run.
+    Retry,
+}
+
+/// Waits between attempts at a locked file, or any unreadable one under
+/// `ErrorPolicy::Retry`.
+const RETRY_DELAYS: [Duration; 3] = [
+    Duration::from_millis(250),
+    Duration::from_secs(1),
+    Duration::from_secs(4),
+];
+
+/// Runs `op` on a payload file, trying again if the file is locked, as another program
+/// often lets go of it in a moment, or if it fails at all under `ErrorPolicy::Retry`.
+/// A file that's gone isn't retried.
+pub fn with_retries<T>(
+    options: &BagOptions,
+    mut op: impl FnMut() -> io::Result<T>,
+) -> io::Result<T> {
+    for delay in RETRY_DELAYS {
+        match op() {
+            Err(e) if is_locked(&e) => std::thread::sleep(delay),
+            Err(e)
+                if options.error_policy == ErrorPolicy::Retry
+                    && e.kind() != io::ErrorKind::NotFound =>
+            {
+                std::thread::sleep(delay)
+            }
+            result => return result,
+        }
+    }
+    op()
+}
+
+/// Whether `err` is Windows refusing access to a file another program has open
+/// (a sharing or lock violation). Other systems don't lock files that way.
+fn is_locked(err: &io::Error) -> bool {
+    cfg!(windows) && matches!(err.raw_os_error(), Some(32 | 33))
+}
+
+/// What to do when the source folder has no files to put in the payload.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptySourcePolicy {
+    /// Refuse with `BagError::EmptySource`, leaving the folder untouched.
+    #[default]
+    Refuse,
+    /// Make an empty bag (`Payload-Oxum: 0.0`) and warn about it in the report.
+    Allow,
+}
+
+/// What to do about payload paths that differ only in case, like `Report.pdf` and
+/// `report.pdf`, which collide when the bag is extracted on Windows or macOS.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CaseCollisionPolicy {
+    /// Bag them anyway and name each pair in the report's warnings.
+    #[default]
+    Warn,
+    /// Refuse with `BagError::CaseCollision`, leaving the folder untouched.
+    Refuse,
+}
+
+/// Folder inside the bag where baggie's tag files are written before being moved into
+/// place.
+pub(crate) const STAGING_DIR: &str = ".baggie-staging";
+
+/// Tag file listing the payload's empty folders, which no manifest can.
+pub const EMPTY_DIRS_FILE: &str = "empty-folders.txt";
+
+/// File put in each empty folder under `EmptyDirPolicy::Placeholder`.
+pub const PLACEHOLDER_FILE: &str = ".keep";
+
+/// What to do about empty folders in the payload. Manifests only list files, so tools
+/// that copy or check a bag by its manifest lose them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum EmptyDirPolicy {
+    /// Keep them as they are and name them in the report's warnings.
+    #[default]
+    Warn,
+    /// Put an empty `.keep` file in each, so it's listed like any other file.
+    Placeholder,
+    /// List them in the tag file `empty-folders.txt`.
+    Record,
+}
+
+/// What to do with payload files whose content is already in another bag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DedupMode {
+    /// Store every file.
+    #[default]
+    Off,
+    /// Store every file, and list the ones already stored elsewhere in `dedup-report.txt`.
+    Report,
+    /// Leave those files out of `data/` and point to the existing copy in `fetch.txt`.
+    Fetch,
+}
+
+/// How the `Internal-Sender-Identifier` of a new bag is made.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub enum IdScheme {
+    /// A random UUID.
+    #[default]
+    Uuid,
+    /// A ULID: sorts by creation time.
+    Ulid,
+    /// The prefix, then a number counting up: `ACC-000042`.
+    Sequence(String),
+}
+
+impl IdScheme {
+    /// A new identifier; `next` is the number a sequence is up to.
+    pub fn generate(&self, next: u64) -> String {
+        match self {
+            IdScheme::Uuid => new_uuid(),
+            IdScheme::Ulid => new_ulid(),
+            IdScheme::Sequence(prefix) => format!("{}{:06}", prefix, next),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct BagOptions {
+    pub date_mode: DateMode,
+    /// Also write a `Bagging-Time` field (`HH:MM:SS+hh:mm`) so the date is unambiguous.
+    pub include_bagging_time: bool,
+    /// File or folder names left out of the payload, matched case-insensitively. `*`
+    /// stands for any run of characters and `?` for any one, as in `*.tmp`.
+    pub skip_names: Vec<String>,
+    /// Leave out AppleDouble files, the `._name` shadows macOS writes beside files on
+    /// FAT and exFAT drives, told apart by their content rather than their name, so
+    /// they go even if `._*` is taken off the skip list.
+    pub skip_appledouble: bool,
+    /// Remove the `com.apple.quarantine` attribute from files copied into the payload.
+    pub strip_quarantine: bool,
+    /// Make payload files of a copied bag copy-on-write clones of the originals on
+    /// filesystems that have them (APFS, Btrfs, XFS), so they take no extra space.
+    /// Elsewhere they're copied as usual.
+    pub clone_files: bool,
+    /// Bag what symlinks point at rather than the links themselves. Only a copy is
+    /// bagged this way; bagging in place would move files out from wherever the links
+    /// lead.
+    pub follow_symlinks: bool,
+    /// Folders deep the payload may nest before bagging stops with
+    /// `BagError::TooDeep`, as a guard against loops that links alone don't reveal.
+    pub max_depth: usize,
+    pub error_policy: ErrorPolicy,
+    pub empty_source: EmptySourcePolicy,
+    pub empty_dirs: EmptyDirPolicy,
+    pub case_collisions: CaseCollisionPolicy,
+    /// Files hashed at once. More helps on SSDs and RAID; 1 is kinder to spinning
+    /// disks and optical media.
+    pub hash_threads: usize,
+    /// Read every payload file back once the manifests are written and compare it with
+    /// them, to catch a copy or move that went wrong without an error (flaky USB
+    /// drives, mostly). A file that doesn't match stops the bag before it's sealed.
+    pub verify_after: bool,
+    /// Extra bag-info.txt fields, written in order after the ones baggie generates.
+    /// Labels may repeat; any that clash with generated fields are ignored.
+    pub metadata: BagInfo,
+    /// External programs run at fixed points while the bag is written.
+    pub plugins: Vec<Plugin>,
+    pub dedup: DedupMode,
+    /// Bags searched for copies of payload files when `dedup` is on.
+    pub dedup_against: Vec<PathBuf>,
+    /// Folders copied into the bag as tag directories of the same name, such as
+    /// `metadata/`, and listed in the tag manifest. One inside the folder being bagged
+    /// is left out of the payload.
+    pub tag_directories: Vec<PathBuf>,
+    /// Makes `Internal-Sender-Identifier` for bags that don't have one yet.
+    pub id_scheme: IdScheme,
+    /// Number the next identifier gets under `IdScheme::Sequence`.
+    pub id_sequence_next: u64,
+    /// Write provenance.csv: each payload file's original path, size, modification
+    /// time and owner.
+    pub record_provenance: bool,
+    /// Write file-metadata.tsv: each payload file's modification, access and creation
+    /// times as found, to the nanosecond.
+    pub record_file_times: bool,
+    /// Write file-permissions.tsv: each payload file's Unix mode, user ID and group ID,
+    /// which unbagging puts back.
+    pub record_permissions: bool,
+    /// Write extended-attributes.tsv: each payload file's extended attributes, such as
+    /// macOS Finder tags and labels.
+    pub record_xattrs: bool,
+    /// Keep events.csv, a log of what was done to the bag. Bags that already have one
+    /// keep logging to it either way.
+    pub event_log: bool,
+    /// Algorithms of the payload and tag manifests, one pair each. The first is the one
+    /// deduplication compares by.
+    pub algorithms: Vec<ChecksumAlgorithm>,
+    pub bagit_version: BagItVersion,
+    /// BagIt Profile the bag must conform to. Bagging is refused before anything is
+    /// moved if the options break it, and the bag names it in bag-info.txt.
+    pub profile: Option<Profile>,
+    /// Sign tagmanifest-sha256.txt once the bag is sealed, with a detached signature
+    /// beside it.
+    pub signing: Option<Signing>,
+}
+
+/// Default for `BagOptions::max_depth`: far deeper than real folders go, and shallower
+/// than most path length limits allow.
+pub const DEFAULT_MAX_DEPTH: usize = 256;
+
+/// Bag-Software-Agent, and the agent of logged events.
+pub const SOFTWARE_AGENT: &str = "baggie 0.1.1";
+
+/// bag-info.txt fields baggie computes itself.
+pub const GENERATED_BAG_INFO_LABELS: &[&str] = &[
+    "Bag-Software-Agent",
+    "Bagging-Date",
+    "Bagging-Time",
+    "Payload-Oxum",
+    "Bag-Size",
+];
+
+/// Payload size for `Bag-Size`, in decimal units to one place (`42.3 GB`), as bagit.py
+/// and most repositories show it.
+pub fn bag_size(bytes: u64) -> String {
+    const UNITS: [&str; 5] = ["bytes", "KB", "MB", "GB", "TB"];
+    let mut value = bytes as f64;
+    let mut unit = 0;
+    while value >= 1000.0 && unit < UNITS.len() - 1 {
+        value /= 1000.0;
+        unit += 1;
+    }
+    match unit {
+        0 => format!("{} bytes", bytes),
+        _ => format!("{:.1} {}", value, UNITS[unit]),
+    }
+}
+
+impl Default for BagOptions {
+    fn default() -> Self {
+        Self {
+            date_mode: DateMode::default(),
+            include_bagging_time: false,
+            skip_names: default_skip_names(),
+            skip_appledouble: true,
+            strip_quarantine: false,
+            clone_files: true,
+            follow_symlinks: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            error_policy: ErrorPolicy::default(),
+            empty_source: EmptySourcePolicy::default(),
+            empty_dirs: EmptyDirPolicy::default(),
+            case_collisions: CaseCollisionPolicy::default(),
+            hash_threads: 1,
+            verify_after: false,
+            metadata: BagInfo::default(),
+            plugins: Vec::new(),
+            dedup: DedupMode::default(),
+            dedup_against: Vec::new(),
+            tag_directories: Vec::new(),
+            id_scheme: IdScheme::default(),
+            id_sequence_next: 1,
+            record_provenance: false,
+            record_file_times: false,
+            record_permissions: false,
+            record_xattrs: false,
+            event_log: false,
+            algorithms: vec![ChecksumAlgorithm::default()],
+            bagit_version: BagItVersion::default(),
+            profile: None,
+            signing: None,
+        }
+    }
+}
+
+impl BagOptions {
+    /// `algorithms` without repeats, or SHA-256 alone if there are none.
+    pub fn manifest_algorithms(&self) -> Vec<ChecksumAlgorithm> {
+        let mut algorithms = Vec::new();
+        for algorithm in &self.algorithms {
+            if !algorithms.contains(algorithm) {
+                algorithms.push(*algorithm);
+            }
+        }
+        if algorithms.is_empty() {
+            algorithms.push(ChecksumAlgorithm::default());
+        }
+        algorithms
+    }
+
+    fn is_skipped(&self, name: &std::ffi::OsStr) -> bool {
+        let name = name.to_string_lossy();
+        self.skip_names.iter().any(|s| glob_match(s, &name))
+    }
+}
+
+/// Whether `name` matches the glob `pattern`, ignoring ASCII case.
+pub fn glob_match(pattern: &str, name: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let name: Vec<char> = name.chars().map(|c| c.to_ascii_lowercase()).collect();
+    let (mut p, mut n) = (0, 0);
+    // The last `*` seen, and where in `name` its match currently ends
+    let mut star = None;
+    while n < name.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                star = Some((p, n));
+                p += 1;
+            }
+            Some(&c) if c == '?' || c == name[n] => {
+                p += 1;
+                n += 1;
+            }
+            _ => match star {
+                Some((star_p, star_n)) => {
+                    // Let the `*` take one more character and try again
+                    p = star_p + 1;
+                    n = star_n + 1;
+                    star = Some((star_p, star_n + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    pattern[p..].iter().all(|&c| c == '*')
+}
+
+/// What happened during bagging beyond the bag itself.
+#[derive(Debug, Clone, Default)]
+pub struct BagReport {
+    /// Entries left out of the payload by the skip list, relative to the bag root.
+    /// They stay where they were, outside `data/`.
+    pub skipped: Vec<PathBuf>,
+    /// Files that disappeared mid-run and were left out under `ErrorPolicy::Skip`.
+    pub vanished: Vec<PathBuf>,
+    /// Things worth telling the user that didn't stop the bag being made.
+    pub warnings: Vec<String>,
+    /// What was done, for the event log.
+    pub events: Vec<Event>,
+    /// Sets of payload files with the same content, most space wasted first.
+    pub duplicates: Vec<Duplicates>,
+    /// The bag as written, so callers needn't read its files back.
+    pub bag: Bag,
+}
+
+/// Payload files with the same checksum, and so the same content.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Duplicates {
+    /// The files (`data/...`, sorted).
+    pub files: Vec<String>,
+    /// Size of each.
+    pub size: u64,
+    /// Separate copies on disk: fewer than `files` where some are hardlinks to one
+    /// another.
+    pub copies: usize,
+}
+
+impl Duplicates {
+    /// Bytes that keeping only one copy would save.
+    pub fn wasted(&self) -> u64 {
+        self.size * (self.copies as u64 - 1)
+    }
+}
+
+/// Groups payload files, given as (checksum, `data/...` path, size), by checksum into
+/// sets of duplicates. `identities` tells hardlinks apart, where the system gives
+/// files one. Empty files are all alike, so aren't reported.
+fn find_duplicates(
+    payload: &[(String, String, u64)],
+    identities: &[Option<(u64, u64)>],
+) -> Vec<Duplicates> {
+    let mut by_checksum: HashMap<&str, Vec<usize>> = HashMap::new();
+    for (i, (checksum, _, size)) in payload.iter().enumerate() {
+        if *size > 0 {
+            by_checksum.entry(checksum).or_default().push(i);
+        }
+    }
+    let mut duplicates: Vec<Duplicates> = by_checksum
+        .into_values()
+        .filter(|files| files.len() > 1)
+        .map(|files| {
+            let mut seen = HashSet::new();
+            let copies = files
+                .iter()
+                .filter(|&&i| identities[i].is_none_or(|identity| seen.insert(identity)))
+                .count();
+            let mut names: Vec<String> = files.iter().map(|&i| payload[i].1.clone()).collect();
+            names.sort();
+            Duplicates {
+                files: names,
+                size: payload[files[0]].2,
+                copies,
+            }
+        })
+        .collect();
+    duplicates.sort_by(|a, b| {
+        b.wasted()
+            .cmp(&a.wasted())
+            .then_with(|| a.files.cmp(&b.files))
+    });
+    duplicates
+}
+
+/// Device and inode of a file, shared by its hardlinks.
+#[cfg(unix)]
+fn file_identity(metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.dev(), metadata.ino()))
+}
+
+#[cfg(not(unix))]
+fn file_identity(_metadata: &fs::Metadata) -> Option<(u64, u64)> {
+    None
+}
+
+/// A bag as its bagit.txt, bag-info.txt and manifests describe it.
+#[derive(Debug, Clone, Default)]
+pub struct Bag {
+    pub path: PathBuf,
+    /// BagIt-Version, as declared.
+    pub version: String,
+    /// Tag-File-Character-Encoding, as declared.
+    pub encoding: String,
+    /// The algorithms of its payload manifests.
+    pub algorithms: Vec<ChecksumAlgorithm>,
+    /// Each payload file (`data/...`, sorted) with its checksums in `algorithms` order,
+    /// including files deduplication left out of `data/`.
+    pub manifest: Vec<(String, Vec<String>)>,
+    /// The algorithms of its tag manifests, which needn't match `algorithms`.
+    pub tag_algorithms: Vec<ChecksumAlgorithm>,
+    /// Each tag file a tag manifest lists (sorted), with its checksum in each tag
+    /// manifest that lists it, in `tag_algorithms` order.
+    pub tag_manifest: Vec<(String, Vec<(ChecksumAlgorithm, String)>)>,
+    /// Payload-Oxum as (octets, streams).
+    pub payload_oxum: (u64, u64),
+    pub bag_info: BagInfo,
+}
+
+impl Bag {
+    /// Reads an existing bag. Manifest lines may separate checksum and path with two
+    /// spaces or with ` *`, and paths are decoded if the bag's version encodes them.
+    /// Payload-Oxum is measured if bag-info.txt has none.
+    pub fn open(path: &Path) -> Result<Self, BagError> {
+        let bagit = match read_tag_file(&path.join("bagit.txt")) {
+            Ok((content, _)) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Err(BagError::NotABag),
+            Err(e) => return Err(e.into()),
+        };
+        let bag_info = match read_tag_file(&path.join("bag-info.txt")) {
+            Ok((content, _)) => BagInfo::parse(&content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => BagInfo::default(),
+            Err(e) => return Err(e.into()),
+        };
+        let mut algorithms = Vec::new();
+        let mut tag_algorithms = Vec::new();
+        let mut manifest: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        let mut tag_manifest: BTreeMap<String, Vec<(ChecksumAlgorithm, String)>> = BTreeMap::new();
+        for algorithm in ChecksumAlgorithm::ALL {
+            if path.join(algorithm.manifest_name()).is_file() {
+                algorithms.push(algorithm);
+                for (file, digest) in read_manifest(path, algorithm)? {
+                    manifest.entry(file).or_default().push(digest);
+                }
+            }
+            if path.join(algorithm.tagmanifest_name()).is_file() {
+                tag_algorithms.push(algorithm);
+                for (file, digest) in read_manifest_file(path, &algorithm.tagmanifest_name())? {
+                    tag_manifest
+                        .entry(file)
+                        .or_default()
+                        .push((algorithm, digest));
+                }
+            }
+        }
+        let payload_oxum = bag_info
+            .get("Payload-Oxum")
+            .and_then(parse_payload_oxum)
+            .unwrap_or_else(|| payload_oxum(&Folder(path)));
+        Ok(Self {
+            path: path.to_path_buf(),
+            version: tag_value(&bagit, "BagIt-Version")
+                .unwrap_or_default()
+                .to_string(),
+            encoding: tag_value(&bagit, "Tag-File-Character-Encoding")
+                .unwrap_or_default()
+                .to_string(),
+            algorithms,
+            tag_algorithms,
+            manifest: manifest.into_iter().collect(),
+            tag_manifest: tag_manifest.into_iter().collect(),
+            payload_oxum,
+            bag_info,
+        })
+    }
+}
+
+/// Refuses to start a bag that wouldn't conform to the BagIt Profile in `options`.
+pub fn check_profile(options: &BagOptions) -> Result<(), BagError> {
+    let Some(profile) = &options.profile else {
+        return Ok(());
+    };
+    let problems = profile.check_options(options);
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(BagError::ProfileViolation(problems)),
+    }
+}
+
+/// Applies the empty-source policy once the payload has been walked.
+pub fn check_empty_source(
+    total_files: usize,
+    options: &BagOptions,
+    report: &mut BagReport,
+) -> Result<(), BagError> {
+    if total_files > 0 {
+        return Ok(());
+    }
+    match options.empty_source {
+        EmptySourcePolicy::Refuse => Err(BagError::EmptySource),
+        EmptySourcePolicy::Allow => {
+            report.warnings.push(
+                "The folder had no files to bag, so the bag is empty (Payload-Oxum: 0.0)"
+                    .to_string(),
+            );
+            Ok(())
+        }
+    }
+}
+
+/// Turns a `NotFound` on an already-listed file into a vanished-file diagnosis,
+/// either aborting or recording it depending on the policy. Other errors pass through.
+fn handle_vanished(
+    err: io::Error,
+    relative_path: &Path,
+    options: &BagOptions,
+    report: &mut BagReport,
+) -> Result<(), BagError> {
+    if err.kind() != io::ErrorKind::NotFound {
+        return Err(err.into());
+    }
+    match options.error_policy {
+        ErrorPolicy::Abort | ErrorPolicy::Retry => {
+            Err(BagError::FileVanished(relative_path.to_path_buf()))
+        }
+        ErrorPolicy::Skip => {
+            report.vanished.push(relative_path.to_path_buf());
+            Ok(())
+        }
+    }
+}
+
+/// Applies the error policy to a file that couldn't be read or moved before it went
+/// into `data/`, leaving it where it was under `ErrorPolicy::Skip`. One that's gone is
+/// handled as vanished.
+pub fn handle_unreadable(
+    err: io::Error,
+    relative_path: &Path,
+    options: &BagOptions,
+    report: &mut BagReport,
+) -> Result<(), BagError> {
+    if err.kind() == io::ErrorKind::NotFound {
+        return handle_vanished(err, relative_path, options, report);
+    }
+    match options.error_policy {
+        ErrorPolicy::Abort | ErrorPolicy::Retry if is_locked(&err) => {
+            Err(BagError::FileLocked(relative_path.to_path_buf()))
+        }
+        ErrorPolicy::Abort | ErrorPolicy::Retry => {
+            Err(BagError::Unreadable(relative_path.to_path_buf(), err))
+        }
+        ErrorPolicy::Skip if is_locked(&err) => {
+            report.warnings.push(format!(
+                "{} was open in another program, so was left out of the bag",
+                relative_path.display()
+            ));
+            Ok(())
+        }
+        ErrorPolicy::Skip => {
+            report.warnings.push(format!(
+                "{} couldn't be read, so was left out of the bag: {}",
+                relative_path.display(),
+                err
+            ));
+            Ok(())
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub enum Progress {
+    /// Payload files found so far by the walk that counts them before bagging starts.
+    Scanning {
+        found: usize,
+    },
+    Started {
+        total_files: usize,
+    },
+    Moving {
+        current: usize,
+        filename: String,
+    },
+    Copying {
+        current: usize,
+        filename: String,
+    },
+    Downloading {
+        current: usize,
+        filename: String,
+    },
+    Checksumming {
+        current: usize,
+        filename: String,
+    },
+    /// A payload file read back after bagging, with `BagOptions::verify_after`.
+    Verifying {
+        current: usize,
+        filename: String,
+    },
+    /// One manifest entry checked during validation, and whether it matched.
+    FileChecked {
+        filename: String,
+        passed: bool,
+    },
+    Done {
+        path: PathBuf,
+        report: Box<BagReport>,
+    },
+    Validated {
+        report: Box<ValidationReport>,
+    },
+    /// Two bags compared, with `crate::diff::compare_bags`.
+    Compared {
+        diff: Box<BagDiff>,
+    },
+    /// A bag written into the archive at `path`.
+    Serialized {
+        path: PathBuf,
+    },
+    /// Volumes joined back into the archive at `path`, every one matching its checksum.
+    Reassembled {
+        path: PathBuf,
+    },
+    Error {
+        message: String,
+    },
+}
+
+/// How closely a bag must follow the letter of the spec to pass validation.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ValidationMode {
+    /// Spec-exact: encoding, separators and required fields are all errors.
+    Strict,
+    /// Interoperability-focused: formatting quirks common in bags from other tools
+    /// are reported as warnings; only integrity problems fail the bag.
+    #[default]
+    Lenient,
+}
+
+#[derive(Debug, Clone)]
+pub struct ValidationOptions {
+    pub mode: ValidationMode,
+    /// Files hashed at once; see `BagOptions::hash_threads`.
+    pub hash_threads: usize,
+    /// Only check the bag is complete (RFC 8493 §3): every manifest entry is there,
+    /// every payload file is listed and Payload-Oxum matches. Nothing is checksummed,
+    /// so it takes seconds where full validation can take hours.
+    pub completeness_only: bool,
+    /// Hash only a sample of the payload files, for audits too big to re-hash in
+    /// full. The rest are checked for completeness; tag files are all hashed.
+    pub sample: Option<Sample>,
+    /// Also check the bag conforms to this BagIt Profile.
+    pub profile: Option<Profile>,
+    /// minisign public key file a `.minisig` signature is checked against. GnuPG
+    /// signatures are checked against the user's keyring.
+    pub minisign_key: Option<PathBuf>,
+    /// GnuPG home folder whose keyring is used instead of the user's.
+    pub gnupg_home: Option<PathBuf>,
+}
+
+impl Default for ValidationOptions {
+    fn default() -> Self {
+        Self {
+            mode: ValidationMode::default(),
+            hash_threads: 1,
+            completeness_only: false,
+            sample: None,
+            profile: None,
+            minisign_key: None,
+            gnupg_home: None,
+        }
+    }
+}
+
+/// Which payload files a spot check hashes.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Sample {
+    /// Share of the payload files to hash, in percent.
+    pub percent: f64,
+    /// Picks the files: the same seed picks the same files from the same bag, so a
+    /// spot check can be repeated exactly.
+    pub seed: u64,
+}
+
+impl Sample {
+    /// A sample of `percent` with a seed of its own.
+    pub fn new(percent: f64) -> Sample {
+        Sample {
+            percent,
+            seed: random_bits() as u64,
+        }
+    }
+
+    /// `percent` of `files` (rounded up, and at least one), picked by the seed. Each
+    /// file's chance depends only on its name and the seed, not on the order given.
+    fn choose(&self, files: &[&String]) -> std::collections::HashSet<String> {
+        let wanted = (files.len() as f64 * self.percent.clamp(0.0, 100.0) / 100.0).ceil() as usize;
+        let mut scored: Vec<(u64, &String)> = files
+            .iter()
+            .map(|file| {
+                // FNV-1a over the name, then a splitmix64 round with the seed
+                let mut score = 0xcbf2_9ce4_8422_2325u64;
+                for byte in file.bytes() {
+                    score = (score ^ byte as u64).wrapping_mul(0x100_0000_01b3);
+                }
+                score ^= self.seed;
+                score = (score ^ (score >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+                score = (score ^ (score >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+                (score ^ (score >> 31), *file)
+            })
+            .collect();
+        scored.sort();
+        scored
+            .into_iter()
+            .take(wanted.max(1))
+            .map(|(_, file)| file.clone())
+            .collect()
+    }
+}
+
+/// What a spot check covered and found.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SampleSummary {
+    pub percent: f64,
+    pub seed: u64,
+    /// Payload files hashed.
+    pub hashed: usize,
+    /// Payload files the manifests list.
+    pub population: usize,
+    /// Hashed files that didn't match or couldn't be read.
+    pub failed: usize,
+}
+
+impl SampleSummary {
+    /// With nothing failing, the share of payload files that could be damaged with a
+    /// 95% chance of the sample having missed them all: `1 - 0.05^(1/hashed)`, about
+    /// 3 / hashed. Sampling without replacement only makes the true bound lower.
+    pub fn damage_bound(&self) -> f64 {
+        match self.hashed {
+            0 => 1.0,
+            n => 1.0 - 0.05f64.powf(1.0 / n as f64),
+        }
+    }
+
+    /// One line for reports.
+    pub fn describe(&self) -> String {
+        let covered = format!(
+            "Spot check: {} of {} payload files hashed ({}%, seed {})",
+            self.hashed, self.population, self.percent, self.seed
+        );
+        match self.failed {
+            0 => format!(
+                "{}; with 95% confidence, fewer than {:.2}% of payload files are damaged",
+                covered,
+                self.damage_bound() * 100.0
+            ),
+            failed => format!("{}; {} of them failed", covered, failed),
+        }
+    }
+}
+
+pub const RULE_BAG_DECLARATION: &str = "RFC 8493 §2.1.1 bag declaration";
+pub const RULE_PAYLOAD_MANIFEST: &str = "RFC 8493 §2.1.3 payload manifest";
+pub const RULE_TAG_MANIFEST: &str = "RFC 8493 §2.2.1 tag manifest";
+pub const RULE_BAG_METADATA: &str = "RFC 8493 §2.2.2 bag metadata";
+pub const RULE_FETCH: &str = "RFC 8493 §2.2.3 fetch file";
+pub const RULE_PROFILE: &str = "BagIt Profile";
+pub const RULE_COMPLETENESS: &str = "RFC 8493 §3 complete bag";
+pub const RULE_SERIALIZATION: &str = "RFC 8493 §4.2 serialization";
+pub const RULE_SIGNATURE: &str = "Detached signature";
+
+/// What a validation finding is about, for grouping them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FindingKind {
+    /// A file a manifest lists isn't in the bag.
+    MissingFile,
+    /// A file is in the bag that no manifest lists.
+    UnexpectedFile,
+    /// A payload file's checksum doesn't match its manifest entry.
+    ChecksumMismatch,
+    /// A file is there but couldn't be read.
+    UnreadableFile,
+    /// bagit.txt, bag-info.txt, a manifest or another tag file is missing, badly
+    /// formed, or doesn't match what it declares.
+    TagFile,
+    /// Anything else, such as a damaged serialization.
+    Other,
+}
+
+impl FindingKind {
+    pub const ALL: [FindingKind; 6] = [
+        FindingKind::MissingFile,
+        FindingKind::UnexpectedFile,
+        FindingKind::ChecksumMismatch,
+        FindingKind::UnreadableFile,
+        FindingKind::TagFile,
+        FindingKind::Other,
+    ];
+
+    /// Short name, as the agent protocol sends it.
+    pub fn name(self) -> &'static str {
+        match self {
+            FindingKind::MissingFile => "missing",
+            FindingKind::UnexpectedFile => "unexpected",
+            FindingKind::ChecksumMismatch => "mismatch",
+            FindingKind::UnreadableFile => "unreadable",
+            FindingKind::TagFile => "tag-file",
+            FindingKind::Other => "other",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<FindingKind> {
+        FindingKind::ALL.into_iter().find(|k| k.name() == name)
+    }
+
+    /// Heading for a group of findings of this kind.
+    pub fn label(self) -> &'static str {
+        match self {
+            FindingKind::MissingFile => "Missing files",
+            FindingKind::UnexpectedFile => "Unexpected files",
+            FindingKind::ChecksumMismatch => "Checksum mismatches",
+            FindingKind::UnreadableFile => "Unreadable files",
+            FindingKind::TagFile => "Tag file problems",
+            FindingKind::Other => "Other problems",
+        }
+    }
+
+    /// The kind of a finding under `rule` that isn't about one particular file.
+    fn for_rule(rule: &str) -> FindingKind {
+        match rule {
+            RULE_COMPLETENESS | RULE_SERIALIZATION | RULE_PROFILE | RULE_SIGNATURE => {
+                FindingKind::Other
+            }
+            _ => FindingKind::TagFile,
+        }
+    }
+}
+
+/// A single validation problem and the spec rule it comes from.
+#[derive(Debug, Clone)]
+pub struct Finding {
+    pub rule: &'static str,
+    pub kind: FindingKind,
+    /// The file it's about, relative to the bag root, if it's about one.
+    pub file: Option<String>,
+    pub message: String,
+}
+
+impl Finding {
+    /// A finding under `rule` that isn't about one particular file.
+    pub fn new(rule: &'static str, message: impl Into<String>) -> Finding {
+        Finding {
+            rule,
+            kind: FindingKind::for_rule(rule),
+            file: None,
+            message: message.into(),
+        }
+    }
+
+    /// A finding of `kind` about `file`.
+    pub fn about(
+        kind: FindingKind,
+        file: &str,
+        rule: &'static str,
+        message: impl Into<String>,
+    ) -> Finding {
+        Finding {
+            rule,
+            kind,
+            file: Some(file.to_string()),
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}]", self.message, self.rule)
+    }
+}
+
+/// A file whose contents no longer match its manifest entry.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Mismatch {
+    /// Path relative to the bag root, as written in the manifest.
+    pub file: String,
+    /// The algorithm of the manifest that lists it.
+    pub algorithm: ChecksumAlgorithm,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Outcome of checking an existing bag.
+#[derive(Debug, Clone, Default)]
+pub struct ValidationReport {
+    pub path: PathBuf,
+    pub mode: ValidationMode,
+    /// Whether only completeness was checked, so no checksums were compared.
+    pub completeness_only: bool,
+    pub files_checked: usize,
+    /// Problems that make the bag invalid.
+    pub errors: Vec<Finding>,
+    /// Oddities worth knowing about that don't make the bag invalid.
+    pub warnings: Vec<Finding>,
+    /// Files whose checksum didn't match their manifest entry, for triage.
+    pub mismatches: Vec<Mismatch>,
+    /// What was hashed, when only a sample of the payload was.
+    pub sample: Option<SampleSummary>,
+    /// Algorithms of the payload manifests that were checked.
+    pub algorithms: Vec<ChecksumAlgorithm>,
+    /// Payload-Oxum against the payload, when bag-info.txt was there to read.
+    pub oxum: Option<OxumCheck>,
+    /// Who signed the tag manifest, when the bag has a good detached signature.
+    pub signed_by: Option<String>,
+    /// How long the validation took.
+    pub duration: std::time::Duration,
+}
+
+/// Identifies the layout of [`ValidationReport::to_json`], and changes if it does.
+pub const JSON_REPORT_SCHEMA: &str = "baggie-validation-report/1";
+
+impl ValidationReport {
+    pub fn is_valid(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// The errors of `kind`.
+    pub fn errors_of(&self, kind: FindingKind) -> impl Iterator<Item = &Finding> {
+        self.errors.iter().filter(move |e| e.kind == kind)
+    }
+
+    /// The files errors of `kind` are about, in the order they were found.
+    pub fn files(&self, kind: FindingKind) -> Vec<&str> {
+        self.errors_of(kind)
+            .filter_map(|e| e.file.as_deref())
+            .collect()
+    }
+
+    /// How many errors there are of each kind that has any, in `FindingKind::ALL`
+    /// order.
+    pub fn error_counts(&self) -> Vec<(FindingKind, usize)> {
+        FindingKind::ALL
+            .into_iter()
+            .map(|kind| (kind, self.errors_of(kind).count()))
+            .filter(|(_, count)| *count > 0)
+            .collect()
+    }
+
+    /// The findings and a verdict, one per line, as the command line prints them.
+    pub fn to_text(&self) -> String {
+        let mode = match self.mode {
+            ValidationMode::Strict => "strict",
+            ValidationMode::Lenient => "lenient",
+        };
+        let mut text = String::new();
+        for error in &self.errors {
+            text.push_str(&format!("error: {}\n", error));
+        }
+        for warning in &self.warnings {
+            text.push_str(&format!("warning: {}\n", warning));
+        }
+        if let Some(sample) = &self.sample {
+            text.push_str(&format!("{}\n", sample.describe()));
+        }
+        if let Some(signer) = &self.signed_by {
+            text.push_str(&format!("Tag manifest signed by {}\n", signer));
+        }
+        let (valid, invalid) = match self.completeness_only {
+            true => ("complete", "INCOMPLETE"),
+            false => ("valid", "INVALID"),
+        };
+        if self.is_valid() {
+            text.push_str(&format!(
+                "{} is {} ({} mode, {} files checked)\n",
+                self.path.display(),
+                valid,
+                mode,
+                self.files_checked
+            ));
+        } else {
+            let counts: Vec<String> = self
+                .error_counts()
+                .iter()
+                .map(|(kind, count)| format!("{} ({})", kind.label().to_lowercase(), count))
+                .collect();
+            text.push_str(&format!(
+                "{} is {} ({} mode, {} errors: {})\n",
+                self.path.display(),
+                invalid,
+                mode,
+                self.errors.len(),
+                counts.join(", ")
+            ));
+        }
+        text
+    }
+
+    /// The report as one JSON object, for scripts:
+    ///
+    /// - `schema`: [`JSON_REPORT_SCHEMA`]
+    /// - `path`, `valid`, `mode` (`"strict"` or `"lenient"`), `completeness_only`
+    /// - `algorithms`: payload manifest algorithms checked, e.g. `["sha256"]`
+    /// - `files_checked`, `duration_seconds`
+    /// - `oxum`: `{"declared", "actual", "matches"}` with `<octets>.<streams>` strings
+    ///   (`declared` is null if bag-info.txt has no readable Payload-Oxum), or null if
+    ///   it wasn't compared
+    /// - `errors`, `warnings`: `[{"kind", "rule", "file", "message"}]`, `kind` being a
+    ///   [`FindingKind::name`] and `file` null when the finding isn't about one
+    /// - `mismatches`: `[{"file", "algorithm", "expected", "actual"}]`
+    /// - `sample`: `{"percent", "seed", "hashed", "population", "failed"}` for a spot
+    ///   check, else null; `seed` is a string as it may not fit a double
+    /// - `signed_by`: who signed the tag manifest, or null if it has no good signature
+    pub fn to_json(&self) -> String {
+        let findings = |findings: &[Finding]| {
+            let items: Vec<String> = findings
+                .iter()
+                .map(|f| {
+                    format!(
+                        "{{\"kind\": {}, \"rule\": {}, \"file\": {}, \"message\": {}}}",
+                        json::quote(f.kind.name()),
+                        json::quote(f.rule),
+                        f.file.as_deref().map_or("null".to_string(), json::quote),
+                        json::quote(&f.message)
+                    )
+                })
+                .collect();
+            format!("[{}]", items.join(", "))
+        };
+        let algorithms: Vec<String> = self
+            .algorithms
+            .iter()
+            .map(|a| json::quote(a.name()))
+            .collect();
+        let oxum = |(bytes, count): (u64, u64)| json::quote(&format!("{}.{}", bytes, count));
+        let oxum = match &self.oxum {
+            Some(check) => format!(
+                "{{\"declared\": {}, \"actual\": {}, \"matches\": {}}}",
+                check.declared.map_or("null".to_string(), oxum),
+                oxum(check.actual),
+                check.matches()
+            ),
+            None => "null".to_string(),
+        };
+        let mismatches: Vec<String> = self
+            .mismatches
+            .iter()
+            .map(|m| {
+                format!(
+                    "{{\"file\": {}, \"algorithm\": {}, \"expected\": {}, \"actual\": {}}}",
+                    json::quote(&m.file),
+                    json::quote(m.algorithm.name()),
+                    json::quote(&m.expected),
+                    json::quote(&m.actual)
+                )
+            })
+            .collect();
+        let sample = match &self.sample {
+            Some(s) => format!(
+                "{{\"percent\": {}, \"seed\": \"{}\", \"hashed\": {}, \"population\": {}, \"failed\": {}}}",
+                s.percent, s.seed, s.hashed, s.population, s.failed
+            ),
+            None => "null".to_string(),
+        };
+        format!(
+            "{{\n  \"schema\": {},\n  \"path\": {},\n  \"valid\": {},\n  \"mode\": {},\n  \
+             \"completeness_only\": {},\n  \"algorithms\": [{}],\n  \"files_checked\": {},\n  \
+             \"duration_seconds\": {:.3},\n  \"oxum\": {},\n  \"errors\": {},\n  \
+             \"warnings\": {},\n  \"mismatches\": [{}],\n  \"sample\": {},\n  \"signed_by\": {}\n}}\n",
+            json::quote(JSON_REPORT_SCHEMA),
+            json::quote(&self.path.to_string_lossy()),
+            self.is_valid(),
+            json::quote(match self.mode {
+                ValidationMode::Strict => "strict",
+                ValidationMode::Lenient => "lenient",
+            }),
+            self.completeness_only,
+            algorithms.join(", "),
+            self.files_checked,
+            self.duration.as_secs_f64(),
+            oxum,
+            findings(&self.errors),
+            findings(&self.warnings),
+            mismatches.join(", "),
+            sample,
+            self.signed_by.as_deref().map_or("null".to_string(), json::quote)
+        )
+    }
+
+    fn error(&mut self, rule: &'static str, message: impl Into<String>) {
+        self.errors.push(Finding::new(rule, message));
+    }
+
+    fn warning(&mut self, rule: &'static str, message: impl Into<String>) {
+        self.warnings.push(Finding::new(rule, message));
+    }
+
+    /// An error in strict mode, a warning in lenient mode.
+    fn strict_error(&mut self, rule: &'static str, message: impl Into<String>) {
+        self.strict(Finding::new(rule, message));
+    }
+
+    /// `finding` as an error in strict mode, a warning in lenient mode.
+    fn strict(&mut self, finding: Finding) {
+        match self.mode {
+            ValidationMode::Strict => self.errors.push(finding),
+            ValidationMode::Lenient => self.warnings.push(finding),
+        }
+    }
+}
+
+/// Manifest algorithms baggie can write and check (RFC 8493 §2.4), plus BLAKE3, which
+/// isn't in the spec's list but is much faster on big payloads and accepted by many
+/// preservation tools.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ChecksumAlgorithm {
+    Md5,
+    Sha1,
+    #[default]
+    Sha256,
+    Sha512,
+    Blake3,
+}
+
+impl ChecksumAlgorithm {
+    pub const ALL: [ChecksumAlgorithm; 5] = [
+        ChecksumAlgorithm::Md5,
+        ChecksumAlgorithm::Sha1,
+        ChecksumAlgorithm::Sha256,
+        ChecksumAlgorithm::Sha512,
+        ChecksumAlgorithm::Blake3,
+    ];
+
+    /// The name used in `manifest-<name>.txt`.
+    pub fn name(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => "md5",
+            ChecksumAlgorithm::Sha1 => "sha1",
+            ChecksumAlgorithm::Sha256 => "sha256",
+            ChecksumAlgorithm::Sha512 => "sha512",
+            ChecksumAlgorithm::Blake3 => "blake3",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<ChecksumAlgorithm> {
+        ChecksumAlgorithm::ALL
+            .into_iter()
+            .find(|a| a.name() == name)
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ChecksumAlgorithm::Md5 => "MD5",
+            ChecksumAlgorithm::Sha1 => "SHA-1",
+            ChecksumAlgorithm::Sha256 => "SHA-256",
+            ChecksumAlgorithm::Sha512 => "SHA-512",
+            ChecksumAlgorithm::Blake3 => "BLAKE3",
+        }
+    }
+
+    pub fn manifest_name(self) -> String {
+        format!("manifest-{}.txt", self.name())
+    }
+
+    pub fn tagmanifest_name(self) -> String {
+        format!("tagmanifest-{}.txt", self.name())
+    }
+}
+
+/// The algorithm of the bag's payload manifest, the strongest if it has several, or
+/// `None` if it has none.
+pub fn bag_algorithm(bag: &Path) -> Option<ChecksumAlgorithm> {
+    bag_algorithms(bag).pop()
+}
+
+/// The algorithms of all the bag's payload manifests, weakest first.
+pub fn bag_algorithms(bag: &Path) -> Vec<ChecksumAlgorithm> {
+    ChecksumAlgorithm::ALL
+        .into_iter()
+        .filter(|a| bag.join(a.manifest_name()).is_file())
+        .collect()
+}
+
+/// A checksum being computed in any of the supported algorithms.
+pub enum Digester {
+    Md5(Md5),
+    Sha1(Sha1),
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Blake3(Box<blake3::Hasher>),
+}
+
+impl Digester {
+    pub fn new(algorithm: ChecksumAlgorithm) -> Self {
+        match algorithm {
+            ChecksumAlgorithm::Md5 => Digester::Md5(Md5::new()),
+            ChecksumAlgorithm::Sha1 => Digester::Sha1(Sha1::new()),
+            ChecksumAlgorithm::Sha256 => Digester::Sha256(Sha256::new()),
+            ChecksumAlgorithm::Sha512 => Digester::Sha512(Sha512::new()),
+            ChecksumAlgorithm::Blake3 => Digester::Blake3(Box::new(blake3::Hasher::new())),
+        }
+    }
+
+    pub fn update(&mut self, data: &[u8]) {
+        match self {
+            Digester::Md5(h) => h.update(data),
+            Digester::Sha1(h) => h.update(data),
+            Digester::Sha256(h) => h.update(data),
+            Digester::Sha512(h) => h.update(data),
+            Digester::Blake3(h) => {
+                h.update(data);
+            }
+        }
+    }
+
+    /// The checksum as lowercase hex.
+    pub fn finish(self) -> String {
+        match self {
+            Digester::Md5(h) => format!("{:x}", h.finalize()),
+            Digester::Sha1(h) => format!("{:x}", h.finalize()),
+            Digester::Sha256(h) => format!("{:x}", h.finalize()),
+            Digester::Sha512(h) => format!("{:x}", h.finalize()),
+            Digester::Blake3(h) => h.finalize().to_hex().to_string(),
+        }
+    }
+}
+
+pub fn calculate_sha256(path: &Path) -> io::Result<String> {
+    calculate_digest(path, ChecksumAlgorithm::Sha256)
+}
+
+pub fn calculate_digest(path: &Path, algorithm: ChecksumAlgorithm) -> io::Result<String> {
+    Ok(calculate_digests(path, &[algorithm])?.remove(0))
+}
+
+/// Checksums of the file in each of `algorithms`, in order, reading it once.
+pub fn calculate_digests(path: &Path, algorithms: &[ChecksumAlgorithm]) -> io::Result<Vec<String>> {
+    let file = File::open(path)?;
+    let mut reader = BufReader::new(file);
+    let mut hashers: Vec<Digester> = algorithms.iter().map(|a| Digester::new(*a)).collect();
+    let mut buffer = [0u8; 8192];
+
+    loop {
+        let bytes_read = reader.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        for hasher in &mut hashers {
+            hasher.update(&buffer[..bytes_read]);
+        }
+    }
+
+    Ok(hashers.into_iter().map(Digester::finish).collect())
+}
+
+#[cfg(test)]
+fn calculate_sha256_str(content: &str) -> String {
+    calculate_digest_str(content, ChecksumAlgorithm::Sha256)
+}
+
+fn calculate_digest_str(content: &str, algorithm: ChecksumAlgorithm) -> String {
+    let mut hasher = Digester::new(algorithm);
+    hasher.update(content.as_bytes());
+    hasher.finish()
+}
+
+/// Runs `job` for every index in `0..count` on up to `threads` threads, returning the
+/// results in index order. Workers take the next index as they finish, so one huge
+/// file doesn't hold up the rest.
+pub fn run_parallel<T: Send>(
+    count: usize,
+    threads: usize,
+    job: impl Fn(usize) -> T + Sync,
+) -> Vec<T> {
+    let threads = threads.clamp(1, count.max(1));
+    if threads == 1 {
+        return (0..count).map(job).collect();
+    }
+
+    let next = AtomicUsize::new(0);
+    let results: Mutex<Vec<Option<T>>> = Mutex::new((0..count).map(|_| None).collect());
+    std::thread::scope(|scope| {
+        for _ in 0..threads {
+            scope.spawn(|| loop {
+                let i = next.fetch_add(1, Ordering::Relaxed);
+                if i >= count {
+                    break;
+                }
+                let result = job(i);
+                results.lock().unwrap()[i] = Some(result);
+            });
+        }
+    });
+    results
+        .into_inner()
+        .unwrap()
+        .into_iter()
+        .map(|r| r.expect("every index is processed"))
+        .collect()
+}
+
+/// Returns `(Bagging-Date, Bagging-Time)` for the current instant.
+///
+/// The date stays a plain `YYYY-MM-DD` so python-bagit and friends parse it as before;
+/// the offset lives only in the separate time field.
+pub fn bagging_timestamp(mode: DateMode) -> (String, String) {
+    match mode {
+        DateMode::Local => {
+            let now = chrono::Local::now();
+            (
+                now.format("%Y-%m-%d").to_string(),
+                now.format("%H:%M:%S%:z").to_string(),
+            )
+        }
+        DateMode::Utc => {
+            let now = chrono::Utc::now();
+            (
+                now.format("%Y-%m-%d").to_string(),
+                now.format("%H:%M:%S%:z").to_string(),
+            )
+        }
+    }
+}
+
+/// Moves the walked entries into `data/` one by one so skipped files stay behind.
+/// Directories are recreated first (the walk is pre-order) so empty ones survive too;
+/// ones a resumed run finds already there are kept. Each move is recorded in `journal`.
+fn move_payload(
+    path: &Path,
+    data_dir: &Path,
+    entries: impl IntoIterator<Item = walkdir::DirEntry>,
+    options: &BagOptions,
+    report: &mut BagReport,
+    progress_tx: &Option<Sender<Progress>>,
+    journal: Option<&Journal>,
+) -> Result<(), BagError> {
+    let mut moved = 0;
+    let mut dirs = Vec::new();
+    for entry in entries {
+        let relative_path = entry.path().strip_prefix(path).unwrap();
+        let dest = data_dir.join(relative_path);
+
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&dest)?;
+            dirs.push(entry.into_path());
+            continue;
+        }
+
+        moved += 1;
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Moving {
+                current: moved,
+                filename: relative_path.to_string_lossy().to_string(),
+            });
+        }
+
+        match with_retries(options, || move_file(entry.path(), &dest)) {
+            Ok(()) => {
+                if let Some(journal) = journal {
+                    let file = Path::new("data").join(relative_path);
+                    journal.record_move(&file.to_string_lossy().replace('\\', "/"))?;
+                }
+            }
+            Err(e) => handle_unreadable(e, relative_path, options, report)?,
+        }
+    }
+
+    // Remove the emptied source directories, deepest first; any still holding
+    // skipped files are left in place.
+    for dir in dirs.iter().rev() {
+        let _ = fs::remove_dir(dir);
+    }
+
+    Ok(())
+}
+
+/// Renames `source` to `dest`, falling back to `copy_and_delete` where they're on
+/// different filesystems (network shares, bind mounts), which rename can't cross.
+fn move_file(source: &Path, dest: &Path) -> io::Result<()> {
+    match fs::rename(source, dest) {
+        Err(e) if e.kind() == io::ErrorKind::CrossesDevices => copy_and_delete(source, dest),
+        result => result,
+    }
+}
+
+/// Copies `source` to `dest` with its permissions and modification time, reads the
+/// copy back to check it against what was read, and only then deletes `source`.
+fn copy_and_delete(source: &Path, dest: &Path) -> io::Result<()> {
+    let metadata = fs::metadata(source)?;
+    let copied = copy_hashing(source, dest, ChecksumAlgorithm::Sha256)?;
+    if calculate_sha256(dest)? != copied {
+        let _ = fs::remove_file(dest);
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("the copy of {} didn't match it", source.display()),
+        ));
+    }
+    fs::set_permissions(dest, metadata.permissions())?;
+    fs::remove_file(source)
+}
+
+/// Copies `source` to `dest` like `fs::copy`, but gives the copy the original's
+/// modification time rather than the time it was made, and its extended attributes.
+pub fn copy_file(source: &Path, dest: &Path) -> io::Result<u64> {
+    copy_file_leaving_out(source, dest, &[])
+}
+
+/// `copy_file`, without the extended attributes named in `leave_out`.
+fn copy_file_leaving_out(source: &Path, dest: &Path, leave_out: &[&str]) -> io::Result<u64> {
+    let mut input = File::open(source)?;
+    let metadata = input.metadata()?;
+    let mut output = File::create(dest)?;
+    let copied = io::copy(&mut input, &mut output)?;
+    if let Ok(modified) = metadata.modified() {
+        output.set_modified(modified)?;
+    }
+    drop(output);
+    copy_xattrs(source, dest, leave_out);
+    // Last, as a read-only copy couldn't have its time set
+    fs::set_permissions(dest, metadata.permissions())?;
+    Ok(copied)
+}
+
+/// Copies what extended attributes of `source` it can to `dest`, bar those named in
+/// `leave_out`. Ones the filesystem or the user may not set are left behind.
+#[cfg(unix)]
+fn copy_xattrs(source: &Path, dest: &Path, leave_out: &[&str]) {
+    let Ok(names) = xattr::list(source) else {
+        return;
+    };
+    for name in names.filter(|name| !leave_out.iter().any(|n| name == n)) {
+        if let Ok(Some(value)) = xattr::get(source, &name) {
+            let _ = xattr::set(dest, &name, &value);
+        }
+    }
+}
+
+#[cfg(not(unix))]
+fn copy_xattrs(_source: &Path, _dest: &Path, _leave_out: &[&str]) {}
+
+/// Removes the extended attributes named in `names` from `path`, where it has them.
+#[cfg(unix)]
+fn remove_xattrs(path: &Path, names: &[&str]) {
+    for name in names {
+        let _ = xattr::remove(path, name);
+    }
+}
+
+#[cfg(not(unix))]
+fn remove_xattrs(_path: &Path, _names: &[&str]) {}
+
+/// Makes `dest` a copy-on-write clone of `source` (`clonefile` on APFS, `FICLONE` on
+/// Btrfs and XFS), which shares the original's blocks so takes no time or space, then
+/// gives it the same modification time, extended attributes (bar `leave_out`) and
+/// permissions as `copy_file` would. Fails where the filesystem can't clone, leaving
+/// nothing at `dest`.
+fn clone_file(source: &Path, dest: &Path, leave_out: &[&str]) -> io::Result<u64> {
+    let metadata = fs::metadata(source)?;
+    reflink_copy::reflink(source, dest)?;
+    let finish = || {
+        // The owner may set the time through a read-only handle, which a clone of a
+        // read-only file only allows
+        if let Ok(modified) = metadata.modified() {
+            File::open(dest)?.set_modified(modified)?;
+        }
+        copy_xattrs(source, dest, leave_out);
+        // clonefile brings every attribute along
+        remove_xattrs(dest, leave_out);
+        fs::set_permissions(dest, metadata.permissions())
+    };
+    match finish() {
+        Ok(()) => Ok(metadata.len()),
+        Err(e) => {
+            let _ = fs::remove_file(dest);
+            Err(e)
+        }
+    }
+}
+
+/// Attribute macOS puts on downloaded files, which makes Gatekeeper ask before they're
+/// opened.
+const QUARANTINE_XATTR: &str = "com.apple.quarantine";
+
+/// Copies a payload file into a copied bag, without the quarantine attribute if
+/// `options` says so. It's cloned rather than copied where `options` allows and the
+/// filesystem can.
+pub fn copy_payload_file(source: &Path, dest: &Path, options: &BagOptions) -> io::Result<u64> {
+    let leave_out: &[&str] = match options.strip_quarantine {
+        true => &[QUARANTINE_XATTR],
+        false => &[],
+    };
+    if options.clone_files {
+        if let Ok(copied) = clone_file(source, dest, leave_out) {
+            return Ok(copied);
+        }
+    }
+    copy_file_leaving_out(source, dest, leave_out)
+}
+
+fn enumeration_event(source: &Path, total_files: usize, skipped: usize) -> Event {
+    Event::now(
+        EventType::Enumeration,
+        format!(
+            "{} files found in {}, {} left out by the skip list",
+            total_files,
+            source.display(),
+            skipped
+        ),
+    )
+}
+
+/// Files found between progress events while the payload is scanned.
+const SCAN_PROGRESS_EVERY: usize = 1000;
+
+/// What the payload walk leaves out, loaded once for both passes over the tree.
+struct PayloadFilter {
+    ignore: BagIgnore,
+    /// Absolute paths of folders walked past: the tag directories, and where a copy is
+    /// being bagged into.
+    excluded: Vec<PathBuf>,
+    /// Bagging in place, so the journal and the `data/` files are moved into as the
+    /// walk goes aren't payload. A folder already holding a `data/` isn't bagged in
+    /// place, so it's only ever the bag's own.
+    in_place: bool,
+    /// Files the scan couldn't read and the error policy left out, relative to the
+    /// folder.
+    unreadable: HashSet<PathBuf>,
+}
+
+impl PayloadFilter {
+    fn load(path: &Path, options: &BagOptions, in_place: bool) -> Result<Self, BagError> {
+        Ok(Self {
+            ignore: BagIgnore::load(path)?,
+            excluded: check_tag_directories(options)?,
+            in_place,
+            unreadable: HashSet::new(),
+        })
+    }
+
+    /// Walks the payload of `path` as it's found, leaving skipped entries (and
+    /// everything under them) out and passing them to `skipped`, relative to `path`.
+    ///
+    /// WalkDir iterates with an explicit stack, so nesting depth is bounded only by
+    /// `options.max_depth`, not by recursion. Entries that can't be read are passed
+    /// over; `scan_payload` has already refused loops and overly deep folders.
+    fn walk<'a>(
+        &'a self,
+        path: &'a Path,
+        options: &'a BagOptions,
+        skipped: impl FnMut(&Path) + 'a,
+    ) -> impl Iterator<Item = walkdir::DirEntry> + 'a {
+        self.walk_entries(path, options, skipped)
+            .filter_map(|e| e.ok())
+    }
+
+    /// `walk`, with the errors left in. Symlinks are followed if `options` asks and
+    /// this isn't bagging in place, and WalkDir reports a link back to a folder it's
+    /// inside as an error rather than descending into it.
+    fn walk_entries<'a>(
+        &'a self,
+        path: &'a Path,
+        options: &'a BagOptions,
+        mut skipped: impl FnMut(&Path) + 'a,
+    ) -> impl Iterator<Item = walkdir::Result<walkdir::DirEntry>> + 'a {
+        WalkDir::new(path)
+            .min_depth(1)
+            // One deeper than allowed, so the scan sees what goes past the limit
+            .max_depth(options.max_depth.saturating_add(1))
+            .follow_links(options.follow_symlinks && !self.in_place)
+            .into_iter()
+            .filter_entry(move |e| {
+                let ours = e.file_name() == JOURNAL_FILE || e.file_name() == "data";
+                if self.in_place && e.depth() == 1 && ours {
+                    return false;
+                }
+                if e.file_type().is_dir() && self.excluded.contains(&absolute(e.path())) {
+                    return false;
+                }
+                let relative = e.path().strip_prefix(path).unwrap();
+                if self.unreadable.contains(relative) {
+                    return false;
+                }
+                let ignored = self.ignore.is_ignored(relative, e.file_type().is_dir())
+                    || (options.skip_appledouble && is_appledouble(e));
+                if options.is_skipped(e.file_name()) || ignored {
+                    skipped(relative);
+                    false
+                } else {
+                    true
+                }
+            })
+    }
+}
+
+/// The first four bytes of an AppleDouble file.
+const APPLEDOUBLE_MAGIC: [u8; 4] = [0x00, 0x05, 0x16, 0x07];
+
+/// Whether `entry` is an AppleDouble file: named `._<name>` and starting with its
+/// magic number.
+fn is_appledouble(entry: &walkdir::DirEntry) -> bool {
+    if !entry.file_type().is_file() || !entry.file_name().to_string_lossy().starts_with("._") {
+        return false;
+    }
+    let mut magic = [0u8; 4];
+    let read = File::open(entry.path()).and_then(|mut file| file.read_exact(&mut magic));
+    read.is_ok() && magic == APPLEDOUBLE_MAGIC
+}
+
+/// The first pass over the payload, before anything moves: records what the skip list
+/// leaves out, applies the checks that could stop bagging, and counts the files and
+/// their bytes. The files are walked again as they're moved or copied, so a huge tree
+/// is never held in memory, and progress goes out as files are found.
+///
+/// Each file is opened, so one that can't be read (locked by another program, say) is
+/// dealt with under the error policy before anything has moved. Ones left out are
+/// added to `filter`. A symlink loop or folders nested past `options.max_depth` stop
+/// bagging here too.
+fn scan_payload(
+    path: &Path,
+    options: &BagOptions,
+    filter: &mut PayloadFilter,
+    report: &mut BagReport,
+    progress_tx: &Option<Sender<Progress>>,
+) -> Result<(usize, u64), BagError> {
+    let mut skipped = Vec::new();
+    let mut unreadable = Vec::new();
+    let mut collisions = CaseCollisions::default();
+    let mut files = 0;
+    let mut bytes = 0;
+    let entries = filter.walk_entries(path, options, |relative| {
+        skipped.push(relative.to_path_buf())
+    });
+    for entry in entries {
+        let entry = match entry {
+            Ok(entry) => entry,
+            Err(e) if e.loop_ancestor().is_some() => {
+                return Err(BagError::CycleDetected(
+                    e.path().unwrap_or(path).to_path_buf(),
+                ));
+            }
+            Err(_) => continue,
+        };
+        if entry.depth() > options.max_depth {
+            return Err(BagError::TooDeep {
+                path: entry.into_path(),
+                max_depth: options.max_depth,
+            });
+        }
+        let relative = entry.path().strip_prefix(path).unwrap();
+        let name = entry.file_name().to_string_lossy();
+        // Caught before anything moves, as bagging couldn't finish
+        if options.bagit_version == BagItVersion::V0_97 && name.contains(['\r', '\n']) {
+            return Err(BagError::UnlistablePath(entry.path().to_path_buf()));
+        }
+        collisions.check(relative, options, report)?;
+        if let Some(problem) = windows_name_problem(&name) {
+            report.warnings.push(format!(
+                "{} can't be extracted on Windows: {}",
+                relative.display(),
+                problem
+            ));
+        }
+        if !entry.file_type().is_dir() {
+            let file = match with_retries(options, || File::open(entry.path())) {
+                Ok(file) => file,
+                Err(e) => {
+                    handle_unreadable(e, relative, options, report)?;
+                    unreadable.push(relative.to_path_buf());
+                    continue;
+                }
+            };
+            files += 1;
+            bytes += file.metadata().map_or(0, |m| m.len());
+            match progress_tx {
+                Some(tx) if files % SCAN_PROGRESS_EVERY == 0 => {
+                    let _ = tx.send(Progress::Scanning { found: files });
+                }
+                _ => {}
+            }
+        }
+    }
+    report.skipped.extend(skipped);
+    filter.unreadable.extend(unreadable);
+    Ok((files, bytes))
+}
+
+/// Room allowed per file for its manifest lines and the filesystem rounding it up to
+/// whole blocks.
+pub const PER_FILE_ALLOWANCE: u64 = 4096;
+
+/// Refuses with `BagError::InsufficientSpace` if the volume `dest` is (or would be
+/// created) on has less than `needed` bytes free. One whose free space can't be read
+/// is let through.
+pub fn check_free_space(dest: &Path, needed: u64) -> Result<(), BagError> {
+    let dest = absolute(dest);
+    let Some(existing) = dest.ancestors().find(|p| p.exists()) else {
+        return Ok(());
+    };
+    match fs2::available_space(existing) {
+        Ok(available) if available < needed => Err(BagError::InsufficientSpace {
+            path: dest,
+            needed,
+            available,
+        }),
+        _ => Ok(()),
+    }
+}
+
+/// The tag directories' absolute paths, once each is known to be usable.
+fn check_tag_directories(options: &BagOptions) -> Result<Vec<PathBuf>, BagError> {
+    let mut checked = Vec::new();
+    for dir in &options.tag_directories {
+        let name = dir.file_name().and_then(|n| n.to_str());
+        let usable = dir.is_dir() && name.is_some_and(|n| n != "data" && n != STAGING_DIR);
+        if !usable {
+            return Err(BagError::BadTagDirectory(dir.clone()));
+        }
+        checked.push(absolute(dir));
+    }
+    Ok(checked)
+}
+
+/// `path` made absolute without touching the filesystem, or as it is if that fails.
+fn absolute(path: &Path) -> PathBuf {
+    std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf())
+}
+
+/// Copies each tag directory into the bag under its own name, unless it's already
+/// there, and returns the tag files it holds.
+fn copy_tag_directories(bag: &Path, options: &BagOptions) -> Result<Vec<String>, BagError> {
+    check_tag_directories(options)?;
+    let mut names = Vec::new();
+    for dir in &options.tag_directories {
+        let name = dir.file_name().unwrap();
+        let dest = bag.join(name);
+        let copy = absolute(dir) != absolute(&dest);
+        let entries = WalkDir::new(dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|e| !options.is_skipped(e.file_name()));
+        for entry in entries {
+            let entry = entry.map_err(io::Error::from)?;
+            let relative = entry.path().strip_prefix(dir).unwrap();
+            if entry.file_type().is_dir() {
+                if copy {
+                    fs::create_dir_all(dest.join(relative))?;
+                }
+                continue;
+            }
+            if copy {
+                fs::create_dir_all(dest.join(relative).parent().unwrap())?;
+                fs::copy(entry.path(), dest.join(relative))?;
+            }
+            let file = Path::new(name).join(relative);
+            names.push(file.to_string_lossy().replace('\\', "/"));
+        }
+    }
+    Ok(names)
+}
+
+/// Device names Windows reserves, with or without an extension.
+const WINDOWS_RESERVED_NAMES: &[&str] = &[
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+/// Why Windows won't create a file or folder called `name`, if it won't.
+fn windows_name_problem(name: &str) -> Option<String> {
+    let stem = name.split('.').next().unwrap_or(name).trim_end();
+    if WINDOWS_RESERVED_NAMES
+        .iter()
+        .any(|r| r.eq_ignore_ascii_case(stem))
+    {
+        return Some(format!(
+            "{} is a reserved device name",
+            stem.to_ascii_uppercase()
+        ));
+    }
+    if let Some(c) = name.chars().find(|c| "<>:\"\\|?*".contains(*c)) {
+        return Some(format!("it contains {:?}", c));
+    }
+    if name.chars().any(char::is_control) {
+        return Some("it contains a control character".to_string());
+    }
+    if name.ends_with('.') || name.ends_with(' ') {
+        return Some("it ends with a dot or space".to_string());
+    }
+    None
+}
+
+/// The case-collision policy, applied as the payload is walked. Only the first entry of
+/// each clash counts, so a folder whose name clashes isn't reported again for every
+/// file inside it.
+#[derive(Debug, Default)]
+struct CaseCollisions {
+    seen: HashMap<String, PathBuf>,
+    clashing: Vec<PathBuf>,
+}
+
+impl CaseCollisions {
+    fn check(
+        &mut self,
+        relative: &Path,
+        options: &BagOptions,
+        report: &mut BagReport,
+    ) -> Result<(), BagError> {
+        if self
+            .clashing
+            .iter()
+            .any(|folder| relative.starts_with(folder))
+        {
+            return Ok(());
+        }
+        let key = relative.to_string_lossy().to_lowercase();
+        let Some(first) = self.seen.get(&key) else {
+            self.seen.insert(key, relative.to_path_buf());
+            return Ok(());
+        };
+        match options.case_collisions {
+            CaseCollisionPolicy::Refuse => {
+                return Err(BagError::CaseCollision(first.clone(), relative.to_path_buf()));
+            }
+            CaseCollisionPolicy::Warn => report.warnings.push(format!(
+                "{} and {} differ only in case, so one will overwrite the other if the bag is extracted on Windows or macOS",
+                first.display(),
+                relative.display()
+            )),
+        }
+        self.clashing.push(relative.to_path_buf());
+        Ok(())
+    }
+}
+
+/// Copies the walked entries into `data_dir`, leaving the source untouched. `copied`
+/// counts the files copied, for progress, and carries on from where it was.
+fn copy_payload(
+    path: &Path,
+    data_dir: &Path,
+    entries: impl IntoIterator<Item = walkdir::DirEntry>,
+    options: &BagOptions,
+    report: &mut BagReport,
+    progress_tx: &Option<Sender<Progress>>,
+    copied: &mut usize,
+) -> Result<(), BagError> {
+    for entry in entries {
+        let relative_path = entry.path().strip_prefix(path).unwrap();
+        let dest = data_dir.join(relative_path);
+
+        if entry.file_type().is_dir() {
+            fs::create_dir(&dest)?;
+            continue;
+        }
+
+        *copied += 1;
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Copying {
+                current: *copied,
+                filename: relative_path.to_string_lossy().to_string(),
+            });
+        }
+
+        if let Err(e) = with_retries(options, || copy_payload_file(entry.path(), &dest, options)) {
+            handle_unreadable(e, relative_path, options, report)?;
+        }
+    }
+    Ok(())
+}
+
+pub fn bag_directory(
+    path: &Path,
+    options: &BagOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<BagReport, BagError> {
+    // Validate input
+    if !path.is_dir() {
+        return Err(BagError::NotADirectory);
+    }
+
+    // Check if already a bag
+    if let Some(reason) = bag_like_reason(path) {
+        return Err(BagError::AlreadyABag(reason));
+    }
+
+    // The journal lets a run that's interrupted be resumed by bagging the folder again.
+    // Once every file is in data/, what's outside it is tag files, so isn't walked.
+    let mut report = BagReport::default();
+    let journal = Journal::open(path, &options.manifest_algorithms())?;
+    let mut filter = PayloadFilter::load(path, options, true)?;
+    let total_files = match journal.moves_done() {
+        true => 0,
+        false => scan_payload(path, options, &mut filter, &mut report, &progress_tx)?.0,
+    };
+    check_profile(options)?;
+    check_empty_source(total_files + journal.moved(), options, &mut report)?;
+    if journal.resumed() {
+        report.warnings.push(format!(
+            "Resumed an interrupted run: {} files had already been moved into data/ and {} checksummed",
+            journal.moved(),
+            journal.hashed()
+        ));
+    }
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started { total_files });
+    }
+
+    report.events.push(enumeration_event(
+        path,
+        total_files + journal.moved(),
+        report.skipped.len(),
+    ));
+    let mut provenance = ProvenanceLog::wanted(path, options);
+    let data_dir = path.join("data");
+    if let Some(log) = provenance.as_mut().filter(|_| journal.resumed()) {
+        log.record_moved(&data_dir);
+    }
+
+    // Files are moved as the walk finds them
+    fs::create_dir_all(&data_dir)?;
+    if !journal.moves_done() {
+        let entries = filter.walk(path, options, |_| {}).inspect(|entry| {
+            if let Some(log) = provenance.as_mut() {
+                log.record(entry);
+            }
+        });
+        let journaled = Some(&journal);
+        move_payload(
+            path,
+            &data_dir,
+            entries,
+            options,
+            &mut report,
+            &progress_tx,
+            journaled,
+        )?;
+    }
+    journal.record_moves_done()?;
+    report.events.push(Event::now(
+        EventType::Move,
+        format!(
+            "{} files moved into data/",
+            total_files + journal.moved() - report.vanished.len()
+        ),
+    ));
+    drop(journal);
+
+    let tag_files = match provenance {
+        Some(log) => write_records(path, &log.records, options)?,
+        None => Vec::new(),
+    };
+    let report = write_bag_files(path, options, report, tag_files, &progress_tx)?;
+    fs::remove_file(path.join(JOURNAL_FILE))?;
+    Ok(report)
+}
+
+/// Name template copied bags get unless the settings give another.
+pub const DEFAULT_NAME_TEMPLATE: &str = "{folder}_bag";
+
+/// The name `template` gives a bag of `source`. `{folder}` is the folder's name,
+/// `{date}` the bagging date (`2024-05-31`), `{time}` the time (`143005`) and
+/// `{algorithm}` the first manifest algorithm; anything else is kept as written.
+/// Characters that can't go in a file name become `-`.
+pub fn bag_name(template: &str, source: &Path, options: &BagOptions) -> String {
+    let folder = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "bag".to_string());
+    let (date, time) = bagging_timestamp(options.date_mode);
+    let time: String = time.chars().take(8).filter(|c| *c != ':').collect();
+    let algorithm = options.manifest_algorithms()[0].name();
+    let name = template
+        .replace("{folder}", &folder)
+        .replace("{date}", &date)
+        .replace("{time}", &time)
+        .replace("{algorithm}", algorithm);
+    let name: String = name
+        .chars()
+        .map(|c| match c {
+            '/' | '\\' | ':' | '<' | '>' | '"' | '|' | '?' | '*' => '-',
+            c if c.is_control() => '-',
+            c => c,
+        })
+        .collect();
+    match name.trim() {
+        "" | "." | ".." => folder,
+        _ => name,
+    }
+}
+
+/// Where a copy of `source` is bagged when no destination is given: a folder called
+/// `name` in `output_dir`, or next to `source` without one, with `_2` and so on added
+/// if that's taken by a non-empty folder.
+pub fn copy_destination(source: &Path, name: &str, output_dir: Option<&Path>) -> PathBuf {
+    let parent = output_dir.unwrap_or_else(|| source.parent().unwrap_or(Path::new(".")));
+    let free = |path: &PathBuf| {
+        fs::read_dir(path).map_or(!path.exists(), |mut entries| entries.next().is_none())
+    };
+    let mut dest = parent.join(name);
+    let mut n = 2;
+    while !free(&dest) {
+        dest = parent.join(format!("{}_{}", name, n));
+        n += 1;
+    }
+    dest
+}
+
+/// Bags a copy of `source` into `dest`, for folders that can't be bagged in place.
+///
+/// `dest` is created if needed and must otherwise be empty. The source is left as it
+/// was, bag-like files and all; everything in it becomes payload.
+pub fn bag_into(
+    source: &Path,
+    dest: &Path,
+    options: &BagOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<BagReport, BagError> {
+    if !source.is_dir() {
+        return Err(BagError::NotADirectory);
+    }
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        return Err(BagError::DestinationNotEmpty(dest.to_path_buf()));
+    }
+
+    let mut report = BagReport::default();
+    // The copy is walked as it's made, so mustn't be inside what's walked
+    let mut filter = PayloadFilter::load(source, options, false)?;
+    filter.excluded.push(absolute(dest));
+    let (total_files, total_bytes) =
+        scan_payload(source, options, &mut filter, &mut report, &progress_tx)?;
+    check_profile(options)?;
+    check_empty_source(total_files, options, &mut report)?;
+    check_free_space(dest, total_bytes + total_files as u64 * PER_FILE_ALLOWANCE)?;
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started { total_files });
+    }
+
+    let data_dir = dest.join("data");
+    fs::create_dir_all(&data_dir)?;
+
+    report
+        .events
+        .push(enumeration_event(source, total_files, report.skipped.len()));
+    let mut provenance = ProvenanceLog::wanted(source, options);
+    let entries = filter.walk(source, options, |_| {}).inspect(|entry| {
+        if let Some(log) = provenance.as_mut() {
+            log.record(entry);
+        }
+    });
+    copy_payload(
+        source,
+        &data_dir,
+        entries,
+        options,
+        &mut report,
+        &progress_tx,
+        &mut 0,
+    )?;
+    report.events.push(Event::now(
+        EventType::Replication,
+        format!(
+            "{} files copied into data/ from {}",
+            total_files - report.vanished.len(),
+            source.display()
+        ),
+    ));
+
+    let tag_files = match provenance {
+        Some(log) => write_records(dest, &log.records, options)?,
+        None => Vec::new(),
+    };
+    write_bag_files(dest, options, report, tag_files, &progress_tx)
+}
+
+/// A folder bagged along with others into one bag, its payload under `data/<name>/`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayloadSource {
+    pub path: PathBuf,
+    /// Subfolder of `data/` the folder's payload is copied into.
+    pub name: String,
+}
+
+/// `paths` as payload sources, each under its own folder name, with `_2`, `_3` and so
+/// on added where two have the same name, as `raw/` from two places would.
+pub fn payload_sources(paths: Vec<PathBuf>) -> Vec<PayloadSource> {
+    let mut sources: Vec<PayloadSource> = Vec::new();
+    for path in paths {
+        let base = path
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "payload".to_string());
+        let taken = |name: &str, sources: &[PayloadSource]| {
+            sources.iter().any(|s| s.name.eq_ignore_ascii_case(name))
+        };
+        let mut name = base.clone();
+        let mut n = 2;
+        while taken(&name, &sources) {
+            name = format!("{}_{}", base, n);
+            n += 1;
+        }
+        sources.push(PayloadSource { path, name });
+    }
+    sources
+}
+
+/// Refuses subfolder names that aren't a single path component, or that clash with
+/// another (ignoring case, as Windows and macOS do).
+fn check_source_names(sources: &[PayloadSource]) -> Result<(), BagError> {
+    for (i, source) in sources.iter().enumerate() {
+        let name = source.name.as_str();
+        let single = !name.is_empty() && name != "." && name != ".." && !name.contains(['/', '\\']);
+        let repeated = sources[..i]
+            .iter()
+            .any(|s| s.name.eq_ignore_ascii_case(name));
+        if !single || repeated {
+            return Err(BagError::BadSourceName(source.name.clone()));
+        }
+    }
+    Ok(())
+}
+
+/// Bags copies of several folders into one bag at `dest`, each under `data/<name>/`,
+/// as when `raw/`, `processed/` and `docs/` from different places make up one
+/// deposit. `dest` is created if needed and must otherwise be empty; the sources are
+/// left as they were.
+pub fn bag_sources(
+    sources: &[PayloadSource],
+    dest: &Path,
+    options: &BagOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<BagReport, BagError> {
+    if sources.iter().any(|s| !s.path.is_dir()) {
+        return Err(BagError::NotADirectory);
+    }
+    check_source_names(sources)?;
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        return Err(BagError::DestinationNotEmpty(dest.to_path_buf()));
+    }
+
+    // Each folder is scanned before anything is copied, so none is half bagged when
+    // another can't be
+    let mut report = BagReport::default();
+    let mut filters = Vec::new();
+    let mut total_files = 0;
+    let mut total_bytes = 0;
+    for source in sources {
+        let mut filter = PayloadFilter::load(&source.path, options, false)?;
+        filter.excluded.push(absolute(dest));
+        let skipped_before = report.skipped.len();
+        let (files, bytes) = scan_payload(
+            &source.path,
+            options,
+            &mut filter,
+            &mut report,
+            &progress_tx,
+        )?;
+        for skipped in &mut report.skipped[skipped_before..] {
+            *skipped = Path::new(&source.name).join(&*skipped);
+        }
+        let skipped = report.skipped.len() - skipped_before;
+        report
+            .events
+            .push(enumeration_event(&source.path, files, skipped));
+        filters.push(filter);
+        total_files += files;
+        total_bytes += bytes;
+    }
+    check_profile(options)?;
+    check_empty_source(total_files, options, &mut report)?;
+    check_free_space(dest, total_bytes + total_files as u64 * PER_FILE_ALLOWANCE)?;
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started { total_files });
+    }
+
+    let data_dir = dest.join("data");
+    let mut records = Vec::new();
+    let mut copied = 0;
+    for (source, filter) in sources.iter().zip(&filters) {
+        let into = data_dir.join(&source.name);
+        fs::create_dir_all(&into)?;
+        let mut provenance = ProvenanceLog::wanted(&source.path, options);
+        let entries = filter.walk(&source.path, options, |_| {}).inspect(|entry| {
+            if let Some(log) = provenance.as_mut() {
+                log.record(entry);
+            }
+        });
+        let vanished_before = report.vanished.len();
+        let copied_before = copied;
+        copy_payload(
+            &source.path,
+            &into,
+            entries,
+            options,
+            &mut report,
+            &progress_tx,
+            &mut copied,
+        )?;
+        for vanished in &mut report.vanished[vanished_before..] {
+            *vanished = Path::new(&source.name).join(&*vanished);
+        }
+        report.events.push(Event::now(
+            EventType::Replication,
+            format!(
+                "{} files copied into data/{}/ from {}",
+                copied - copied_before - (report.vanished.len() - vanished_before),
+                source.name,
+                source.path.display()
+            ),
+        ));
+        for mut record in provenance.map(|log| log.records).unwrap_or_default() {
+            record.relative = Path::new(&source.name).join(&record.relative);
+            records.push(record);
+        }
+    }
+
+    // Writes only the tag files asked for, so none without a log
+    let tag_files = write_records(dest, &records, options)?;
+    write_bag_files(dest, options, report, tag_files, &progress_tx)
+}
+
+/// Space held back on every volume for the tag files.
+const VOLUME_TAG_ALLOWANCE: u64 = 1024 * 1024;
+
+/// Space a file takes on the volume: rounded up to whole 4 KiB blocks, plus its lines in
+/// the manifest.
+fn volume_footprint(size: u64, relative_path: &Path) -> u64 {
+    size.div_ceil(4096) * 4096 + 80 + relative_path.as_os_str().len() as u64
+}
+
+/// One volume's share of a payload split into sub-bags.
+#[derive(Debug, Clone, Default)]
+pub struct VolumePlan {
+    /// Payload files, relative to the source folder, in walk order.
+    pub files: Vec<PathBuf>,
+    pub bytes: u64,
+}
+
+/// Splits the payload of `source` into sub-bags that each fit on a volume holding
+/// `capacity` bytes, keeping files in walk order so folders stay together where they can.
+///
+/// Returns the plan plus a report of what the skip list left out. One volume means the
+/// payload fits as it is.
+pub fn plan_volumes(
+    source: &Path,
+    options: &BagOptions,
+    capacity: u64,
+) -> Result<(Vec<VolumePlan>, BagReport), BagError> {
+    if !source.is_dir() {
+        return Err(BagError::NotADirectory);
+    }
+    let mut report = BagReport::default();
+    let mut filter = PayloadFilter::load(source, options, false)?;
+    let (total_files, _) = scan_payload(source, options, &mut filter, &mut report, &None)?;
+    check_profile(options)?;
+    check_empty_source(total_files, options, &mut report)?;
+    let files = filter
+        .walk(source, options, |_| {})
+        .filter(|e| !e.file_type().is_dir());
+
+    let usable = capacity.saturating_sub(VOLUME_TAG_ALLOWANCE);
+    let mut volumes = vec![VolumePlan::default()];
+    let mut used = 0;
+    for entry in files {
+        let relative = entry.path().strip_prefix(source).unwrap().to_path_buf();
+        let size = entry.metadata().map_err(io::Error::from)?.len();
+        let footprint = volume_footprint(size, &relative);
+        if footprint > usable {
+            return Err(BagError::FileTooLargeForVolume(relative));
+        }
+        if used + footprint > usable {
+            volumes.push(VolumePlan::default());
+            used = 0;
+        }
+        used += footprint;
+        let volume = volumes.last_mut().unwrap();
+        volume.bytes += size;
+        volume.files.push(relative);
+    }
+    Ok((volumes, report))
+}
+
+/// Bags volume `index` (0-based) of a split payload into `dest`, which must be empty.
+///
+/// Each sub-bag records `Bag-Group-Identifier` and `Bag-Count` ("2 of 3") so the
+/// receiving end can tell the volumes belong together.
+pub fn bag_volume(
+    source: &Path,
+    dest: &Path,
+    volumes: &[VolumePlan],
+    index: usize,
+    group_identifier: &str,
+    options: &BagOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<BagReport, BagError> {
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        return Err(BagError::DestinationNotEmpty(dest.to_path_buf()));
+    }
+    let files = &volumes[index].files;
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started {
+            total_files: files.len(),
+        });
+    }
+
+    let mut options = options.clone();
+    options
+        .metadata
+        .push("Bag-Group-Identifier", group_identifier);
+    options
+        .metadata
+        .push("Bag-Count", format!("{} of {}", index + 1, volumes.len()));
+
+    let data_dir = dest.join("data");
+    fs::create_dir_all(&data_dir)?;
+    let mut report = BagReport::default();
+    for (i, relative) in files.iter().enumerate() {
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Copying {
+                current: i + 1,
+                filename: relative.to_string_lossy().to_string(),
+            });
+        }
+        let target = data_dir.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let copy = || copy_payload_file(&source.join(relative), &target, &options);
+        if let Err(e) = with_retries(&options, copy) {
+            handle_unreadable(e, relative, &options, &mut report)?;
+        }
+    }
+    report.events.push(Event::now(
+        EventType::Replication,
+        format!(
+            "{} files copied into data/ from {} for volume {} of {}",
+            files.len() - report.vanished.len(),
+            source.display(),
+            index + 1,
+            volumes.len()
+        ),
+    ));
+
+    write_bag_files(dest, &options, report, Vec::new(), &progress_tx)
+}
+
+/// Re-checksums the payload of an existing bag and rewrites its tag files.
+///
+/// User fields in bag-info.txt are kept; the generated ones are recomputed. The bag
+/// keeps the algorithms of its payload manifests, and every manifest is rewritten.
+pub fn update_bag(
+    path: &Path,
+    options: &BagOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<BagReport, BagError> {
+    if !path.is_dir() {
+        return Err(BagError::NotADirectory);
+    }
+    if !path.join("bagit.txt").is_file() || !path.join("data").is_dir() {
+        return Err(BagError::NotABag);
+    }
+    if path.join(FETCH_FILE).is_file() {
+        return Err(BagError::HasFetchReferences);
+    }
+
+    let mut options = options.clone();
+    let algorithms = bag_algorithms(path);
+    if !algorithms.is_empty() {
+        options.algorithms = algorithms;
+    }
+    // A bag that lists its empty folders goes on listing them
+    if path.join(EMPTY_DIRS_FILE).is_file() {
+        options.empty_dirs = EmptyDirPolicy::Record;
+    }
+    options.metadata = match read_tag_file(&path.join("bag-info.txt")) {
+        Ok((content, _)) => BagInfo::parse(&content),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => BagInfo::default(),
+        Err(e) => return Err(e.into()),
+    };
+
+    if let Some(ref tx) = progress_tx {
+        let total_files = WalkDir::new(path.join("data"))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .count();
+        let _ = tx.send(Progress::Started { total_files });
+    }
+
+    // Where the payload came from doesn't change, so the records stay in the bag
+    let mut tag_files = Vec::new();
+    for name in [
+        PROVENANCE_FILE,
+        FILE_METADATA_FILE,
+        FILE_PERMISSIONS_FILE,
+        XATTRS_FILE,
+    ] {
+        if path.join(name).is_file() {
+            tag_files.push(name.to_string());
+        }
+    }
+    write_bag_files(
+        path,
+        &options,
+        BagReport::default(),
+        tag_files,
+        &progress_tx,
+    )
+}
+
+/// Undoes bagging in place: moves everything in `data/` back up to the top level and
+/// removes the tag files baggie writes. Other files outside `data/` (skipped system
+/// files, tag files from elsewhere) are left where they are.
+pub fn unbag_directory(path: &Path) -> Result<(), BagError> {
+    if !path.is_dir() {
+        return Err(BagError::NotADirectory);
+    }
+    if !path.join("bagit.txt").is_file() || !path.join("data").is_dir() {
+        return Err(BagError::NotABag);
+    }
+    if path.join(FETCH_FILE).is_file() {
+        return Err(BagError::HasFetchReferences);
+    }
+
+    let generated = [
+        "bagit.txt",
+        "bag-info.txt",
+        PROVENANCE_FILE,
+        FILE_METADATA_FILE,
+        FILE_PERMISSIONS_FILE,
+        XATTRS_FILE,
+        EVENT_LOG_FILE,
+        DEDUP_REPORT_FILE,
+        SOURCE_URLS_FILE,
+        EMPTY_DIRS_FILE,
+    ];
+    let mut tag_files = Vec::new();
+    for entry in fs::read_dir(path)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        let is_manifest = name.starts_with("manifest-") || name.starts_with("tagmanifest-");
+        if generated.contains(&name.as_str()) || (is_manifest && name.ends_with(".txt")) {
+            tag_files.push(name);
+        }
+    }
+    // Check every move before making any, so a clash leaves the bag as it was
+    let mut payload = Vec::new();
+    for entry in fs::read_dir(path.join("data"))? {
+        let name = entry?.file_name();
+        let target = path.join(&name);
+        let freed = tag_files.iter().any(|t| name == t.as_str()) || name == "data";
+        if !freed && fs::symlink_metadata(&target).is_ok() {
+            return Err(BagError::UnbagConflict(target));
+        }
+        payload.push(name);
+    }
+
+    // Moving files keeps their mode and owner, so they're put back while the record is
+    // still there
+    restore_permissions(path)?;
+    for name in &tag_files {
+        fs::remove_file(path.join(name))?;
+    }
+    // data/ is moved aside first, in case the payload has a data folder of its own
+    let staging = path.join(".baggie-unbag");
+    fs::rename(path.join("data"), &staging)?;
+    for name in payload {
+        fs::rename(staging.join(&name), path.join(&name))?;
+    }
+    fs::remove_dir(staging)?;
+    Ok(())
+}
+
+/// Applies `policy` to the empty folders under `data/`, returning the tag files written.
+fn handle_empty_dirs(
+    path: &Path,
+    policy: EmptyDirPolicy,
+    report: &mut BagReport,
+) -> Result<Vec<String>, BagError> {
+    let mut empty = Vec::new();
+    for entry in WalkDir::new(path.join("data"))
+        .min_depth(1)
+        .sort_by_file_name()
+        .into_iter()
+        .filter_map(|e| e.ok())
+    {
+        if entry.file_type().is_dir() && fs::read_dir(entry.path())?.next().is_none() {
+            let relative = entry.path().strip_prefix(path).unwrap();
+            empty.push(relative.to_string_lossy().replace('\\', "/"));
+        }
+    }
+
+    let record = path.join(EMPTY_DIRS_FILE);
+    match policy {
+        EmptyDirPolicy::Warn if !empty.is_empty() => report.warnings.push(format!(
+            "{} empty folders can't be listed in a manifest, so copies of the bag may lose them: {}",
+            empty.len(),
+            empty.join(", ")
+        )),
+        EmptyDirPolicy::Warn => {}
+        EmptyDirPolicy::Placeholder => {
+            for dir in &empty {
+                fs::write(path.join(dir).join(PLACEHOLDER_FILE), "")?;
+            }
+        }
+        EmptyDirPolicy::Record if !empty.is_empty() => {
+            let listing: String = empty.iter().map(|dir| format!("{}\n", dir)).collect();
+            fs::write(&record, listing)?;
+            return Ok(vec![EMPTY_DIRS_FILE.to_string()]);
+        }
+        // A list left from an earlier run would be out of date
+        EmptyDirPolicy::Record => {
+            if record.is_file() {
+                fs::remove_file(&record)?;
+            }
+        }
+    }
+    Ok(Vec::new())
+}
+
+/// Checksums `data/` and writes the tag files, finishing a bag whose payload is in place.
+/// `tag_files` names extra tag files already in the bag, for the tag manifest.
+pub fn write_bag_files(
+    path: &Path,
+    options: &BagOptions,
+    mut report: BagReport,
+    tag_files: Vec<String>,
+    progress_tx: &Option<Sender<Progress>>,
+) -> Result<BagReport, BagError> {
+    let data_dir = path.join("data");
+    let empty_dir_files = handle_empty_dirs(path, options.empty_dirs, &mut report)?;
+    let tag_dir_files = copy_tag_directories(path, options)?;
+
+    // Calculate checksums for all files in data/, every algorithm in one read. Sizes
+    // are summed as u64 so Payload-Oxum stays right past 4 GiB on 32-bit targets too.
+    let algorithms = options.manifest_algorithms();
+    let mut manifest = Vec::new();
+    let mut payload = Vec::new();
+    let mut identities = Vec::new();
+    let mut total_bytes: u64 = 0;
+    let mut file_count: u64 = 0;
+
+    let data_files: Vec<_> = WalkDir::new(&data_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .collect();
+
+    let listing: String = data_files
+        .iter()
+        .map(|e| {
+            let relative = e.path().strip_prefix(path).unwrap();
+            relative.to_string_lossy().replace('\\', "/") + "\n"
+        })
+        .collect();
+    let mut extra_tag_files = tag_files;
+    extra_tag_files.extend(empty_dir_files);
+    extra_tag_files.extend(tag_dir_files);
+    extra_tag_files.extend(run_plugins(
+        &options.plugins,
+        PluginStage::AfterEnumeration,
+        path,
+        None,
+        &listing,
+        &mut report,
+    )?);
+
+    // Bagging in place journals each checksum, and a resumed run reuses them
+    let journal = match path.join(JOURNAL_FILE).is_file() {
+        true => Some(Journal::open(path, &algorithms)?),
+        false => None,
+    };
+    let hashed = AtomicUsize::new(0);
+    let results = run_parallel(data_files.len(), options.hash_threads, |i| {
+        let file_path = data_files[i].path();
+        let file = file_path
+            .strip_prefix(path)
+            .unwrap()
+            .to_string_lossy()
+            .replace('\\', "/");
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Checksumming {
+                current: hashed.fetch_add(1, Ordering::Relaxed) + 1,
+                filename: file.clone(),
+            });
+        }
+        let metadata = fs::metadata(file_path)?;
+        let Some(ref journal) = journal else {
+            let digests = with_retries(options, || calculate_digests(file_path, &algorithms))?;
+            return Ok((digests, metadata));
+        };
+        if let Some(digests) = journal.digests(&file, &metadata) {
+            return Ok((digests, metadata));
+        }
+        let digests = with_retries(options, || calculate_digests(file_path, &algorithms))?;
+        journal.record_digests(&file, &metadata, &digests)?;
+        Ok((digests, metadata))
+    });
+    drop(journal);
+
+    // Tag files go to a staging folder and are moved into place once written, bagit.txt
+    // last, so an interrupted run never leaves a folder declaring itself a bag that
+    // isn't one. An update leaves the old tag files alone until then, so a failure on
+    // the way leaves the bag as valid as it was.
+    let staging = path.join(STAGING_DIR);
+    if staging.exists() {
+        fs::remove_dir_all(&staging)?;
+    }
+    fs::create_dir(&staging)?;
+
+    // Manifest lines are sorted on disk (for reproducibility, matching Python bagit),
+    // so a payload of millions of files doesn't need them all in memory
+    let mut sorters: Vec<LineSorter> = algorithms
+        .iter()
+        .map(|a| LineSorter::new(&staging, &a.manifest_name()))
+        .collect();
+    for (entry, result) in data_files.iter().zip(results) {
+        let relative_path = entry.path().strip_prefix(path).unwrap();
+        let (checksums, metadata) = match result {
+            Ok(result) => result,
+            Err(e) => {
+                handle_vanished(e, relative_path, options, &mut report)?;
+                continue;
+            }
+        };
+        total_bytes += metadata.len();
+        file_count += 1;
+
+        // Use forward slashes for manifest (BagIt spec)
+        let manifest_path = relative_path.to_string_lossy().replace('\\', "/");
+        let listed_path = options.bagit_version.listed_path(&manifest_path);
+        for (sorter, checksum) in sorters.iter_mut().zip(&checksums) {
+            sorter.push(format!("{}  {}", checksum, listed_path))?;
+        }
+        payload.push((checksums[0].clone(), manifest_path.clone(), metadata.len()));
+        identities.push(file_identity(&metadata));
+        manifest.push((manifest_path, checksums));
+    }
+    report.duplicates = find_duplicates(&payload, &identities);
+    drop(identities);
+
+    // Payload-Oxum and the manifest still cover files dedup leaves out of data/
+    extra_tag_files.extend(dedup_payload(path, options, &payload, &mut report)?);
+
+    // Write the payload manifests. Plugins see the first, read back only if there are any.
+    let mut manifests = Vec::new();
+    for (algorithm, sorter) in algorithms.iter().zip(sorters) {
+        let name = algorithm.manifest_name();
+        sorter.finish(&staging.join(&name))?;
+        manifests.push(name);
+    }
+    let manifest_text = match options.plugins.is_empty() {
+        true => String::new(),
+        false => fs::read_to_string(staging.join(&manifests[0]))?,
+    };
+    let manifest_text = &manifest_text;
+    extra_tag_files.extend(run_plugins(
+        &options.plugins,
+        PluginStage::AfterHashing,
+        path,
+        Some(&staging),
+        manifest_text,
+        &mut report,
+    )?);
+
+    let bagit_content = write_tag_file(
+        &staging.join("bagit.txt"),
+        &format!(
+            "BagIt-Version: {}\nTag-File-Character-Encoding: UTF-8\n",
+            options.bagit_version.as_str()
+        ),
+    )?;
+
+    // Write bag-info.txt (field order matches Python bagit library)
+    let (date, time) = bagging_timestamp(options.date_mode);
+    let mut bag_info = BagInfo::default();
+    bag_info.push("Bag-Software-Agent", SOFTWARE_AGENT);
+    bag_info.push("Bagging-Date", date);
+    if options.include_bagging_time {
+        bag_info.push("Bagging-Time", time);
+    }
+    bag_info.push("Payload-Oxum", format!("{}.{}", total_bytes, file_count));
+    bag_info.push("Bag-Size", bag_size(total_bytes));
+    // One set by the user or kept from an earlier run stays
+    if options.metadata.get("Internal-Sender-Identifier").is_none() {
+        bag_info.push(
+            "Internal-Sender-Identifier",
+            options.id_scheme.generate(options.id_sequence_next),
+        );
+    }
+    if let Some(profile) = &options.profile {
+        if options.metadata.get("BagIt-Profile-Identifier").is_none() {
+            bag_info.push("BagIt-Profile-Identifier", profile.identifier.clone());
+        }
+    }
+    for (label, value) in options.metadata.fields() {
+        let generated = GENERATED_BAG_INFO_LABELS
+            .iter()
+            .any(|g| g.eq_ignore_ascii_case(label));
+        if !generated && !label.is_empty() {
+            bag_info.push(label.clone(), value.clone());
+        }
+    }
+    let bag_info_content = write_tag_file(&staging.join("bag-info.txt"), &bag_info.serialize())?;
+
+    report.events.push(Event::now(
+        EventType::MessageDigestCalculation,
+        format!(
+            "{} of {} payload files, Payload-Oxum {}.{}",
+            algorithms
+                .iter()
+                .map(|a| a.label())
+                .collect::<Vec<_>>()
+                .join(" and "),
+            file_count,
+            total_bytes,
+            file_count
+        ),
+    ));
+    if options.verify_after {
+        let algorithm = algorithms[0];
+        let verified = verify_written_payload(path, &staging, algorithm, options, progress_tx)?;
+        report.events.push(Event::now(
+            EventType::FixityCheck,
+            format!(
+                "{} payload files read back after bagging match {}",
+                verified,
+                algorithm.manifest_name()
+            ),
+        ));
+    }
+    if options.event_log || path.join(EVENT_LOG_FILE).is_file() {
+        extra_tag_files.push(write_event_log(path, &staging, &report.events)?);
+    }
+
+    extra_tag_files.extend(run_plugins(
+        &options.plugins,
+        PluginStage::BeforeSealing,
+        path,
+        Some(&staging),
+        manifest_text,
+        &mut report,
+    )?);
+
+    // BagIt 1.0 wants every tag file covered, including ones baggie didn't write
+    if options.bagit_version == BagItVersion::V1_0 {
+        extra_tag_files.extend(other_tag_files(path, options)?);
+    }
+
+    // Write a tag manifest per algorithm, each listing every payload manifest (sorted
+    // alphabetically to match Python bagit)
+    extra_tag_files.sort();
+    extra_tag_files.dedup();
+    // Tag files written since staging began are still there
+    let staged: Vec<&String> = extra_tag_files
+        .iter()
+        .filter(|name| staging.join(name).is_file())
+        .collect();
+    let mut extra_checksums = Vec::new();
+    for name in &extra_tag_files {
+        let file = match staged.contains(&name) {
+            true => staging.join(name),
+            false => path.join(name),
+        };
+        extra_checksums.push(calculate_digests(&file, &algorithms)?);
+    }
+    let written = [
+        ("bag-info.txt".to_string(), bag_info_content),
+        ("bagit.txt".to_string(), bagit_content),
+    ];
+    let mut tag_manifest: Vec<(String, Vec<String>)> = written
+        .iter()
+        .map(|(name, content)| {
+            let checksums = algorithms.iter().map(|a| calculate_digest_str(content, *a));
+            (name.clone(), checksums.collect())
+        })
+        .collect();
+    for name in &manifests {
+        let checksums = calculate_digests(&staging.join(name), &algorithms)?;
+        tag_manifest.push((name.clone(), checksums));
+    }
+    let staged: Vec<String> = staged.into_iter().cloned().collect();
+    tag_manifest.extend(extra_tag_files.into_iter().zip(extra_checksums));
+    tag_manifest.sort();
+    for (i, algorithm) in algorithms.iter().enumerate() {
+        let mut entries = Vec::new();
+        for (name, checksums) in &tag_manifest {
+            let name = options.bagit_version.listed_path(name);
+            entries.push(format!("{}  {}", checksums[i], name));
+        }
+        // Sort by filename (after the checksum and spaces)
+        entries.sort_by(|a, b| {
+            a.split_whitespace()
+                .last()
+                .cmp(&b.split_whitespace().last())
+        });
+        let content = entries.join("\n") + "\n";
+        write_tag_file(&staging.join(algorithm.tagmanifest_name()), &content)?;
+    }
+
+    // Sealing: the manifests, bag-info.txt and other staged tag files, then the tag
+    // manifests, then bagit.txt. A signature over the old tag manifest goes first, and
+    // a new one is made once sealed.
+    remove_signatures(path)?;
+    let manifest_names: Vec<&str> = manifests.iter().map(String::as_str).collect();
+    remove_other_manifests(path, "manifest-", &manifest_names)?;
+    let tagmanifests: Vec<String> = algorithms.iter().map(|a| a.tagmanifest_name()).collect();
+    let tagmanifest_names: Vec<&str> = tagmanifests.iter().map(String::as_str).collect();
+    remove_other_manifests(path, "tagmanifest-", &tagmanifest_names)?;
+    for name in staged
+        .iter()
+        .map(String::as_str)
+        .chain(manifest_names)
+        .chain(["bag-info.txt"])
+    {
+        let target = path.join(name);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::rename(staging.join(name), target)?;
+    }
+    for name in tagmanifest_names.iter().chain(&["bagit.txt"]) {
+        fs::rename(staging.join(name), path.join(name))?;
+    }
+    // Plugins' tag files may have left empty folders behind
+    fs::remove_dir_all(&staging)?;
+    if let Some(signing) = &options.signing {
+        sign_bag(path, signing)?;
+    }
+
+    manifest.sort();
+    let tag_manifest = tag_manifest
+        .into_iter()
+        .map(|(name, checksums)| (name, algorithms.iter().copied().zip(checksums).collect()))
+        .collect();
+    report.bag = Bag {
+        path: path.to_path_buf(),
+        version: options.bagit_version.as_str().to_string(),
+        encoding: "UTF-8".to_string(),
+        tag_algorithms: algorithms.clone(),
+        algorithms,
+        manifest,
+        tag_manifest,
+        payload_oxum: (total_bytes, file_count),
+        bag_info,
+    };
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Done {
+            path: path.to_path_buf(),
+            report: Box::new(report.clone()),
+        });
+    }
+
+    Ok(report)
+}
+
+/// Reads every payload file of the bag being written at `path` back and compares it
+/// with the payload manifest in `algorithm`, as written to `staging`. Files deduplication left
+/// in fetch.txt aren't there to read. Returns the number of files checked.
+fn verify_written_payload(
+    path: &Path,
+    staging: &Path,
+    algorithm: ChecksumAlgorithm,
+    options: &BagOptions,
+    progress_tx: &Option<Sender<Progress>>,
+) -> Result<usize, BagError> {
+    // bagit.txt isn't written yet, so the version comes from the options
+    let encoded = options.bagit_version == BagItVersion::V1_0;
+    let (content, _) = read_tag_file(&staging.join(algorithm.manifest_name()))?;
+    let fetched: std::collections::HashSet<String> = match read_tag_file(&path.join(FETCH_FILE)) {
+        Ok((fetch, _)) => parse_fetch_file(&fetch, encoded)
+            .into_iter()
+            .map(|e| e.file)
+            .collect(),
+        Err(_) => Default::default(),
+    };
+    let entries: Vec<(String, String)> = parse_manifest_lines(&content, encoded)
+        .into_iter()
+        .filter(|(file, _)| !fetched.contains(file))
+        .collect();
+
+    let read = AtomicUsize::new(0);
+    let results = run_parallel(entries.len(), options.hash_threads, |i| {
+        let (file, _) = &entries[i];
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Verifying {
+                current: read.fetch_add(1, Ordering::Relaxed) + 1,
+                filename: file.clone(),
+            });
+        }
+        calculate_digest(&path.join(file), algorithm)
+    });
+    let failed: Vec<String> = entries
+        .iter()
+        .zip(results)
+        .filter(|((_, expected), actual)| !matches!(actual, Ok(actual) if actual == expected))
+        .map(|((file, _), _)| file.clone())
+        .collect();
+    match failed.is_empty() {
+        true => Ok(entries.len()),
+        false => Err(BagError::VerifyFailed(failed)),
+    }
+}
+
+/// Removes the `<prefix><algorithm>.txt` files in `path` not named in `keep`, such as
+/// the manifests of algorithms an updated bag no longer uses.
+fn remove_other_manifests(path: &Path, prefix: &str, keep: &[&str]) -> io::Result<()> {
+    for entry in fs::read_dir(path)? {
+        let name = entry?.file_name().to_string_lossy().to_string();
+        let stale = name.starts_with(prefix) && name.ends_with(".txt");
+        if stale && !keep.contains(&name.as_str()) {
+            fs::remove_file(path.join(name))?;
+        }
+    }
+    Ok(())
+}
+
+/// Files outside `data/` that a tag manifest should list but baggie didn't write:
+/// anything but the manifests, bagit.txt, bag-info.txt and skipped system files.
+fn other_tag_files(path: &Path, options: &BagOptions) -> Result<Vec<String>, BagError> {
+    let generated = ["bagit.txt", "bag-info.txt"];
+    // What .bagignore left out of an in-place bag stays beside it, and isn't tag files
+    let ignore = BagIgnore::load(path)?;
+    Ok(WalkDir::new(path)
+        .min_depth(1)
+        .into_iter()
+        .filter_entry(|e| {
+            let ours = ["data", STAGING_DIR, JOURNAL_FILE];
+            e.depth() > 1 || !ours.iter().any(|name| e.file_name() == *name)
+        })
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .filter_map(|e| {
+            let relative = e.path().strip_prefix(path).ok()?;
+            if relative.iter().any(|part| options.is_skipped(part)) {
+                return None;
+            }
+            let ignored = relative
+                .ancestors()
+                .filter(|a| !a.as_os_str().is_empty())
+                .any(|a| ignore.is_ignored(a, a != relative));
+            if ignored {
+                return None;
+            }
+            let name = relative.to_string_lossy().replace('\\', "/");
+            let manifest = name.starts_with("manifest-") || name.starts_with("tagmanifest-");
+            (!manifest && !generated.contains(&name.as_str())).then_some(name)
+        })
+        .collect())
+}
+
+/// The bag's payload manifest (see `bag_algorithm`): its algorithm, and digest by path.
+pub fn read_payload_manifest(
+    bag: &Path,
+) -> Result<(ChecksumAlgorithm, HashMap<String, String>), BagError> {
+    let algorithm = bag_algorithm(bag).unwrap_or_default();
+    Ok((algorithm, read_manifest(bag, algorithm)?))
+}
+
+/// The bag's payload manifest in `algorithm`: digest by path, with paths decoded if
+/// the bag's version encodes them.
+pub fn read_manifest(
+    bag: &Path,
+    algorithm: ChecksumAlgorithm,
+) -> Result<HashMap<String, String>, BagError> {
+    read_manifest_file(bag, &algorithm.manifest_name())
+}
+
+/// The manifest or tag manifest `name` in `bag`: digest by path.
+fn read_manifest_file(bag: &Path, name: &str) -> Result<HashMap<String, String>, BagError> {
+    let (manifest, _) = read_tag_file(&bag.join(name))?;
+    Ok(parse_manifest_lines(&manifest, bag_encodes_paths(bag))
+        .into_iter()
+        .collect())
+}
+
+/// The `(path, digest)` pairs of manifest lines, paths decoded if `encoded`.
+fn parse_manifest_lines(manifest: &str, encoded: bool) -> Vec<(String, String)> {
+    manifest
+        .lines()
+        .filter_map(|line| {
+            let (digest, file) = line.split_once(char::is_whitespace)?;
+            let file = file.trim_start();
+            let file = file.strip_prefix('*').unwrap_or(file);
+            let file = match encoded {
+                true => decode_manifest_path(file),
+                false => file.to_string(),
+            };
+            Some((file, digest.to_ascii_lowercase()))
+        })
+        .collect()
+}
+
+/// Recomputes the tag manifest lines of the tag files `names` after they changed,
+/// adding lines for any it didn't list yet, in every tag manifest the bag has. Names
+/// that no longer exist lose their lines. Bags without a tag manifest are left alone.
+/// Returns whether that removed a signature over the old tag manifest.
+pub fn refresh_tag_manifest(bag: &Path, names: &[&str]) -> Result<bool, BagError> {
+    let mut unsigned = false;
+    for algorithm in ChecksumAlgorithm::ALL {
+        let tagmanifest = bag.join(algorithm.tagmanifest_name());
+        if !tagmanifest.is_file() {
+            continue;
+        }
+        let (content, _) = read_tag_file(&tagmanifest)?;
+        let mut entries: Vec<String> = content
+            .lines()
+            .filter(|line| match line.split_once(char::is_whitespace) {
+                Some((_, file)) => !names.contains(&file.trim_start().trim_start_matches('*')),
+                None => !line.trim().is_empty(),
+            })
+            .map(String::from)
+            .collect();
+        for name in names.iter().filter(|name| bag.join(name).is_file()) {
+            let checksum = calculate_digest(&bag.join(name), algorithm)?;
+            entries.push(format!("{}  {}", checksum, name));
+        }
+        entries.sort_by(|a, b| {
+            a.split_whitespace()
+                .last()
+                .cmp(&b.split_whitespace().last())
+        });
+        write_tag_file(&tagmanifest, &(entries.join("\n") + "\n"))?;
+        // A signature over what was there no longer holds
+        if algorithm == ChecksumAlgorithm::Sha256 {
+            unsigned = is_signed(bag);
+            remove_signatures(bag)?;
+        }
+    }
+    Ok(unsigned)
+}
+
+/// 128 unpredictable bits. Each RandomState is seeded differently, so hashing the
+/// clock with two of them does without a random number crate.
+fn random_bits() -> u128 {
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let mut bits = 0;
+    for _ in 0..2 {
+        let mut hasher = std::collections::hash_map::RandomState::new().build_hasher();
+        hasher.write_u128(nanos);
+        bits = (bits << 64) | hasher.finish() as u128;
+    }
+    bits
+}
+
+/// A random (version 4) UUID.
+fn new_uuid() -> String {
+    let mut bytes = random_bits().to_be_bytes();
+    bytes[6] = (bytes[6] & 0x0f) | 0x40;
+    bytes[8] = (bytes[8] & 0x3f) | 0x80;
+    let hex: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+    format!(
+        "{}-{}-{}-{}-{}",
+        &hex[..8],
+        &hex[8..12],
+        &hex[12..16],
+        &hex[16..20],
+        &hex[20..]
+    )
+}
+
+/// A ULID: 48 bits of milliseconds since 1970 then 80 random bits, in Crockford's
+/// base 32.
+fn new_ulid() -> String {
+    const ALPHABET: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+    let millis = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_millis())
+        & ((1 << 48) - 1);
+    let value = (millis << 80) | (random_bits() & ((1 << 80) - 1));
+    (0..26)
+        .rev()
+        .map(|i| ALPHABET[((value >> (i * 5)) & 31) as usize] as char)
+        .collect()
+}
+
+/// The facts that identify a finished bag, for labels, QR codes and receipts.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BagSummary {
+    /// `External-Identifier` from bag-info.txt, or the folder name if there isn't one.
+    pub identifier: String,
+    pub payload_oxum: String,
+    /// SHA-256 of the tag manifest (of the payload manifest's algorithm), which pins
+    /// every other file in the bag.
+    pub tagmanifest_sha256: String,
+    /// `Internal-Sender-Identifier` from bag-info.txt, empty if there isn't one.
+    pub sender_id: String,
+}
+
+impl BagSummary {
+    pub fn read(path: &Path) -> Result<Self, BagError> {
+        let (bag_info, _) = read_tag_file(&path.join("bag-info.txt"))?;
+        let bag_info = BagInfo::parse(&bag_info);
+        let identifier = match bag_info.get("External-Identifier") {
+            Some(id) if !id.is_empty() => id.to_string(),
+            _ => path
+                .file_name()
+                .map(|n| n.to_string_lossy().to_string())
+                .unwrap_or_default(),
+        };
+        Ok(Self {
+            identifier,
+            payload_oxum: bag_info.get("Payload-Oxum").unwrap_or_default().to_string(),
+            tagmanifest_sha256: calculate_sha256(
+                &path.join(bag_algorithm(path).unwrap_or_default().tagmanifest_name()),
+            )?,
+            sender_id: bag_info
+                .get("Internal-Sender-Identifier")
+                .unwrap_or_default()
+                .to_string(),
+        })
+    }
+
+    /// `Label: value` lines, readable by a person and easy to split after scanning.
+    pub fn to_label_text(&self) -> String {
+        let mut text = format!(
+            "Identifier: {}\nPayload-Oxum: {}\ntagmanifest-sha256: {}\n",
+            self.identifier, self.payload_oxum, self.tagmanifest_sha256
+        );
+        if !self.sender_id.is_empty() {
+            text.push_str(&format!("Internal-Sender-Identifier: {}\n", self.sender_id));
+        }
+        text
+    }
+}
+
+/// The files of a bag being validated, wherever they are kept. Names are relative to
+/// the bag root, with `/` separators.
+pub trait BagFiles: Sync {
+    /// The contents of `name`, or `None` if there's no such file.
+    fn read(&self, name: &str) -> Option<io::Result<Vec<u8>>>;
+    /// The checksum of `name`, or `None` if there's no such file.
+    fn digest(&self, name: &str, algorithm: ChecksumAlgorithm) -> Option<io::Result<String>>;
+    /// Whether there's a file called `name`.
+    fn exists(&self, name: &str) -> bool;
+    /// Every file under `data/`, with its size.
+    fn payload(&self) -> Vec<(String, u64)>;
+    /// The names of the files at the top of the bag.
+    fn top_level(&self) -> Vec<String>;
+    /// Every file outside `data/`, as paths relative to the bag.
+    fn tag_files(&self) -> Vec<String>;
+    /// Something wrong with how the bag was packed up, for serialized bags.
+    fn serialization_problem(&self) -> Option<String> {
+        None
+    }
+    /// The MIME type of the file the bag is serialized in, `None` for a folder.
+    fn serialization(&self) -> Option<&'static str> {
+        None
+    }
+}
+
+/// A bag in a folder on disk.
+struct Folder<'a>(&'a Path);
+
+impl BagFiles for Folder<'_> {
+    fn read(&self, name: &str) -> Option<io::Result<Vec<u8>>> {
+        let path = self.0.join(name);
+        path.is_file().then(|| fs::read(path))
+    }
+
+    fn digest(&self, name: &str, algorithm: ChecksumAlgorithm) -> Option<io::Result<String>> {
+        let path = self.0.join(name);
+        path.is_file().then(|| calculate_digest(&path, algorithm))
+    }
+
+    fn exists(&self, name: &str) -> bool {
+        self.0.join(name).is_file()
+    }
+
+    fn payload(&self) -> Vec<(String, u64)> {
+        WalkDir::new(self.0.join("data"))
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| {
+                let relative = e.path().strip_prefix(self.0).unwrap();
+                let size = e.metadata().map(|m| m.len()).unwrap_or(0);
+                (relative.to_string_lossy().replace('\\', "/"), size)
+            })
+            .collect()
+    }
+
+    fn top_level(&self) -> Vec<String> {
+        fs::read_dir(self.0)
+            .into_iter()
+            .flatten()
+            .flatten()
+            .filter(|e| e.file_type().is_ok_and(|t| t.is_file()))
+            .map(|e| e.file_name().to_string_lossy().to_string())
+            .collect()
+    }
+
+    fn tag_files(&self) -> Vec<String> {
+        WalkDir::new(self.0)
+            .min_depth(1)
+            .into_iter()
+            .filter_entry(|e| e.depth() > 1 || e.file_name() != "data")
+            .filter_map(|e| e.ok())
+            .filter(|e| e.file_type().is_file())
+            .map(|e| {
+                let relative = e.path().strip_prefix(self.0).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+    }
+}
+
+/// Reads a tag file leniently as `encoding`, filing any encoding problems under
+/// `rule`. `None` if the bag has no such file.
+fn read_tag_checked(
+    files: &dyn BagFiles,
+    name: &str,
+    rule: &'static str,
+    encoding: TagEncoding,
+    report: &mut ValidationReport,
+) -> Result<Option<String>, BagError> {
+    let Some(bytes) = files.read(name) else {
+        return Ok(None);
+    };
+    Ok(Some(decode_tag_checked(
+        &bytes?, name, rule, encoding, report,
+    )))
+}
+
+/// Decodes the tag file `name` as `encoding`, reporting a BOM or bytes that aren't
+/// in it.
+fn decode_tag_checked(
+    bytes: &[u8],
+    name: &str,
+    rule: &'static str,
+    encoding: TagEncoding,
+    report: &mut ValidationReport,
+) -> String {
+    let (content, warnings) = decode_tag_bytes_as(bytes, name, encoding);
+    for warning in warnings {
+        report.strict(Finding::about(FindingKind::TagFile, name, rule, warning));
+    }
+    content
+}
+
+/// The encoding bagit.txt declares for the other tag files. One baggie can't check
+/// is noted, and the tag files are checked as UTF-8.
+fn declared_encoding(bagit: &str, report: &mut ValidationReport) -> TagEncoding {
+    let Some(declared) = tag_value(bagit, "Tag-File-Character-Encoding") else {
+        return TagEncoding::Utf8;
+    };
+    TagEncoding::parse(declared).unwrap_or_else(|| {
+        report.warning(
+            RULE_BAG_DECLARATION,
+            format!(
+                "Tag-File-Character-Encoding is {}, which baggie can't check; tag files were checked as UTF-8",
+                declared
+            ),
+        );
+        TagEncoding::Utf8
+    })
+}
+
+/// Checks bagit.txt: exactly `BagIt-Version` then `Tag-File-Character-Encoding`.
+fn check_bag_declaration(content: &str, report: &mut ValidationReport) {
+    if tag_value(content, "BagIt-Version").is_none() {
+        report.error(RULE_BAG_DECLARATION, "bagit.txt has no BagIt-Version");
+    }
+    if tag_value(content, "Tag-File-Character-Encoding").is_none() {
+        report.strict_error(
+            RULE_BAG_DECLARATION,
+            "bagit.txt has no Tag-File-Character-Encoding",
+        );
+    }
+
+    let lines: Vec<&str> = content.lines().filter(|l| !l.is_empty()).collect();
+    let expected = ["BagIt-Version: ", "Tag-File-Character-Encoding: "];
+    let exact = lines.len() == expected.len()
+        && lines
+            .iter()
+            .zip(expected)
+            .all(|(line, prefix)| line.starts_with(prefix));
+    if !exact {
+        report.strict_error(
+            RULE_BAG_DECLARATION,
+            "bagit.txt must contain exactly the BagIt-Version and Tag-File-Character-Encoding lines, in that order, separated by \": \"",
+        );
+    }
+}
+
+/// Checks that every bag-info.txt line is `Label: value` or an indented continuation.
+fn check_bag_metadata_format(content: &str, report: &mut ValidationReport) {
+    for (number, line) in content.lines().enumerate() {
+        if line.is_empty() || line.starts_with([' ', '\t']) {
+            continue;
+        }
+        match line.split_once(':') {
+            Some((label, _)) if !label.trim().is_empty() && label.trim() == label => {}
+            _ => report.strict_error(
+                RULE_BAG_METADATA,
+                format!(
+                    "bag-info.txt line {} is not a \"Label: value\" line",
+                    number + 1
+                ),
+            ),
+        }
+    }
+}
+
+/// Splits manifest lines into `(digest, path)` pairs, reporting malformed lines.
+///
+/// A leading `*` on the path (the binary-mode marker some checksum tools emit) is
+/// tolerated in lenient mode and taken literally in strict mode. With `encoded`
+/// (BagIt 1.0 on), percent-encoded CR, LF and `%` in paths are decoded.
+fn parse_manifest(
+    content: &str,
+    name: &str,
+    rule: &'static str,
+    encoded: bool,
+    report: &mut ValidationReport,
+) -> Vec<(String, String)> {
+    let mut entries = Vec::new();
+    for (number, line) in content.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+        let Some((digest, file)) = line.split_once(char::is_whitespace) else {
+            report.error(
+                rule,
+                format!("{} line {} has no file path", name, number + 1),
+            );
+            continue;
+        };
+        let mut file = file.trim_start();
+        if let Some(stripped) = file.strip_prefix('*') {
+            if report.mode == ValidationMode::Lenient {
+                report.warning(
+                    rule,
+                    format!(
+                        "{} line {} uses the \"*\" binary-mode marker",
+                        name,
+                        number + 1
+                    ),
+                );
+                file = stripped;
+            }
+        }
+        let file = match encoded {
+            true => decode_manifest_path(file),
+            false => file.to_string(),
+        };
+        // Hex digits are case-insensitive and other tools often write uppercase, so
+        // digests are normalised to lowercase, the way baggie writes them.
+        entries.push((digest.to_ascii_lowercase(), file));
+    }
+    entries
+}
+
+/// Why a manifest path would reach outside the bag (or, for the payload manifest,
+/// outside `data/`), if it would.
+///
+/// Anything that reads files named by a manifest must go through this first so a
+/// crafted bag can't point it at the rest of the filesystem.
+pub fn unsafe_manifest_path(file: &str, payload: bool) -> Option<&'static str> {
+    let bytes = file.as_bytes();
+    let has_drive = bytes.len() >= 2 && bytes[0].is_ascii_alphabetic() && bytes[1] == b':';
+    if file.starts_with(['/', '\\']) || has_drive {
+        return Some("absolute path");
+    }
+    if file.split(['/', '\\']).any(|part| part == "..") {
+        return Some("path contains \"..\"");
+    }
+    if payload && !file.starts_with("data/") {
+        return Some("path is outside data/");
+    }
+    None
+}
+
+/// Drops manifest entries whose paths escape the bag, recording each as an error.
+fn reject_unsafe_paths(
+    entries: Vec<(String, String)>,
+    payload: bool,
+    rule: &'static str,
+    report: &mut ValidationReport,
+) -> Vec<(String, String)> {
+    entries
+        .into_iter()
+        .filter(|(_, file)| match unsafe_manifest_path(file, payload) {
+            Some(problem) => {
+                report.error(
+                    rule,
+                    format!("Refusing manifest entry {} ({})", file, problem),
+                );
+                false
+            }
+            None => true,
+        })
+        .collect()
+}
+
+/// Checks every entry of a manifest in `algorithm`, recording missing files and
+/// mismatches, and reporting each file's result as it goes. A file missing from the
+/// bag but listed in `fetched` is checked against the local copy fetch.txt points to,
+/// if there is one. Checking only completeness, a file that's there passes.
+#[allow(clippy::too_many_arguments)]
+fn verify_manifest_entries(
+    files: &dyn BagFiles,
+    entries: &[(String, String)],
+    algorithm: ChecksumAlgorithm,
+    rule: &'static str,
+    options: &ValidationOptions,
+    fetched: &HashMap<String, Option<PathBuf>>,
+    report: &mut ValidationReport,
+    progress_tx: &Option<Sender<Progress>>,
+) {
+    let start = report.files_checked;
+    let checked = AtomicUsize::new(start);
+    let results = run_parallel(entries.len(), options.hash_threads, |i| {
+        let (digest, file) = &entries[i];
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Checksumming {
+                current: checked.fetch_add(1, Ordering::Relaxed) + 1,
+                filename: file.clone(),
+            });
+        }
+        // The expected digest stands in for the one there's no need to compute
+        let present = |there: bool| there.then(|| Ok(digest.clone()));
+        let result = match options.completeness_only {
+            true => present(files.exists(file)),
+            false => files.digest(file, algorithm),
+        };
+        match (result, fetched.get(file)) {
+            (None, Some(Some(copy))) if options.completeness_only => present(copy.is_file()),
+            (None, Some(Some(copy))) => copy.is_file().then(|| calculate_digest(copy, algorithm)),
+            (result, _) => result,
+        }
+    });
+    report.files_checked = start + entries.len();
+
+    for ((digest, file), result) in entries.iter().zip(results) {
+        if let Some(ref tx) = progress_tx {
+            let passed = matches!(&result, Some(Ok(actual)) if actual == digest);
+            let _ = tx.send(Progress::FileChecked {
+                filename: file.clone(),
+                passed,
+            });
+        }
+        // A tag file that's missing or changed is a problem with the tag files
+        let kind = |kind| match rule {
+            RULE_TAG_MANIFEST => FindingKind::TagFile,
+            _ => kind,
+        };
+        // Listed in fetch.txt only if the validation pass above found it missing
+        if let Some(copy) = fetched.get(file) {
+            let missing = |message| {
+                Finding::about(FindingKind::MissingFile, file, RULE_COMPLETENESS, message)
+            };
+            match (copy, &result) {
+                (Some(copy), Some(_)) => report.strict(missing(format!(
+                    "Not fetched: {} (checked against {}, from fetch.txt)",
+                    file,
+                    copy.display()
+                ))),
+                _ => {
+                    report.errors.push(missing(format!(
+                        "Not fetched: {} (listed in fetch.txt)",
+                        file
+                    )));
+                    continue;
+                }
+            }
+        }
+        match result {
+            None => report.errors.push(Finding::about(
+                kind(FindingKind::MissingFile),
+                file,
+                rule,
+                format!("Missing file: {}", file),
+            )),
+            Some(Ok(actual)) if actual == *digest => {}
+            Some(Ok(actual)) => {
+                report.errors.push(Finding::about(
+                    kind(FindingKind::ChecksumMismatch),
+                    file,
+                    rule,
+                    format!(
+                        "Checksum mismatch: {} (expected {}, got {})",
+                        file, digest, actual
+                    ),
+                ));
+                report.mismatches.push(Mismatch {
+                    file: file.clone(),
+                    algorithm,
+                    expected: digest.clone(),
+                    actual,
+                });
+            }
+            Some(Err(e)) => report.errors.push(Finding::about(
+                kind(FindingKind::UnreadableFile),
+                file,
+                rule,
+                format!("Could not read {}: {}", file, e),
+            )),
+        }
+    }
+}
+
+/// Lists files in `data/` that no manifest entry covers.
+///
+/// `manifest` holds the entries of the manifests that were checked. Entries in
+/// manifests baggie can't check count too, since a complete bag only needs every
+/// payload file listed in at least one manifest.
+fn check_unlisted_payload(
+    files: &dyn BagFiles,
+    manifest: &[(String, String)],
+    encoded: bool,
+    report: &mut ValidationReport,
+) {
+    let mut listed: std::collections::HashSet<String> =
+        manifest.iter().map(|(_, file)| file.clone()).collect();
+    for name in files.top_level() {
+        if !name.starts_with("manifest-") {
+            continue;
+        }
+        let Some(Ok(bytes)) = files.read(&name) else {
+            continue;
+        };
+        let (content, _) = decode_tag_bytes(&bytes, &name);
+        for line in content.lines() {
+            if let Some((_, file)) = line.split_once(char::is_whitespace) {
+                let file = file.trim_start();
+                let file = file.strip_prefix('*').unwrap_or(file);
+                listed.insert(match encoded {
+                    true => decode_manifest_path(file),
+                    false => file.to_string(),
+                });
+            }
+        }
+    }
+
+    let mut payload = files.payload();
+    payload.sort();
+    for (file, _) in payload {
+        if !listed.contains(&file) {
+            report.errors.push(Finding::about(
+                FindingKind::UnexpectedFile,
+                &file,
+                RULE_COMPLETENESS,
+                format!("Payload file not in any manifest: {}", file),
+            ));
+        }
+    }
+}
+
+/// Reports tag files that no tag manifest lists, such as ones added to the bag after
+/// it was made, as errors in strict mode. The tag files the spec defines, baggie's own
+/// working files and system files aren't expected to be listed.
+fn check_unlisted_tag_files(
+    files: &dyn BagFiles,
+    tagmanifests: &[(ChecksumAlgorithm, Vec<(String, String)>)],
+    report: &mut ValidationReport,
+) {
+    let listed: std::collections::HashSet<&str> = tagmanifests
+        .iter()
+        .flat_map(|(_, entries)| entries.iter().map(|(_, file)| file.as_str()))
+        .collect();
+    let system_files = default_skip_names();
+    let mut tag_files = files.tag_files();
+    tag_files.sort();
+    for file in tag_files {
+        let first = file.split('/').next().unwrap_or(&file);
+        let name = file.rsplit('/').next().unwrap_or(&file);
+        let defined = ["bagit.txt", "bag-info.txt", FETCH_FILE].contains(&file.as_str())
+            || file.starts_with("manifest-") && !file.contains('/');
+        if listed.contains(file.as_str())
+            || (defined && tagmanifests.is_empty())
+            || file.starts_with("tagmanifest-")
+            || [STAGING_DIR, JOURNAL_FILE].contains(&first)
+            || system_files.iter().any(|s| glob_match(s, name))
+        {
+            continue;
+        }
+        report.strict(Finding::about(
+            FindingKind::UnexpectedFile,
+            &file,
+            RULE_TAG_MANIFEST,
+            format!(
+                "Tag file not in any tag manifest: {} (updating the bag adds it)",
+                file
+            ),
+        ));
+    }
+}
+
+/// Compares Payload-Oxum in bag-info.txt against `actual`, the size and count of
+/// the payload.
+fn check_payload_oxum(bag_info: &BagInfo, actual: (u64, u64), report: &mut ValidationReport) {
+    let Some(declared) = bag_info.get("Payload-Oxum") else {
+        return;
+    };
+    let Some(declared) = parse_payload_oxum(declared) else {
+        report.strict_error(
+            RULE_BAG_METADATA,
+            format!("Payload-Oxum \"{}\" is not <octets>.<streams>", declared),
+        );
+        return;
+    };
+
+    if declared != actual {
+        report.error(
+            RULE_BAG_METADATA,
+            format!(
+                "Payload-Oxum mismatch: bag-info.txt says {}.{}, payload is {}.{}",
+                declared.0, declared.1, actual.0, actual.1
+            ),
+        );
+    }
+}
+
+/// Parses `<octets>.<streams>`. Compared as numbers, so leading zeros don't matter.
+fn parse_payload_oxum(value: &str) -> Option<(u64, u64)> {
+    let (bytes, count) = value.split_once('.')?;
+    Some((bytes.parse().ok()?, count.parse().ok()?))
+}
+
+/// Payload-Oxum as bag-info.txt declares it and as `data/` holds it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OxumCheck {
+    /// `(octets, streams)`, or `None` if bag-info.txt has no readable Payload-Oxum.
+    pub declared: Option<(u64, u64)>,
+    pub actual: (u64, u64),
+}
+
+impl OxumCheck {
+    pub fn matches(&self) -> bool {
+        self.declared == Some(self.actual)
+    }
+}
+
+/// Sums the sizes and count of the files in `data/` and compares them with
+/// Payload-Oxum, without hashing anything: a sanity check in seconds after moving a
+/// large bag. Files only listed in fetch.txt aren't there to count.
+pub fn verify_oxum(bag: &Path) -> Result<OxumCheck, BagError> {
+    if !bag.join("bagit.txt").is_file() {
+        return Err(BagError::NotABag);
+    }
+    let declared = match read_tag_file(&bag.join("bag-info.txt")) {
+        Ok((content, _)) => BagInfo::parse(&content)
+            .get("Payload-Oxum")
+            .and_then(parse_payload_oxum),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+        Err(e) => return Err(e.into()),
+    };
+    Ok(OxumCheck {
+        declared,
+        actual: payload_oxum(&Folder(bag)),
+    })
+}
+
+/// Total size and number of payload files, from metadata alone.
+fn payload_oxum(files: &dyn BagFiles) -> (u64, u64) {
+    files
+        .payload()
+        .iter()
+        .fold((0, 0), |(bytes, count), (_, size)| {
+            (bytes + size, count + 1)
+        })
+}
+
+/// Checks an existing bag: recomputes SHA-256 manifest and tagmanifest checksums and
+/// compares Payload-Oxum. How formatting quirks are judged depends on the mode.
+pub fn validate_bag(
+    path: &Path,
+    options: &ValidationOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<ValidationReport, BagError> {
+    if !path.is_dir() {
+        return Err(BagError::NotADirectory);
+    }
+    validate_files(&Folder(path), path, options, progress_tx)
+}
+
+/// [`validate_bag`] for a bag whose files come from `files`; `path` is what the
+/// report names.
+pub fn validate_files(
+    files: &dyn BagFiles,
+    path: &Path,
+    options: &ValidationOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<ValidationReport, BagError> {
+    let started = std::time::Instant::now();
+    let mut report = ValidationReport {
+        path: path.to_path_buf(),
+        mode: options.mode,
+        completeness_only: options.completeness_only,
+        ..ValidationReport::default()
+    };
+
+    // The bag declaration itself is always UTF-8
+    let Some(bagit) = read_tag_checked(
+        files,
+        "bagit.txt",
+        RULE_BAG_DECLARATION,
+        TagEncoding::Utf8,
+        &mut report,
+    )?
+    else {
+        return Err(BagError::NotABag);
+    };
+    if let Some(problem) = files.serialization_problem() {
+        report.error(RULE_SERIALIZATION, problem);
+    }
+    check_bag_declaration(&bagit, &mut report);
+    let encoding = declared_encoding(&bagit, &mut report);
+    let encoded = tag_value(&bagit, "BagIt-Version").is_some_and(encodes_paths);
+
+    // Every payload and tag manifest, by algorithm. Ones in algorithms baggie can't
+    // compute are noted and skipped.
+    let mut manifests = Vec::new();
+    let mut tagmanifests = Vec::new();
+    let mut unchecked_payload_manifest = false;
+    let mut top_level = files.top_level();
+    top_level.sort();
+    for name in &top_level {
+        let (payload, rule, suffix) = if let Some(rest) = name.strip_prefix("manifest-") {
+            (true, RULE_PAYLOAD_MANIFEST, rest)
+        } else if let Some(rest) = name.strip_prefix("tagmanifest-") {
+            (false, RULE_TAG_MANIFEST, rest)
+        } else {
+            continue;
+        };
+        let Some(algorithm) = suffix.strip_suffix(".txt") else {
+            continue;
+        };
+        let Some(algorithm) = ChecksumAlgorithm::from_name(algorithm) else {
+            unchecked_payload_manifest |= payload;
+            report.warning(
+                rule,
+                format!(
+                    "{} is in an algorithm baggie can't check, so it was skipped",
+                    name
+                ),
+            );
+            continue;
+        };
+        let Some(content) = read_tag_checked(files, name, rule, encoding, &mut report)? else {
+            continue;
+        };
+        let entries = parse_manifest(&content, name, rule, encoded, &mut report);
+        let entries = reject_unsafe_paths(entries, payload, rule, &mut report);
+        match payload {
+            true => manifests.push((algorithm, entries)),
+            false => tagmanifests.push((algorithm, entries)),
+        }
+    }
+    report.algorithms = manifests.iter().map(|(algorithm, _)| *algorithm).collect();
+    if manifests.is_empty() {
+        let message = match unchecked_payload_manifest {
+            true => "No payload manifest is in an algorithm baggie can check (md5, sha1, sha256, sha512 or blake3)",
+            false => "No payload manifest (manifest-<algorithm>.txt)",
+        };
+        report.error(RULE_PAYLOAD_MANIFEST, message);
+    }
+
+    // Files fetch.txt lists that aren't in the bag, with the local copy (file: URL)
+    // to check instead when there is one
+    let present: std::collections::HashSet<String> =
+        files.payload().into_iter().map(|(file, _)| file).collect();
+    let mut fetched = HashMap::new();
+    let mut fetched_oxum = Some((0, 0));
+    if let Some(Ok(bytes)) = files.read(FETCH_FILE) {
+        let content = decode_tag_checked(&bytes, FETCH_FILE, RULE_FETCH, encoding, &mut report);
+        for entry in parse_fetch_file(&content, encoded) {
+            if present.contains(&entry.file) {
+                continue;
+            }
+            fetched_oxum = fetched_oxum
+                .zip(entry.size)
+                .map(|((bytes, count), size)| (bytes + size, count + 1));
+            fetched.insert(entry.file.clone(), entry.local_copy());
+        }
+    }
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started {
+            total_files: manifests
+                .iter()
+                .chain(&tagmanifests)
+                .map(|(_, entries)| entries.len())
+                .sum(),
+        });
+    }
+
+    // A spot check hashes the same sample of files in every payload manifest, and
+    // only checks the rest are there
+    let sampled = match options.sample {
+        Some(sample) if !options.completeness_only => {
+            let mut population: Vec<&String> = manifests
+                .iter()
+                .flat_map(|(_, e)| e.iter().map(|(_, file)| file))
+                .collect();
+            population.sort();
+            population.dedup();
+            Some((sample, population.len(), sample.choose(&population)))
+        }
+        _ => None,
+    };
+    let present_only = ValidationOptions {
+        completeness_only: true,
+        ..options.clone()
+    };
+
+    let not_fetched = HashMap::new();
+    for (algorithm, entries, rule, fetched) in manifests
+        .iter()
+        .map(|(a, e)| (a, e, RULE_PAYLOAD_MANIFEST, &fetched))
+        .chain(
+            tagmanifests
+                .iter()
+                .map(|(a, e)| (a, e, RULE_TAG_MANIFEST, &not_fetched)),
+        )
+    {
+        let (hashed, rest): (Vec<_>, Vec<_>) = match &sampled {
+            Some((_, _, chosen)) if rule == RULE_PAYLOAD_MANIFEST => entries
+                .iter()
+                .cloned()
+                .partition(|(_, file)| chosen.contains(file)),
+            _ => (entries.clone(), Vec::new()),
+        };
+        for (entries, options) in [(&hashed, options), (&rest, &present_only)] {
+            verify_manifest_entries(
+                files,
+                entries,
+                *algorithm,
+                rule,
+                options,
+                fetched,
+                &mut report,
+                &progress_tx,
+            );
+        }
+    }
+    if let Some((sample, population, chosen)) = sampled {
+        let failed: std::collections::HashSet<&str> = report
+            .errors
+            .iter()
+            .filter(|e| {
+                matches!(
+                    e.kind,
+                    FindingKind::ChecksumMismatch | FindingKind::UnreadableFile
+                )
+            })
+            .filter_map(|e| e.file.as_deref())
+            .filter(|file| chosen.contains(*file))
+            .collect();
+        report.sample = Some(SampleSummary {
+            percent: sample.percent,
+            seed: sample.seed,
+            hashed: chosen.len(),
+            population,
+            failed: failed.len(),
+        });
+    }
+    let listed: Vec<(String, String)> = manifests
+        .into_iter()
+        .flat_map(|(_, entries)| entries)
+        .collect();
+    check_unlisted_payload(files, &listed, encoded, &mut report);
+    check_unlisted_tag_files(files, &tagmanifests, &mut report);
+
+    if let Some(bag_info) = read_tag_checked(
+        files,
+        "bag-info.txt",
+        RULE_BAG_METADATA,
+        encoding,
+        &mut report,
+    )? {
+        check_bag_metadata_format(&bag_info, &mut report);
+        // Unknown sizes in fetch.txt leave nothing to compare against
+        if let Some(fetched_oxum) = fetched_oxum {
+            let on_hand = payload_oxum(files);
+            let actual = (on_hand.0 + fetched_oxum.0, on_hand.1 + fetched_oxum.1);
+            let bag_info = BagInfo::parse(&bag_info);
+            check_payload_oxum(&bag_info, actual, &mut report);
+            report.oxum = Some(OxumCheck {
+                declared: bag_info.get("Payload-Oxum").and_then(parse_payload_oxum),
+                actual,
+            });
+        }
+    }
+    if let Some(profile) = &options.profile {
+        for problem in profile.check_bag(files) {
+            report.error(RULE_PROFILE, problem);
+        }
+    }
+    match check_signature(
+        files,
+        options.minisign_key.as_deref(),
+        options.gnupg_home.as_deref(),
+    ) {
+        SignatureCheck::Unsigned => {}
+        SignatureCheck::Good(signer) => report.signed_by = Some(signer),
+        SignatureCheck::Bad(message) => report.error(RULE_SIGNATURE, message),
+        SignatureCheck::Unchecked(message) => report.warning(RULE_SIGNATURE, message),
+    }
+    report.duration = started.elapsed();
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Validated {
+            report: Box::new(report.clone()),
+        });
+    }
+
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_sender_ids() {
+        let uuid = IdScheme::Uuid.generate(1);
+        assert_eq!(uuid.len(), 36);
+        assert_eq!(&uuid[14..15], "4");
+        assert_ne!(uuid, IdScheme::Uuid.generate(1));
+        let ulid = IdScheme::Ulid.generate(1);
+        assert_eq!(ulid.len(), 26);
+        assert!(ulid
+            .chars()
+            .all(|c| c.is_ascii_digit() || c.is_ascii_uppercase()));
+        assert_eq!(
+            IdScheme::Sequence("ACC-".to_string()).generate(42),
+            "ACC-000042"
+        );
+
+        let temp_dir = std::env::temp_dir().join("bagit_test_sender_id");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("a.txt"), "a").unwrap();
+        let options = BagOptions {
+            id_scheme: IdScheme::Sequence("ACC-".to_string()),
+            id_sequence_next: 7,
+            ..BagOptions::default()
+        };
+        bag_directory(&temp_dir, &options, None).unwrap();
+        let summary = BagSummary::read(&temp_dir).unwrap();
+        assert_eq!(summary.sender_id, "ACC-000007");
+        assert!(summary
+            .to_label_text()
+            .ends_with("Internal-Sender-Identifier: ACC-000007\n"));
+
+        // Updating keeps the identifier the bag already has
+        update_bag(&temp_dir, &BagOptions::default(), None).unwrap();
+        assert_eq!(BagSummary::read(&temp_dir).unwrap().sender_id, "ACC-000007");
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_bag_directory() {
+        // Create a temporary directory
+        let temp_dir = std::env::temp_dir().join("bagit_test");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+
+        // Create test files
+        fs::write(temp_dir.join("file1.txt"), "Hello, world!").unwrap();
+        fs::write(temp_dir.join("file2.txt"), "Test content").unwrap();
+        fs::create_dir(temp_dir.join("subdir")).unwrap();
+        fs::write(temp_dir.join("subdir").join("nested.txt"), "Nested file").unwrap();
+
+        // Bag the directory
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+
+        // Verify structure
+        assert!(temp_dir.join("data").exists());
+        assert!(temp_dir.join("data").join("file1.txt").exists());
+        assert!(temp_dir.join("data").join("file2.txt").exists());
+        assert!(temp_dir.join("data").join("subdir").join("nested.txt").exists());
+        assert!(temp_dir.join("bagit.txt").exists());
+        assert!(temp_dir.join("manifest-sha256.txt").exists());
+        assert!(temp_dir.join("bag-info.txt").exists());
+        assert!(temp_dir.join("tagmanifest-sha256.txt").exists());
+
+        // Verify bagit.txt content
+        let bagit_content = fs::read_to_string(temp_dir.join("bagit.txt")).unwrap();
+        assert!(bagit_content.contains("BagIt-Version: 1.0"));
+        assert!(bagit_content.contains("Tag-File-Character-Encoding: UTF-8"));
+
+        // Verify manifest has correct format and file count
+        let manifest = fs::read_to_string(temp_dir.join("manifest-sha256.txt")).unwrap();
+        assert!(manifest.contains("data/file1.txt"));
+        assert!(manifest.contains("data/file2.txt"));
+        assert!(manifest.contains("data/subdir/nested.txt"));
+        assert_eq!(manifest.lines().count(), 3);
+
+        // Cleanup
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_already_a_bag() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_already_bag");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("bagit.txt"), "existing").unwrap();
+
+        let result = bag_directory(&temp_dir, &BagOptions::default(), None);
+        assert!(matches!(
+            result,
+            Err(BagError::AlreadyABag(BagLikeReason::Declaration))
+        ));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_data_folder_is_bag_like() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_data_folder");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(temp_dir.join("data")).unwrap();
+        fs::write(temp_dir.join("data/file.txt"), "content").unwrap();
+
+        let result = bag_directory(&temp_dir, &BagOptions::default(), None);
+        assert!(matches!(
+            result,
+            Err(BagError::AlreadyABag(BagLikeReason::DataFolder))
+        ));
+        assert!(!temp_dir.join("bagit.txt").exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_bag_into_new_destination() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_bag_into");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("source");
+        fs::create_dir_all(source.join("data")).unwrap();
+        fs::write(source.join("data/file.txt"), "content").unwrap();
+        fs::write(source.join("notes.txt"), "notes").unwrap();
+        let dest = temp_dir.join("bag");
+
+        bag_into(&source, &dest, &BagOptions::default(), None).unwrap();
+
+        // Source untouched; its data/ folder is just payload in the copy
+        assert!(source.join("data/file.txt").exists());
+        assert!(!source.join("bagit.txt").exists());
+        assert!(dest.join("data/data/file.txt").exists());
+        assert!(dest.join("data/notes.txt").exists());
+        let report = validate_bag(&dest, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // A non-empty destination is refused
+        let result = bag_into(&source, &dest, &BagOptions::default(), None);
+        assert!(matches!(result, Err(BagError::DestinationNotEmpty(_))));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_bag_several_sources() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_bag_sources");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let raw = temp_dir.join("scanner/raw");
+        let more_raw = temp_dir.join("camera/raw");
+        let docs = temp_dir.join("docs");
+        for dir in [&raw, &more_raw, &docs] {
+            fs::create_dir_all(dir).unwrap();
+        }
+        fs::write(raw.join("page1.tif"), "page").unwrap();
+        fs::write(raw.join(".DS_Store"), "junk").unwrap();
+        fs::write(more_raw.join("IMG_0001.CR2"), "photo").unwrap();
+        fs::write(docs.join("readme.txt"), "notes").unwrap();
+        let mut sources = payload_sources(vec![raw.clone(), more_raw, docs]);
+        let names: Vec<_> = sources.iter().map(|s| s.name.as_str()).collect();
+        assert_eq!(names, ["raw", "raw_2", "docs"]);
+        sources[2].name = "documentation".to_string();
+
+        let dest = temp_dir.join("bag");
+        let report = bag_sources(&sources, &dest, &BagOptions::default(), None).unwrap();
+        assert!(dest.join("data/raw/page1.tif").exists());
+        assert!(dest.join("data/raw_2/IMG_0001.CR2").exists());
+        assert!(dest.join("data/documentation/readme.txt").exists());
+        assert_eq!(report.skipped, vec![PathBuf::from("raw/.DS_Store")]);
+        assert!(raw.join("page1.tif").exists());
+        let report = validate_bag(&dest, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // Names must be single, distinct folder names
+        sources[1].name = "RAW".to_string();
+        let result = bag_sources(
+            &sources,
+            &temp_dir.join("clash"),
+            &BagOptions::default(),
+            None,
+        );
+        assert!(matches!(result, Err(BagError::BadSourceName(name)) if name == "RAW"));
+        sources[1].name = "raw/2".to_string();
+        let result = bag_sources(
+            &sources,
+            &temp_dir.join("nested"),
+            &BagOptions::default(),
+            None,
+        );
+        assert!(matches!(result, Err(BagError::BadSourceName(_))));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_copy_inside_source() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_copy_inside_source");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(temp_dir.join("sub")).unwrap();
+        fs::write(temp_dir.join("sub/deep.txt"), "deep").unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+
+        // The copy isn't walked as it's written
+        let dest = temp_dir.join("copy");
+        let report = bag_into(&temp_dir, &dest, &BagOptions::default(), None).unwrap();
+        assert_eq!(report.bag.payload_oxum, (11, 2));
+        assert!(!dest.join("data/copy").exists());
+        let report = validate_bag(&dest, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_plugins_add_tag_files() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = std::env::temp_dir().join("bagit_test_plugins");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("source");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "a").unwrap();
+        fs::write(source.join("b.txt"), "b").unwrap();
+        let script = |name: &str, body: &str| {
+            let path = temp_dir.join(name);
+            fs::write(&path, format!("#!/bin/sh\n{}\n", body)).unwrap();
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o755)).unwrap();
+            path.to_string_lossy().to_string()
+        };
+        let count = script(
+            "count.sh",
+            "mkdir -p \"$BAGGIE_TAG_DIR/metadata\"\n\
+             wc -l > \"$BAGGIE_TAG_DIR/metadata/count.txt\"\n\
+             # The manifest and bag-info.txt are written, but the bag isn't sealed yet\n\
+             test -f \"$BAGGIE_STAGED/bag-info.txt\" && test ! -f \"$BAGGIE_BAG/bagit.txt\" &&\n\
+             echo \"saw $BAGGIE_STAGE\"",
+        );
+        let refuse = script("refuse.sh", "echo 'no licence file' >&2\nexit 3");
+
+        let mut options = BagOptions::default();
+        options.plugins.push(Plugin {
+            stage: PluginStage::BeforeSealing,
+            command: count.clone(),
+        });
+        let dest = temp_dir.join("bag");
+        let report = bag_into(&source, &dest, &options, None).unwrap();
+        assert_eq!(
+            report.warnings,
+            vec![format!("{}: saw before-sealing", count)]
+        );
+        assert_eq!(
+            fs::read_to_string(dest.join("metadata/count.txt"))
+                .unwrap()
+                .trim(),
+            "2"
+        );
+        let tagmanifest = fs::read_to_string(dest.join("tagmanifest-sha256.txt")).unwrap();
+        assert!(tagmanifest.contains("  metadata/count.txt\n"));
+        let validation = validate_bag(&dest, &ValidationOptions::default(), None).unwrap();
+        assert!(validation.is_valid(), "{:?}", validation.errors);
+
+        // A failing plugin stops the bag with its message
+        options.plugins = vec![Plugin {
+            stage: PluginStage::AfterEnumeration,
+            command: refuse,
+        }];
+        let result = bag_into(&source, &temp_dir.join("bag2"), &options, None);
+        match result {
+            Err(BagError::PluginFailed { message, .. }) => assert_eq!(message, "no licence file"),
+            other => panic!("expected a plugin failure, got {:?}", other),
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_update_bag_plugin_fails() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = std::env::temp_dir().join("bagit_test_update_plugin_fails");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("bag");
+        fs::create_dir_all(&bag).unwrap();
+        fs::write(bag.join("file.txt"), "content").unwrap();
+        let options = BagOptions {
+            event_log: true,
+            ..BagOptions::default()
+        };
+        bag_directory(&bag, &options, None).unwrap();
+        let events = fs::read_to_string(bag.join(EVENT_LOG_FILE)).unwrap();
+
+        let refuse = temp_dir.join("refuse.sh");
+        fs::write(
+            &refuse,
+            "#!/bin/sh
+echo late > \"$BAGGIE_TAG_DIR/late.txt\"
+echo 'not today' >&2
+exit 1
+",
+        )
+        .unwrap();
+        fs::set_permissions(&refuse, fs::Permissions::from_mode(0o755)).unwrap();
+        let mut failing = options.clone();
+        failing.plugins.push(Plugin {
+            stage: PluginStage::BeforeSealing,
+            command: refuse.to_string_lossy().to_string(),
+        });
+        assert!(matches!(
+            update_bag(&bag, &failing, None),
+            Err(BagError::PluginFailed { .. })
+        ));
+
+        // Nothing of the update reached the bag, so it's as valid as it was
+        let report = validate_bag(&bag, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert_eq!(
+            fs::read_to_string(bag.join(EVENT_LOG_FILE)).unwrap(),
+            events
+        );
+        assert!(!bag.join("late.txt").exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_verify_after_bagging() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let temp_dir = std::env::temp_dir().join("bagit_test_verify_after");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("source");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("a.txt"), "a").unwrap();
+        fs::write(source.join("b.txt"), "b").unwrap();
+
+        let mut options = BagOptions {
+            verify_after: true,
+            ..BagOptions::default()
+        };
+        let (tx, rx) = std::sync::mpsc::channel();
+        let report = bag_into(&source, &temp_dir.join("bag"), &options, Some(tx)).unwrap();
+        let read_back = rx
+            .try_iter()
+            .filter(|p| matches!(p, Progress::Verifying { .. }));
+        assert_eq!(read_back.count(), 2);
+        assert!(report
+            .events
+            .iter()
+            .any(|e| e.event_type == EventType::FixityCheck));
+
+        // A file that changes after it's hashed, as a failing drive might change it
+        let damage = temp_dir.join("damage.sh");
+        fs::write(
+            &damage,
+            "#!/bin/sh\necho damaged > \"$BAGGIE_BAG/data/b.txt\"\n",
+        )
+        .unwrap();
+        fs::set_permissions(&damage, fs::Permissions::from_mode(0o755)).unwrap();
+        options.plugins.push(Plugin {
+            stage: PluginStage::AfterHashing,
+            command: damage.to_string_lossy().to_string(),
+        });
+        let dest = temp_dir.join("damaged");
+        match bag_into(&source, &dest, &options, None) {
+            Err(BagError::VerifyFailed(files)) => assert_eq!(files, vec!["data/b.txt"]),
+            other => panic!("expected the read-back to fail, got {:?}", other),
+        }
+        assert!(!dest.join("bagit.txt").exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_update_bag_keeps_metadata() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_update_bag");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+
+        let mut options = BagOptions::default();
+        options.metadata.push("Contact-Name", "Someone");
+        bag_directory(&temp_dir, &options, None).unwrap();
+        fs::write(temp_dir.join("manifest-md5.txt"), "stale  data/file.txt\n").unwrap();
+
+        // Payload changes after bagging, and an earlier run was interrupted
+        fs::write(temp_dir.join("data/added.txt"), "more").unwrap();
+        fs::create_dir(temp_dir.join(STAGING_DIR)).unwrap();
+        fs::write(temp_dir.join(STAGING_DIR).join("bagit.txt"), "half written").unwrap();
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(!report.is_valid());
+
+        update_bag(&temp_dir, &BagOptions::default(), None).unwrap();
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert!(!temp_dir.join(STAGING_DIR).exists());
+        // Every manifest the bag had is rewritten
+        let md5 = fs::read_to_string(temp_dir.join("manifest-md5.txt")).unwrap();
+        assert!(!md5.contains("stale"));
+        assert_eq!(md5.lines().count(), 2);
+
+        let bag_info = fs::read_to_string(temp_dir.join("bag-info.txt")).unwrap();
+        assert!(bag_info.contains("Contact-Name: Someone"));
+        assert!(bag_info.contains("Payload-Oxum: 11.2"));
+        assert_eq!(bag_info.matches("Bagging-Date").count(), 1);
+
+        // And so do removals
+        fs::remove_file(temp_dir.join("data/file.txt")).unwrap();
+        update_bag(&temp_dir, &BagOptions::default(), None).unwrap();
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        let bag_info = fs::read_to_string(temp_dir.join("bag-info.txt")).unwrap();
+        assert!(bag_info.contains("Payload-Oxum: 4.1"));
+        assert_eq!(bag_info.matches("Bag-Size: 4 bytes\n").count(), 1);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_bag_size() {
+        assert_eq!(bag_size(0), "0 bytes");
+        assert_eq!(bag_size(999), "999 bytes");
+        assert_eq!(bag_size(1000), "1.0 KB");
+        assert_eq!(bag_size(42_300_000_000), "42.3 GB");
+        assert_eq!(bag_size(5_000_000_000_000_000), "5000.0 TB");
+    }
+
+    #[test]
+    fn test_unbag_directory() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_unbag");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let folder = temp_dir.join("scans");
+        fs::create_dir_all(folder.join("box 1")).unwrap();
+        fs::write(folder.join("box 1/page.tif"), "tiff").unwrap();
+        fs::write(folder.join("notes.txt"), "notes").unwrap();
+        let options = BagOptions {
+            event_log: true,
+            ..BagOptions::default()
+        };
+        bag_directory(&folder, &options, None).unwrap();
+
+        unbag_directory(&folder).unwrap();
+        let mut names: Vec<_> = fs::read_dir(&folder)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().to_string())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["box 1", "notes.txt"]);
+        assert_eq!(
+            fs::read_to_string(folder.join("box 1/page.tif")).unwrap(),
+            "tiff"
+        );
+        assert!(matches!(unbag_directory(&folder), Err(BagError::NotABag)));
+
+        // A clash with a file outside data/ leaves the bag as it was
+        bag_directory(&folder, &BagOptions::default(), None).unwrap();
+        fs::write(folder.join("notes.txt"), "other notes").unwrap();
+        assert!(matches!(
+            unbag_directory(&folder),
+            Err(BagError::UnbagConflict(_))
+        ));
+        let report = validate_bag(&folder, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_skips_system_files() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_skip_system");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+        fs::write(temp_dir.join(".DS_Store"), "junk").unwrap();
+        fs::create_dir(temp_dir.join("photos")).unwrap();
+        fs::write(temp_dir.join("photos").join("a.jpg"), "jpeg").unwrap();
+        fs::write(temp_dir.join("photos").join("thumbs.db"), "junk").unwrap();
+        fs::write(temp_dir.join("photos").join("._a.jpg"), "resource fork").unwrap();
+
+        let report = bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+
+        let manifest = fs::read_to_string(temp_dir.join("manifest-sha256.txt")).unwrap();
+        assert_eq!(manifest.lines().count(), 2);
+        assert!(!manifest.contains(".DS_Store"));
+        assert!(!manifest.contains("thumbs.db"));
+        assert!(temp_dir.join("data").join("photos").join("a.jpg").exists());
+        assert!(!temp_dir
+            .join("data")
+            .join("photos")
+            .join("thumbs.db")
+            .exists());
+        assert!(temp_dir.join("photos").join("thumbs.db").exists());
+        assert!(temp_dir.join("photos").join("._a.jpg").exists());
+        assert_eq!(report.skipped.len(), 3);
+        assert!(report.skipped.contains(&PathBuf::from(".DS_Store")));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_skips_appledouble_by_content() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_appledouble");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("usb");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("photo.jpg"), "jpeg").unwrap();
+        let mut shadow = APPLEDOUBLE_MAGIC.to_vec();
+        shadow.extend(b"\x00\x02\x00\x00resource fork");
+        fs::write(source.join("._photo.jpg"), shadow).unwrap();
+        fs::write(source.join("._notes.txt"), "a real file with an odd name").unwrap();
+        // `._*` taken off the skip list doesn't bring the shadows back
+        let options = BagOptions {
+            skip_names: Vec::new(),
+            ..BagOptions::default()
+        };
+
+        let copy = temp_dir.join("copy");
+        let report = bag_into(&source, &copy, &options, None).unwrap();
+        assert_eq!(report.skipped, vec![PathBuf::from("._photo.jpg")]);
+        assert!(copy.join("data/._notes.txt").exists());
+        assert!(!copy.join("data/._photo.jpg").exists());
+
+        let options = BagOptions {
+            skip_appledouble: false,
+            ..options
+        };
+        let copy = temp_dir.join("everything");
+        bag_into(&source, &copy, &options, None).unwrap();
+        assert!(copy.join("data/._photo.jpg").exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_symlink_loop_and_depth_limit() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_symlink_loop");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("project");
+        fs::create_dir_all(source.join("a/b")).unwrap();
+        fs::write(source.join("a/b/notes.txt"), "notes").unwrap();
+        std::os::unix::fs::symlink("..", source.join("a/b/up")).unwrap();
+
+        let options = BagOptions {
+            follow_symlinks: true,
+            ..BagOptions::default()
+        };
+        let result = bag_into(&source, &temp_dir.join("followed"), &options, None);
+        match result {
+            Err(BagError::CycleDetected(path)) => assert_eq!(path, source.join("a/b/up")),
+            other => panic!("expected a cycle, got {:?}", other),
+        }
+
+        fs::remove_file(source.join("a/b/up")).unwrap();
+        let options = BagOptions {
+            max_depth: 2,
+            ..BagOptions::default()
+        };
+        let result = bag_into(&source, &temp_dir.join("shallow"), &options, None);
+        match result {
+            Err(BagError::TooDeep { path, max_depth: 2 }) => {
+                assert_eq!(path, source.join("a/b/notes.txt"))
+            }
+            other => panic!("expected too deep, got {:?}", other),
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_clone_keeps_modification_time() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_clone_file");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(&temp_dir).unwrap();
+        let source = temp_dir.join("scan.tif");
+        fs::write(&source, "tiff").unwrap();
+        let modified = UNIX_EPOCH + Duration::from_secs(1_000_000_000);
+        File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(modified)
+            .unwrap();
+
+        let dest = temp_dir.join("clone.tif");
+        match clone_file(&source, &dest, &[]) {
+            Ok(copied) => {
+                assert_eq!(copied, 4);
+                assert_eq!(fs::read(&dest).unwrap(), b"tiff");
+                assert_eq!(fs::metadata(&dest).unwrap().modified().unwrap(), modified);
+            }
+            // Where the filesystem can't clone, nothing is left behind
+            Err(_) => assert!(!dest.exists()),
+        }
+
+        // Bagging a copy gets there either way
+        let dest = temp_dir.join("copy.tif");
+        copy_payload_file(&source, &dest, &BagOptions::default()).unwrap();
+        assert_eq!(fs::read(&dest).unwrap(), b"tiff");
+        assert_eq!(fs::metadata(&dest).unwrap().modified().unwrap(), modified);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_copies_keep_xattrs() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_copy_xattrs");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("downloads");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("report.pdf"), "pdf").unwrap();
+        if xattr::set(source.join("report.pdf"), "user.xdg.tags", b"red").is_err() {
+            // Nothing to keep where the filesystem has no extended attributes
+            fs::remove_dir_all(&temp_dir).unwrap();
+            return;
+        }
+        // Only macOS lets a file have an attribute outside the user namespace
+        let quarantined = xattr::set(source.join("report.pdf"), QUARANTINE_XATTR, b"0081").is_ok();
+        let options = BagOptions {
+            strip_quarantine: true,
+            ..BagOptions::default()
+        };
+
+        let copy = temp_dir.join("copy");
+        bag_into(&source, &copy, &options, None).unwrap();
+        let copied = copy.join("data/report.pdf");
+        assert_eq!(
+            xattr::get(&copied, "user.xdg.tags").unwrap(),
+            Some(b"red".to_vec())
+        );
+        if quarantined {
+            assert_eq!(xattr::get(&copied, QUARANTINE_XATTR).unwrap(), None);
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_skip_list_override() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_skip_override");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join(".DS_Store"), "kept on purpose").unwrap();
+
+        let options = BagOptions {
+            skip_names: Vec::new(),
+            ..BagOptions::default()
+        };
+        let report = bag_directory(&temp_dir, &options, None).unwrap();
+
+        assert!(report.skipped.is_empty());
+        assert!(temp_dir.join("data").join(".DS_Store").exists());
+
+        assert!(glob_match("*.TMP", "report.tmp"));
+        assert!(glob_match("draft?.doc", "draft2.doc"));
+        assert!(glob_match("a*b*c", "axxbyybc"));
+        assert!(!glob_match("draft?.doc", "draft.doc"));
+        assert!(!glob_match("*.tmp", "tmp"));
+        assert!(!glob_match(".DS_Store", ".DS_Store.bak"));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_copy_and_delete() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_copy_and_delete");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        let source = temp_dir.join("reel.mov");
+        fs::write(&source, "frames").unwrap();
+        let modified = fs::metadata(&source).unwrap().modified().unwrap();
+        let earlier = modified - std::time::Duration::from_secs(86400);
+        File::options()
+            .write(true)
+            .open(&source)
+            .unwrap()
+            .set_modified(earlier)
+            .unwrap();
+
+        let dest = temp_dir.join("moved.mov");
+        copy_and_delete(&source, &dest).unwrap();
+        assert!(!source.exists());
+        assert_eq!(fs::read_to_string(&dest).unwrap(), "frames");
+        assert_eq!(fs::metadata(&dest).unwrap().modified().unwrap(), earlier);
+
+        // A source that's gone is reported as such, for the vanished-file policy
+        let err = copy_and_delete(&source, &dest).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::NotFound);
+        assert!(dest.exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_vanished_after_walk() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_vanished");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("goes.txt"), "gone soon").unwrap();
+
+        let entries: Vec<_> = WalkDir::new(&temp_dir)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(|e| e.ok())
+            .collect();
+        // Simulate a sync client removing the file between the walk and the move
+        fs::remove_file(temp_dir.join("goes.txt")).unwrap();
+        let data_dir = temp_dir.join("data");
+        fs::create_dir(&data_dir).unwrap();
+
+        let mut report = BagReport::default();
+        let result = move_payload(
+            &temp_dir,
+            &data_dir,
+            entries.clone(),
+            &BagOptions::default(),
+            &mut report,
+            &None,
+            None,
+        );
+        match result {
+            Err(BagError::FileVanished(p)) => assert_eq!(p, PathBuf::from("goes.txt")),
+            other => panic!("expected FileVanished, got {:?}", other),
+        }
+
+        let options = BagOptions {
+            error_policy: ErrorPolicy::Skip,
+            ..BagOptions::default()
+        };
+        let mut report = BagReport::default();
+        move_payload(
+            &temp_dir,
+            &data_dir,
+            entries,
+            &options,
+            &mut report,
+            &None,
+            None,
+        )
+        .unwrap();
+        assert_eq!(report.vanished, vec![PathBuf::from("goes.txt")]);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_unreadable_file_policy() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_unreadable");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("source");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("fine.txt"), "fine").unwrap();
+        // Tests run as root, so a socket stands in for a file that can't be opened
+        let _listener = std::os::unix::net::UnixListener::bind(source.join("locked")).unwrap();
+
+        let result = bag_directory(&source, &BagOptions::default(), None);
+        match result {
+            Err(BagError::Unreadable(p, _)) => assert_eq!(p, PathBuf::from("locked")),
+            other => panic!("expected Unreadable, got {:?}", other),
+        }
+        // Stopped before anything moved
+        assert!(source.join("fine.txt").exists());
+        assert!(!source.join("data").exists());
+
+        let options = BagOptions {
+            error_policy: ErrorPolicy::Skip,
+            ..BagOptions::default()
+        };
+        let dest = temp_dir.join("bag");
+        let report = bag_into(&source, &dest, &options, None).unwrap();
+        assert!(report
+            .warnings
+            .iter()
+            .any(|w| w.starts_with("locked couldn't be read")));
+        assert_eq!(report.bag.payload_oxum, (4, 1));
+        assert!(!dest.join("data/locked").exists());
+        let report = validate_bag(&dest, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn test_locked_file_policy() {
+        // ERROR_SHARING_VIOLATION, as renaming a file open in Word gives
+        let locked = || io::Error::from_raw_os_error(32);
+        let mut report = BagReport::default();
+        let result = handle_unreadable(
+            locked(),
+            Path::new("letter.docx"),
+            &BagOptions::default(),
+            &mut report,
+        );
+        assert!(matches!(result, Err(BagError::FileLocked(_))));
+
+        let options = BagOptions {
+            error_policy: ErrorPolicy::Skip,
+            ..BagOptions::default()
+        };
+        handle_unreadable(locked(), Path::new("letter.docx"), &options, &mut report).unwrap();
+        assert!(report.warnings[0].contains("open in another program"));
+    }
+
+    #[test]
+    fn test_check_free_space() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_free_space");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(&temp_dir).unwrap();
+
+        // A destination that doesn't exist yet is measured on the volume it'd be made on
+        let dest = temp_dir.join("not/yet/a/bag");
+        check_free_space(&dest, 0).unwrap();
+        match check_free_space(&dest, u64::MAX) {
+            Err(BagError::InsufficientSpace { path, needed, .. }) => {
+                assert_eq!((path, needed), (dest.clone(), u64::MAX))
+            }
+            other => panic!("expected InsufficientSpace, got {:?}", other),
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_duplicate_detection() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_duplicates");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(temp_dir.join("copy")).unwrap();
+        fs::write(temp_dir.join("scan.tif"), "same pixels").unwrap();
+        fs::write(temp_dir.join("copy/scan.tif"), "same pixels").unwrap();
+        fs::write(temp_dir.join("notes.txt"), "different").unwrap();
+        fs::write(temp_dir.join("a.txt"), "").unwrap();
+        fs::write(temp_dir.join("b.txt"), "").unwrap();
+        #[cfg(unix)]
+        fs::hard_link(temp_dir.join("notes.txt"), temp_dir.join("notes-link.txt")).unwrap();
+
+        let report = bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+        let scans = Duplicates {
+            files: vec!["data/copy/scan.tif".into(), "data/scan.tif".into()],
+            size: 11,
+            copies: 2,
+        };
+        assert_eq!(scans.wasted(), 11);
+        #[cfg(not(unix))]
+        assert_eq!(report.duplicates, vec![scans]);
+        #[cfg(unix)]
+        {
+            // Hardlinks share content but take no extra space
+            let links = Duplicates {
+                files: vec!["data/notes-link.txt".into(), "data/notes.txt".into()],
+                size: 9,
+                copies: 1,
+            };
+            assert_eq!(links.wasted(), 0);
+            assert_eq!(report.duplicates, vec![scans, links]);
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_deep_nesting() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_deep");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+
+        // ~1,400 characters of relative path: well past Windows' classic MAX_PATH
+        let mut dir = temp_dir.clone();
+        let mut relative = String::from("data");
+        for level in 0..100 {
+            let name = format!("nested-dir-{:02}", level);
+            dir.push(&name);
+            relative.push('/');
+            relative.push_str(&name);
+        }
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("bottom.txt"), "deep").unwrap();
+
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+
+        let manifest = fs::read_to_string(temp_dir.join("manifest-sha256.txt")).unwrap();
+        assert_eq!(manifest.lines().count(), 1);
+        assert!(manifest.contains(&format!("{}/bottom.txt", relative)));
+        assert!(!temp_dir.join("nested-dir-00").exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_wide_tree() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_wide");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        for d in 0..50 {
+            let dir = temp_dir.join(format!("dir{}", d));
+            fs::create_dir(&dir).unwrap();
+            for f in 0..40 {
+                fs::write(dir.join(format!("file{}.txt", f)), format!("{}-{}", d, f)).unwrap();
+            }
+        }
+
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+
+        let manifest = fs::read_to_string(temp_dir.join("manifest-sha256.txt")).unwrap();
+        assert_eq!(manifest.lines().count(), 2000);
+        let bag_info = fs::read_to_string(temp_dir.join("bag-info.txt")).unwrap();
+        assert!(bag_info.contains(".2000\n"));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_round_trip() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_validate");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+
+        let strict = ValidationOptions {
+            mode: ValidationMode::Strict,
+            ..ValidationOptions::default()
+        };
+        let report = validate_bag(&temp_dir, &strict, None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert!(report.warnings.is_empty());
+
+        fs::write(temp_dir.join("data").join("file.txt"), "tampered").unwrap();
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(!report.is_valid());
+        assert!(report
+            .errors
+            .iter()
+            .any(|e| e.message.contains("Checksum mismatch") && e.rule == RULE_PAYLOAD_MANIFEST));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_every_algorithm() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_validate_algorithms");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+
+        // An MD5 manifest alongside the SHA-256 one, as bags from older tools carry
+        let payload = temp_dir.join("data/file.txt");
+        let md5 = calculate_digest(&payload, ChecksumAlgorithm::Md5).unwrap();
+        assert_eq!(md5, "9a0364b9e99bb480dd25e1f0284c8555");
+        fs::write(
+            temp_dir.join("manifest-md5.txt"),
+            format!("{}  data/file.txt\n", md5),
+        )
+        .unwrap();
+        fs::write(
+            temp_dir.join("manifest-crc32.txt"),
+            "00000000  data/file.txt\n",
+        )
+        .unwrap();
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert!(report
+            .warnings
+            .iter()
+            .any(|w| w.message.contains("manifest-crc32.txt")));
+
+        fs::write(
+            temp_dir.join("manifest-md5.txt"),
+            "00000000000000000000000000000000  data/file.txt\n",
+        )
+        .unwrap();
+        let (tx, rx) = std::sync::mpsc::channel();
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), Some(tx)).unwrap();
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].algorithm, ChecksumAlgorithm::Md5);
+        assert_eq!(report.mismatches[0].actual, md5);
+        let checked: Vec<(String, bool)> = rx
+            .try_iter()
+            .filter_map(|p| match p {
+                Progress::FileChecked { filename, passed } => Some((filename, passed)),
+                _ => None,
+            })
+            .collect();
+        assert!(checked.contains(&("data/file.txt".to_string(), false)));
+        assert!(checked.contains(&("data/file.txt".to_string(), true)));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_bag_in_other_algorithms() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_algorithms");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        for algorithm in ChecksumAlgorithm::ALL {
+            let bag = temp_dir.join(algorithm.name());
+            fs::create_dir_all(&bag).unwrap();
+            fs::write(bag.join("file.txt"), "content").unwrap();
+            let options = BagOptions {
+                algorithms: vec![algorithm],
+                ..BagOptions::default()
+            };
+            bag_directory(&bag, &options, None).unwrap();
+            assert!(bag.join(algorithm.manifest_name()).is_file());
+            assert!(bag.join(algorithm.tagmanifest_name()).is_file());
+            assert_eq!(bag_algorithm(&bag), Some(algorithm));
+            let (read, digests) = read_payload_manifest(&bag).unwrap();
+            assert_eq!(read, algorithm);
+            assert_eq!(
+                digests["data/file.txt"],
+                calculate_digest(&bag.join("data/file.txt"), algorithm).unwrap()
+            );
+            let report = validate_bag(&bag, &ValidationOptions::default(), None).unwrap();
+            assert!(report.is_valid(), "{:?}", report.errors);
+
+            // Updating keeps the bag's algorithms, whatever the options say
+            update_bag(&bag, &BagOptions::default(), None).unwrap();
+            assert_eq!(bag_algorithm(&bag), Some(algorithm));
+            let manifests = fs::read_dir(&bag)
+                .unwrap()
+                .filter(|e| {
+                    let name = e.as_ref().unwrap().file_name();
+                    name.to_string_lossy().contains("manifest-")
+                })
+                .count();
+            assert_eq!(manifests, 2);
+        }
+        let md5 = fs::read_to_string(temp_dir.join("md5/manifest-md5.txt")).unwrap();
+        assert_eq!(md5, "9a0364b9e99bb480dd25e1f0284c8555  data/file.txt\n");
+        assert_eq!(
+            calculate_digest_str("", ChecksumAlgorithm::Blake3),
+            "af1349b9f5f9a1a6a0404dea36dcc9499bcb25c9adc112b7cc9a93cae41f3262"
+        );
+
+        // Several at once, as APTrust wants
+        let both = temp_dir.join("both");
+        fs::create_dir_all(&both).unwrap();
+        fs::write(both.join("file.txt"), "content").unwrap();
+        let options = BagOptions {
+            algorithms: vec![ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha256],
+            ..BagOptions::default()
+        };
+        bag_directory(&both, &options, None).unwrap();
+        for algorithm in [ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha256] {
+            let tagmanifest = fs::read_to_string(both.join(algorithm.tagmanifest_name())).unwrap();
+            assert!(tagmanifest.contains("  manifest-md5.txt\n"));
+            assert!(tagmanifest.contains("  manifest-sha256.txt\n"));
+        }
+        assert_eq!(
+            fs::read_to_string(both.join("manifest-md5.txt")).unwrap(),
+            md5
+        );
+        update_bag(&both, &BagOptions::default(), None).unwrap();
+        assert_eq!(
+            bag_algorithms(&both),
+            [ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha256]
+        );
+        let report = validate_bag(&both, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_bagit_1_0_encodes_paths() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_bagit_1_0");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        assert_eq!(encode_manifest_path("a%b\r\nc"), "a%25b%0D%0Ac");
+        assert_eq!(decode_manifest_path("a%25b%0d%0Ac%zz"), "a%b\r\nc%zz");
+
+        let bag = temp_dir.join("v1");
+        fs::create_dir_all(&bag).unwrap();
+        fs::write(bag.join("100% done.txt"), "x").unwrap();
+        #[cfg(unix)]
+        fs::write(bag.join("line\nbreak.txt"), "y").unwrap();
+        bag_directory(&bag, &BagOptions::default(), None).unwrap();
+        let manifest = fs::read_to_string(bag.join("manifest-sha256.txt")).unwrap();
+        assert!(manifest.contains("  data/100%25 done.txt\n"));
+        #[cfg(unix)]
+        assert!(manifest.contains("  data/line%0Abreak.txt\n"));
+        let report = validate_bag(&bag, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        let (_, digests) = read_payload_manifest(&bag).unwrap();
+        assert!(digests.contains_key("data/100% done.txt"));
+
+        // Tag files baggie didn't write are covered too, once the bag is rewritten
+        fs::write(bag.join("mets.xml"), "<mets/>").unwrap();
+        fs::create_dir(bag.join("metadata")).unwrap();
+        fs::write(bag.join("metadata/notes.txt"), "notes").unwrap();
+        fs::write(bag.join("metadata/50% off.txt"), "sale").unwrap();
+        update_bag(&bag, &BagOptions::default(), None).unwrap();
+        let tagmanifest = fs::read_to_string(bag.join("tagmanifest-sha256.txt")).unwrap();
+        assert!(tagmanifest.contains("  mets.xml\n"));
+        assert!(tagmanifest.contains("  metadata/notes.txt\n"));
+        assert!(tagmanifest.contains("  metadata/50%25 off.txt\n"));
+        let report = validate_bag(&bag, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // 0.97 bags keep paths as they are
+        let old = temp_dir.join("v0_97");
+        fs::create_dir_all(&old).unwrap();
+        fs::write(old.join("100% done.txt"), "x").unwrap();
+        let options = BagOptions {
+            bagit_version: BagItVersion::V0_97,
+            ..BagOptions::default()
+        };
+        bag_directory(&old, &options, None).unwrap();
+        let bagit = fs::read_to_string(old.join("bagit.txt")).unwrap();
+        assert!(bagit.starts_with("BagIt-Version: 0.97\n"));
+        let manifest = fs::read_to_string(old.join("manifest-sha256.txt")).unwrap();
+        assert!(manifest.contains("  data/100% done.txt\n"));
+        let report = validate_bag(&old, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // ...and can't list a line break, so such a folder is left as it was
+        #[cfg(unix)]
+        {
+            let broken = temp_dir.join("v0_97_broken");
+            fs::create_dir_all(&broken).unwrap();
+            fs::write(broken.join("line\nbreak.txt"), "y").unwrap();
+            let result = bag_directory(&broken, &options, None);
+            assert!(matches!(result, Err(BagError::UnlistablePath(_))));
+            assert!(broken.join("line\nbreak.txt").is_file());
+            assert!(!broken.join("data").exists());
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_tag_manifest_covers_extra_tag_files() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_extra_tag_files");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("scan.tif"), "tif").unwrap();
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+
+        // Added after bagging: valid still, but each is named
+        fs::create_dir(temp_dir.join("metadata")).unwrap();
+        fs::write(temp_dir.join("metadata/marc.xml"), "<record/>").unwrap();
+        fs::write(temp_dir.join("metadata/.DS_Store"), "junk").unwrap();
+        fs::write(temp_dir.join("README"), "About this bag").unwrap();
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        let warnings: Vec<&str> = report.warnings.iter().map(|w| w.message.as_str()).collect();
+        assert_eq!(warnings.len(), 2, "{:?}", warnings);
+        assert!(warnings[1].starts_with("Tag file not in any tag manifest: metadata/marc.xml"));
+
+        update_bag(&temp_dir, &BagOptions::default(), None).unwrap();
+        let tagmanifest = fs::read_to_string(temp_dir.join("tagmanifest-sha256.txt")).unwrap();
+        assert!(tagmanifest.contains("  README\n"));
+        assert!(tagmanifest.contains("  metadata/marc.xml\n"));
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(report.warnings.is_empty(), "{:?}", report.warnings);
+
+        // Strict mode rejects a stray tag file, with tag manifests or without
+        fs::write(temp_dir.join("metadata/x.txt"), "stray").unwrap();
+        let strict = ValidationOptions {
+            mode: ValidationMode::Strict,
+            ..ValidationOptions::default()
+        };
+        let report = validate_bag(&temp_dir, &strict, None).unwrap();
+        assert!(!report.is_valid());
+        assert_eq!(
+            report.files(FindingKind::UnexpectedFile),
+            vec!["metadata/x.txt"]
+        );
+        for algorithm in ChecksumAlgorithm::ALL {
+            let _ = fs::remove_file(temp_dir.join(algorithm.tagmanifest_name()));
+        }
+        let report = validate_bag(&temp_dir, &strict, None).unwrap();
+        assert_eq!(
+            report.files(FindingKind::UnexpectedFile),
+            vec!["README", "metadata/marc.xml", "metadata/x.txt"]
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_tag_directories() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_tag_directories");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("source");
+        fs::create_dir_all(source.join("metadata")).unwrap();
+        fs::create_dir_all(temp_dir.join("dpn-tags/sub")).unwrap();
+        fs::write(source.join("scan.tif"), "tif").unwrap();
+        fs::write(source.join("metadata/marc.xml"), "<record/>").unwrap();
+        fs::write(temp_dir.join("dpn-tags/sub/dpn-info.txt"), "info").unwrap();
+        fs::write(temp_dir.join("dpn-tags/.DS_Store"), "junk").unwrap();
+
+        // Both BagIt versions list them, though 0.97 lists no other extra tag files
+        let options = BagOptions {
+            tag_directories: vec![source.join("metadata"), temp_dir.join("dpn-tags")],
+            bagit_version: BagItVersion::V0_97,
+            ..BagOptions::default()
+        };
+        bag_directory(&source, &options, None).unwrap();
+        let manifest = fs::read_to_string(source.join("manifest-sha256.txt")).unwrap();
+        assert_eq!(manifest.lines().count(), 1);
+        assert!(source.join("metadata/marc.xml").is_file());
+        assert!(source.join("dpn-tags/sub/dpn-info.txt").is_file());
+        assert!(!source.join("dpn-tags/.DS_Store").exists());
+        let tagmanifest = fs::read_to_string(source.join("tagmanifest-sha256.txt")).unwrap();
+        assert!(tagmanifest.contains("  metadata/marc.xml\n"));
+        assert!(tagmanifest.contains("  dpn-tags/sub/dpn-info.txt\n"));
+        let report = validate_bag(&source, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // A folder named data would be taken for the payload
+        fs::create_dir_all(temp_dir.join("data")).unwrap();
+        let options = BagOptions {
+            tag_directories: vec![temp_dir.join("data")],
+            ..BagOptions::default()
+        };
+        let result = bag_into(
+            &temp_dir.join("dpn-tags"),
+            &temp_dir.join("bag"),
+            &options,
+            None,
+        );
+        assert!(matches!(result, Err(BagError::BadTagDirectory(_))));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_verify_oxum() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_verify_oxum");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(&temp_dir).unwrap();
+        assert!(matches!(verify_oxum(&temp_dir), Err(BagError::NotABag)));
+        fs::write(temp_dir.join("a.txt"), "alpha").unwrap();
+        fs::write(temp_dir.join("b.txt"), "bravo!").unwrap();
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+
+        let check = verify_oxum(&temp_dir).unwrap();
+        assert_eq!(check.declared, Some((11, 2)));
+        assert!(check.matches());
+        fs::remove_file(temp_dir.join("data/b.txt")).unwrap();
+        let check = verify_oxum(&temp_dir).unwrap();
+        assert_eq!(check.actual, (5, 1));
+        assert!(!check.matches());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_open_bag() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_report_bag");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("a.txt"), "alpha").unwrap();
+        fs::write(temp_dir.join("b.txt"), "bravo!").unwrap();
+        let options = BagOptions {
+            algorithms: vec![ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha256],
+            ..Default::default()
+        };
+
+        let bag = bag_directory(&temp_dir, &options, None).unwrap().bag;
+        assert_eq!(bag.path, temp_dir);
+        assert_eq!(bag.algorithms, options.algorithms);
+        assert_eq!(bag.payload_oxum, (11, 2));
+        assert_eq!(bag.bag_info.get("Payload-Oxum"), Some("11.2"));
+        let (file, digests) = &bag.manifest[0];
+        assert_eq!(file, "data/a.txt");
+        assert_eq!(
+            digests[1],
+            calculate_sha256(&temp_dir.join("data/a.txt")).unwrap()
+        );
+
+        assert_eq!(
+            (bag.version.as_str(), bag.encoding.as_str()),
+            ("1.0", "UTF-8")
+        );
+        assert!(bag
+            .tag_manifest
+            .iter()
+            .any(|(file, _)| file == "manifest-sha256.txt"));
+
+        // Reading it back gives the same, whichever separator the manifest uses
+        let md5 = temp_dir.join("manifest-md5.txt");
+        fs::write(&md5, fs::read_to_string(&md5).unwrap().replace("  ", " *")).unwrap();
+        let read = Bag::open(&temp_dir).unwrap();
+        assert_eq!((read.version, read.encoding), (bag.version, bag.encoding));
+        assert_eq!(read.algorithms, bag.algorithms);
+        assert_eq!(read.manifest, bag.manifest);
+        assert_eq!(read.tag_algorithms, bag.tag_algorithms);
+        assert_eq!(read.tag_manifest, bag.tag_manifest);
+        assert_eq!(
+            (read.payload_oxum, read.bag_info),
+            (bag.payload_oxum, bag.bag_info)
+        );
+
+        // Tag manifests in other algorithms than the payload manifests keep their own
+        fs::remove_file(temp_dir.join("tagmanifest-md5.txt")).unwrap();
+        let sha1 = temp_dir.join("tagmanifest-sha1.txt");
+        fs::write(
+            &sha1,
+            format!(
+                "{}  bagit.txt\n",
+                calculate_digest_str("x", ChecksumAlgorithm::Sha1)
+            ),
+        )
+        .unwrap();
+        let read = Bag::open(&temp_dir).unwrap();
+        assert_eq!(read.algorithms, options.algorithms);
+        assert_eq!(
+            read.tag_algorithms,
+            [ChecksumAlgorithm::Sha1, ChecksumAlgorithm::Sha256]
+        );
+        let (file, digests) = &read.tag_manifest[0];
+        assert_eq!(file, "bag-info.txt");
+        let sha256 = calculate_sha256(&temp_dir.join("bag-info.txt")).unwrap();
+        assert_eq!(digests, &[(ChecksumAlgorithm::Sha256, sha256)]);
+        let bagit = read
+            .tag_manifest
+            .iter()
+            .find(|(file, _)| file == "bagit.txt")
+            .unwrap();
+        let sha1 = calculate_digest_str("x", ChecksumAlgorithm::Sha1);
+        let sha256 = calculate_sha256(&temp_dir.join("bagit.txt")).unwrap();
+        assert_eq!(
+            bagit.1,
+            [
+                (ChecksumAlgorithm::Sha1, sha1),
+                (ChecksumAlgorithm::Sha256, sha256)
+            ]
+        );
+
+        fs::remove_file(temp_dir.join("bagit.txt")).unwrap();
+        assert!(matches!(Bag::open(&temp_dir), Err(BagError::NotABag)));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_copy_destination() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_copy_destination");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("masters");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("tape.wav"), "audio").unwrap();
+
+        let name = bag_name(DEFAULT_NAME_TEMPLATE, &source, &BagOptions::default());
+        let dest = copy_destination(&source, &name, None);
+        assert_eq!(dest, temp_dir.join("masters_bag"));
+        bag_into(&source, &dest, &BagOptions::default(), None).unwrap();
+        assert!(source.join("tape.wav").is_file());
+        assert!(!source.join("data").exists());
+        assert_eq!(
+            copy_destination(&source, &name, None),
+            temp_dir.join("masters_bag_2")
+        );
+
+        // An empty folder of that name is used as it is
+        fs::create_dir(temp_dir.join("masters_bag_2")).unwrap();
+        assert_eq!(
+            copy_destination(&source, &name, None),
+            temp_dir.join("masters_bag_2")
+        );
+
+        // Elsewhere, under a name from a template
+        let options = BagOptions {
+            algorithms: vec![ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha256],
+            ..BagOptions::default()
+        };
+        let name = bag_name("{folder}-{date}-{algorithm}", &source, &options);
+        let (date, _) = bagging_timestamp(options.date_mode);
+        assert_eq!(name, format!("masters-{}-md5", date));
+        let output = temp_dir.join("outbox");
+        assert_eq!(
+            copy_destination(&source, &name, Some(&output)),
+            output.join(&name)
+        );
+        assert_eq!(
+            bag_name("{folder}/{time}", &source, &options).len(),
+            "masters-".len() + 6
+        );
+        assert_eq!(bag_name("", &source, &options), "masters");
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_tolerates_bom_and_latin1() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_validate_encoding");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
 
-#[derive(Debug, Clone)]
-pub enum Progress {
-    Started { total_files: usize },
-    Moving { current: usize, filename: String },
-    Checksumming { current: usize, filename: String },
-    Done { path: PathBuf },
-    Error { message: String },
-}
+        // Rewrite the tag files the way an older Windows tool might have
+        let mut bagit = b"\xEF\xBB\xBF".to_vec();
+        bagit.extend_from_slice(b"BagIt-Version: 0.97\nTag-File-Character-Encoding: UTF-8\n");
+        fs::write(temp_dir.join("bagit.txt"), bagit).unwrap();
+        fs::write(
+            temp_dir.join("bag-info.txt"),
+            b"Source-Organization: Caf\xE9\nPayload-Oxum: 7.1\n",
+        )
+        .unwrap();
+        fs::remove_file(temp_dir.join("tagmanifest-sha256.txt")).unwrap();
 
-fn calculate_sha256(path: &Path) -> io::Result<String> {
-    let file = File::open(path)?;
-    let mut reader = BufReader::new(file);
-    let mut hasher = Sha256::new();
-    let mut buffer = [0u8; 8192];
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert_eq!(report.warnings.len(), 2);
 
-    loop {
-        let bytes_read = reader.read(&mut buffer)?;
-        if bytes_read == 0 {
-            break;
+        // The same quirks fail a strict validation
+        let strict = ValidationOptions {
+            mode: ValidationMode::Strict,
+            ..ValidationOptions::default()
+        };
+        let report = validate_bag(&temp_dir, &strict, None).unwrap();
+        assert_eq!(report.errors.len(), 2);
+        assert!(report.errors.iter().any(|e| e.rule == RULE_BAG_DECLARATION));
+        assert!(report.errors.iter().any(|e| e.rule == RULE_BAG_METADATA));
+        assert_eq!(
+            report.files(FindingKind::TagFile),
+            vec!["bagit.txt", "bag-info.txt"]
+        );
+
+        // Declared as Latin-1, the same bag-info.txt is fine even in strict mode
+        fs::write(
+            temp_dir.join("bagit.txt"),
+            "BagIt-Version: 0.97\nTag-File-Character-Encoding: ISO-8859-1\n",
+        )
+        .unwrap();
+        let report = validate_bag(&temp_dir, &strict, None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // An encoding baggie doesn't know is noted, and the files checked as UTF-8
+        fs::write(
+            temp_dir.join("bagit.txt"),
+            "BagIt-Version: 0.97\nTag-File-Character-Encoding: Shift_JIS\n",
+        )
+        .unwrap();
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(report
+            .warnings
+            .iter()
+            .any(|w| w.message.contains("Shift_JIS")));
+        assert!(report
+            .warnings
+            .iter()
+            .any(|w| w.file.as_deref() == Some("bag-info.txt")));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_findings_are_grouped_by_kind() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_finding_kinds");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
         }
-        hasher.update(&buffer[..bytes_read]);
+        fs::create_dir(&temp_dir).unwrap();
+        for name in ["a.txt", "b.txt", "c.txt"] {
+            fs::write(temp_dir.join(name), name).unwrap();
+        }
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+
+        fs::remove_file(temp_dir.join("data/a.txt")).unwrap();
+        fs::write(temp_dir.join("data/b.txt"), "changed").unwrap();
+        fs::write(temp_dir.join("data/stray.txt"), "not listed").unwrap();
+        let bag_info = fs::read_to_string(temp_dir.join("bag-info.txt")).unwrap();
+        fs::write(
+            temp_dir.join("bag-info.txt"),
+            bag_info + "Source-Organization: Edited\n",
+        )
+        .unwrap();
+
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert_eq!(report.files(FindingKind::MissingFile), vec!["data/a.txt"]);
+        assert_eq!(
+            report.files(FindingKind::ChecksumMismatch),
+            vec!["data/b.txt"]
+        );
+        assert_eq!(
+            report.files(FindingKind::UnexpectedFile),
+            vec!["data/stray.txt"]
+        );
+        // The edited bag-info.txt fails the tag manifest; Payload-Oxum no longer matches
+        assert_eq!(report.files(FindingKind::TagFile), vec!["bag-info.txt"]);
+        assert_eq!(report.errors_of(FindingKind::TagFile).count(), 2);
+        assert!(report
+            .to_text()
+            .contains("5 errors: missing files (1), unexpected files (1), checksum mismatches (1), tag file problems (2)"));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
     }
 
-    Ok(format!("{:x}", hasher.finalize()))
-}
+    #[test]
+    fn test_json_report() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_json_report");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("a.txt"), "a").unwrap();
+        fs::write(temp_dir.join("b.txt"), "b").unwrap();
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
 
-fn calculate_sha256_str(content: &str) -> String {
-    let mut hasher = Sha256::new();
-    hasher.update(content.as_bytes());
-    format!("{:x}", hasher.finalize())
-}
+        let json = validate_bag(&temp_dir, &ValidationOptions::default(), None)
+            .unwrap()
+            .to_json();
+        assert!(json.contains("\"schema\": \"baggie-validation-report/1\""));
+        assert!(json.contains("\"valid\": true"));
+        assert!(json.contains("\"algorithms\": [\"sha256\"]"));
+        assert!(json
+            .contains("\"oxum\": {\"declared\": \"2.2\", \"actual\": \"2.2\", \"matches\": true}"));
+        assert!(json.contains("\"errors\": []"));
 
-pub fn bag_directory(path: &Path, progress_tx: Option<Sender<Progress>>) -> Result<(), BagError> {
-    // Validate input
-    if !path.is_dir() {
-        return Err(BagError::NotADirectory);
+        fs::write(temp_dir.join("data/b.txt"), "bb").unwrap();
+        let json = validate_bag(&temp_dir, &ValidationOptions::default(), None)
+            .unwrap()
+            .to_json();
+        assert!(json.contains("\"valid\": false"));
+        assert!(json.contains("\"actual\": \"3.2\", \"matches\": false"));
+        assert!(json.contains("{\"kind\": \"mismatch\", \"rule\": "));
+        assert!(json.contains("\"file\": \"data/b.txt\", \"algorithm\": \"sha256\""));
+        assert!(json.contains("\"sample\": null,\n  \"signed_by\": null\n}"));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
     }
 
-    // Check if already a bag
-    if path.join("bagit.txt").exists() || path.join("data").exists() {
-        return Err(BagError::AlreadyABag);
+    #[test]
+    fn test_spot_check() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_spot_check");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        for i in 0..40 {
+            fs::write(temp_dir.join(format!("{:02}.txt", i)), i.to_string()).unwrap();
+        }
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+
+        let options = ValidationOptions {
+            sample: Some(Sample {
+                percent: 10.0,
+                seed: 7,
+            }),
+            ..ValidationOptions::default()
+        };
+        let report = validate_bag(&temp_dir, &options, None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        let sample = report.sample.clone().unwrap();
+        assert_eq!(
+            (sample.hashed, sample.population, sample.failed),
+            (4, 40, 0)
+        );
+        assert!(report
+            .to_text()
+            .contains("4 of 40 payload files hashed (10%, seed 7)"));
+        // 1 - 0.05^(1/4)
+        assert!((sample.damage_bound() - 0.527).abs() < 0.001);
+
+        // The same seed picks the same files
+        let chosen = Sample {
+            percent: 10.0,
+            seed: 7,
+        };
+        let files: Vec<String> = (0..40).map(|i| format!("data/{:02}.txt", i)).collect();
+        let refs: Vec<&String> = files.iter().collect();
+        let reversed: Vec<&String> = files.iter().rev().collect();
+        assert_eq!(chosen.choose(&refs), chosen.choose(&reversed));
+        assert_ne!(
+            chosen.choose(&refs),
+            Sample { seed: 8, ..chosen }.choose(&refs)
+        );
+
+        // Damage in a sampled file is found; a missing unsampled one is still noticed
+        let picked: Vec<String> = chosen.choose(&refs).into_iter().collect();
+        fs::write(temp_dir.join(&picked[0]), "damaged").unwrap();
+        let unpicked = files.iter().find(|f| !picked.contains(f)).unwrap();
+        fs::remove_file(temp_dir.join(unpicked)).unwrap();
+        let report = validate_bag(&temp_dir, &options, None).unwrap();
+        assert_eq!(
+            report.files(FindingKind::ChecksumMismatch),
+            vec![picked[0].as_str()]
+        );
+        assert_eq!(
+            report.files(FindingKind::MissingFile),
+            vec![unpicked.as_str()]
+        );
+        assert_eq!(report.sample.unwrap().failed, 1);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
     }
 
-    // Count files first
-    let entries: Vec<_> = WalkDir::new(path)
-        .min_depth(1)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .collect();
+    #[test]
+    fn test_strict_rejects_formatting_quirks() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_validate_strict");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
 
-    let total_files = entries.iter().filter(|e| e.file_type().is_file()).count();
+        // Binary-mode marker in the manifest, an unlisted payload file, no tagmanifest
+        let manifest = fs::read_to_string(temp_dir.join("manifest-sha256.txt")).unwrap();
+        fs::write(
+            temp_dir.join("manifest-sha256.txt"),
+            manifest.replace("  data/", " *data/"),
+        )
+        .unwrap();
+        fs::write(temp_dir.join("data").join("stray.txt"), "not listed").unwrap();
+        fs::remove_file(temp_dir.join("tagmanifest-sha256.txt")).unwrap();
 
-    if let Some(ref tx) = progress_tx {
-        let _ = tx.send(Progress::Started { total_files });
+        let lenient = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        // Only the stray file and the Oxum (which now counts it) fail leniently
+        assert_eq!(lenient.errors.len(), 2, "{:?}", lenient.errors);
+        assert!(lenient.errors.iter().any(|e| e.rule == RULE_COMPLETENESS));
+        assert!(lenient.errors.iter().any(|e| e.rule == RULE_BAG_METADATA));
+
+        let strict = ValidationOptions {
+            mode: ValidationMode::Strict,
+            ..ValidationOptions::default()
+        };
+        let report = validate_bag(&temp_dir, &strict, None).unwrap();
+        // Taken literally, "*data/file.txt" isn't a payload path at all
+        assert!(report
+            .errors
+            .iter()
+            .any(|e| e.rule == RULE_PAYLOAD_MANIFEST && e.message.contains("*data/file.txt")));
+        assert!(report.errors.iter().any(|e| e.rule == RULE_COMPLETENESS));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
     }
 
-    // Create data directory
-    let data_dir = path.join("data");
-    fs::create_dir(&data_dir)?;
+    #[test]
+    fn test_repeated_metadata_labels() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_repeated_labels");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
 
-    // Get list of items to move (top-level only)
-    let items_to_move: Vec<_> = fs::read_dir(path)?
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_name() != "data")
-        .collect();
+        let mut options = BagOptions::default();
+        options.metadata.push("Contact-Email", "first@example.org");
+        options.metadata.push("Source-Organization", "Archive");
+        options.metadata.push("Contact-Email", "second@example.org");
+        options.metadata.push("Payload-Oxum", "1.1");
+        bag_directory(&temp_dir, &options, None).unwrap();
 
-    // Move all items into data/
-    for (i, entry) in items_to_move.iter().enumerate() {
-        let filename = entry.file_name();
-        let dest = data_dir.join(&filename);
+        let bag_info = fs::read_to_string(temp_dir.join("bag-info.txt")).unwrap();
+        assert!(bag_info.ends_with(
+            "Contact-Email: first@example.org\nSource-Organization: Archive\nContact-Email: second@example.org\n"
+        ));
+        assert_eq!(bag_info.matches("Payload-Oxum").count(), 1);
 
-        if let Some(ref tx) = progress_tx {
-            let _ = tx.send(Progress::Moving {
-                current: i + 1,
-                filename: filename.to_string_lossy().to_string(),
-            });
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_empty_source_policy() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_empty_source");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
         }
+        // Only an empty folder and a skipped system file: nothing to bag
+        fs::create_dir_all(temp_dir.join("empty")).unwrap();
+        fs::write(temp_dir.join(".DS_Store"), "junk").unwrap();
+
+        let result = bag_directory(&temp_dir, &BagOptions::default(), None);
+        assert!(matches!(result, Err(BagError::EmptySource)));
+        assert!(!temp_dir.join("data").exists());
+        assert!(temp_dir.join("empty").is_dir());
 
-        fs::rename(entry.path(), dest)?;
+        let options = BagOptions {
+            empty_source: EmptySourcePolicy::Allow,
+            ..BagOptions::default()
+        };
+        let report = bag_directory(&temp_dir, &options, None).unwrap();
+        // One for the empty bag, one for the empty folder in it
+        assert_eq!(report.warnings.len(), 2);
+        let bag_info = fs::read_to_string(temp_dir.join("bag-info.txt")).unwrap();
+        assert!(bag_info.contains("Payload-Oxum: 0.0"));
+        let validation = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(validation.is_valid(), "{:?}", validation.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
     }
 
-    // Calculate checksums for all files in data/
-    let mut manifest_entries = Vec::new();
-    let mut total_bytes: u64 = 0;
-    let mut file_count: usize = 0;
+    #[test]
+    fn test_completeness_only() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_completeness");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(&temp_dir).unwrap();
+        fs::write(temp_dir.join("a.txt"), "alpha").unwrap();
+        fs::write(temp_dir.join("b.txt"), "bravo").unwrap();
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+        let complete = ValidationOptions {
+            completeness_only: true,
+            ..ValidationOptions::default()
+        };
 
-    let data_files: Vec<_> = WalkDir::new(&data_dir)
-        .into_iter()
-        .filter_map(|e| e.ok())
-        .filter(|e| e.file_type().is_file())
-        .collect();
+        // Changed contents of the same size go unnoticed; only full validation sees them
+        fs::write(temp_dir.join("data/a.txt"), "ALPHA").unwrap();
+        let report = validate_bag(&temp_dir, &complete, None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert!(report
+            .to_text()
+            .ends_with("is complete (lenient mode, 5 files checked)\n"));
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(!report.is_valid());
 
-    for (i, entry) in data_files.iter().enumerate() {
-        let file_path = entry.path();
-        let relative_path = file_path.strip_prefix(path).unwrap();
+        // Missing and unlisted files don't
+        fs::rename(temp_dir.join("data/b.txt"), temp_dir.join("data/c.txt")).unwrap();
+        let report = validate_bag(&temp_dir, &complete, None).unwrap();
+        let errors: Vec<String> = report.errors.iter().map(|e| e.message.clone()).collect();
+        assert!(
+            errors.contains(&"Missing file: data/b.txt".to_string()),
+            "{:?}",
+            errors
+        );
+        assert!(errors
+            .iter()
+            .any(|e| e.contains("not in any manifest: data/c.txt")));
+        assert!(report.to_text().contains("is INCOMPLETE"));
 
-        if let Some(ref tx) = progress_tx {
-            let _ = tx.send(Progress::Checksumming {
-                current: i + 1,
-                filename: relative_path.to_string_lossy().to_string(),
-            });
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_case_collisions() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_case_collisions");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
         }
+        fs::create_dir_all(temp_dir.join("Scans")).unwrap();
+        fs::create_dir_all(temp_dir.join("scans")).unwrap();
+        fs::write(temp_dir.join("Report.pdf"), "a").unwrap();
+        fs::write(temp_dir.join("report.pdf"), "b").unwrap();
+        fs::write(temp_dir.join("Scans/page1.tif"), "c").unwrap();
+        fs::write(temp_dir.join("scans/page1.tif"), "d").unwrap();
+        fs::write(temp_dir.join("notes.txt"), "e").unwrap();
 
-        let checksum = calculate_sha256(file_path)?;
-        let metadata = fs::metadata(file_path)?;
-        total_bytes += metadata.len();
-        file_count += 1;
+        // Refused before anything moves
+        let options = BagOptions {
+            case_collisions: CaseCollisionPolicy::Refuse,
+            ..BagOptions::default()
+        };
+        let result = bag_directory(&temp_dir, &options, None);
+        assert!(matches!(result, Err(BagError::CaseCollision(_, _))));
+        assert!(!temp_dir.join("data").exists());
 
-        // Use forward slashes for manifest (BagIt spec)
-        let manifest_path = relative_path.to_string_lossy().replace('\\', "/");
-        manifest_entries.push(format!("{}  {}", checksum, manifest_path));
+        // Warned about once per clash, not once per file in a clashing folder
+        let report = bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+        assert_eq!(report.warnings.len(), 2, "{:?}", report.warnings);
+        assert!(report.warnings.iter().any(|w| w.contains("report.pdf")));
+        assert!(temp_dir.join("data/Report.pdf").is_file());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
     }
 
-    // Write bagit.txt
-    let bagit_content = "BagIt-Version: 0.97\nTag-File-Character-Encoding: UTF-8\n";
-    fs::write(path.join("bagit.txt"), bagit_content)?;
+    #[test]
+    fn test_windows_name_problems() {
+        assert!(windows_name_problem("CON").is_some());
+        assert!(windows_name_problem("aux.txt").is_some());
+        assert!(windows_name_problem("Lpt1.tar.gz").is_some());
+        assert!(windows_name_problem("what?.txt").is_some());
+        assert!(windows_name_problem("a|b").is_some());
+        assert!(windows_name_problem("trailing.").is_some());
+        assert!(windows_name_problem("console.txt").is_none());
+        assert!(windows_name_problem("COM10").is_none());
+        assert!(windows_name_problem("report.pdf").is_none());
 
-    // Write manifest-sha256.txt (sorted for reproducibility, matching Python bagit)
-    manifest_entries.sort();
-    let manifest_content = manifest_entries.join("\n") + "\n";
-    fs::write(path.join("manifest-sha256.txt"), &manifest_content)?;
+        let temp_dir = std::env::temp_dir().join("bagit_test_windows_names");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(temp_dir.join("aux")).unwrap();
+        fs::write(temp_dir.join("aux/notes.txt"), "a").unwrap();
+        fs::write(temp_dir.join("Q&A: draft.txt"), "b").unwrap();
+        fs::write(temp_dir.join("fine.txt"), "c").unwrap();
 
-    // Write bag-info.txt (field order matches Python bagit library)
-    let date = chrono::Local::now().format("%Y-%m-%d").to_string();
-    let payload_oxum = format!("{}.{}", total_bytes, file_count);
-    let bag_info_content = format!(
-        "Bag-Software-Agent: baggie 0.1.1\nBagging-Date: {}\nPayload-Oxum: {}\n",
-        date, payload_oxum
-    );
-    fs::write(path.join("bag-info.txt"), &bag_info_content)?;
-
-    // Write tagmanifest-sha256.txt (sorted alphabetically to match Python bagit)
-    let bagit_checksum = calculate_sha256_str(bagit_content);
-    let manifest_checksum = calculate_sha256_str(&manifest_content);
-    let bag_info_checksum = calculate_sha256_str(&bag_info_content);
-
-    let mut tagmanifest_entries = vec![
-        format!("{}  bag-info.txt", bag_info_checksum),
-        format!("{}  bagit.txt", bagit_checksum),
-        format!("{}  manifest-sha256.txt", manifest_checksum),
-    ];
-    tagmanifest_entries.sort_by(|a, b| {
-        // Sort by filename (after the checksum and spaces)
-        a.split_whitespace().last().cmp(&b.split_whitespace().last())
-    });
-    let tagmanifest_content = tagmanifest_entries.join("\n") + "\n";
-    fs::write(path.join("tagmanifest-sha256.txt"), tagmanifest_content)?;
+        let report = bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+        assert_eq!(report.warnings.len(), 2, "{:?}", report.warnings);
+        assert!(report
+            .warnings
+            .iter()
+            .any(|w| w.contains("AUX is a reserved device name")));
+        assert!(report.warnings.iter().any(|w| w.contains("Q&A: draft.txt")));
+        assert!(temp_dir.join("data/aux/notes.txt").is_file());
 
-    if let Some(ref tx) = progress_tx {
-        let _ = tx.send(Progress::Done {
-            path: path.to_path_buf(),
-        });
+        fs::remove_dir_all(&temp_dir).unwrap();
     }
 
-    Ok(())
-}
+    #[test]
+    fn test_empty_folder_policy() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_empty_folders");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let make = |name: &str| {
+            let folder = temp_dir.join(name);
+            fs::create_dir_all(folder.join("scans/unsorted")).unwrap();
+            fs::create_dir_all(folder.join("later")).unwrap();
+            fs::write(folder.join("scans/page.tif"), "tiff").unwrap();
+            folder
+        };
+        let bag = |folder: &Path, empty_dirs| {
+            let options = BagOptions {
+                empty_dirs,
+                ..BagOptions::default()
+            };
+            let report = bag_directory(folder, &options, None).unwrap();
+            let validation = validate_bag(folder, &ValidationOptions::default(), None).unwrap();
+            assert!(validation.is_valid(), "{:?}", validation.errors);
+            report
+        };
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use std::fs;
+        let warned = make("warn");
+        let report = bag(&warned, EmptyDirPolicy::Warn);
+        assert_eq!(report.warnings.len(), 1);
+        assert!(report.warnings[0].contains("data/later, data/scans/unsorted"));
+        assert!(warned.join("data/later").is_dir());
+
+        let kept = make("keep");
+        let report = bag(&kept, EmptyDirPolicy::Placeholder);
+        assert!(report.warnings.is_empty());
+        let manifest = fs::read_to_string(kept.join("manifest-sha256.txt")).unwrap();
+        assert!(manifest.contains("  data/scans/unsorted/.keep\n"));
+        assert_eq!(manifest.lines().count(), 3);
+
+        let recorded = make("record");
+        let report = bag(&recorded, EmptyDirPolicy::Record);
+        assert!(report.warnings.is_empty());
+        let listing = fs::read_to_string(recorded.join(EMPTY_DIRS_FILE)).unwrap();
+        assert_eq!(listing, "data/later\ndata/scans/unsorted\n");
+        let tagmanifest = fs::read_to_string(recorded.join("tagmanifest-sha256.txt")).unwrap();
+        assert!(tagmanifest.contains(EMPTY_DIRS_FILE));
+        // Updating keeps the list, and drops it once no folder is empty
+        fs::write(recorded.join("data/later/note.txt"), "note").unwrap();
+        update_bag(&recorded, &BagOptions::default(), None).unwrap();
+        let listing = fs::read_to_string(recorded.join(EMPTY_DIRS_FILE)).unwrap();
+        assert_eq!(listing, "data/scans/unsorted\n");
+        fs::remove_dir(recorded.join("data/scans/unsorted")).unwrap();
+        update_bag(&recorded, &BagOptions::default(), None).unwrap();
+        assert!(!recorded.join(EMPTY_DIRS_FILE).exists());
+        let validation = validate_bag(&recorded, &ValidationOptions::default(), None).unwrap();
+        assert!(validation.is_valid(), "{:?}", validation.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
 
     #[test]
-    fn test_bag_directory() {
-        // Create a temporary directory
-        let temp_dir = std::env::temp_dir().join("bagit_test");
+    fn test_split_across_volumes() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_volumes");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("source");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        let chunk = vec![b'x'; 400 * 1024];
+        for name in ["a.bin", "b.bin", "sub/c.bin", "sub/d.bin", "sub/e.bin"] {
+            fs::write(source.join(name), &chunk).unwrap();
+        }
+
+        // Room for two 400 KiB files per volume after the tag allowance
+        let capacity = VOLUME_TAG_ALLOWANCE + 900 * 1024;
+        let (volumes, _) = plan_volumes(&source, &BagOptions::default(), capacity).unwrap();
+        assert_eq!(volumes.len(), 3);
+        assert_eq!(volumes.iter().map(|v| v.files.len()).sum::<usize>(), 5);
+        assert!(volumes.iter().all(|v| v.bytes <= 900 * 1024));
+
+        for index in 0..volumes.len() {
+            let dest = temp_dir.join(format!("volume{}", index + 1));
+            bag_volume(
+                &source,
+                &dest,
+                &volumes,
+                index,
+                "source",
+                &BagOptions::default(),
+                None,
+            )
+            .unwrap();
+            let bag_info = fs::read_to_string(dest.join("bag-info.txt")).unwrap();
+            assert!(bag_info.contains(&format!("Bag-Count: {} of 3", index + 1)));
+            assert!(bag_info.contains("Bag-Group-Identifier: source"));
+            let report = validate_bag(&dest, &ValidationOptions::default(), None).unwrap();
+            assert!(report.is_valid(), "{:?}", report.errors);
+        }
+        assert!(source.join("sub/e.bin").exists());
+
+        let result = plan_volumes(&source, &BagOptions::default(), VOLUME_TAG_ALLOWANCE + 1024);
+        assert!(matches!(result, Err(BagError::FileTooLargeForVolume(_))));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_bag_summary() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_summary");
         if temp_dir.exists() {
             fs::remove_dir_all(&temp_dir).unwrap();
         }
         fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
 
-        // Create test files
-        fs::write(temp_dir.join("file1.txt"), "Hello, world!").unwrap();
-        fs::write(temp_dir.join("file2.txt"), "Test content").unwrap();
-        fs::create_dir(temp_dir.join("subdir")).unwrap();
-        fs::write(temp_dir.join("subdir").join("nested.txt"), "Nested file").unwrap();
+        let summary = BagSummary::read(&temp_dir).unwrap();
+        assert_eq!(summary.identifier, "bagit_test_summary");
+        assert_eq!(summary.payload_oxum, "7.1");
+        assert_eq!(
+            summary.tagmanifest_sha256,
+            calculate_sha256(&temp_dir.join("tagmanifest-sha256.txt")).unwrap()
+        );
+        assert!(summary
+            .to_label_text()
+            .starts_with("Identifier: bagit_test_summary\nPayload-Oxum: 7.1\n"));
 
-        // Bag the directory
-        bag_directory(&temp_dir, None).unwrap();
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
 
-        // Verify structure
-        assert!(temp_dir.join("data").exists());
-        assert!(temp_dir.join("data").join("file1.txt").exists());
-        assert!(temp_dir.join("data").join("file2.txt").exists());
-        assert!(temp_dir.join("data").join("subdir").join("nested.txt").exists());
-        assert!(temp_dir.join("bagit.txt").exists());
-        assert!(temp_dir.join("manifest-sha256.txt").exists());
-        assert!(temp_dir.join("bag-info.txt").exists());
-        assert!(temp_dir.join("tagmanifest-sha256.txt").exists());
+    #[test]
+    fn test_parallel_hashing_matches_sequential() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_parallel_hashing");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(temp_dir.join("one")).unwrap();
+        fs::create_dir_all(temp_dir.join("many")).unwrap();
+        for i in 0..50 {
+            let content = format!("file number {}", i).repeat(i + 1);
+            fs::write(temp_dir.join("one").join(format!("{}.txt", i)), &content).unwrap();
+            fs::write(temp_dir.join("many").join(format!("{}.txt", i)), &content).unwrap();
+        }
 
-        // Verify bagit.txt content
-        let bagit_content = fs::read_to_string(temp_dir.join("bagit.txt")).unwrap();
-        assert!(bagit_content.contains("BagIt-Version: 0.97"));
-        assert!(bagit_content.contains("Tag-File-Character-Encoding: UTF-8"));
+        bag_directory(&temp_dir.join("one"), &BagOptions::default(), None).unwrap();
+        let options = BagOptions {
+            hash_threads: 8,
+            ..BagOptions::default()
+        };
+        let (tx, rx) = std::sync::mpsc::channel();
+        bag_directory(&temp_dir.join("many"), &options, Some(tx)).unwrap();
+        let checksummed = rx
+            .iter()
+            .filter(|p| matches!(p, Progress::Checksumming { .. }))
+            .count();
+        assert_eq!(checksummed, 50);
 
-        // Verify manifest has correct format and file count
+        let one = fs::read_to_string(temp_dir.join("one/manifest-sha256.txt")).unwrap();
+        let many = fs::read_to_string(temp_dir.join("many/manifest-sha256.txt")).unwrap();
+        assert_eq!(one, many);
+
+        fs::write(temp_dir.join("many/data/7.txt"), "changed").unwrap();
+        let validation = ValidationOptions {
+            hash_threads: 8,
+            ..ValidationOptions::default()
+        };
+        let report = validate_bag(&temp_dir.join("many"), &validation, None).unwrap();
+        assert_eq!(report.files_checked, 53);
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].file, "data/7.txt");
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_unsafe_manifest_paths() {
+        assert_eq!(unsafe_manifest_path("data/file.txt", true), None);
+        assert_eq!(unsafe_manifest_path("data/a..b/file.txt", true), None);
+        assert_eq!(unsafe_manifest_path("bag-info.txt", false), None);
+        assert!(unsafe_manifest_path("bag-info.txt", true).is_some());
+        assert!(unsafe_manifest_path("data/../../etc/passwd", true).is_some());
+        assert!(unsafe_manifest_path("data\\..\\secret", true).is_some());
+        assert!(unsafe_manifest_path("/etc/passwd", false).is_some());
+        assert!(unsafe_manifest_path("C:/Windows/win.ini", false).is_some());
+        assert!(unsafe_manifest_path("../outside.txt", false).is_some());
+    }
+
+    #[test]
+    fn test_validate_rejects_escaping_entries() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_escaping_entries");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("bag");
+        fs::create_dir_all(&bag).unwrap();
+        fs::write(bag.join("file.txt"), "content").unwrap();
+        bag_directory(&bag, &BagOptions::default(), None).unwrap();
+
+        // A file outside the bag whose checksum would otherwise match
+        fs::write(temp_dir.join("outside.txt"), "secret").unwrap();
+        let mut manifest = fs::read_to_string(bag.join("manifest-sha256.txt")).unwrap();
+        manifest.push_str(&format!(
+            "{}  data/../../outside.txt\n",
+            calculate_sha256_str("secret")
+        ));
+        fs::write(bag.join("manifest-sha256.txt"), manifest).unwrap();
+
+        let report = validate_bag(&bag, &ValidationOptions::default(), None).unwrap();
+        assert!(report.errors.iter().any(|e| e
+            .message
+            .contains("Refusing manifest entry data/../../outside.txt")));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_reports_untracked_files() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_untracked");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+
+        fs::create_dir(temp_dir.join("data/later")).unwrap();
+        fs::write(temp_dir.join("data/later/added.txt"), "drift").unwrap();
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        let untracked: Vec<_> = report
+            .errors
+            .iter()
+            .filter(|e| e.rule == RULE_COMPLETENESS)
+            .collect();
+        assert_eq!(untracked.len(), 1);
+        assert!(untracked[0].message.contains("data/later/added.txt"));
+
+        // Listed in a manifest for another algorithm is enough
+        fs::write(
+            temp_dir.join("manifest-md5.txt"),
+            "00000000000000000000000000000000  data/later/added.txt\n",
+        )
+        .unwrap();
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(!report.errors.iter().any(|e| e.rule == RULE_COMPLETENESS));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_validate_accepts_uppercase_digests() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_uppercase_digests");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+
+        // Rewrite both manifests the way some other tools do
         let manifest = fs::read_to_string(temp_dir.join("manifest-sha256.txt")).unwrap();
-        assert!(manifest.contains("data/file1.txt"));
-        assert!(manifest.contains("data/file2.txt"));
-        assert!(manifest.contains("data/subdir/nested.txt"));
-        assert_eq!(manifest.lines().count(), 3);
+        let (digest, file) = manifest.trim_end().split_once("  ").unwrap();
+        let manifest = format!("{}  {}\n", digest.to_uppercase(), file);
+        fs::write(temp_dir.join("manifest-sha256.txt"), &manifest).unwrap();
+        let tagmanifest: String = fs::read_to_string(temp_dir.join("tagmanifest-sha256.txt"))
+            .unwrap()
+            .lines()
+            .map(|line| {
+                let (digest, file) = line.split_once("  ").unwrap();
+                let digest = if file == "manifest-sha256.txt" {
+                    calculate_sha256_str(&manifest)
+                } else {
+                    digest.to_string()
+                };
+                format!("{}  {}\n", digest.to_uppercase(), file)
+            })
+            .collect();
+        fs::write(temp_dir.join("tagmanifest-sha256.txt"), tagmanifest).unwrap();
+
+        for mode in [ValidationMode::Strict, ValidationMode::Lenient] {
+            let options = ValidationOptions {
+                mode,
+                ..ValidationOptions::default()
+            };
+            let report = validate_bag(&temp_dir, &options, None).unwrap();
+            assert!(report.is_valid(), "{:?}", report.errors);
+        }
+
+        // Mismatches are reported in lowercase whatever the manifest used
+        fs::write(temp_dir.join("data/file.txt"), "changed").unwrap();
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert_eq!(report.errors.len(), 1);
+        let message = &report.errors[0].message;
+        assert!(message.contains(&digest.to_lowercase()), "{}", message);
+        assert!(!message.contains(digest.to_uppercase().as_str()));
 
-        // Cleanup
         fs::remove_dir_all(&temp_dir).unwrap();
     }
 
     #[test]
-    fn test_already_a_bag() {
-        let temp_dir = std::env::temp_dir().join("bagit_test_already_bag");
+    fn test_payload_oxum_past_4_gib() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_oxum_4gib");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(temp_dir.join("data")).unwrap();
+
+        // Sparse, so this takes no real disk space
+        let size: u64 = 5 * 1024 * 1024 * 1024 + 7;
+        File::create(temp_dir.join("data/big.bin"))
+            .unwrap()
+            .set_len(size)
+            .unwrap();
+        fs::write(temp_dir.join("data/small.txt"), "abc").unwrap();
+
+        let actual = payload_oxum(&Folder(&temp_dir));
+        assert_eq!(actual, (size + 3, 2));
+        assert_eq!(parse_payload_oxum("5368709130.2"), Some((size + 3, 2)));
+        assert_eq!(parse_payload_oxum("05368709130.2"), Some((size + 3, 2)));
+
+        let mut report = ValidationReport::default();
+        let mut bag_info = BagInfo::default();
+        bag_info.push("Payload-Oxum", format!("{}.2", size + 3));
+        check_payload_oxum(&bag_info, actual, &mut report);
+        assert!(report.errors.is_empty(), "{:?}", report.errors);
+
+        // What a 32-bit accumulator would have wrapped to
+        let mut bag_info = BagInfo::default();
+        bag_info.push("Payload-Oxum", format!("{}.2", (size + 3) as u32));
+        check_payload_oxum(&bag_info, actual, &mut report);
+        assert_eq!(report.errors.len(), 1);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    /// Hashes 4 GiB of zeros, so it's slow; run with `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_bag_file_past_4_gib() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_bag_4gib");
         if temp_dir.exists() {
             fs::remove_dir_all(&temp_dir).unwrap();
         }
         fs::create_dir(&temp_dir).unwrap();
-        fs::write(temp_dir.join("bagit.txt"), "existing").unwrap();
+        let size: u64 = 4 * 1024 * 1024 * 1024 + 1;
+        File::create(temp_dir.join("big.bin"))
+            .unwrap()
+            .set_len(size)
+            .unwrap();
+
+        bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+        let bag_info = fs::read_to_string(temp_dir.join("bag-info.txt")).unwrap();
+        assert!(bag_info.contains(&format!("Payload-Oxum: {}.1", size)));
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_utc_bagging_time() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_utc_time");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+
+        let options = BagOptions {
+            date_mode: DateMode::Utc,
+            include_bagging_time: true,
+            ..BagOptions::default()
+        };
+        bag_directory(&temp_dir, &options, None).unwrap();
 
-        let result = bag_directory(&temp_dir, None);
-        assert!(matches!(result, Err(BagError::AlreadyABag)));
+        let bag_info = fs::read_to_string(temp_dir.join("bag-info.txt")).unwrap();
+        let today = chrono::Utc::now().format("%Y-%m-%d").to_string();
+        assert!(bag_info.contains(&format!("Bagging-Date: {}\n", today)));
+        let time = bag_info
+            .lines()
+            .find_map(|l| l.strip_prefix("Bagging-Time: "))
+            .unwrap();
+        assert!(time.ends_with("+00:00"));
 
         fs::remove_dir_all(&temp_dir).unwrap();
     }
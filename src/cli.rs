@@ -0,0 +1,1303 @@
+use crate::agent::{serve, DEFAULT_AGENT_ADDRESS};
+use crate::archive::{serialize_bag, validate_archive, SerialFormat, SerialOptions};
+use crate::bagit::{
+    bag_directory, bag_into, bag_size, bag_sources, payload_sources, unbag_directory, update_bag,
+    validate_bag, verify_oxum, Bag, BagError, BagItVersion, BagOptions, BagReport,
+    ChecksumAlgorithm, Sample, ValidationMode, ValidationOptions,
+};
+use crate::clone::clone_bag;
+use crate::diff::compare_bags;
+use crate::download::{bag_from_urls, complete_bag, parse_url_list, DEFAULT_DOWNLOADS};
+use crate::encrypt::{Encryption, EncryptionTool};
+use crate::filelist::{bag_from_list, parse_file_list};
+use crate::fixtures::{broken_bag, generate_tree, Breakage, TreeSpec};
+use crate::group::{check_group, reassemble_group, split_into_bags};
+use crate::intake::{serve_intake, Intake, DEFAULT_INTAKE_ADDRESS};
+use crate::inventory::{AuditRecord, FixityStatus, Inventory};
+use crate::locale::Locale;
+use crate::optical::{plan_discs, write_discs, DiscLayout, DISC_LABELS_FILE, DISC_MEDIA};
+use crate::profile::{remove_profile, save_profile, saved_profiles, Profile};
+use crate::provenance::{restore_permissions, FILE_PERMISSIONS_FILE};
+use crate::s3::{bag_from_s3, S3Source};
+use crate::settings::Settings;
+use crate::signature::{sign_bag, Signing, SigningTool};
+use crate::split::join_volumes;
+use crate::store::export_to_store;
+use crate::tape::{parse_size, write_tape, TapeOptions, TAPE_INDEX_FILE};
+use std::net::TcpListener;
+use std::path::{Path, PathBuf};
+
+const USAGE: &str = "Usage:
+  baggie bag [--algorithm md5|sha1|sha256|sha512|blake3[,...]] [--bagit-version 1.0|0.97]
+             [--tag-dir <folder>]... [--threads <n>] [--profile <file, URL or name>] [--copy | --in-place]
+             <folder> [--to <new folder>]
+  baggie bag [options] <folder> [--as <name>] <folder> [--as <name>]... --to <new folder>
+  baggie bag-list [--csv] <file list> <new folder>
+  baggie update <bag>
+  baggie unbag <bag>
+  baggie restore-permissions <bag>
+  baggie complete [--parallel <n>] <bag>
+  baggie clone [--verify] <bag> <new folder>
+  baggie stats [--csv]
+  baggie audit [--all]
+  baggie audit-log [--csv]
+  baggie validate [--strict | --lenient] [--complete] [--threads <n>] [--sample <percent> [--seed <n>]]
+                  [--profile <file, URL or name>] [--minisign-key <file>] [--json] <bag or serialized bag>
+  baggie oxum <bag>
+  baggie sign [--with gpg|minisign] [--key <key>] <bag>
+  baggie diff <bag> <other bag>
+  baggie info <bag>
+  baggie download [--parallel <n>] <url list> <new folder>
+  baggie download [--parallel <n>] [--verify] <s3://bucket/prefix> <new folder>
+  baggie tape [--block-size <size>] [--segment-size <size>] <bag> <output folder>
+  baggie serialize [--format zip|tar|tar.gz|tar.zst|7z] [--level <1-22>] [--threads <n>]
+                   [--reproducible] [--encrypt age|gpg|none [--recipient <key>]...]
+                   [--split <size>|none] [--profile <file, URL or name>] <bag> <output folder>
+  baggie join <volume or .sha256 list> [<output folder>]
+  baggie store <bag> <store folder>
+  baggie discs [--media bd25|bd50|bd100 | --capacity <size>] [--folders] <bag> <output folder>
+  baggie split --size <size> <folder> <output folder>
+  baggie reassemble <bag>... [--to <new folder>]
+  baggie fixture [--files <n>] [--size <size>] [--depth <n>] [--unicode] [--symlinks] [--seed <n>] <new folder>
+  baggie fixture --broken <kind> <new folder>
+  baggie profiles [add <file or URL> | remove <name> | use <name> | use none]
+  baggie export-settings <file>
+  baggie import-settings <file>
+  baggie agent [--listen <address>]
+  baggie intake [--listen <address>] <staging folder>";
+
+/// Runs a command-line invocation, returning the process exit code, or `None` when the
+/// arguments aren't a baggie command and the GUI should start instead.
+pub fn run(args: &[String]) -> Option<i32> {
+    let (command, rest) = args.split_first()?;
+    let settings = Settings::load();
+
+    let code = match command.as_str() {
+        "bag" => {
+            let mut options = settings.bag_options(true);
+            let mut copy = settings.bag_copy;
+            let mut paths = Vec::new();
+            // A name given with --as, for the folder before it
+            let mut names: Vec<Option<&str>> = Vec::new();
+            let mut dest = None;
+            let mut args = rest.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--algorithm" => {
+                        let names = args.next().map(String::as_str).unwrap_or_default();
+                        let algorithms: Option<Vec<_>> =
+                            names.split(',').map(ChecksumAlgorithm::from_name).collect();
+                        match algorithms {
+                            Some(a) => options.algorithms = a,
+                            None => return Some(usage()),
+                        }
+                    }
+                    "--tag-dir" => match args.next() {
+                        Some(dir) => options.tag_directories.push(PathBuf::from(dir)),
+                        None => return Some(usage()),
+                    },
+                    "--threads" => match args.next().and_then(|n| n.parse().ok()) {
+                        Some(n) if n > 0 => options.hash_threads = n,
+                        _ => return Some(usage()),
+                    },
+                    "--copy" => copy = true,
+                    "--in-place" => copy = false,
+                    "--bagit-version" => match args.next().and_then(|v| BagItVersion::parse(v)) {
+                        Some(v) => options.bagit_version = v,
+                        None => return Some(usage()),
+                    },
+                    "--profile" => match profile_arg(args.next()) {
+                        Ok(profile) => profile.preset(&mut options),
+                        Err(code) => return Some(code),
+                    },
+                    "--to" => match args.next() {
+                        Some(to) => dest = Some(Path::new(to)),
+                        None => return Some(usage()),
+                    },
+                    "--as" => match (args.next(), names.last_mut()) {
+                        (Some(name), Some(last @ None)) => *last = Some(name.as_str()),
+                        _ => return Some(usage()),
+                    },
+                    _ => {
+                        paths.push(arg.as_str());
+                        names.push(None);
+                    }
+                }
+            }
+            let named = names.iter().any(Option::is_some);
+            match (paths.as_slice(), dest) {
+                ([folder], None) if copy && !named => {
+                    let dest = settings.copy_destination(Path::new(folder), &options);
+                    let result = bag_into(Path::new(folder), &dest, &options, None);
+                    print_bag_result(&dest, result)
+                }
+                ([folder], None) if !named => bag(Path::new(folder), &options),
+                ([folder], Some(dest)) if !named => {
+                    let result = bag_into(Path::new(folder), dest, &options, None);
+                    print_bag_result(dest, result)
+                }
+                ([_, ..], Some(dest)) => {
+                    let mut sources = payload_sources(paths.iter().map(PathBuf::from).collect());
+                    for (source, name) in sources.iter_mut().zip(names) {
+                        if let Some(name) = name {
+                            source.name = name.to_string();
+                        }
+                    }
+                    let result = bag_sources(&sources, dest, &options, None);
+                    print_bag_result(dest, result)
+                }
+                _ => usage(),
+            }
+        }
+        "bag-list" => {
+            let csv = rest.iter().any(|a| a == "--csv");
+            let paths: Vec<&String> = rest.iter().filter(|a| *a != "--csv").collect();
+            match paths.as_slice() {
+                [list, dest] => bag_list(Path::new(list), Path::new(dest), csv, &settings),
+                _ => usage(),
+            }
+        }
+        "clone" => {
+            let verify = rest.iter().any(|a| a == "--verify");
+            let paths: Vec<&String> = rest.iter().filter(|a| *a != "--verify").collect();
+            match paths.as_slice() {
+                [bag, dest] => {
+                    let options = settings.bag_options(false);
+                    let result = clone_bag(&bag_path(bag), Path::new(dest), &options, verify, None);
+                    print_bag_result(Path::new(dest), result)
+                }
+                _ => usage(),
+            }
+        }
+        "update" => match rest {
+            [bag] => {
+                let bag = bag_path(bag);
+                let result = update_bag(&bag, &settings.bag_options(true), None);
+                print_bag_result(&bag, result)
+            }
+            _ => usage(),
+        },
+        "complete" => {
+            let mut concurrency = DEFAULT_DOWNLOADS;
+            let mut paths = Vec::new();
+            let mut args = rest.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--parallel" => match args.next().and_then(|n| n.parse().ok()) {
+                        Some(n) if n > 0 => concurrency = n,
+                        _ => return Some(usage()),
+                    },
+                    _ => paths.push(arg.as_str()),
+                }
+            }
+            match paths.as_slice() {
+                [bag] => {
+                    let bag = bag_path(bag);
+                    match complete_bag(&bag, concurrency, None) {
+                        Ok(_) => {
+                            println!("Completed {}", bag.display());
+                            0
+                        }
+                        Err(e) => {
+                            eprintln!("error: {}", e);
+                            1
+                        }
+                    }
+                }
+                _ => usage(),
+            }
+        }
+        "unbag" => match rest {
+            [bag] => unbag(&bag_path(bag)),
+            _ => usage(),
+        },
+        "restore-permissions" => match rest {
+            [bag] => restore(&bag_path(bag)),
+            _ => usage(),
+        },
+        "oxum" => match rest {
+            [bag] => oxum(&bag_path(bag)),
+            _ => usage(),
+        },
+        "join" => match rest {
+            [volume] => join(Path::new(volume), None),
+            [volume, dest] => join(Path::new(volume), Some(Path::new(dest))),
+            _ => usage(),
+        },
+        "sign" => {
+            let mut tool = settings.signing_tool;
+            let mut key = settings.signing_key.clone();
+            let mut paths = Vec::new();
+            let mut args = rest.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--with" => match args.next().and_then(|t| SigningTool::parse(t)) {
+                        Some(t) => tool = Some(t),
+                        None => return Some(usage()),
+                    },
+                    "--key" => match args.next() {
+                        Some(k) => key = k.clone(),
+                        None => return Some(usage()),
+                    },
+                    _ => paths.push(arg),
+                }
+            }
+            let Some(tool) = tool else {
+                eprintln!("error: choose gpg or minisign with --with, or in Settings");
+                return Some(1);
+            };
+            match paths.as_slice() {
+                [bag] => {
+                    let signing = Signing {
+                        tool,
+                        key,
+                        gnupg_home: None,
+                    };
+                    sign(&bag_path(bag), &signing)
+                }
+                _ => usage(),
+            }
+        }
+        "diff" => match rest {
+            [original, copy] => diff(&bag_path(original), &bag_path(copy), settings.locale),
+            _ => usage(),
+        },
+        "info" => match rest {
+            [bag] => info(&bag_path(bag)),
+            _ => usage(),
+        },
+        "validate" => {
+            let mut options = settings.validation_options();
+            let mut seed = None;
+            let mut json = false;
+            let mut paths = Vec::new();
+            let mut args = rest.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--strict" => options.mode = ValidationMode::Strict,
+                    "--lenient" => options.mode = ValidationMode::Lenient,
+                    "--complete" => options.completeness_only = true,
+                    "--json" => json = true,
+                    "--profile" => match profile_arg(args.next()) {
+                        Ok(profile) => options.profile = Some(profile),
+                        Err(code) => return Some(code),
+                    },
+                    "--minisign-key" => match args.next() {
+                        Some(key) => options.minisign_key = Some(PathBuf::from(key)),
+                        None => return Some(usage()),
+                    },
+                    "--threads" => match args.next().and_then(|n| n.parse().ok()) {
+                        Some(n) if n > 0 => options.hash_threads = n,
+                        _ => return Some(usage()),
+                    },
+                    "--sample" => match args
+                        .next()
+                        .and_then(|p| p.trim_end_matches('%').parse().ok())
+                    {
+                        Some(percent) if percent > 0.0 && percent <= 100.0 => {
+                            options.sample = Some(Sample::new(percent));
+                        }
+                        _ => return Some(usage()),
+                    },
+                    "--seed" => match args.next().and_then(|n| n.parse().ok()) {
+                        Some(n) => seed = Some(n),
+                        None => return Some(usage()),
+                    },
+                    _ => paths.push(arg),
+                }
+            }
+            match (&mut options.sample, seed) {
+                (Some(sample), Some(seed)) => sample.seed = seed,
+                (None, Some(_)) => return Some(usage()),
+                _ => {}
+            }
+            match paths.as_slice() {
+                [bag] => validate(&bag_path(bag), &options, json),
+                _ => usage(),
+            }
+        }
+        "stats" => match rest {
+            [] => stats(false, settings.locale),
+            [flag] if flag == "--csv" => stats(true, settings.locale),
+            _ => usage(),
+        },
+        "audit" => match rest {
+            [] => audit(false, &settings),
+            [flag] if flag == "--all" => audit(true, &settings),
+            _ => usage(),
+        },
+        "audit-log" => match rest {
+            [] => audit_log(false, settings.locale),
+            [flag] if flag == "--csv" => audit_log(true, settings.locale),
+            _ => usage(),
+        },
+        "profiles" => match rest {
+            [] => {
+                for name in saved_profiles() {
+                    let marker = if name == settings.bag_profile {
+                        " (used for new bags)"
+                    } else {
+                        ""
+                    };
+                    println!("{}{}", name, marker);
+                }
+                0
+            }
+            [command, source] if command == "add" => match save_profile(source) {
+                Ok(name) => {
+                    println!("Saved the profile as {}", name);
+                    0
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    2
+                }
+            },
+            [command, name] if command == "remove" => match remove_profile(name) {
+                Ok(()) => {
+                    if settings.bag_profile == *name {
+                        use_profile(String::new(), settings)
+                    } else {
+                        0
+                    }
+                }
+                Err(e) => {
+                    eprintln!("error: {}: {}", name, e);
+                    1
+                }
+            },
+            [command, name] if command == "use" && name == "none" => {
+                use_profile(String::new(), settings)
+            }
+            [command, name] if command == "use" => match Profile::named(name) {
+                Ok(_) => use_profile(name.clone(), settings),
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    2
+                }
+            },
+            _ => usage(),
+        },
+        "export-settings" => match rest {
+            [file] => match std::fs::write(file, settings.to_bundle()) {
+                Ok(()) => {
+                    println!("Exported settings to {}", file);
+                    0
+                }
+                Err(e) => {
+                    eprintln!("error: {}", e);
+                    1
+                }
+            },
+            _ => usage(),
+        },
+        "import-settings" => match rest {
+            [file] => import_settings(Path::new(file), &settings),
+            _ => usage(),
+        },
+        "agent" => match rest {
+            [] => agent(DEFAULT_AGENT_ADDRESS, settings),
+            [flag, address] if flag == "--listen" => agent(address, settings),
+            _ => usage(),
+        },
+        "download" => {
+            let mut concurrency = DEFAULT_DOWNLOADS;
+            let mut verify = false;
+            let mut paths = Vec::new();
+            let mut args = rest.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--parallel" => match args.next().and_then(|n| n.parse().ok()) {
+                        Some(n) if n > 0 => concurrency = n,
+                        _ => return Some(usage()),
+                    },
+                    "--verify" => verify = true,
+                    _ => paths.push(arg.as_str()),
+                }
+            }
+            match paths.as_slice() {
+                [source, dest] if source.starts_with("s3://") => {
+                    let result = S3Source::from_env(source).and_then(|source| {
+                        let options = settings.bag_options(true);
+                        bag_from_s3(
+                            &source,
+                            Path::new(dest),
+                            &options,
+                            concurrency,
+                            verify,
+                            None,
+                        )
+                    });
+                    print_bag_result(Path::new(dest), result)
+                }
+                [list, dest] => download(Path::new(list), Path::new(dest), concurrency, &settings),
+                _ => usage(),
+            }
+        }
+        "tape" => {
+            let mut options = TapeOptions::default();
+            let mut paths = Vec::new();
+            let mut args = rest.iter();
+            while let Some(arg) = args.next() {
+                let size = match arg.as_str() {
+                    "--block-size" | "--segment-size" => {
+                        match args.next().and_then(|s| parse_size(s)) {
+                            Some(size) if size > 0 => size,
+                            _ => return Some(usage()),
+                        }
+                    }
+                    _ => {
+                        paths.push(arg.as_str());
+                        continue;
+                    }
+                };
+                if arg == "--block-size" {
+                    options.block_size = size;
+                } else {
+                    options.segment_size = Some(size);
+                }
+            }
+            match paths.as_slice() {
+                [bag, dest] => tape(&bag_path(bag), Path::new(dest), &options),
+                _ => usage(),
+            }
+        }
+        "serialize" => {
+            let mut options = settings.serial_options();
+            let mut recipients = Vec::new();
+            let mut profile = None;
+            let mut paths = Vec::new();
+            let mut args = rest.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--format" => match args.next().and_then(|f| SerialFormat::parse(f)) {
+                        Some(f) => options.format = f,
+                        None => return Some(usage()),
+                    },
+                    "--reproducible" => options.reproducible = true,
+                    "--split" => match args.next().map(String::as_str) {
+                        Some("none") => options.volume_size = None,
+                        Some(size) => match parse_size(size) {
+                            Some(size) if size > 0 => options.volume_size = Some(size),
+                            _ => return Some(usage()),
+                        },
+                        None => return Some(usage()),
+                    },
+                    "--encrypt" => match args.next().map(String::as_str) {
+                        Some("none") => options.encryption = None,
+                        Some(tool) => match EncryptionTool::parse(tool) {
+                            Some(tool) => {
+                                options.encryption = Some(Encryption {
+                                    tool,
+                                    recipients: settings.encryption_recipients.clone(),
+                                })
+                            }
+                            None => return Some(usage()),
+                        },
+                        None => return Some(usage()),
+                    },
+                    "--recipient" => match args.next() {
+                        Some(recipient) => recipients.push(recipient.clone()),
+                        None => return Some(usage()),
+                    },
+                    "--level" => match args.next().and_then(|n| n.parse().ok()) {
+                        Some(n) if (1..=22).contains(&n) => options.zstd_level = n,
+                        _ => return Some(usage()),
+                    },
+                    "--threads" => match args.next().and_then(|n| n.parse().ok()) {
+                        Some(n) if n > 0 => options.threads = n,
+                        _ => return Some(usage()),
+                    },
+                    "--profile" => match profile_arg(args.next()) {
+                        Ok(p) => profile = Some(p),
+                        Err(code) => return Some(code),
+                    },
+                    _ => paths.push(arg.as_str()),
+                }
+            }
+            if let Some(encryption) = options
+                .encryption
+                .as_mut()
+                .filter(|_| !recipients.is_empty())
+            {
+                encryption.recipients = recipients;
+            }
+            let problem =
+                profile.and_then(|p| p.check_serialization(Some(options.format.mime_type())));
+            if let Some(problem) = problem {
+                eprintln!("error: the bag's BagIt Profile says {}", problem);
+                return Some(1);
+            }
+            match paths.as_slice() {
+                [bag, dest] => serialize(&bag_path(bag), Path::new(dest), &options),
+                _ => usage(),
+            }
+        }
+        "store" => match rest {
+            [bag, store] => export(&bag_path(bag), Path::new(store), settings.locale),
+            _ => usage(),
+        },
+        "discs" => {
+            let mut capacity = DISC_MEDIA[0].1;
+            let mut layout = DiscLayout::SubBags;
+            let mut paths = Vec::new();
+            let mut args = rest.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--media" => {
+                        let media = args.next().map(String::as_str).unwrap_or_default();
+                        match DISC_MEDIA.iter().find(|(name, _)| *name == media) {
+                            Some((_, size)) => capacity = *size,
+                            None => return Some(usage()),
+                        }
+                    }
+                    "--capacity" => match args.next().and_then(|s| parse_size(s)) {
+                        Some(size) => capacity = size,
+                        None => return Some(usage()),
+                    },
+                    "--folders" => layout = DiscLayout::Folders,
+                    _ => paths.push(arg.as_str()),
+                }
+            }
+            match paths.as_slice() {
+                [bag, dest] => discs(&bag_path(bag), Path::new(dest), capacity, layout, &settings),
+                _ => usage(),
+            }
+        }
+        "split" => match rest {
+            [flag, size, folder, dest] if flag == "--size" => match parse_size(size) {
+                Some(size) if size > 0 => {
+                    split(Path::new(folder), Path::new(dest), size, &settings)
+                }
+                _ => usage(),
+            },
+            _ => usage(),
+        },
+        "reassemble" => {
+            let mut bags = Vec::new();
+            let mut dest = None;
+            let mut args = rest.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--to" => match args.next() {
+                        Some(to) => dest = Some(Path::new(to)),
+                        None => return Some(usage()),
+                    },
+                    _ => bags.push(bag_path(arg)),
+                }
+            }
+            match (bags.is_empty(), dest) {
+                (true, _) => usage(),
+                (false, Some(dest)) => {
+                    let options = settings.bag_options(false);
+                    let validation = settings.validation_options();
+                    let result = reassemble_group(&bags, dest, &options, &validation, None);
+                    print_bag_result(dest, result)
+                }
+                (false, None) => check(&bags, &settings),
+            }
+        }
+        "fixture" => {
+            let mut spec = TreeSpec::default();
+            let mut broken = None;
+            let mut paths = Vec::new();
+            let mut args = rest.iter();
+            while let Some(arg) = args.next() {
+                match arg.as_str() {
+                    "--unicode" => spec.unicode_names = true,
+                    "--symlinks" => spec.symlinks = true,
+                    "--size" => match args.next().and_then(|s| parse_size(s)) {
+                        Some(size) => spec.file_size = size,
+                        None => return Some(usage()),
+                    },
+                    "--broken" => match args.next().and_then(|kind| Breakage::parse(kind)) {
+                        Some(breakage) => broken = Some(breakage),
+                        None => return Some(usage()),
+                    },
+                    "--files" | "--depth" | "--seed" => {
+                        let n: u64 = match args.next().and_then(|n| n.parse().ok()) {
+                            Some(n) => n,
+                            None => return Some(usage()),
+                        };
+                        match arg.as_str() {
+                            "--files" => spec.files = n as usize,
+                            "--depth" => spec.depth = n as usize,
+                            _ => spec.seed = n,
+                        }
+                    }
+                    _ => paths.push(arg.as_str()),
+                }
+            }
+            match paths.as_slice() {
+                [dest] => fixture(Path::new(dest), &spec, broken),
+                _ => usage(),
+            }
+        }
+        "intake" => match rest {
+            [staging] => intake(DEFAULT_INTAKE_ADDRESS, Path::new(staging), settings),
+            [flag, address, staging] if flag == "--listen" => {
+                intake(address, Path::new(staging), settings)
+            }
+            _ => usage(),
+        },
+        "help" | "--help" | "-h" => {
+            println!("{}", USAGE);
+            0
+        }
+        _ => return None,
+    };
+    Some(code)
+}
+
+fn usage() -> i32 {
+    eprintln!("{}", USAGE);
+    2
+}
+
+/// Loads the BagIt Profile (file, URL or saved name) given after `--profile`.
+fn profile_arg(source: Option<&String>) -> Result<Profile, i32> {
+    let Some(source) = source else {
+        return Err(usage());
+    };
+    Profile::load(source).map_err(|e| {
+        eprintln!("error: {}", e);
+        2
+    })
+}
+
+/// Makes new bags be made under the saved profile `name`, or none if it's empty.
+fn use_profile(name: String, mut settings: Settings) -> i32 {
+    settings.bag_profile = name;
+    match settings.save() {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+/// Bags the resources in the URL list at `list` into `dest`.
+fn download(list: &Path, dest: &Path, concurrency: usize, settings: &Settings) -> i32 {
+    let entries = match std::fs::read_to_string(list) {
+        Ok(text) => parse_url_list(&text),
+        Err(e) => {
+            eprintln!("{}: {}", list.display(), e);
+            return 1;
+        }
+    };
+    let result = entries.and_then(|entries| {
+        bag_from_urls(
+            &entries,
+            dest,
+            &settings.bag_options(true),
+            concurrency,
+            None,
+        )
+    });
+    print_bag_result(dest, result)
+}
+
+/// Bags the files `list` names into `dest`. A list ending `.csv` is read as CSV
+/// whether or not `csv` is set.
+fn bag_list(list: &Path, dest: &Path, csv: bool, settings: &Settings) -> i32 {
+    let csv = csv
+        || list
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("csv"));
+    let entries = match std::fs::read_to_string(list) {
+        Ok(text) => parse_file_list(&text, csv),
+        Err(e) => {
+            eprintln!("{}: {}", list.display(), e);
+            return 1;
+        }
+    };
+    let result = entries
+        .and_then(|entries| bag_from_list(&entries, dest, &settings.bag_options(true), None));
+    print_bag_result(dest, result)
+}
+
+/// Writes the bag at `bag` into `dest` as tar segments for tape.
+fn tape(bag: &Path, dest: &Path, options: &TapeOptions) -> i32 {
+    match write_tape(bag, dest, options, None) {
+        Ok(segments) => {
+            for segment in &segments {
+                println!("{}", segment.display());
+            }
+            let name = bag
+                .file_name()
+                .map(|n| n.to_string_lossy())
+                .unwrap_or_default();
+            let index = dest.join(format!("{}.{}", name, TAPE_INDEX_FILE));
+            println!("Index: {}", index.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+/// Signs the tag manifest of the bag at `bag`, replacing any earlier signature.
+fn sign(bag: &Path, signing: &Signing) -> i32 {
+    match sign_bag(bag, signing) {
+        Ok(signature) => {
+            println!("{}", signature.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+fn serialize(bag: &Path, dest: &Path, options: &SerialOptions) -> i32 {
+    match serialize_bag(bag, dest, options, None) {
+        Ok(path) => {
+            println!("{}", path.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+/// Reassembles a serialized bag split into volumes, checking each one on the way.
+fn join(volume: &Path, dest: Option<&Path>) -> i32 {
+    match join_volumes(volume, dest) {
+        Ok(path) => {
+            println!("{}", path.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+/// Copies the payload of the bag at `bag` into the content-addressed store at `store`.
+fn export(bag: &Path, store: &Path, locale: Locale) -> i32 {
+    match export_to_store(bag, store, None) {
+        Ok(report) => {
+            println!(
+                "{} objects written ({}), {} already stored",
+                locale.count(report.objects_written as u64),
+                locale.bytes(report.bytes_written),
+                locale.count(report.objects_present as u64)
+            );
+            println!("Map: {}", report.map.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+/// Splits the bag at `bag` across discs of `capacity` bytes, laid out under `dest`.
+fn discs(bag: &Path, dest: &Path, capacity: u64, layout: DiscLayout, settings: &Settings) -> i32 {
+    let result = plan_discs(bag, capacity).and_then(|plan| {
+        let discs = write_discs(bag, dest, &plan, layout, &settings.bag_options(false), None)?;
+        Ok((plan, discs))
+    });
+    match result {
+        Ok((plan, discs)) => {
+            let locale = settings.locale;
+            for (volume, disc) in plan.iter().zip(&discs) {
+                println!(
+                    "{}: {} files, {}",
+                    disc.display(),
+                    locale.count(volume.files.len() as u64),
+                    locale.bytes(volume.bytes)
+                );
+            }
+            println!("Labels: {}", dest.join(DISC_LABELS_FILE).display());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+/// Splits the folder at `folder` into bags of at most `size` bytes under `dest`.
+fn split(folder: &Path, dest: &Path, size: u64, settings: &Settings) -> i32 {
+    match split_into_bags(folder, dest, size, &settings.bag_options(true), None) {
+        Ok(bags) => {
+            for bag in &bags {
+                println!("{}", bag.display());
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+/// Checks that `bags` make a whole, valid bag group.
+fn check(bags: &[PathBuf], settings: &Settings) -> i32 {
+    match check_group(bags, &settings.validation_options(), None) {
+        Ok(bags) => {
+            println!("Whole group of {} valid bags", bags.len());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+/// Writes a synthetic tree, or a bag broken in the given way, for testing pipelines.
+fn fixture(dest: &Path, spec: &TreeSpec, broken: Option<Breakage>) -> i32 {
+    let result = match broken {
+        Some(breakage) => broken_bag(dest, breakage).map(|_| ()),
+        None => generate_tree(dest, spec)
+            .map(|_| ())
+            .map_err(BagError::IoError),
+    };
+    match result {
+        Ok(()) => {
+            println!("{}", dest.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+/// Binds `address` for the agent or the intake endpoint, with the token clients must
+/// give (`BAGGIE_AGENT_TOKEN`, or Agent-Token in the settings). Without a token it
+/// only listens on this machine.
+fn listen(address: &str, settings: &Settings) -> Result<(TcpListener, String), String> {
+    let token =
+        std::env::var("BAGGIE_AGENT_TOKEN").unwrap_or_else(|_| settings.agent_token.clone());
+    let listener =
+        TcpListener::bind(address).map_err(|e| format!("can't listen on {}: {}", address, e))?;
+    let local = listener
+        .local_addr()
+        .map(|a| a.ip().is_loopback())
+        .unwrap_or(false);
+    if token.is_empty() && !local {
+        return Err(
+            "set BAGGIE_AGENT_TOKEN (or Agent-Token in the settings) before listening \
+                    beyond this machine"
+                .to_string(),
+        );
+    }
+    Ok((listener, token))
+}
+
+/// Serves remote jobs until killed.
+fn agent(address: &str, settings: Settings) -> i32 {
+    let (listener, token) = match listen(address, &settings) {
+        Ok(bound) => bound,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return 1;
+        }
+    };
+    println!("Agent listening on {}", address);
+    match serve(listener, settings, token) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+/// Accepts uploads into `staging` and bags each finished one, until killed.
+fn intake(address: &str, staging: &Path, settings: Settings) -> i32 {
+    let (listener, token) = match listen(address, &settings) {
+        Ok(bound) => bound,
+        Err(message) => {
+            eprintln!("error: {}", message);
+            return 1;
+        }
+    };
+    println!(
+        "Accepting uploads on http://{} into {}",
+        address,
+        staging.display()
+    );
+    let intake = Intake {
+        staging: staging.to_path_buf(),
+        settings,
+        token,
+    };
+    match serve_intake(listener, intake) {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+fn import_settings(file: &Path, settings: &Settings) -> i32 {
+    let bundle = match std::fs::read_to_string(file) {
+        Ok(bundle) => bundle,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return 1;
+        }
+    };
+    let Some(imported) = settings.with_bundle(&bundle) else {
+        eprintln!("error: {} is not a Baggie settings bundle", file.display());
+        return 1;
+    };
+    match imported.save() {
+        Ok(()) => {
+            println!("Imported settings from {}", file.display());
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+fn bag(folder: &Path, options: &BagOptions) -> i32 {
+    let result = bag_directory(folder, options, None);
+    if let Err(BagError::AlreadyABag(_)) = result {
+        eprintln!("error: {}", result.unwrap_err());
+        eprintln!(
+            "hint: `baggie validate` checks it, `baggie update` refreshes its manifests, \
+             and `baggie bag <folder> --to <new folder>` bags a copy"
+        );
+        return 1;
+    }
+    print_bag_result(folder, result)
+}
+
+fn unbag(bag: &Path) -> i32 {
+    match unbag_directory(bag) {
+        Ok(()) => {
+            println!("Unbagged {}", bag.display());
+            let mut inventory = Inventory::load();
+            inventory.forget(bag);
+            let _ = inventory.save();
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+fn restore(bag: &Path) -> i32 {
+    if !bag.join(FILE_PERMISSIONS_FILE).is_file() {
+        eprintln!("error: {} has no {}", bag.display(), FILE_PERMISSIONS_FILE);
+        return 1;
+    }
+    match restore_permissions(bag) {
+        Ok(restored) => {
+            println!("Restored the permissions of {} files", restored.files);
+            if restored.owners_kept > 0 {
+                println!(
+                    "warning: {} files kept their owner, as only root can change it",
+                    restored.owners_kept
+                );
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+fn oxum(bag: &Path) -> i32 {
+    let check = match verify_oxum(bag) {
+        Ok(check) => check,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return 2;
+        }
+    };
+    let (bytes, count) = check.actual;
+    match check.declared {
+        _ if check.matches() => println!("Payload-Oxum matches: {}.{}", bytes, count),
+        Some((declared_bytes, declared_count)) => println!(
+            "Payload-Oxum MISMATCH: bag-info.txt says {}.{}, data/ holds {}.{}",
+            declared_bytes, declared_count, bytes, count
+        ),
+        None => println!(
+            "No Payload-Oxum in bag-info.txt; data/ holds {}.{}",
+            bytes, count
+        ),
+    }
+    match check.matches() {
+        true => 0,
+        false => 1,
+    }
+}
+
+/// Lists the payload files added (`+`), removed (`-`) and changed (`~`) between the
+/// two bags; exits 1 if there are any.
+fn diff(original: &Path, copy: &Path, locale: Locale) -> i32 {
+    let diff = match compare_bags(original, copy, None) {
+        Ok(diff) => diff,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return 2;
+        }
+    };
+    for (marker, files) in [
+        ("+", &diff.added),
+        ("-", &diff.removed),
+        ("~", &diff.changed),
+    ] {
+        for file in files {
+            println!("{} {}", marker, file);
+        }
+    }
+    println!(
+        "{} added, {} removed, {} changed, {} the same, by {} manifests",
+        locale.count(diff.added.len() as u64),
+        locale.count(diff.removed.len() as u64),
+        locale.count(diff.changed.len() as u64),
+        locale.count(diff.unchanged as u64),
+        diff.algorithm.name()
+    );
+    if diff.hashed > 0 {
+        println!(
+            "(no algorithm in common: {} files of the second bag were hashed)",
+            diff.hashed
+        );
+    }
+    match diff.is_identical() {
+        true => 0,
+        false => 1,
+    }
+}
+
+fn info(bag: &Path) -> i32 {
+    let bag = match Bag::open(bag) {
+        Ok(bag) => bag,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return 2;
+        }
+    };
+    let algorithms: Vec<&str> = bag.algorithms.iter().map(|a| a.name()).collect();
+    println!("BagIt {} ({})", bag.version, bag.encoding);
+    println!(
+        "{} payload files listed in {}, Payload-Oxum {}.{}",
+        bag.manifest.len(),
+        algorithms.join(", "),
+        bag.payload_oxum.0,
+        bag.payload_oxum.1
+    );
+    let tag_algorithms: Vec<&str> = bag.tag_algorithms.iter().map(|a| a.name()).collect();
+    match tag_algorithms.is_empty() {
+        true => println!("No tag manifests"),
+        false => println!(
+            "{} tag files listed in {}",
+            bag.tag_manifest.len(),
+            tag_algorithms.join(", ")
+        ),
+    }
+    for (label, value) in bag.bag_info.fields() {
+        println!("{}: {}", label, value);
+    }
+    0
+}
+
+fn print_bag_result(folder: &Path, result: Result<BagReport, BagError>) -> i32 {
+    match result {
+        Ok(report) => {
+            let bag = &report.bag;
+            let algorithms: Vec<&str> = bag.algorithms.iter().map(|a| a.name()).collect();
+            println!(
+                "Bagged {}: {} files, {} ({})",
+                bag.path.display(),
+                bag.payload_oxum.1,
+                bag.bag_info.get("Bag-Size").unwrap_or_default(),
+                algorithms.join(", ")
+            );
+            if let Some(id) = bag.bag_info.get("Internal-Sender-Identifier") {
+                println!("identifier: {}", id);
+            }
+            let mut inventory = Inventory::load();
+            if inventory.register(folder).is_ok() {
+                let _ = inventory.save();
+            }
+            for skipped in &report.skipped {
+                println!("skipped: {}", skipped.display());
+            }
+            for vanished in &report.vanished {
+                println!("vanished: {}", vanished.display());
+            }
+            for warning in &report.warnings {
+                println!("warning: {}", warning);
+            }
+            if !report.duplicates.is_empty() {
+                let wasted = report.duplicates.iter().map(|d| d.wasted()).sum();
+                println!(
+                    "{} sets of identical files; keeping one of each would save {}",
+                    report.duplicates.len(),
+                    bag_size(wasted)
+                );
+                for duplicates in &report.duplicates {
+                    let hardlinked = match duplicates.copies {
+                        1 => " (hardlinks)",
+                        _ => "",
+                    };
+                    println!("identical{}: {}", hardlinked, duplicates.files.join(", "));
+                }
+            }
+            0
+        }
+        Err(e) => {
+            eprintln!("error: {}", e);
+            1
+        }
+    }
+}
+
+fn stats(csv: bool, locale: Locale) -> i32 {
+    let stats = Inventory::load().stats();
+    if csv {
+        print!("{}", stats.to_csv());
+        return 0;
+    }
+    println!(
+        "{} bags, {} files, {}",
+        locale.count(stats.total.bags),
+        locale.count(stats.total.files),
+        locale.bytes(stats.total.bytes)
+    );
+    for (month, tally) in stats.cumulative_by_month() {
+        println!(
+            "{}: {} bags, {} total",
+            locale.date(&month),
+            locale.count(tally.bags),
+            locale.bytes(tally.bytes)
+        );
+    }
+    0
+}
+
+/// Re-checks the inventoried bags that are due (or, with `all`, every one) and adds
+/// the outcomes to their audit history, for running from cron or Task Scheduler.
+/// Checks that are due sample the payload as set in Settings.
+fn audit(all: bool, settings: &Settings) -> i32 {
+    let mut inventory = Inventory::load();
+    let today = chrono::Local::now().date_naive();
+    let bags: Vec<PathBuf> = if all {
+        inventory.entries.iter().map(|e| e.path.clone()).collect()
+    } else {
+        let interval = settings.audit_interval_days;
+        inventory
+            .due(today, interval)
+            .into_iter()
+            .map(|e| e.path.clone())
+            .collect()
+    };
+    let mut failed = 0;
+    for bag in &bags {
+        let mut options = settings.validation_options();
+        if !all && settings.audit_sample_percent > 0 {
+            options.sample = Some(Sample::new(settings.audit_sample_percent as f64));
+        }
+        let result = validate_bag(bag, &options, None).map_err(|e| e.to_string());
+        let audit = AuditRecord::new(&result, !all);
+        match audit.status {
+            FixityStatus::Failed => {
+                failed += 1;
+                println!("{}: failed", bag.display());
+                for failure in &audit.failures {
+                    println!("  {}", failure);
+                }
+            }
+            _ => println!("{}: ok", bag.display()),
+        }
+        inventory.record_check(bag, audit);
+        if let Err(e) = inventory.save() {
+            eprintln!("error: can't save the inventory: {}", e);
+            return 2;
+        }
+    }
+    println!("{} bags checked, {} failed", bags.len(), failed);
+    i32::from(failed > 0)
+}
+
+/// Prints the audit history of every inventoried bag, newest first.
+fn audit_log(csv: bool, locale: Locale) -> i32 {
+    let inventory = Inventory::load();
+    if csv {
+        print!("{}", inventory.audits_csv());
+        return 0;
+    }
+    for (entry, audit) in inventory.audits() {
+        println!(
+            "{} {}  {}  {}",
+            locale.date(audit.date()),
+            audit.time.get(11..).unwrap_or_default(),
+            match audit.status {
+                FixityStatus::Failed => "failed",
+                _ => "ok    ",
+            },
+            entry.path.display()
+        );
+        for failure in &audit.failures {
+            println!("    {}", failure);
+        }
+    }
+    0
+}
+
+/// A bag named on the command line: a path, or the Internal-Sender-Identifier (or
+/// identifier) of a bag in the inventory.
+fn bag_path(arg: &str) -> PathBuf {
+    let path = PathBuf::from(arg);
+    if path.exists() {
+        return path;
+    }
+    Inventory::load()
+        .find(arg)
+        .map(|e| e.path.clone())
+        .unwrap_or(path)
+}
+
+/// Prints the report as text, or with `json` as one JSON object.
+fn validate(bag: &Path, options: &ValidationOptions, json: bool) -> i32 {
+    let result = if bag.is_file() {
+        validate_archive(bag, options, None)
+    } else {
+        validate_bag(bag, options, None)
+    };
+    let report = match result {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            return 2;
+        }
+    };
+
+    match json {
+        true => print!("{}", report.to_json()),
+        false => print!("{}", report.to_text()),
+    }
+    if report.is_valid() {
+        0
+    } else {
+        1
+    }
+}
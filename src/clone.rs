@@ -0,0 +1,227 @@
+use crate::bagit::{
+    bag_encodes_paths, bagging_timestamp, copy_file, read_payload_manifest, refresh_tag_manifest,
+    Bag, BagError, BagOptions, BagReport, BagSummary, ChecksumAlgorithm, Digester, Progress,
+};
+use crate::dedup::{parse_fetch_file, FETCH_FILE};
+use crate::events::{append_events, Event, EventType};
+use crate::tagfile::{read_tag_file, write_tag_file, BagInfo};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::mpsc::Sender;
+use walkdir::WalkDir;
+
+/// bag-info.txt label naming the bag a clone was copied from.
+pub const CLONED_FROM_LABEL: &str = "Cloned-From";
+
+/// Copies `source` to `dest` with its modification time, returning the checksum of
+/// what was written.
+pub fn copy_hashing(
+    source: &Path,
+    dest: &Path,
+    algorithm: ChecksumAlgorithm,
+) -> io::Result<String> {
+    let mut input = File::open(source)?;
+    let modified = input.metadata()?.modified();
+    let mut output = BufWriter::new(File::create(dest)?);
+    let mut hasher = Digester::new(algorithm);
+    let mut buffer = [0u8; 65536];
+    loop {
+        let bytes_read = input.read(&mut buffer)?;
+        if bytes_read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..bytes_read]);
+        output.write_all(&buffer[..bytes_read])?;
+    }
+    output.flush()?;
+    if let Ok(modified) = modified {
+        output.get_ref().set_modified(modified)?;
+    }
+    Ok(hasher.finish())
+}
+
+/// Copies the bag at `source` to `dest` as a bag of its own: a new
+/// Internal-Sender-Identifier and Bagging-Date, and a `Cloned-From` field naming the
+/// original. The payload is untouched, so only bag-info.txt and the tag manifest
+/// change. With `verify`, every payload file is checked against the manifest as it's
+/// copied.
+pub fn clone_bag(
+    source: &Path,
+    dest: &Path,
+    options: &BagOptions,
+    verify: bool,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<BagReport, BagError> {
+    if !source.join("bagit.txt").is_file() {
+        return Err(BagError::NotABag);
+    }
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        return Err(BagError::DestinationNotEmpty(dest.to_path_buf()));
+    }
+    let (algorithm, manifest) = read_payload_manifest(source)?;
+    let summary = BagSummary::read(source)?;
+
+    let files: Vec<_> = WalkDir::new(source)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .collect();
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started {
+            total_files: files.len(),
+        });
+    }
+    for (i, entry) in files.iter().enumerate() {
+        let relative = entry.path().strip_prefix(source).unwrap();
+        let name = relative.to_string_lossy().replace('\\', "/");
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Copying {
+                current: i + 1,
+                filename: name.clone(),
+            });
+        }
+        let target = dest.join(relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        match manifest.get(&name).filter(|_| verify) {
+            Some(expected) => {
+                if copy_hashing(entry.path(), &target, algorithm)? != *expected {
+                    return Err(BagError::CopyMismatch(target));
+                }
+            }
+            None => {
+                copy_file(entry.path(), &target)?;
+            }
+        }
+    }
+    // Everything in the manifest must have been there to copy, bar fetch.txt entries
+    if verify {
+        let fetched = match read_tag_file(&source.join(FETCH_FILE)) {
+            Ok((content, _)) => parse_fetch_file(&content, bag_encodes_paths(source)),
+            Err(_) => Vec::new(),
+        };
+        let missing = manifest.keys().find(|file| {
+            !dest.join(file).is_file() && !fetched.iter().any(|entry| entry.file == **file)
+        });
+        if let Some(missing) = missing {
+            return Err(BagError::FileVanished(source.join(missing)));
+        }
+    }
+
+    // New identity, with a link back to the original
+    let (old_info, _) = read_tag_file(&source.join("bag-info.txt"))?;
+    let old_info = BagInfo::parse(&old_info);
+    let origin = old_info
+        .get("Internal-Sender-Identifier")
+        .unwrap_or(&summary.identifier);
+    let (date, time) = bagging_timestamp(options.date_mode);
+    let mut bag_info = BagInfo::default();
+    bag_info.push("Bagging-Date", date);
+    if options.include_bagging_time {
+        bag_info.push("Bagging-Time", time);
+    }
+    for (label, value) in old_info.fields() {
+        let replaced = ["Internal-Sender-Identifier", "Bagging-Date", "Bagging-Time"]
+            .iter()
+            .any(|l| l.eq_ignore_ascii_case(label));
+        if !replaced {
+            bag_info.push(label.clone(), value.clone());
+        }
+    }
+    bag_info.push(
+        "Internal-Sender-Identifier",
+        options.id_scheme.generate(options.id_sequence_next),
+    );
+    bag_info.push(
+        CLONED_FROM_LABEL,
+        format!(
+            "{} (tagmanifest-sha256 {})",
+            origin, summary.tagmanifest_sha256
+        ),
+    );
+    write_tag_file(&dest.join("bag-info.txt"), &bag_info.serialize())?;
+
+    // Only bag-info.txt changed, so only its tag manifest line needs redoing
+    refresh_tag_manifest(dest, &["bag-info.txt"])?;
+    let mut events = vec![Event::now(
+        EventType::Replication,
+        format!("Cloned from {} at {}", origin, source.display()),
+    )];
+    if verify {
+        events.push(Event::now(
+            EventType::FixityCheck,
+            "Every payload file matched the manifest as it was copied",
+        ));
+    }
+    append_events(dest, &events)?;
+
+    let report = BagReport {
+        bag: Bag::open(dest)?,
+        ..Default::default()
+    };
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Done {
+            path: dest.to_path_buf(),
+            report: Box::new(report.clone()),
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{bag_directory, validate_bag, ValidationOptions};
+
+    #[test]
+    fn test_clone_bag() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_clone");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("original");
+        fs::create_dir_all(bag.join("scans")).unwrap();
+        fs::write(bag.join("scans/p1.tif"), "page one").unwrap();
+        let mut metadata = BagInfo::default();
+        metadata.push("Source-Organization", "County Archive");
+        metadata.push("Internal-Sender-Identifier", "ORIG-1");
+        let options = BagOptions {
+            metadata,
+            ..BagOptions::default()
+        };
+        bag_directory(&bag, &options, None).unwrap();
+        fs::write(bag.join("notes.txt"), "a tag file").unwrap();
+
+        let copy = temp_dir.join("copy");
+        clone_bag(&bag, &copy, &BagOptions::default(), true, None).unwrap();
+        let report = validate_bag(&copy, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert!(copy.join("notes.txt").is_file());
+
+        let info = BagInfo::parse(&fs::read_to_string(copy.join("bag-info.txt")).unwrap());
+        assert_eq!(info.get("Source-Organization"), Some("County Archive"));
+        let identifier = info.get("Internal-Sender-Identifier").unwrap();
+        assert_ne!(identifier, "ORIG-1");
+        assert_eq!(identifier.len(), 36);
+        assert!(info
+            .get(CLONED_FROM_LABEL)
+            .unwrap()
+            .starts_with("ORIG-1 (tagmanifest-sha256 "));
+        assert!(matches!(
+            clone_bag(&bag, &copy, &BagOptions::default(), false, None),
+            Err(BagError::DestinationNotEmpty(_))
+        ));
+
+        // A damaged original is caught while copying
+        fs::write(bag.join("data/scans/p1.tif"), "page 0ne").unwrap();
+        let again = temp_dir.join("again");
+        assert!(matches!(
+            clone_bag(&bag, &again, &BagOptions::default(), true, None),
+            Err(BagError::CopyMismatch(_))
+        ));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,286 @@
+use crate::bagit::{
+    decode_manifest_path, read_manifest, BagError, BagOptions, BagReport, ChecksumAlgorithm,
+    DedupMode,
+};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Tag file listing payload files to fetch from elsewhere (RFC 8493 §2.2.3).
+pub const FETCH_FILE: &str = "fetch.txt";
+
+/// Tag file listing payload files already stored in other bags.
+pub const DEDUP_REPORT_FILE: &str = "dedup-report.txt";
+
+/// One line of fetch.txt: `<url> <length or -> <data/path>`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchEntry {
+    pub url: String,
+    pub size: Option<u64>,
+    pub file: String,
+}
+
+impl FetchEntry {
+    /// The file a `file:` URL points to, if that's what it is.
+    pub fn local_copy(&self) -> Option<PathBuf> {
+        let path = percent_decode(self.url.strip_prefix("file://")?)?;
+        // file:///C:/... on Windows
+        let path = match path.strip_prefix('/') {
+            Some(rest) if rest.get(1..2) == Some(":") => rest.to_string(),
+            _ => path,
+        };
+        Some(PathBuf::from(path))
+    }
+}
+
+/// Reads fetch.txt lines, skipping any that don't parse. `encoded` paths (BagIt 1.0)
+/// are decoded.
+pub fn parse_fetch_file(content: &str, encoded: bool) -> Vec<FetchEntry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let mut parts = line.splitn(3, ' ');
+            let url = parts.next()?.to_string();
+            let size = parts.next()?;
+            let file = parts.next()?.trim();
+            let file = match encoded {
+                true => decode_manifest_path(file),
+                false => file.to_string(),
+            };
+            (!file.is_empty()).then(|| FetchEntry {
+                url,
+                size: size.parse().ok(),
+                file,
+            })
+        })
+        .collect()
+}
+
+/// A `file://` URL for `path`.
+pub fn file_url(path: &Path) -> String {
+    let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+    let path = path.to_string_lossy().replace('\\', "/");
+    let mut url = String::from("file://");
+    if !path.starts_with('/') {
+        url.push('/');
+    }
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' | b'/' | b':' => {
+                url.push(byte as char)
+            }
+            _ => url.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    url
+}
+
+fn percent_decode(text: &str) -> Option<String> {
+    let mut bytes = Vec::new();
+    let mut rest = text.as_bytes();
+    while let Some((&byte, tail)) = rest.split_first() {
+        if byte == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(byte);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+/// Payload files of other bags by digest, read from their manifests in one algorithm.
+/// Only files still in the bag count, so a reference never points at another reference.
+struct DedupIndex {
+    by_digest: HashMap<String, PathBuf>,
+}
+
+impl DedupIndex {
+    fn build(bags: &[PathBuf], except: &Path, algorithm: ChecksumAlgorithm) -> Self {
+        let except = std::path::absolute(except).unwrap_or_else(|_| except.to_path_buf());
+        let mut by_digest = HashMap::new();
+        for bag in bags.iter().filter(|b| **b != except) {
+            let Ok(manifest) = read_manifest(bag, algorithm) else {
+                continue;
+            };
+            for (file, digest) in manifest {
+                let copy = bag.join(file);
+                if copy.is_file() {
+                    by_digest.entry(digest).or_insert(copy);
+                }
+            }
+        }
+        Self { by_digest }
+    }
+}
+
+/// Looks for each payload file (`(digest, data/path, size)`) in the bags named in
+/// `options`, and deals with the ones found as `options.dedup` says. Returns the tag
+/// file written, if any.
+pub fn dedup_payload(
+    bag: &Path,
+    options: &BagOptions,
+    payload: &[(String, String, u64)],
+    report: &mut BagReport,
+) -> Result<Vec<String>, BagError> {
+    if options.dedup == DedupMode::Off || options.dedup_against.is_empty() {
+        return Ok(Vec::new());
+    }
+    let index = DedupIndex::build(
+        &options.dedup_against,
+        bag,
+        options.manifest_algorithms()[0],
+    );
+    let duplicates: Vec<(&(String, String, u64), &PathBuf)> = payload
+        .iter()
+        .filter_map(|p| Some((p, index.by_digest.get(&p.0)?)))
+        .collect();
+    if duplicates.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let mut lines = String::new();
+    let name = match options.dedup {
+        DedupMode::Fetch => {
+            for ((_, file, size), copy) in &duplicates {
+                let listed = options.bagit_version.listed_path(file);
+                lines.push_str(&format!("{} {} {}\n", file_url(copy), size, listed));
+                fs::remove_file(bag.join(file))?;
+            }
+            report.warnings.push(format!(
+                "{} files are already stored in other bags, so fetch.txt points to them instead of copying them",
+                duplicates.len()
+            ));
+            FETCH_FILE
+        }
+        _ => {
+            for ((_, file, _), copy) in &duplicates {
+                lines.push_str(&format!("{}  {}\n", file, copy.display()));
+            }
+            report.warnings.push(format!(
+                "{} files are already stored in other bags (listed in {})",
+                duplicates.len(),
+                DEDUP_REPORT_FILE
+            ));
+            DEDUP_REPORT_FILE
+        }
+    };
+    fs::write(bag.join(name), lines)?;
+    Ok(vec![name.to_string()])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{
+        bag_directory, update_bag, validate_bag, ValidationMode, ValidationOptions,
+    };
+
+    #[test]
+    fn test_file_urls() {
+        let entry = FetchEntry {
+            url: file_url(Path::new("/archive/bag one/data/a%b.tif")),
+            size: None,
+            file: "data/a.tif".to_string(),
+        };
+        assert_eq!(entry.url, "file:///archive/bag%20one/data/a%25b.tif");
+        assert_eq!(
+            entry.local_copy(),
+            Some(PathBuf::from("/archive/bag one/data/a%b.tif"))
+        );
+        let windows = FetchEntry {
+            url: "file:///C:/bags/a.tif".to_string(),
+            ..entry
+        };
+        assert_eq!(windows.local_copy(), Some(PathBuf::from("C:/bags/a.tif")));
+    }
+
+    #[test]
+    fn test_dedup_against_earlier_bag() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_dedup");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let first = temp_dir.join("first");
+        let second = temp_dir.join("second");
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        fs::write(first.join("master.tif"), "the same scan").unwrap();
+        fs::write(second.join("100% copy.tif"), "the same scan").unwrap();
+        fs::write(second.join("new.txt"), "only here").unwrap();
+        bag_directory(&first, &BagOptions::default(), None).unwrap();
+
+        let options = BagOptions {
+            dedup: DedupMode::Fetch,
+            dedup_against: vec![std::path::absolute(&first).unwrap()],
+            ..BagOptions::default()
+        };
+        let report = bag_directory(&second, &options, None).unwrap();
+        assert_eq!(report.warnings.len(), 1);
+        assert!(!second.join("data/100% copy.tif").exists());
+        let fetch = fs::read_to_string(second.join(FETCH_FILE)).unwrap();
+        assert!(fetch.ends_with(" 13 data/100%25 copy.tif\n"));
+        let fetch = parse_fetch_file(&fetch, true);
+        assert_eq!(fetch.len(), 1);
+        assert_eq!(fetch[0].file, "data/100% copy.tif");
+        assert_eq!(fetch[0].size, Some(13));
+        let manifest = fs::read_to_string(second.join("manifest-sha256.txt")).unwrap();
+        assert!(manifest.contains("data/100%25 copy.tif"));
+        let tagmanifest = fs::read_to_string(second.join("tagmanifest-sha256.txt")).unwrap();
+        assert!(tagmanifest.contains("fetch.txt"));
+
+        // Checked against the other bag's copy: fine leniently, incomplete strictly
+        let report = validate_bag(&second, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert_eq!(report.warnings.len(), 1);
+        let strict = ValidationOptions {
+            mode: ValidationMode::Strict,
+            ..ValidationOptions::default()
+        };
+        assert!(!validate_bag(&second, &strict, None).unwrap().is_valid());
+        assert!(matches!(
+            update_bag(&second, &BagOptions::default(), None),
+            Err(BagError::HasFetchReferences)
+        ));
+
+        // Once the copy is gone, the reference can't be checked
+        fs::remove_file(first.join("data/master.tif")).unwrap();
+        let report = validate_bag(&second, &ValidationOptions::default(), None).unwrap();
+        assert!(!report.is_valid());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_dedup_report_keeps_files() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_dedup_report");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let first = temp_dir.join("first");
+        let second = temp_dir.join("second");
+        fs::create_dir_all(&first).unwrap();
+        fs::create_dir_all(&second).unwrap();
+        fs::write(first.join("a.txt"), "shared").unwrap();
+        fs::write(second.join("b.txt"), "shared").unwrap();
+        bag_directory(&first, &BagOptions::default(), None).unwrap();
+
+        let options = BagOptions {
+            dedup: DedupMode::Report,
+            dedup_against: vec![first.clone(), second.clone()],
+            ..BagOptions::default()
+        };
+        bag_directory(&second, &options, None).unwrap();
+        assert!(second.join("data/b.txt").exists());
+        let listed = fs::read_to_string(second.join(DEDUP_REPORT_FILE)).unwrap();
+        assert!(listed.starts_with("data/b.txt  "));
+        assert!(listed.contains("a.txt"));
+        assert!(!second.join(FETCH_FILE).exists());
+        let report = validate_bag(&second, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
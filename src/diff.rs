@@ -0,0 +1,164 @@
+use crate::bagit::{
+    bag_algorithms, calculate_digest, read_manifest, BagError, ChecksumAlgorithm, Progress,
+};
+use std::path::Path;
+use std::sync::mpsc::Sender;
+
+/// How the payloads of two bags differ, going by their manifests.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BagDiff {
+    /// Algorithm the digests were compared in.
+    pub algorithm: ChecksumAlgorithm,
+    /// Files only in the second bag.
+    pub added: Vec<String>,
+    /// Files only in the first bag.
+    pub removed: Vec<String>,
+    /// Files in both, with different digests.
+    pub changed: Vec<String>,
+    pub unchanged: usize,
+    /// Files of the second bag hashed because the bags share no manifest algorithm.
+    pub hashed: usize,
+}
+
+impl BagDiff {
+    pub fn is_identical(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+/// Compares the payloads of the bags at `original` and `copy`, such as a bag and its
+/// copy on another drive. When the bags share a manifest algorithm (the strongest is
+/// used), only their manifests are read and nothing is hashed; otherwise the copy's
+/// files are hashed in the original's algorithm. Either way, whether each bag's files
+/// still match its own manifest is left to validation.
+pub fn compare_bags(
+    original: &Path,
+    copy: &Path,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<BagDiff, BagError> {
+    for bag in [original, copy] {
+        if !bag.join("bagit.txt").is_file() {
+            return Err(BagError::NotABag);
+        }
+    }
+    let original_algorithms = bag_algorithms(original);
+    let copy_algorithms = bag_algorithms(copy);
+    let shared = original_algorithms
+        .iter()
+        .rev()
+        .find(|a| copy_algorithms.contains(a))
+        .copied();
+    let strongest = |algorithms: &[ChecksumAlgorithm]| {
+        shared
+            .or_else(|| algorithms.last().copied())
+            .unwrap_or_default()
+    };
+    let algorithm = strongest(&original_algorithms);
+    let before = read_manifest(original, algorithm)?;
+    let after = read_manifest(copy, strongest(&copy_algorithms))?;
+
+    let mut diff = BagDiff {
+        algorithm,
+        ..BagDiff::default()
+    };
+    let mut in_both: Vec<&String> = before.keys().filter(|f| after.contains_key(*f)).collect();
+    in_both.sort();
+    if shared.is_none() {
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Started {
+                total_files: in_both.len(),
+            });
+        }
+    }
+    for (i, file) in in_both.into_iter().enumerate() {
+        let digest = match shared {
+            Some(_) => after[file].clone(),
+            None => {
+                if let Some(ref tx) = progress_tx {
+                    let _ = tx.send(Progress::Checksumming {
+                        current: i + 1,
+                        filename: file.clone(),
+                    });
+                }
+                diff.hashed += 1;
+                calculate_digest(&copy.join(file), algorithm)?
+            }
+        };
+        match digest == before[file] {
+            true => diff.unchanged += 1,
+            false => diff.changed.push(file.clone()),
+        }
+    }
+    diff.removed = before
+        .keys()
+        .filter(|f| !after.contains_key(*f))
+        .cloned()
+        .collect();
+    diff.added = after
+        .keys()
+        .filter(|f| !before.contains_key(*f))
+        .cloned()
+        .collect();
+    diff.removed.sort();
+    diff.added.sort();
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{bag_directory, BagOptions};
+    use std::fs;
+
+    #[test]
+    fn test_compare_bags() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_diff");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let original = temp_dir.join("original");
+        let copy = temp_dir.join("copy");
+        let other = temp_dir.join("other");
+        for (bag, changed, extra) in [(&original, "old", "gone.txt"), (&copy, "new", "added.txt")] {
+            fs::create_dir_all(bag.join("sub")).unwrap();
+            fs::write(bag.join("same.txt"), "same").unwrap();
+            fs::write(bag.join("sub/changed.txt"), changed).unwrap();
+            fs::write(bag.join(extra), "extra").unwrap();
+        }
+        fs::create_dir_all(&other).unwrap();
+        fs::write(other.join("same.txt"), "same").unwrap();
+        fs::write(other.join("sub.txt"), "new").unwrap();
+
+        bag_directory(&original, &BagOptions::default(), None).unwrap();
+        bag_directory(&copy, &BagOptions::default(), None).unwrap();
+        let md5 = BagOptions {
+            algorithms: vec![ChecksumAlgorithm::Md5],
+            ..BagOptions::default()
+        };
+        bag_directory(&other, &md5, None).unwrap();
+
+        // Shared algorithm: the manifests alone tell
+        let diff = compare_bags(&original, &copy, None).unwrap();
+        assert_eq!(diff.added, vec!["data/added.txt"]);
+        assert_eq!(diff.removed, vec!["data/gone.txt"]);
+        assert_eq!(diff.changed, vec!["data/sub/changed.txt"]);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.hashed, 0);
+        assert!(compare_bags(&original, &original, None)
+            .unwrap()
+            .is_identical());
+
+        // No shared algorithm: the files in both are hashed
+        let diff = compare_bags(&original, &other, None).unwrap();
+        assert_eq!(diff.algorithm, ChecksumAlgorithm::default());
+        assert_eq!(diff.hashed, 1);
+        assert_eq!(diff.unchanged, 1);
+        assert_eq!(diff.added, vec!["data/sub.txt"]);
+
+        assert!(matches!(
+            compare_bags(&original, &temp_dir, None),
+            Err(BagError::NotABag)
+        ));
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,540 @@
+use crate::bagit::{
+    bag_encodes_paths, calculate_digest, calculate_sha256, check_empty_source, check_profile,
+    read_payload_manifest, refresh_tag_manifest, run_parallel, write_bag_files, Bag, BagError,
+    BagOptions, BagReport, ChecksumAlgorithm, Progress,
+};
+use crate::dedup::{parse_fetch_file, FetchEntry, FETCH_FILE};
+use crate::events::{append_events, Event, EventType};
+use crate::tagfile::read_tag_file;
+use std::fs::{self, File};
+use std::io;
+use std::path::Path;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::mpsc::Sender;
+use std::thread;
+use std::time::Duration;
+
+/// Tag file recording where each payload file was downloaded from.
+pub const SOURCE_URLS_FILE: &str = "source-urls.txt";
+
+/// Downloads run at once unless told otherwise.
+pub const DEFAULT_DOWNLOADS: usize = 4;
+
+/// Attempts per resource before giving up on the bag.
+const DOWNLOAD_ATTEMPTS: u32 = 3;
+
+/// How long a server may take to accept a connection, and then to send each piece of
+/// its answer, before the request fails. There's no limit on the whole transfer, as
+/// payload files may be big.
+const CONNECT_TIMEOUT: Duration = Duration::from_secs(30);
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// An HTTP client that gives up on servers that stop answering, rather than waiting
+/// on them forever.
+pub fn http_agent() -> ureq::Agent {
+    ureq::AgentBuilder::new()
+        .timeout_connect(CONNECT_TIMEOUT)
+        .timeout_read(READ_TIMEOUT)
+        .build()
+}
+
+/// One line of a URL list: a resource and where it goes in the payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlEntry {
+    pub url: String,
+    /// Expected SHA-256, checked after download when the list gives one.
+    pub sha256: Option<String>,
+    /// Path under `data/`, with `/` separators.
+    pub path: String,
+}
+
+fn is_sha256(token: &str) -> bool {
+    token.len() == 64 && token.chars().all(|c| c.is_ascii_hexdigit())
+}
+
+/// Whether `path` (with `/` separators) stays inside `data/`.
+pub fn safe_payload_path(path: &str) -> bool {
+    !path.is_empty()
+        && path
+            .split('/')
+            .all(|c| !c.is_empty() && c != "." && c != ".." && !c.contains(['\\', ':']))
+}
+
+/// Reads a URL list: one `<url> [<sha256>] [<path in data/>]` per line, with blank
+/// lines and `#` comments ignored. Without a path, the file is named after the
+/// last part of the URL.
+pub fn parse_url_list(text: &str) -> Result<Vec<UrlEntry>, BagError> {
+    let mut entries: Vec<UrlEntry> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| BagError::BadUrlList {
+            line: i + 1,
+            message: message.to_string(),
+        };
+        let mut tokens = line.split_whitespace();
+        let url = tokens.next().unwrap_or_default().to_string();
+        if !is_http(&url) {
+            return Err(error("not an http(s) URL"));
+        }
+        let mut sha256 = None;
+        let mut path = None;
+        for token in tokens {
+            if sha256.is_none() && path.is_none() && is_sha256(token) {
+                sha256 = Some(token.to_ascii_lowercase());
+            } else if path.is_none() {
+                path = Some(token.to_string());
+            } else {
+                return Err(error("expected `<url> [<sha256>] [<path>]`"));
+            }
+        }
+        let path = path.unwrap_or_else(|| {
+            let name = url.split(['?', '#']).next().unwrap_or_default();
+            match name.rsplit('/').next() {
+                Some(last) if !last.is_empty() => last.to_string(),
+                _ => "index.html".to_string(),
+            }
+        });
+        if !safe_payload_path(&path) {
+            return Err(error("the path must stay inside data/"));
+        }
+        if entries.iter().any(|e| e.path == path) {
+            return Err(error(&format!("{} is already used by another line", path)));
+        }
+        entries.push(UrlEntry { url, sha256, path });
+    }
+    Ok(entries)
+}
+
+fn is_http(url: &str) -> bool {
+    url.starts_with("http://") || url.starts_with("https://")
+}
+
+/// Writes what `url` answers with to `target`.
+fn get(url: &str, target: &Path) -> Result<(), String> {
+    let response = http_agent().get(url).call().map_err(|e| e.to_string())?;
+    let mut file = File::create(target).map_err(|e| e.to_string())?;
+    io::copy(&mut response.into_reader(), &mut file).map_err(|e| e.to_string())?;
+    Ok(())
+}
+
+/// One try at fetching `entry` into `target`, checking the digest if there is one.
+fn download_once(entry: &UrlEntry, target: &Path) -> Result<(), String> {
+    get(&entry.url, target)?;
+    if let Some(expected) = &entry.sha256 {
+        let actual = calculate_sha256(target).map_err(|e| e.to_string())?;
+        if &actual != expected {
+            return Err(format!(
+                "checksum mismatch: expected {}, got {}",
+                expected, actual
+            ));
+        }
+    }
+    Ok(())
+}
+
+fn download(
+    url: &str,
+    target: &Path,
+    fetch: &impl Fn(&Path) -> Result<(), String>,
+) -> Result<(), BagError> {
+    let mut attempt = 1;
+    loop {
+        match fetch(target) {
+            Ok(()) => return Ok(()),
+            Err(message) if attempt == DOWNLOAD_ATTEMPTS => {
+                let _ = fs::remove_file(target);
+                return Err(BagError::DownloadFailed {
+                    url: url.to_string(),
+                    message,
+                });
+            }
+            Err(_) => {
+                thread::sleep(Duration::from_millis(500 * u64::from(attempt)));
+                attempt += 1;
+            }
+        }
+    }
+}
+
+/// Downloads every entry into a new bag at `dest`, `concurrency` at a time, and
+/// finishes it as a normal complete bag. The URLs are kept in `source-urls.txt`.
+pub fn bag_from_urls(
+    entries: &[UrlEntry],
+    dest: &Path,
+    options: &BagOptions,
+    concurrency: usize,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<BagReport, BagError> {
+    bag_downloads(
+        entries,
+        dest,
+        options,
+        concurrency,
+        progress_tx,
+        |i, target| download_once(&entries[i], target),
+    )
+}
+
+/// Like [`bag_from_urls`], with `fetch` writing the entry at an index to its target
+/// file. A fetch that fails is retried, and the bag fails if it never succeeds.
+pub fn bag_downloads(
+    entries: &[UrlEntry],
+    dest: &Path,
+    options: &BagOptions,
+    concurrency: usize,
+    progress_tx: Option<Sender<Progress>>,
+    fetch: impl Fn(usize, &Path) -> Result<(), String> + Sync,
+) -> Result<BagReport, BagError> {
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        return Err(BagError::DestinationNotEmpty(dest.to_path_buf()));
+    }
+    let mut report = BagReport::default();
+    check_profile(options)?;
+    check_empty_source(entries.len(), options, &mut report)?;
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started {
+            total_files: entries.len(),
+        });
+    }
+
+    let data_dir = dest.join("data");
+    fs::create_dir_all(&data_dir)?;
+    for entry in entries {
+        if let Some(parent) = data_dir.join(&entry.path).parent() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+
+    let done = AtomicUsize::new(0);
+    let results = run_parallel(entries.len(), concurrency, |i| {
+        let entry = &entries[i];
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Downloading {
+                current: done.fetch_add(1, Ordering::Relaxed) + 1,
+                filename: entry.url.clone(),
+            });
+        }
+        download(&entry.url, &data_dir.join(&entry.path), &|target| {
+            fetch(i, target)
+        })
+    });
+    results.into_iter().collect::<Result<Vec<()>, BagError>>()?;
+    report.events.push(Event::now(
+        EventType::Transfer,
+        format!("{} files downloaded into data/", entries.len()),
+    ));
+    let checked = entries.iter().filter(|e| e.sha256.is_some()).count();
+    if checked > 0 {
+        report.events.push(Event::now(
+            EventType::FixityCheck,
+            format!("{} downloads matched the checksum in the URL list", checked),
+        ));
+    }
+
+    let mut sources = String::new();
+    for entry in entries {
+        sources.push_str(&format!("{}  data/{}\n", entry.url, entry.path));
+    }
+    fs::write(dest.join(SOURCE_URLS_FILE), sources)?;
+
+    write_bag_files(
+        dest,
+        options,
+        report,
+        vec![SOURCE_URLS_FILE.to_string()],
+        &progress_tx,
+    )
+}
+
+/// One try at fetching a fetch.txt entry into `target`, checking it against the
+/// payload manifest.
+fn fetch_once(
+    entry: &FetchEntry,
+    target: &Path,
+    algorithm: ChecksumAlgorithm,
+    expected: &str,
+) -> Result<(), String> {
+    match entry.local_copy() {
+        Some(copy) => {
+            fs::copy(copy, target).map_err(|e| e.to_string())?;
+        }
+        None => get(&entry.url, target)?,
+    }
+    let size = fs::metadata(target).map_err(|e| e.to_string())?.len();
+    if let Some(expected) = entry.size.filter(|&expected| expected != size) {
+        return Err(format!("expected {} bytes, got {}", expected, size));
+    }
+    let actual = calculate_digest(target, algorithm).map_err(|e| e.to_string())?;
+    if actual != expected {
+        return Err(format!(
+            "checksum mismatch: expected {}, got {}",
+            expected, actual
+        ));
+    }
+    Ok(())
+}
+
+/// Completes a holey bag: fetches the files fetch.txt lists into `data/`, `concurrency`
+/// at a time, and checks each against the payload manifest. http(s) URLs are
+/// downloaded and `file:` URLs, as deduplication writes, are copied. Once every file is
+/// in place fetch.txt is removed. Files already there are left alone, so a run that
+/// failed part way can be repeated.
+pub fn complete_bag(
+    bag: &Path,
+    concurrency: usize,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<BagReport, BagError> {
+    if !bag.join("bagit.txt").is_file() {
+        return Err(BagError::NotABag);
+    }
+    let (content, _) = read_tag_file(&bag.join(FETCH_FILE))?;
+    let (algorithm, manifest) = read_payload_manifest(bag)?;
+    let entries: Vec<FetchEntry> = parse_fetch_file(&content, bag_encodes_paths(bag))
+        .into_iter()
+        .filter(|entry| !bag.join(&entry.file).is_file())
+        .collect();
+    // Anything that can't work stops the run before a file is fetched
+    for entry in &entries {
+        let inside = entry
+            .file
+            .strip_prefix("data/")
+            .is_some_and(safe_payload_path);
+        let message = if !inside {
+            "the path must stay inside data/"
+        } else if !manifest.contains_key(&entry.file) {
+            "the file isn't in the payload manifest"
+        } else if entry.local_copy().is_none() && !is_http(&entry.url) {
+            "only http(s) and file: URLs can be fetched"
+        } else {
+            continue;
+        };
+        return Err(BagError::DownloadFailed {
+            url: entry.url.clone(),
+            message: message.to_string(),
+        });
+    }
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started {
+            total_files: entries.len(),
+        });
+    }
+    for entry in &entries {
+        if let Some(parent) = bag.join(&entry.file).parent() {
+            fs::create_dir_all(parent)?;
+        }
+    }
+    let done = AtomicUsize::new(0);
+    let results = run_parallel(entries.len(), concurrency, |i| {
+        let entry = &entries[i];
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Downloading {
+                current: done.fetch_add(1, Ordering::Relaxed) + 1,
+                filename: entry.file.clone(),
+            });
+        }
+        let expected = &manifest[&entry.file];
+        let result = download(&entry.url, &bag.join(&entry.file), &|target| {
+            fetch_once(entry, target, algorithm, expected)
+        });
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::FileChecked {
+                filename: entry.file.clone(),
+                passed: result.is_ok(),
+            });
+        }
+        result
+    });
+    results.into_iter().collect::<Result<Vec<()>, BagError>>()?;
+
+    fs::remove_file(bag.join(FETCH_FILE))?;
+    refresh_tag_manifest(bag, &[FETCH_FILE])?;
+    append_events(
+        bag,
+        &[
+            Event::now(
+                EventType::Transfer,
+                format!("{} files in fetch.txt fetched into data/", entries.len()),
+            ),
+            Event::now(
+                EventType::FixityCheck,
+                format!(
+                    "{} fetched files matched the payload manifest",
+                    entries.len()
+                ),
+            ),
+        ],
+    )?;
+
+    let report = BagReport {
+        bag: Bag::open(bag)?,
+        ..Default::default()
+    };
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Done {
+            path: bag.to_path_buf(),
+            report: Box::new(report.clone()),
+        });
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{validate_bag, ValidationOptions};
+    use std::io::{BufRead, BufReader, Write};
+    use std::net::TcpListener;
+
+    #[test]
+    fn test_parse_url_list() {
+        let digest = "ab".repeat(32);
+        let list = format!(
+            "# deliverables\n\
+             https://example.org/files/report.pdf\n\
+             https://example.org/get?id=7 {} scans/page-7.tif\n\
+             \n\
+             https://example.org/\n",
+            digest.to_uppercase()
+        );
+        let entries = parse_url_list(&list).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].path, "report.pdf");
+        assert_eq!(entries[1].sha256.as_deref(), Some(digest.as_str()));
+        assert_eq!(entries[1].path, "scans/page-7.tif");
+        assert_eq!(entries[2].path, "index.html");
+
+        assert!(matches!(
+            parse_url_list("ftp://example.org/a"),
+            Err(BagError::BadUrlList { line: 1, .. })
+        ));
+        assert!(parse_url_list("https://example.org/a ../escape").is_err());
+        assert!(parse_url_list("https://a.org/x\nhttps://b.org/x").is_err());
+    }
+
+    /// Serves `/good` with fixed content and answers anything else with 404.
+    fn serve_files(listener: TcpListener) {
+        for stream in listener.incoming() {
+            let mut stream = stream.unwrap();
+            let mut reader = BufReader::new(&stream);
+            let mut line = String::new();
+            reader.read_line(&mut line).unwrap();
+            // Read the rest of the headers, so closing doesn't reset the connection
+            let mut header = String::new();
+            while reader.read_line(&mut header).unwrap_or(0) > 2 {
+                header.clear();
+            }
+            let reply = if line.starts_with("GET /good ") {
+                "HTTP/1.0 200 OK\r\nContent-Length: 5\r\n\r\nhello"
+            } else {
+                "HTTP/1.0 404 Not Found\r\nContent-Length: 0\r\n\r\n"
+            };
+            let _ = stream.write_all(reply.as_bytes());
+        }
+    }
+
+    #[test]
+    fn test_bag_from_urls() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_download");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("probe.txt"), "hello").unwrap();
+        let digest = calculate_sha256(&temp_dir.join("probe.txt")).unwrap();
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || serve_files(listener));
+
+        let list = format!("{base}/good {digest}\n{base}/good copies/again.txt\n");
+        let entries = parse_url_list(&list).unwrap();
+        let dest = temp_dir.join("bag");
+        bag_from_urls(&entries, &dest, &BagOptions::default(), 2, None).unwrap();
+        assert_eq!(
+            fs::read_to_string(dest.join("data/copies/again.txt")).unwrap(),
+            "hello"
+        );
+        let sources = fs::read_to_string(dest.join(SOURCE_URLS_FILE)).unwrap();
+        assert!(sources.contains(&format!("{base}/good  data/good\n")));
+        let report = validate_bag(&dest, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // A wrong digest fails after retrying, and so does a missing resource
+        let wrong = parse_url_list(&format!("{base}/good {}", "0".repeat(64))).unwrap();
+        let options = BagOptions::default();
+        let result = bag_from_urls(&wrong, &temp_dir.join("bag2"), &options, 1, None);
+        assert!(
+            matches!(&result, Err(BagError::DownloadFailed { message, .. }) if message.contains("checksum")),
+            "{:?}",
+            result
+        );
+        let missing = parse_url_list(&format!("{base}/missing")).unwrap();
+        let result = bag_from_urls(&missing, &temp_dir.join("bag3"), &options, 1, None);
+        assert!(matches!(result, Err(BagError::DownloadFailed { .. })));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_complete_bag() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_complete_bag");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("bag");
+        fs::create_dir_all(bag.join("letters")).unwrap();
+        fs::write(bag.join("good"), "hello").unwrap();
+        fs::write(bag.join("letters/kept.txt"), "kept").unwrap();
+        fs::write(bag.join("letters/elsewhere.txt"), "stored elsewhere").unwrap();
+        let options = BagOptions {
+            event_log: true,
+            ..BagOptions::default()
+        };
+        crate::bagit::bag_directory(&bag, &options, None).unwrap();
+
+        // Hollow it out: one file to download, one to copy from a file: URL
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let base = format!("http://{}", listener.local_addr().unwrap());
+        thread::spawn(move || serve_files(listener));
+        let copy = temp_dir.join("elsewhere.txt");
+        fs::rename(bag.join("data/letters/elsewhere.txt"), &copy).unwrap();
+        fs::remove_file(bag.join("data/good")).unwrap();
+        let fetch = format!(
+            "{base}/good 5 data/good\n{} 16 data/letters/elsewhere.txt\n",
+            crate::dedup::file_url(&copy)
+        );
+        fs::write(bag.join(FETCH_FILE), fetch).unwrap();
+        refresh_tag_manifest(&bag, &[FETCH_FILE]).unwrap();
+
+        complete_bag(&bag, 2, None).unwrap();
+        assert_eq!(fs::read_to_string(bag.join("data/good")).unwrap(), "hello");
+        assert!(bag.join("data/letters/elsewhere.txt").is_file());
+        assert!(!bag.join(FETCH_FILE).exists());
+        let tagmanifest = fs::read_to_string(bag.join("tagmanifest-sha256.txt")).unwrap();
+        assert!(!tagmanifest.contains(FETCH_FILE));
+        let report = validate_bag(&bag, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // Content that doesn't match the manifest isn't kept
+        fs::rename(bag.join("data/letters/kept.txt"), temp_dir.join("kept.txt")).unwrap();
+        fs::write(
+            bag.join(FETCH_FILE),
+            format!("{base}/good - data/letters/kept.txt\n"),
+        )
+        .unwrap();
+        let result = complete_bag(&bag, 1, None);
+        assert!(
+            matches!(&result, Err(BagError::DownloadFailed { message, .. }) if message.contains("checksum")),
+            "{:?}",
+            result
+        );
+        assert!(!bag.join("data/letters/kept.txt").exists());
+        assert!(bag.join(FETCH_FILE).exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+use crate::bagit::{refresh_tag_manifest, BagError};
+use crate::tagfile::{read_tag_file, write_tag_file, BagInfo};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// bag-info.txt field recording how the bag's serialized copies are encrypted.
+pub const ENCRYPTION_LABEL: &str = "Encryption";
+
+/// Programs a serialized bag can be encrypted with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EncryptionTool {
+    /// age, to X25519 or SSH public keys.
+    Age,
+    /// GnuPG, to keys already in the user's keyring.
+    Gpg,
+}
+
+impl EncryptionTool {
+    pub fn parse(text: &str) -> Option<Self> {
+        match text {
+            "age" => Some(EncryptionTool::Age),
+            "gpg" => Some(EncryptionTool::Gpg),
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            EncryptionTool::Age => "age",
+            EncryptionTool::Gpg => "gpg",
+        }
+    }
+}
+
+/// Public-key encryption applied to a serialized bag once it's written.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Encryption {
+    pub tool: EncryptionTool,
+    /// age public keys, or GnuPG key IDs, fingerprints or email addresses. Any one
+    /// of them can decrypt.
+    pub recipients: Vec<String>,
+}
+
+impl Encryption {
+    /// Where the encrypted copy of the file at `path` goes: beside it, with the
+    /// tool's extension added.
+    pub fn encrypted_path(&self, path: &Path) -> PathBuf {
+        let mut name = path.as_os_str().to_owned();
+        name.push(".");
+        name.push(self.tool.name());
+        PathBuf::from(name)
+    }
+
+    /// The bag-info.txt value recording it.
+    fn description(&self) -> String {
+        format!("{} for {}", self.tool.name(), self.recipients.join(", "))
+    }
+
+    fn failed(&self, message: impl Into<String>) -> BagError {
+        BagError::EncryptionFailed {
+            tool: self.tool.name().to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// Records `encryption` in the bag-info.txt of the bag at `bag`, replacing any earlier
+/// record, so the bag says how its serialized copies were protected. Returns whether
+/// that removed the bag's signature.
+pub fn record_encryption(bag: &Path, encryption: &Encryption) -> Result<bool, BagError> {
+    if encryption.recipients.is_empty() {
+        return Err(encryption.failed("no recipients are set"));
+    }
+    set_encryption_field(bag, Some(encryption.description()))
+}
+
+/// Removes the record of encryption from the bag-info.txt of the bag at `bag`, if
+/// there is one, so a copy serialized without it doesn't claim to be encrypted.
+/// Returns whether that removed the bag's signature.
+pub fn clear_encryption(bag: &Path) -> Result<bool, BagError> {
+    set_encryption_field(bag, None)
+}
+
+/// Replaces the Encryption field with `value`, or removes it if `None`; bag-info.txt
+/// and the tag manifests are only rewritten if that changes anything.
+fn set_encryption_field(bag: &Path, value: Option<String>) -> Result<bool, BagError> {
+    let path = bag.join("bag-info.txt");
+    let mut info = match path.is_file() {
+        true => BagInfo::parse(&read_tag_file(&path)?.0),
+        false => BagInfo::default(),
+    };
+    let before = info.fields().len();
+    info.fields_mut()
+        .retain(|(label, _)| !label.eq_ignore_ascii_case(ENCRYPTION_LABEL));
+    if value.is_none() && info.fields().len() == before {
+        return Ok(false);
+    }
+    if let Some(value) = value {
+        info.push(ENCRYPTION_LABEL, value);
+    }
+    write_tag_file(&path, &info.serialize())?;
+    refresh_tag_manifest(bag, &["bag-info.txt"])
+}
+
+/// Encrypts the file at `path` to every recipient of `encryption`, then removes the
+/// unencrypted file and returns the encrypted one's path. The unencrypted file is
+/// removed on failure too, so it's never left where it was meant to leave from.
+pub fn encrypt_file(path: &Path, encryption: &Encryption) -> Result<PathBuf, BagError> {
+    let out = encryption.encrypted_path(path);
+    let mut command = Command::new(encryption.tool.name());
+    match encryption.tool {
+        EncryptionTool::Age => {
+            command.arg("--encrypt");
+            for recipient in &encryption.recipients {
+                command.arg("--recipient").arg(recipient);
+            }
+        }
+        EncryptionTool::Gpg => {
+            // The recipients were chosen in Settings, so they're trusted as given
+            command.args(["--batch", "--yes", "--trust-model", "always", "--encrypt"]);
+            for recipient in &encryption.recipients {
+                command.arg("--recipient").arg(recipient);
+            }
+        }
+    }
+    let result = command.arg("--output").arg(&out).arg(path).output();
+    let _ = fs::remove_file(path);
+    let output = result.map_err(|e| encryption.failed(e.to_string()))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&out);
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(encryption.failed(if stderr.is_empty() {
+            output.status.to_string()
+        } else {
+            stderr
+        }));
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{serialize_bag, SerialOptions};
+    use crate::bagit::{bag_directory, validate_bag, BagOptions, ValidationOptions};
+    use crate::events::EVENT_LOG_FILE;
+
+    #[test]
+    fn test_record_encryption() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_encrypt");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("bag");
+        fs::create_dir_all(&bag).unwrap();
+        fs::write(bag.join("secret.txt"), "names and addresses").unwrap();
+        bag_directory(&bag, &BagOptions::default(), None).unwrap();
+
+        let age = Encryption {
+            tool: EncryptionTool::Age,
+            recipients: vec!["age1first".to_string(), "age1second".to_string()],
+        };
+        record_encryption(&bag, &age).unwrap();
+        record_encryption(&bag, &age).unwrap();
+        let bag_info = fs::read_to_string(bag.join("bag-info.txt")).unwrap();
+        assert_eq!(bag_info.matches("Encryption:").count(), 1);
+        assert!(bag_info.contains("Encryption: age for age1first, age1second"));
+        let report = validate_bag(&bag, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert_eq!(
+            age.encrypted_path(Path::new("out/bag.tar.zst")),
+            PathBuf::from("out/bag.tar.zst.age")
+        );
+
+        let nobody = Encryption {
+            tool: EncryptionTool::Age,
+            recipients: Vec::new(),
+        };
+        assert!(record_encryption(&bag, &nobody).is_err());
+
+        // A key age refuses (or no age at all) fails without leaving the unencrypted
+        // file behind
+        let archive = temp_dir.join("bag.tar");
+        fs::write(&archive, "plain").unwrap();
+        let bad_key = Encryption {
+            recipients: vec!["not-a-key".to_string()],
+            ..age.clone()
+        };
+        assert!(matches!(
+            encrypt_file(&archive, &bad_key),
+            Err(BagError::EncryptionFailed { .. })
+        ));
+        assert!(!archive.exists());
+        assert!(!age.encrypted_path(&archive).exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_encryption_record_follows_the_archive() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_encrypt_record");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("bag");
+        fs::create_dir_all(&bag).unwrap();
+        fs::write(bag.join("secret.txt"), "names and addresses").unwrap();
+        let options = BagOptions {
+            event_log: true,
+            ..BagOptions::default()
+        };
+        bag_directory(&bag, &options, None).unwrap();
+        let bag_info = fs::read_to_string(bag.join("bag-info.txt")).unwrap();
+        let events = fs::read_to_string(bag.join(EVENT_LOG_FILE)).unwrap();
+
+        // Encryption that fails (age refuses the key, or isn't there) leaves the bag
+        // saying nothing of it, and no archive behind
+        let failing = SerialOptions {
+            encryption: Some(Encryption {
+                tool: EncryptionTool::Age,
+                recipients: vec!["not-a-key".to_string()],
+            }),
+            ..SerialOptions::default()
+        };
+        let out = temp_dir.join("out");
+        assert!(serialize_bag(&bag, &out, &failing, None).is_err());
+        assert_eq!(
+            fs::read_to_string(bag.join("bag-info.txt")).unwrap(),
+            bag_info
+        );
+        assert_eq!(
+            fs::read_to_string(bag.join(EVENT_LOG_FILE)).unwrap(),
+            events
+        );
+        assert_eq!(fs::read_dir(&out).unwrap().count(), 0);
+        let report = validate_bag(&bag, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // A record left from an encrypted copy goes when the next one isn't encrypted
+        let age = Encryption {
+            tool: EncryptionTool::Age,
+            recipients: vec!["age1first".to_string()],
+        };
+        record_encryption(&bag, &age).unwrap();
+        serialize_bag(&bag, &out, &SerialOptions::default(), None).unwrap();
+        let bag_info = fs::read_to_string(bag.join("bag-info.txt")).unwrap();
+        assert!(!bag_info.contains("Encryption:"));
+        let report = validate_bag(&bag, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
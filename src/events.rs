@@ -0,0 +1,180 @@
+use crate::bagit::{refresh_tag_manifest, BagError, SOFTWARE_AGENT};
+use crate::inventory::csv_field;
+use crate::tagfile::{read_tag_file, write_tag_file};
+use std::io;
+use std::path::Path;
+
+/// Tag file logging what was done to the bag, one event per line, after the manner
+/// of PREMIS events.
+pub const EVENT_LOG_FILE: &str = "events.csv";
+
+const EVENT_LOG_HEADER: &str = "date_time,event_type,outcome,agent,detail\n";
+
+/// What kind of action an event records, named after the PREMIS event types.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum EventType {
+    /// The source folder was walked for payload files.
+    Enumeration,
+    /// Payload files were moved into `data/`.
+    Move,
+    /// Payload files or the whole bag were copied.
+    Replication,
+    MessageDigestCalculation,
+    /// Files were checked against checksums they were expected to have.
+    FixityCheck,
+    /// The bag was written into an archive or onto tape.
+    Packing,
+    /// Payload files arrived over the network.
+    Transfer,
+}
+
+impl EventType {
+    pub fn label(self) -> &'static str {
+        match self {
+            EventType::Enumeration => "enumeration",
+            EventType::Move => "move",
+            EventType::Replication => "replication",
+            EventType::MessageDigestCalculation => "message digest calculation",
+            EventType::FixityCheck => "fixity check",
+            EventType::Packing => "packing",
+            EventType::Transfer => "transfer",
+        }
+    }
+}
+
+/// One action, stamped with when it finished.
+#[derive(Debug, Clone)]
+pub struct Event {
+    /// UTC, RFC 3339.
+    pub date_time: String,
+    pub event_type: EventType,
+    pub success: bool,
+    pub detail: String,
+}
+
+impl Event {
+    /// A successful event happening now.
+    pub fn now(event_type: EventType, detail: impl Into<String>) -> Self {
+        Self {
+            date_time: chrono::Utc::now().format("%Y-%m-%dT%H:%M:%SZ").to_string(),
+            event_type,
+            success: true,
+            detail: detail.into(),
+        }
+    }
+
+    pub fn with_outcome(self, success: bool) -> Self {
+        Self { success, ..self }
+    }
+
+    fn to_line(&self) -> String {
+        format!(
+            "{},{},{},{},{}\n",
+            self.date_time,
+            csv_field(self.event_type.label()),
+            if self.success { "success" } else { "failure" },
+            csv_field(SOFTWARE_AGENT),
+            csv_field(&self.detail)
+        )
+    }
+}
+
+/// Adds `events` to the log in `bag`, after any already there, starting the log if
+/// there's none, and writes it into `dir`: the bag, or the staging folder of one being
+/// sealed. Returns the tag file's name; the caller lists it in the tag manifest.
+pub fn write_event_log(bag: &Path, dir: &Path, events: &[Event]) -> io::Result<String> {
+    let mut log = match read_tag_file(&bag.join(EVENT_LOG_FILE)) {
+        Ok((content, _)) => content,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => EVENT_LOG_HEADER.to_string(),
+        Err(e) => return Err(e),
+    };
+    for event in events {
+        log.push_str(&event.to_line());
+    }
+    write_tag_file(&dir.join(EVENT_LOG_FILE), &log)?;
+    Ok(EVENT_LOG_FILE.to_string())
+}
+
+/// Logs `events` in a finished bag and updates its tag manifest to match. Bags that
+/// don't keep a log are left alone. Returns whether that removed the bag's signature.
+pub fn append_events(bag: &Path, events: &[Event]) -> Result<bool, BagError> {
+    if !bag.join(EVENT_LOG_FILE).is_file() {
+        return Ok(false);
+    }
+    write_event_log(bag, bag, events)?;
+    refresh_tag_manifest(bag, &[EVENT_LOG_FILE])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{serialize_bag, validate_archive, SerialOptions};
+    use crate::bagit::{bag_directory, update_bag, validate_bag, BagOptions, ValidationOptions};
+    use crate::clone::clone_bag;
+    use std::fs;
+
+    fn event_types(bag: &Path) -> Vec<String> {
+        fs::read_to_string(bag.join(EVENT_LOG_FILE))
+            .unwrap()
+            .lines()
+            .skip(1)
+            .map(|line| line.split(',').nth(1).unwrap().to_string())
+            .collect()
+    }
+
+    #[test]
+    fn test_event_log() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_events");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("minutes");
+        fs::create_dir_all(&bag).unwrap();
+        fs::write(bag.join("1921.txt"), "Meeting opened").unwrap();
+        let options = BagOptions {
+            event_log: true,
+            ..BagOptions::default()
+        };
+        let valid = |path: &Path| {
+            let report = validate_bag(path, &ValidationOptions::default(), None).unwrap();
+            assert!(report.is_valid(), "{:?}", report.errors);
+        };
+
+        bag_directory(&bag, &options, None).unwrap();
+        valid(&bag);
+        assert_eq!(
+            event_types(&bag),
+            ["enumeration", "move", "message digest calculation"]
+        );
+        let log = fs::read_to_string(bag.join(EVENT_LOG_FILE)).unwrap();
+        assert!(log.starts_with(EVENT_LOG_HEADER));
+        assert!(log.contains(",move,success,baggie 0.1.1,1 files moved into data/\n"));
+
+        // A bag that keeps a log goes on logging, whatever the options say
+        update_bag(&bag, &BagOptions::default(), None).unwrap();
+        valid(&bag);
+        assert_eq!(event_types(&bag).len(), 4);
+
+        let copy = temp_dir.join("copy");
+        clone_bag(&bag, &copy, &BagOptions::default(), true, None).unwrap();
+        valid(&copy);
+        assert_eq!(event_types(&copy)[4..], ["replication", "fixity check"]);
+
+        let out = temp_dir.join("out");
+        let archive = serialize_bag(&bag, &out, &SerialOptions::default(), None).unwrap();
+        valid(&bag);
+        assert_eq!(event_types(&bag).last().unwrap(), "packing");
+        let report = validate_archive(&archive, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // Bags without a log don't get one
+        let plain = temp_dir.join("plain");
+        fs::create_dir_all(&plain).unwrap();
+        fs::write(plain.join("a.txt"), "a").unwrap();
+        bag_directory(&plain, &BagOptions::default(), None).unwrap();
+        append_events(&plain, &[Event::now(EventType::Packing, "x")]).unwrap();
+        assert!(!plain.join(EVENT_LOG_FILE).exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
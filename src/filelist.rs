@@ -0,0 +1,255 @@
+use crate::bagit::{
+    check_empty_source, check_free_space, check_profile, copy_payload_file, handle_unreadable,
+    with_retries, write_bag_files, BagError, BagOptions, BagReport, Progress, PER_FILE_ALLOWANCE,
+};
+use crate::download::safe_payload_path;
+use crate::events::{Event, EventType};
+use crate::provenance::{write_records, ProvenanceLog};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+/// One line of a file list: a file, wherever it is, and where it goes in the payload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListedFile {
+    /// Absolute path of the file to bag.
+    pub source: PathBuf,
+    /// Path under `data/`, with `/` separators.
+    pub path: String,
+}
+
+/// Splits a CSV line into its fields, unquoting `"..."` and `""`. `None` if a quote is
+/// left open.
+fn csv_fields(line: &str) -> Option<Vec<String>> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    (!quoted).then_some(fields)
+}
+
+/// Reads a file list: one absolute path per line, optionally followed by a tab and
+/// the path it should have under `data/`, with blank lines and `#` comments ignored.
+/// With `csv`, each line is instead `<path>[,<path in data/>]`, quoted as CSV, and a
+/// header row is skipped. Without a target, a file goes at the top of `data/` under its
+/// own name.
+pub fn parse_file_list(text: &str, csv: bool) -> Result<Vec<ListedFile>, BagError> {
+    let mut entries: Vec<ListedFile> = Vec::new();
+    for (i, line) in text.lines().enumerate() {
+        let line = line.trim_end_matches('\r');
+        if line.trim().is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let error = |message: &str| BagError::BadFileList {
+            line: i + 1,
+            message: message.to_string(),
+        };
+        let fields = match csv {
+            true => csv_fields(line).ok_or_else(|| error("a quote is never closed"))?,
+            false => line.split('\t').map(String::from).collect(),
+        };
+        let (source, target) = match fields.as_slice() {
+            [source] => (source, None),
+            [source, target] => (source, Some(target.trim()).filter(|t| !t.is_empty())),
+            _ => return Err(error("expected a path and at most one path in data/")),
+        };
+        let source = PathBuf::from(source.trim());
+        if !source.is_absolute() {
+            // The header row of a CSV list names the columns rather than a file
+            if csv && entries.is_empty() && i == first_line(text) {
+                continue;
+            }
+            return Err(error("the path must be absolute"));
+        }
+        let path = match target {
+            Some(target) => target.replace('\\', "/"),
+            None => match source.file_name() {
+                Some(name) => name.to_string_lossy().to_string(),
+                None => return Err(error("the path doesn't name a file")),
+            },
+        };
+        if !safe_payload_path(&path) {
+            return Err(error("the path must stay inside data/"));
+        }
+        if entries.iter().any(|e| e.path == path) {
+            return Err(error(&format!("{} is already used by another line", path)));
+        }
+        entries.push(ListedFile { source, path });
+    }
+    Ok(entries)
+}
+
+/// Index of the first line of `text` that isn't blank or a comment.
+fn first_line(text: &str) -> usize {
+    text.lines()
+        .position(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .unwrap_or(0)
+}
+
+/// Bags copies of exactly the files `entries` lists into a new bag at `dest`, for
+/// query results and curated selections that were never gathered into one folder. A
+/// file that can't be read is dealt with under the error policy, as in a folder.
+pub fn bag_from_list(
+    entries: &[ListedFile],
+    dest: &Path,
+    options: &BagOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<BagReport, BagError> {
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        return Err(BagError::DestinationNotEmpty(dest.to_path_buf()));
+    }
+    let mut report = BagReport::default();
+    check_profile(options)?;
+    check_empty_source(entries.len(), options, &mut report)?;
+    let bytes: u64 = entries
+        .iter()
+        .filter_map(|e| fs::metadata(&e.source).ok())
+        .map(|m| m.len())
+        .sum();
+    check_free_space(dest, bytes + entries.len() as u64 * PER_FILE_ALLOWANCE)?;
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started {
+            total_files: entries.len(),
+        });
+    }
+
+    let data_dir = dest.join("data");
+    fs::create_dir_all(&data_dir)?;
+    let mut provenance = ProvenanceLog::wanted(&data_dir, options);
+    let mut copied = 0;
+    for (i, entry) in entries.iter().enumerate() {
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Copying {
+                current: i + 1,
+                filename: entry.path.clone(),
+            });
+        }
+        let target = data_dir.join(&entry.path);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        if let Some(log) = provenance.as_mut() {
+            log.record_file(&entry.source, Path::new(&entry.path));
+        }
+        match with_retries(options, || {
+            copy_payload_file(&entry.source, &target, options)
+        }) {
+            Ok(_) => copied += 1,
+            Err(e) => handle_unreadable(e, &entry.source, options, &mut report)?,
+        }
+    }
+    report.events.push(Event::now(
+        EventType::Replication,
+        format!("{} listed files copied into data/", copied),
+    ));
+
+    let records = provenance.map(|log| log.records).unwrap_or_default();
+    let tag_files = write_records(dest, &records, options)?;
+    write_bag_files(dest, options, report, tag_files, &progress_tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{validate_bag, ErrorPolicy, ValidationOptions};
+
+    #[test]
+    fn test_parse_file_list() {
+        let list = "# query results\n\
+                    /archive/scans/page1.tif\n\
+                    /archive/scans/page2.tif\tvolume 1/page2.tif\n";
+        let entries = parse_file_list(list, false).unwrap();
+        assert_eq!(entries[0].path, "page1.tif");
+        assert_eq!(entries[1].source, PathBuf::from("/archive/scans/page2.tif"));
+        assert_eq!(entries[1].path, "volume 1/page2.tif");
+
+        let csv = "source,target\n\
+                   \"/archive/Smith, J/letter.pdf\",correspondence/letter.pdf\n\
+                   /archive/notes.txt,\n";
+        let entries = parse_file_list(csv, true).unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(
+            entries[0].source,
+            PathBuf::from("/archive/Smith, J/letter.pdf")
+        );
+        assert_eq!(entries[1].path, "notes.txt");
+
+        let relative = parse_file_list("scans/page1.tif\n", false);
+        assert!(matches!(
+            relative,
+            Err(BagError::BadFileList { line: 1, .. })
+        ));
+        let escaping = parse_file_list("/a/page1.tif\t../page1.tif\n", false);
+        assert!(matches!(
+            escaping,
+            Err(BagError::BadFileList { line: 1, .. })
+        ));
+        let repeated = parse_file_list("/a/page1.tif\n/b/page1.tif\n", false);
+        assert!(matches!(
+            repeated,
+            Err(BagError::BadFileList { line: 2, .. })
+        ));
+    }
+
+    #[test]
+    fn test_bag_from_list() {
+        let temp_dir = std::env::temp_dir().join("baggie_test_file_list");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let scans = temp_dir.join("scans");
+        fs::create_dir_all(&scans).unwrap();
+        fs::write(scans.join("page1.tif"), "one").unwrap();
+        fs::write(scans.join("page2.tif"), "two").unwrap();
+        fs::write(scans.join("unwanted.tif"), "not listed").unwrap();
+        let absolute = std::path::absolute(&scans).unwrap();
+        let list = format!(
+            "{}\n{}\tvolume 1/page2.tif\n",
+            absolute.join("page1.tif").display(),
+            absolute.join("page2.tif").display()
+        );
+        let entries = parse_file_list(&list, false).unwrap();
+
+        let dest = temp_dir.join("bag");
+        bag_from_list(&entries, &dest, &BagOptions::default(), None).unwrap();
+        assert!(dest.join("data/page1.tif").exists());
+        assert!(dest.join("data/volume 1/page2.tif").exists());
+        assert!(!dest.join("data/unwanted.tif").exists());
+        let report = validate_bag(&dest, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // A listed file that's missing goes by the error policy
+        let missing = ListedFile {
+            source: absolute.join("page3.tif"),
+            path: "page3.tif".to_string(),
+        };
+        let entries = [entries, vec![missing]].concat();
+        let result = bag_from_list(
+            &entries,
+            &temp_dir.join("strict"),
+            &BagOptions::default(),
+            None,
+        );
+        assert!(result.is_err());
+        let options = BagOptions {
+            error_policy: ErrorPolicy::Skip,
+            ..BagOptions::default()
+        };
+        let report = bag_from_list(&entries, &temp_dir.join("lenient"), &options, None).unwrap();
+        assert_eq!(report.vanished, vec![absolute.join("page3.tif")]);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
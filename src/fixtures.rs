@@ -0,0 +1,255 @@
+use crate::bagit::{bag_directory, BagError, BagOptions};
+use crate::tagfile::{read_tag_file, write_tag_file};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// Names used when `TreeSpec::unicode_names` is on: accents in both normalization
+/// forms, non-Latin scripts, emoji and spaces.
+const UNICODE_NAMES: [&str; 6] = [
+    "résumé",
+    "re\u{301}sume\u{301}",
+    "日本語",
+    "Ελληνικά",
+    "box 📦",
+    "Ærøskøbing",
+];
+
+/// The shape of a synthetic tree. The same spec always gives the same tree, content
+/// included.
+#[derive(Debug, Clone)]
+pub struct TreeSpec {
+    pub files: usize,
+    pub file_size: u64,
+    /// Folders nested inside each other; files are spread over every level.
+    pub depth: usize,
+    pub unicode_names: bool,
+    /// Adds relative symbolic links to a file and to the deepest folder (Unix only).
+    pub symlinks: bool,
+    pub seed: u64,
+}
+
+impl Default for TreeSpec {
+    fn default() -> Self {
+        Self {
+            files: 10,
+            file_size: 1024,
+            depth: 2,
+            unicode_names: false,
+            symlinks: false,
+            seed: 1,
+        }
+    }
+}
+
+/// Ways `broken_bag` can damage a bag, each of which validation must catch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakage {
+    /// A payload file's content no longer matches its checksum.
+    ChangedPayload,
+    /// A payload file listed in the manifest is gone.
+    MissingPayload,
+    /// A file in `data/` no manifest lists.
+    ExtraPayload,
+    /// bag-info.txt was edited after the tag manifest was written.
+    ChangedTagFile,
+    /// Payload-Oxum counts one file more than there is.
+    WrongOxum,
+    /// bagit.txt is missing.
+    NoDeclaration,
+}
+
+impl Breakage {
+    pub const ALL: [Breakage; 6] = [
+        Breakage::ChangedPayload,
+        Breakage::MissingPayload,
+        Breakage::ExtraPayload,
+        Breakage::ChangedTagFile,
+        Breakage::WrongOxum,
+        Breakage::NoDeclaration,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Breakage::ChangedPayload => "changed-payload",
+            Breakage::MissingPayload => "missing-payload",
+            Breakage::ExtraPayload => "extra-payload",
+            Breakage::ChangedTagFile => "changed-tag-file",
+            Breakage::WrongOxum => "wrong-oxum",
+            Breakage::NoDeclaration => "no-declaration",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Breakage> {
+        Breakage::ALL.into_iter().find(|b| b.name() == name)
+    }
+}
+
+/// xorshift64: repeatable filler without a random number crate.
+fn next(state: &mut u64) -> u64 {
+    *state ^= *state << 13;
+    *state ^= *state >> 7;
+    *state ^= *state << 17;
+    *state
+}
+
+/// Writes the tree described by `spec` under `root`, which must not exist yet or be
+/// empty. Returns the regular files written, relative to `root`.
+pub fn generate_tree(root: &Path, spec: &TreeSpec) -> io::Result<Vec<PathBuf>> {
+    if root.exists() && fs::read_dir(root)?.next().is_some() {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} isn't empty", root.display()),
+        ));
+    }
+    let mut state = spec.seed.max(1);
+    let mut levels = vec![PathBuf::new()];
+    for level in 1..=spec.depth {
+        let name = match spec.unicode_names {
+            true => format!("{} {}", UNICODE_NAMES[level % UNICODE_NAMES.len()], level),
+            false => format!("level-{}", level),
+        };
+        levels.push(levels[level - 1].join(name));
+    }
+    fs::create_dir_all(root.join(levels.last().unwrap()))?;
+
+    let mut files = Vec::with_capacity(spec.files);
+    for i in 0..spec.files {
+        let name = match spec.unicode_names {
+            true => format!("{} {:04}.bin", UNICODE_NAMES[i % UNICODE_NAMES.len()], i),
+            false => format!("file-{:04}.bin", i),
+        };
+        let relative = levels[i % levels.len()].join(name);
+        let mut content = Vec::with_capacity(spec.file_size as usize + 8);
+        while (content.len() as u64) < spec.file_size {
+            content.extend_from_slice(&next(&mut state).to_le_bytes());
+        }
+        content.truncate(spec.file_size as usize);
+        fs::write(root.join(&relative), content)?;
+        files.push(relative);
+    }
+
+    #[cfg(unix)]
+    if spec.symlinks {
+        use std::os::unix::fs::symlink;
+        if let Some(first) = files.first() {
+            symlink(first, root.join("link-to-file"))?;
+        }
+        symlink(levels.last().unwrap(), root.join("link-to-folder"))?;
+    }
+    Ok(files)
+}
+
+/// Makes a bag at `dest` from a small generated tree, then damages it as `breakage`
+/// says.
+pub fn broken_bag(dest: &Path, breakage: Breakage) -> Result<(), BagError> {
+    let spec = TreeSpec {
+        files: 3,
+        file_size: 64,
+        depth: 1,
+        ..TreeSpec::default()
+    };
+    let files = generate_tree(dest, &spec)?;
+    bag_directory(dest, &BagOptions::default(), None)?;
+    let payload = dest.join("data").join(&files[0]);
+    match breakage {
+        Breakage::ChangedPayload => {
+            let mut content = fs::read(&payload)?;
+            content[0] ^= 0xff;
+            fs::write(&payload, content)?;
+        }
+        Breakage::MissingPayload => fs::remove_file(&payload)?,
+        Breakage::ExtraPayload => fs::write(dest.join("data/unlisted.txt"), "not in any manifest")?,
+        Breakage::ChangedTagFile => {
+            let (info, _) = read_tag_file(&dest.join("bag-info.txt"))?;
+            write_tag_file(
+                &dest.join("bag-info.txt"),
+                &format!("{}Edited: later\n", info),
+            )?;
+        }
+        Breakage::WrongOxum => {
+            let (info, _) = read_tag_file(&dest.join("bag-info.txt"))?;
+            let total = spec.file_size * spec.files as u64;
+            let wrong = format!("Payload-Oxum: {}.{}", total, spec.files + 1);
+            let info = info.replace(&format!("Payload-Oxum: {}.{}", total, spec.files), &wrong);
+            write_tag_file(&dest.join("bag-info.txt"), &info)?;
+        }
+        Breakage::NoDeclaration => fs::remove_file(dest.join("bagit.txt"))?,
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{validate_bag, ValidationOptions};
+
+    #[test]
+    fn test_generated_tree_bags() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_fixtures");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let spec = TreeSpec {
+            files: 12,
+            file_size: 100,
+            depth: 5,
+            unicode_names: true,
+            ..TreeSpec::default()
+        };
+        let tree = temp_dir.join("tree");
+        let files = generate_tree(&tree, &spec).unwrap();
+        assert_eq!(files.len(), 12);
+        assert_eq!(files[5].components().count(), 6);
+        assert_eq!(fs::metadata(tree.join(&files[11])).unwrap().len(), 100);
+        assert!(generate_tree(&tree, &spec).is_err());
+
+        #[cfg(unix)]
+        {
+            let linked = temp_dir.join("linked");
+            let spec = TreeSpec {
+                symlinks: true,
+                ..TreeSpec::default()
+            };
+            let files = generate_tree(&linked, &spec).unwrap();
+            assert_eq!(
+                fs::read(linked.join("link-to-file")).unwrap(),
+                fs::read(linked.join(&files[0])).unwrap()
+            );
+            assert!(linked.join("link-to-folder").is_dir());
+        }
+
+        // Same spec, same bytes
+        let again = temp_dir.join("again");
+        generate_tree(&again, &spec).unwrap();
+        assert_eq!(
+            fs::read(tree.join(&files[3])).unwrap(),
+            fs::read(again.join(&files[3])).unwrap()
+        );
+
+        bag_directory(&tree, &BagOptions::default(), None).unwrap();
+        let report = validate_bag(&tree, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_broken_bags_fail() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_fixtures_broken");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        for breakage in Breakage::ALL {
+            assert_eq!(Breakage::parse(breakage.name()), Some(breakage));
+            let bag = temp_dir.join(breakage.name());
+            broken_bag(&bag, breakage).unwrap();
+            match validate_bag(&bag, &ValidationOptions::default(), None) {
+                Ok(report) => assert!(!report.is_valid(), "{} passed", breakage.name()),
+                Err(_) => assert_eq!(breakage, Breakage::NoDeclaration),
+            }
+        }
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
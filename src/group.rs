@@ -0,0 +1,325 @@
+use crate::bagit::{
+    bag_algorithms, bag_volume, calculate_digest, check_free_space, copy_payload_file,
+    plan_volumes, read_payload_manifest, validate_bag, write_bag_files, BagError, BagOptions,
+    BagReport, Progress, ValidationOptions, PER_FILE_ALLOWANCE,
+};
+use crate::events::{Event, EventType};
+use crate::tagfile::{read_tag_file, BagInfo};
+use std::collections::HashSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+/// Splits the folder at `source` into bags of at most `capacity` bytes each, written
+/// to `<name>_1_of_3`, `<name>_2_of_3`, ... under `dest`, and returns them. The bags
+/// share the folder name as their `Bag-Group-Identifier`.
+pub fn split_into_bags(
+    source: &Path,
+    dest: &Path,
+    capacity: u64,
+    options: &BagOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<Vec<PathBuf>, BagError> {
+    let (volumes, _) = plan_volumes(source, options, capacity)?;
+    let name = source
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "bag".to_string());
+    let mut bags = Vec::new();
+    for index in 0..volumes.len() {
+        let bag = dest.join(format!("{}_{}_of_{}", name, index + 1, volumes.len()));
+        bag_volume(
+            source,
+            &bag,
+            &volumes,
+            index,
+            &name,
+            options,
+            progress_tx.clone(),
+        )?;
+        bags.push(bag);
+    }
+    Ok(bags)
+}
+
+/// Reads `Bag-Count: 2 of 3` as `(2, 3)`.
+fn parse_bag_count(value: &str) -> Option<(usize, usize)> {
+    let (index, count) = value.split_once(" of ")?;
+    let index = index.trim().parse().ok()?;
+    let count = count.trim().parse().ok()?;
+    (index >= 1 && index <= count).then_some((index, count))
+}
+
+/// Checks that `bags` are a whole group: every one has the same
+/// `Bag-Group-Identifier`, their `Bag-Count`s run from 1 to N with none missing or
+/// repeated, and each is valid. Returns the bags in `Bag-Count` order.
+pub fn check_group(
+    bags: &[PathBuf],
+    options: &ValidationOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<Vec<PathBuf>, BagError> {
+    let mut group: Option<String> = None;
+    let mut members: Vec<Option<PathBuf>> = Vec::new();
+    for bag in bags {
+        if !bag.join("bagit.txt").is_file() {
+            return Err(BagError::BadBagGroup(format!(
+                "{} is not a bag",
+                bag.display()
+            )));
+        }
+        let (content, _) = read_tag_file(&bag.join("bag-info.txt"))?;
+        let info = BagInfo::parse(&content);
+        let identifier = info.get("Bag-Group-Identifier").ok_or_else(|| {
+            BagError::BadBagGroup(format!("{} has no Bag-Group-Identifier", bag.display()))
+        })?;
+        match &group {
+            Some(group) if group != identifier => {
+                return Err(BagError::BadBagGroup(format!(
+                    "{} belongs to group {}, not {}",
+                    bag.display(),
+                    identifier,
+                    group
+                )))
+            }
+            _ => group = Some(identifier.to_string()),
+        }
+        let (index, count) = info
+            .get("Bag-Count")
+            .and_then(parse_bag_count)
+            .ok_or_else(|| {
+                BagError::BadBagGroup(format!(
+                    "{} has no Bag-Count like \"2 of 3\"",
+                    bag.display()
+                ))
+            })?;
+        if members.is_empty() {
+            members = vec![None; count];
+        } else if members.len() != count {
+            return Err(BagError::BadBagGroup(format!(
+                "{} is one of {} bags, but the others are one of {}",
+                bag.display(),
+                count,
+                members.len()
+            )));
+        }
+        if let Some(other) = &members[index - 1] {
+            return Err(BagError::BadBagGroup(format!(
+                "{} and {} are both bag {} of {}",
+                other.display(),
+                bag.display(),
+                index,
+                count
+            )));
+        }
+        members[index - 1] = Some(bag.clone());
+    }
+
+    let count = members.len();
+    let mut ordered = Vec::new();
+    for (index, member) in members.into_iter().enumerate() {
+        match member {
+            Some(bag) => ordered.push(bag),
+            None => {
+                return Err(BagError::BadBagGroup(format!(
+                    "bag {} of {} is missing",
+                    index + 1,
+                    count
+                )))
+            }
+        }
+    }
+    if ordered.is_empty() {
+        return Err(BagError::BadBagGroup("no bags were given".to_string()));
+    }
+    for bag in &ordered {
+        let report = validate_bag(bag, options, progress_tx.clone())?;
+        if let Some(error) = report.errors.first() {
+            return Err(BagError::BadBagGroup(format!(
+                "{} isn't valid: {}",
+                bag.display(),
+                error
+            )));
+        }
+    }
+    Ok(ordered)
+}
+
+/// Checks the group `bags` with [`check_group`], then merges their payloads into one
+/// new bag at `dest`, checking every copy against the manifest of the bag it came
+/// from. The new bag keeps the first bag's fields, less `Bag-Count` and
+/// `Bag-Group-Identifier`, and its algorithms.
+pub fn reassemble_group(
+    bags: &[PathBuf],
+    dest: &Path,
+    options: &BagOptions,
+    validation: &ValidationOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<BagReport, BagError> {
+    if dest.exists() && fs::read_dir(dest)?.next().is_some() {
+        return Err(BagError::DestinationNotEmpty(dest.to_path_buf()));
+    }
+    let bags = check_group(bags, validation, progress_tx.clone())?;
+
+    let mut manifests = Vec::new();
+    let mut bytes = 0;
+    let mut total_files = 0;
+    for bag in &bags {
+        let (algorithm, manifest) = read_payload_manifest(bag)?;
+        for file in manifest.keys() {
+            bytes += fs::metadata(bag.join(file))?.len();
+        }
+        total_files += manifest.len();
+        manifests.push((algorithm, manifest));
+    }
+    check_free_space(dest, bytes + total_files as u64 * PER_FILE_ALLOWANCE)?;
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started { total_files });
+    }
+
+    let (content, _) = read_tag_file(&bags[0].join("bag-info.txt"))?;
+    let info = BagInfo::parse(&content);
+    let group = info
+        .get("Bag-Group-Identifier")
+        .unwrap_or_default()
+        .to_string();
+    let mut options = options.clone();
+    options.metadata = info;
+    options
+        .metadata
+        .fields_mut()
+        .retain(|(label, _)| label != "Bag-Count" && label != "Bag-Group-Identifier");
+    options.algorithms = bag_algorithms(&bags[0]);
+    options.skip_names = Vec::new();
+
+    let mut seen = HashSet::new();
+    let mut current = 0;
+    for (bag, (algorithm, manifest)) in bags.iter().zip(&manifests) {
+        let mut files: Vec<&String> = manifest.keys().collect();
+        files.sort();
+        for file in files {
+            current += 1;
+            if let Some(ref tx) = progress_tx {
+                let _ = tx.send(Progress::Copying {
+                    current,
+                    filename: file.clone(),
+                });
+            }
+            if !seen.insert(file.clone()) {
+                return Err(BagError::BadBagGroup(format!(
+                    "{} is in more than one bag of the group",
+                    file
+                )));
+            }
+            let target = dest.join(file);
+            if let Some(parent) = target.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            copy_payload_file(&bag.join(file), &target, &options)?;
+            if calculate_digest(&target, *algorithm)? != manifest[file] {
+                return Err(BagError::CopyMismatch(target));
+            }
+        }
+    }
+    fs::create_dir_all(dest.join("data"))?;
+
+    let mut report = BagReport::default();
+    report.events.push(Event::now(
+        EventType::Replication,
+        format!(
+            "{} files copied into data/ from the {} bags of group {}",
+            total_files,
+            bags.len(),
+            group
+        ),
+    ));
+    write_bag_files(dest, &options, report, Vec::new(), &progress_tx)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_source(name: &str) -> PathBuf {
+        let temp_dir = std::env::temp_dir().join(name);
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("survey");
+        fs::create_dir_all(source.join("sub")).unwrap();
+        for (i, name) in ["a.bin", "b.bin", "sub/c.bin", "sub/d.bin", "sub/e.bin"]
+            .iter()
+            .enumerate()
+        {
+            fs::write(source.join(name), vec![i as u8; 400 * 1024]).unwrap();
+        }
+        source
+    }
+
+    #[test]
+    fn test_split_and_reassemble() {
+        let source = make_source("bagit_test_group");
+        let temp_dir = source.parent().unwrap().to_path_buf();
+
+        // Room for two 400 KiB files per bag after the 1 MiB tag allowance
+        let capacity = (1024 + 900) * 1024;
+        let out = temp_dir.join("split");
+        let bags = split_into_bags(&source, &out, capacity, &BagOptions::default(), None).unwrap();
+        assert_eq!(bags.len(), 3);
+        assert_eq!(bags[2], out.join("survey_3_of_3"));
+
+        // Given in any order, the group comes back in Bag-Count order
+        let shuffled = vec![bags[2].clone(), bags[0].clone(), bags[1].clone()];
+        let ordered = check_group(&shuffled, &ValidationOptions::default(), None).unwrap();
+        assert_eq!(ordered, bags);
+
+        let whole = temp_dir.join("whole");
+        let options = BagOptions::default();
+        reassemble_group(
+            &shuffled,
+            &whole,
+            &options,
+            &ValidationOptions::default(),
+            None,
+        )
+        .unwrap();
+        for name in ["a.bin", "b.bin", "sub/c.bin", "sub/d.bin", "sub/e.bin"] {
+            assert_eq!(
+                fs::read(whole.join("data").join(name)).unwrap(),
+                fs::read(source.join(name)).unwrap()
+            );
+        }
+        let bag_info = fs::read_to_string(whole.join("bag-info.txt")).unwrap();
+        assert!(!bag_info.contains("Bag-Count"));
+        assert!(!bag_info.contains("Bag-Group-Identifier"));
+        assert!(bag_info.contains("Payload-Oxum: 2048000.5"));
+        let report = validate_bag(&whole, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_incomplete_group() {
+        let source = make_source("bagit_test_group_incomplete");
+        let temp_dir = source.parent().unwrap().to_path_buf();
+        let out = temp_dir.join("split");
+        let capacity = (1024 + 900) * 1024;
+        let bags = split_into_bags(&source, &out, capacity, &BagOptions::default(), None).unwrap();
+        let validation = ValidationOptions::default();
+
+        let missing = check_group(&bags[..2], &validation, None);
+        assert!(matches!(missing, Err(BagError::BadBagGroup(m)) if m.contains("3 of 3")));
+        let repeated = check_group(&[bags[0].clone(), bags[0].clone()], &validation, None);
+        assert!(matches!(repeated, Err(BagError::BadBagGroup(_))));
+
+        // A damaged member fails the group, and nothing is reassembled
+        let (_, manifest) = read_payload_manifest(&bags[1]).unwrap();
+        fs::write(bags[1].join(manifest.keys().next().unwrap()), "damaged").unwrap();
+        let dest = temp_dir.join("whole");
+        let result = reassemble_group(&bags, &dest, &BagOptions::default(), &validation, None);
+        assert!(matches!(result, Err(BagError::BadBagGroup(m)) if m.contains("isn't valid")));
+        assert!(!dest.exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,521 @@
+use crate::agent::read_line_limited;
+use crate::bagit::{bag_directory, validate_bag};
+use crate::events::{append_events, Event, EventType};
+use crate::settings::Settings;
+use std::fs::{self, OpenOptions};
+use std::io::{self, BufRead, BufReader, Read, Write};
+use std::net::{TcpListener, TcpStream};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::thread;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Where `baggie intake` listens unless told otherwise.
+pub const DEFAULT_INTAKE_ADDRESS: &str = "127.0.0.1:8493";
+
+/// Longest request line or header read, and most headers read, before the token is
+/// checked, so a client can't fill the server's memory without one.
+const MAX_LINE: u64 = 8 * 1024;
+const MAX_HEADERS: usize = 100;
+
+/// How long a connection may go without sending anything, uploads included.
+const READ_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// Where the deposit endpoint keeps uploads, and what it bags them with.
+///
+/// The HTTP interface, one upload session per bag:
+/// - `POST /uploads` starts a session and answers with its id
+/// - `PUT /uploads/<id>/<path>` stores one file of the payload
+/// - `HEAD /uploads/<id>/<path>` reports how much of a file has arrived in
+///   `Upload-Offset`, and `PATCH` with a matching `Upload-Offset` appends the rest,
+///   so an interrupted upload resumes where it stopped
+/// - `POST /uploads/<id>/complete` bags the session and answers with the validation
+///   report (200 when valid, 422 when not); while it's being bagged, uploads and
+///   completes for it get 409
+///
+/// When a token is set, every request needs `Authorization: Bearer <token>`.
+#[derive(Clone)]
+pub struct Intake {
+    pub staging: PathBuf,
+    pub settings: Settings,
+    pub token: String,
+}
+
+struct Request {
+    method: String,
+    /// Percent-decoded path segments.
+    segments: Vec<String>,
+    headers: Vec<(String, String)>,
+}
+
+impl Request {
+    fn header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find(|(n, _)| n.eq_ignore_ascii_case(name))
+            .map(|(_, v)| v.as_str())
+    }
+
+    fn content_length(&self) -> Option<u64> {
+        self.header("Content-Length").and_then(|v| v.parse().ok())
+    }
+}
+
+struct Response {
+    status: u16,
+    headers: Vec<(&'static str, String)>,
+    body: String,
+}
+
+fn response(status: u16, body: impl Into<String>) -> Response {
+    Response {
+        status,
+        headers: Vec::new(),
+        body: body.into(),
+    }
+}
+
+fn reason(status: u16) -> &'static str {
+    match status {
+        200 => "OK",
+        201 => "Created",
+        204 => "No Content",
+        400 => "Bad Request",
+        401 => "Unauthorized",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        409 => "Conflict",
+        411 => "Length Required",
+        422 => "Unprocessable Entity",
+        _ => "Internal Server Error",
+    }
+}
+
+fn percent_decode(segment: &str) -> Option<String> {
+    let bytes = segment.as_bytes();
+    let mut out = Vec::new();
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = segment.get(i + 1..i + 3)?;
+            out.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            out.push(bytes[i]);
+            i += 1;
+        }
+    }
+    String::from_utf8(out).ok()
+}
+
+/// Payload paths may not climb out of their session or hide in odd names.
+fn safe_relative_path(segments: &[String]) -> Option<PathBuf> {
+    if segments.is_empty() {
+        return None;
+    }
+    let mut path = PathBuf::new();
+    for segment in segments {
+        if segment.is_empty()
+            || segment == "."
+            || segment == ".."
+            || segment.contains(['\\', '/', ':', '\0'])
+        {
+            return None;
+        }
+        path.push(segment);
+    }
+    Some(path)
+}
+
+fn new_session_id() -> String {
+    static COUNTER: AtomicU64 = AtomicU64::new(0);
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    format!("{:x}-{:x}", nanos, COUNTER.fetch_add(1, Ordering::Relaxed))
+}
+
+fn is_session_id(id: &str) -> bool {
+    !id.is_empty() && id.chars().all(|c| c.is_ascii_hexdigit() || c == '-')
+}
+
+/// Accepts uploads until the listener fails, one thread per connection.
+pub fn serve_intake(listener: TcpListener, intake: Intake) -> io::Result<()> {
+    fs::create_dir_all(&intake.staging)?;
+    for stream in listener.incoming() {
+        let stream = stream?;
+        let intake = intake.clone();
+        thread::spawn(move || {
+            let _ = handle_connection(stream, &intake);
+        });
+    }
+    Ok(())
+}
+
+fn read_request(reader: &mut impl BufRead) -> io::Result<Option<Request>> {
+    let line = read_line_limited(reader, MAX_LINE)?;
+    let mut parts = line.split_whitespace();
+    let (Some(method), Some(target)) = (parts.next(), parts.next()) else {
+        return Ok(None);
+    };
+    let path = target.split('?').next().unwrap_or("");
+    let segments: Option<Vec<String>> = path
+        .trim_start_matches('/')
+        .split('/')
+        .map(percent_decode)
+        .collect();
+
+    let mut headers = Vec::new();
+    loop {
+        let header = read_line_limited(reader, MAX_LINE)?;
+        if header.is_empty() {
+            break;
+        }
+        if headers.len() == MAX_HEADERS {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                "too many headers",
+            ));
+        }
+        let header = header.trim_end();
+        if header.is_empty() {
+            break;
+        }
+        if let Some((name, value)) = header.split_once(':') {
+            headers.push((name.trim().to_string(), value.trim().to_string()));
+        }
+    }
+    Ok(segments.map(|segments| Request {
+        method: method.to_string(),
+        segments,
+        headers,
+    }))
+}
+
+fn handle_connection(stream: TcpStream, intake: &Intake) -> io::Result<()> {
+    stream.set_read_timeout(Some(READ_TIMEOUT))?;
+    let mut reader = BufReader::new(stream.try_clone()?);
+    let response = match read_request(&mut reader) {
+        Ok(Some(request)) => route(&request, &mut reader, intake),
+        Ok(None) => response(400, "Malformed request\n"),
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            response(400, "Request line or headers too long\n")
+        }
+        Err(e) => return Err(e),
+    };
+
+    let mut writer = stream;
+    write!(
+        writer,
+        "HTTP/1.1 {} {}\r\n",
+        response.status,
+        reason(response.status)
+    )?;
+    for (name, value) in &response.headers {
+        write!(writer, "{}: {}\r\n", name, value)?;
+    }
+    write!(
+        writer,
+        "Content-Type: text/plain; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.body.len(),
+        response.body
+    )?;
+    writer.flush()
+}
+
+fn route(request: &Request, body: &mut impl Read, intake: &Intake) -> Response {
+    if !intake.token.is_empty()
+        && request.header("Authorization") != Some(&format!("Bearer {}", intake.token))
+    {
+        return response(401, "Missing or wrong token\n");
+    }
+    let segments: Vec<&str> = request.segments.iter().map(String::as_str).collect();
+    let method = request.method.as_str();
+    match segments.as_slice() {
+        ["uploads"] if method == "POST" => {
+            let id = new_session_id();
+            match fs::create_dir(intake.staging.join(&id)) {
+                Ok(()) => Response {
+                    status: 201,
+                    headers: vec![("Location", format!("/uploads/{}", id))],
+                    body: format!("{}\n", id),
+                },
+                Err(e) => response(500, format!("{}\n", e)),
+            }
+        }
+        ["uploads", id, "complete"] if method == "POST" => match session_dir(intake, id) {
+            Ok(dir) => match CompletingLock::take(&dir) {
+                Some(_lock) => complete(&dir, &intake.settings),
+                None => response(409, "This upload is being bagged\n"),
+            },
+            Err(r) => r,
+        },
+        ["uploads", id, ..] => {
+            let dir = match session_dir(intake, id) {
+                Ok(dir) => dir,
+                Err(r) => return r,
+            };
+            if dir.join("bagit.txt").exists() {
+                return response(409, "This upload has already been bagged\n");
+            }
+            if completing_path(&dir).exists() {
+                return response(409, "This upload is being bagged\n");
+            }
+            let Some(relative) = safe_relative_path(&request.segments[2..]) else {
+                return response(400, "Bad file path\n");
+            };
+            match upload_file(request, body, &dir.join(relative)) {
+                Ok(r) | Err(r) => r,
+            }
+        }
+        _ => response(404, "Not found\n"),
+    }
+}
+
+fn session_dir(intake: &Intake, id: &str) -> Result<PathBuf, Response> {
+    let dir = intake.staging.join(id);
+    if is_session_id(id) && dir.is_dir() {
+        Ok(dir)
+    } else {
+        Err(response(404, "No such upload\n"))
+    }
+}
+
+/// The file that marks a session as being bagged, beside its folder rather than in
+/// it, so it isn't bagged too.
+fn completing_path(dir: &Path) -> PathBuf {
+    let mut name = dir.as_os_str().to_owned();
+    name.push(".completing");
+    PathBuf::from(name)
+}
+
+/// Held while a session is bagged, so a second complete or a late upload can't
+/// change it underneath; removed when dropped.
+struct CompletingLock(PathBuf);
+
+impl CompletingLock {
+    /// `None` if the session is already being bagged.
+    fn take(dir: &Path) -> Option<Self> {
+        let path = completing_path(dir);
+        OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&path)
+            .ok()?;
+        Some(Self(path))
+    }
+}
+
+impl Drop for CompletingLock {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+fn upload_file(
+    request: &Request,
+    body: &mut impl Read,
+    target: &Path,
+) -> Result<Response, Response> {
+    let io_error = |e: io::Error| response(500, format!("{}\n", e));
+    let received = fs::metadata(target).map_or(0, |m| m.len());
+    let offset_header = |offset: u64| vec![("Upload-Offset", offset.to_string())];
+
+    let append = match request.method.as_str() {
+        "HEAD" => {
+            return Ok(Response {
+                status: 200,
+                headers: offset_header(received),
+                body: String::new(),
+            })
+        }
+        "PUT" => false,
+        "PATCH" => {
+            let offset: Option<u64> = request.header("Upload-Offset").and_then(|v| v.parse().ok());
+            if offset != Some(received) {
+                return Err(Response {
+                    status: 409,
+                    headers: offset_header(received),
+                    body: format!("Upload-Offset must be {}\n", received),
+                });
+            }
+            true
+        }
+        _ => return Err(response(405, "Use PUT, PATCH or HEAD for files\n")),
+    };
+    let Some(length) = request.content_length() else {
+        return Err(response(411, "Content-Length is required\n"));
+    };
+
+    if let Some(parent) = target.parent() {
+        fs::create_dir_all(parent).map_err(io_error)?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .write(true)
+        .append(append)
+        .truncate(!append)
+        .open(target)
+        .map_err(io_error)?;
+    // A dropped connection leaves what arrived in place, for PATCH to resume from.
+    let written = io::copy(&mut body.take(length), &mut file).map_err(io_error)?;
+    let total = if append { received + written } else { written };
+    if written < length {
+        return Err(Response {
+            status: 400,
+            headers: offset_header(total),
+            body: "Upload ended early\n".to_string(),
+        });
+    }
+    Ok(Response {
+        status: 204,
+        headers: offset_header(total),
+        body: String::new(),
+    })
+}
+
+/// Bags a finished upload in place and validates it.
+fn complete(dir: &Path, settings: &Settings) -> Response {
+    if dir.join("bagit.txt").exists() {
+        return response(409, "This upload has already been bagged\n");
+    }
+    let mut text = match bag_directory(dir, &settings.bag_options(true), None) {
+        Ok(report) => report
+            .warnings
+            .iter()
+            .map(|w| format!("warning: {}\n", w))
+            .collect::<String>(),
+        Err(e) => return response(422, format!("error: {}\n", e)),
+    };
+    let report = match validate_bag(dir, &settings.validation_options(), None) {
+        Ok(report) => report,
+        Err(e) => return response(500, format!("error: {}\n", e)),
+    };
+    let events = [
+        Event::now(
+            EventType::Transfer,
+            "Payload received by upload to the intake endpoint",
+        ),
+        Event::now(EventType::FixityCheck, "Validated on receipt").with_outcome(report.is_valid()),
+    ];
+    if let Err(e) = append_events(dir, &events) {
+        return response(500, format!("error: {}\n", e));
+    }
+    text.push_str(&report.to_text());
+    response(if report.is_valid() { 200 } else { 422 }, text)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Sends one raw HTTP request and returns the status line's code and the body.
+    fn send(address: &str, request: &str) -> (u16, String) {
+        let mut stream = TcpStream::connect(address).unwrap();
+        stream.write_all(request.as_bytes()).unwrap();
+        let mut reply = String::new();
+        stream.read_to_string(&mut reply).unwrap();
+        let status = reply[9..12].parse().unwrap();
+        let body = reply.split_once("\r\n\r\n").unwrap().1.to_string();
+        (status, body)
+    }
+
+    #[test]
+    fn test_safe_relative_path() {
+        let path =
+            |s: &[&str]| safe_relative_path(&s.iter().map(|p| p.to_string()).collect::<Vec<_>>());
+        assert_eq!(
+            path(&["scans", "a b.tif"]),
+            Some(PathBuf::from("scans/a b.tif"))
+        );
+        assert!(path(&["..", "etc"]).is_none());
+        assert!(path(&["a", ""]).is_none());
+        assert!(path(&["C:", "x"]).is_none());
+        assert!(path(&[]).is_none());
+        assert_eq!(percent_decode("a%20b").as_deref(), Some("a b"));
+    }
+
+    #[test]
+    fn test_oversized_requests() {
+        let read = |request: String| read_request(&mut io::Cursor::new(request));
+        let request =
+            read("PUT /uploads/1/a.txt HTTP/1.1\r\nContent-Length: 5\r\n\r\n".to_string());
+        assert_eq!(request.unwrap().unwrap().content_length(), Some(5));
+        let long = format!(
+            "POST /uploads HTTP/1.1\r\nX-Filler: {}\r\n\r\n",
+            "x".repeat(10_000)
+        );
+        assert_eq!(read(long).err().unwrap().kind(), io::ErrorKind::InvalidData);
+        let many = format!(
+            "POST /uploads HTTP/1.1\r\n{}\r\n",
+            "X-Filler: x\r\n".repeat(200)
+        );
+        assert_eq!(read(many).err().unwrap().kind(), io::ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn test_upload_resume_and_complete() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_intake");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let intake = Intake {
+            staging: temp_dir.clone(),
+            settings: Settings::default(),
+            token: "secret".to_string(),
+        };
+        thread::spawn(move || serve_intake(listener, intake));
+        let auth = "Authorization: Bearer secret\r\n";
+
+        let (status, _) = send(&address, "POST /uploads HTTP/1.1\r\n\r\n");
+        assert_eq!(status, 401);
+        let (status, id) = send(&address, &format!("POST /uploads HTTP/1.1\r\n{}\r\n", auth));
+        assert_eq!(status, 201);
+        let id = id.trim();
+
+        let file = format!("/uploads/{}/scans/page%201.txt", id);
+        let put = format!(
+            "PUT {} HTTP/1.1\r\n{}Content-Length: 5\r\n\r\nhello",
+            file, auth
+        );
+        assert_eq!(send(&address, &put).0, 204);
+        // A resumed upload has to say where it carries on from
+        let patch = |offset: u64| {
+            format!(
+                "PATCH {} HTTP/1.1\r\n{}Upload-Offset: {}\r\nContent-Length: 6\r\n\r\n world",
+                file, auth, offset
+            )
+        };
+        assert_eq!(send(&address, &patch(2)).0, 409);
+        assert_eq!(send(&address, &patch(5)).0, 204);
+        let escape = format!(
+            "PUT /uploads/{}/../x HTTP/1.1\r\n{}Content-Length: 1\r\n\r\nx",
+            id, auth
+        );
+        assert_eq!(send(&address, &escape).0, 400);
+
+        // While the session is being bagged, another complete and more uploads wait
+        let complete = format!("POST /uploads/{}/complete HTTP/1.1\r\n{}\r\n", id, auth);
+        let lock = CompletingLock::take(&temp_dir.join(id)).unwrap();
+        let (status, body) = send(&address, &complete);
+        assert_eq!(
+            (status, body.as_str()),
+            (409, "This upload is being bagged\n")
+        );
+        assert_eq!(send(&address, &put).0, 409);
+        drop(lock);
+
+        let (status, report) = send(&address, &complete);
+        assert_eq!(status, 200, "{}", report);
+        assert!(report.contains("is valid"), "{}", report);
+        assert_eq!(
+            fs::read_to_string(temp_dir.join(id).join("data/scans/page 1.txt")).unwrap(),
+            "hello world"
+        );
+        assert_eq!(send(&address, &complete).0, 409);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,723 @@
+use crate::bagit::{BagError, BagSummary, ValidationReport};
+use crate::settings::config_dir;
+use crate::tagfile::{read_tag_file, BagInfo};
+use chrono::NaiveDate;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Outcome of the most recent fixity check of a bag.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum FixityStatus {
+    #[default]
+    Unchecked,
+    Ok,
+    Failed,
+}
+
+impl FixityStatus {
+    fn name(&self) -> &'static str {
+        match self {
+            FixityStatus::Unchecked => "unchecked",
+            FixityStatus::Ok => "ok",
+            FixityStatus::Failed => "failed",
+        }
+    }
+
+    fn parse(value: &str) -> Self {
+        match value {
+            "ok" => FixityStatus::Ok,
+            "failed" => FixityStatus::Failed,
+            _ => FixityStatus::Unchecked,
+        }
+    }
+}
+
+/// How many audits of each bag the inventory keeps; older ones are dropped.
+pub const AUDIT_HISTORY: usize = 100;
+
+/// One fixity check of a bag, as kept in its audit history.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// Local time the check finished (`YYYY-MM-DD HH:MM:SS`).
+    pub time: String,
+    pub status: FixityStatus,
+    /// Whether the check ran because it was due, rather than being asked for.
+    pub scheduled: bool,
+    pub files_checked: usize,
+    /// What failed: one line per error, or why the bag couldn't be read at all.
+    pub failures: Vec<String>,
+}
+
+impl AuditRecord {
+    /// The record of a check that finished just now with `result`.
+    pub fn new(result: &Result<ValidationReport, String>, scheduled: bool) -> Self {
+        let (status, files_checked, failures) = match result {
+            Ok(report) if report.is_valid() => (FixityStatus::Ok, report.files_checked, Vec::new()),
+            Ok(report) => (
+                FixityStatus::Failed,
+                report.files_checked,
+                report.errors.iter().map(|e| e.message.clone()).collect(),
+            ),
+            Err(message) => (FixityStatus::Failed, 0, vec![message.clone()]),
+        };
+        Self {
+            time: chrono::Local::now().format("%Y-%m-%d %H:%M:%S").to_string(),
+            status,
+            scheduled,
+            files_checked,
+            failures,
+        }
+    }
+
+    /// `YYYY-MM-DD` part of the time.
+    pub fn date(&self) -> &str {
+        self.time.get(..10).unwrap_or(&self.time)
+    }
+
+    /// One `Audit:` line's value: time, status, how it ran, files checked, then each
+    /// failure, separated by tabs.
+    fn serialize(&self) -> String {
+        let mut fields = vec![
+            self.time.clone(),
+            self.status.name().to_string(),
+            if self.scheduled {
+                "scheduled"
+            } else {
+                "manual"
+            }
+            .to_string(),
+            self.files_checked.to_string(),
+        ];
+        fields.extend(
+            self.failures
+                .iter()
+                .map(|f| f.replace(['\t', '\n', '\r'], " ")),
+        );
+        fields.join("\t")
+    }
+
+    fn parse(value: &str) -> Option<Self> {
+        let mut fields = value.split('\t');
+        Some(Self {
+            time: fields.next()?.to_string(),
+            status: FixityStatus::parse(fields.next()?),
+            scheduled: fields.next()? == "scheduled",
+            files_checked: fields.next()?.parse().ok()?,
+            failures: fields.map(String::from).collect(),
+        })
+    }
+}
+
+/// One bag baggie has made, as recorded in the inventory.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct InventoryEntry {
+    pub path: PathBuf,
+    pub identifier: String,
+    /// `Internal-Sender-Identifier` from bag-info.txt, if any.
+    pub sender_id: String,
+    /// `Bagging-Date` from bag-info.txt (`YYYY-MM-DD`).
+    pub bagging_date: String,
+    pub files: u64,
+    pub bytes: u64,
+    /// `BagIt-Profile-Identifier` from bag-info.txt, if any.
+    pub profile: String,
+    /// `Bag-Group-Identifier` from bag-info.txt, if any.
+    pub project: String,
+    /// Date of the last fixity check (`YYYY-MM-DD`), empty if never checked.
+    pub last_checked: String,
+    pub status: FixityStatus,
+    /// Payload files known to be damaged, with the note left when they were triaged.
+    pub damaged: Vec<(String, String)>,
+    /// Past fixity checks, oldest first, up to [`AUDIT_HISTORY`] of them.
+    pub audits: Vec<AuditRecord>,
+}
+
+impl InventoryEntry {
+    /// Reads the facts for the bag at `path` from its tag files.
+    pub fn read(path: &Path) -> Result<Self, BagError> {
+        let summary = BagSummary::read(path)?;
+        let (bag_info, _) = read_tag_file(&path.join("bag-info.txt"))?;
+        let bag_info = BagInfo::parse(&bag_info);
+        let (bytes, files) = summary
+            .payload_oxum
+            .split_once('.')
+            .and_then(|(b, f)| Some((b.parse().ok()?, f.parse().ok()?)))
+            .unwrap_or_default();
+        let field = |label| bag_info.get(label).unwrap_or_default().to_string();
+        Ok(Self {
+            path: std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf()),
+            identifier: summary.identifier,
+            sender_id: summary.sender_id,
+            bagging_date: field("Bagging-Date"),
+            files,
+            bytes,
+            profile: field("BagIt-Profile-Identifier"),
+            project: field("Bag-Group-Identifier"),
+            last_checked: String::new(),
+            status: FixityStatus::Unchecked,
+            damaged: Vec::new(),
+            audits: Vec::new(),
+        })
+    }
+
+    /// Whether a fixity check is due: `interval_days` have passed since the last check,
+    /// or since bagging for a bag never checked. An interval of 0 turns checks off.
+    pub fn is_overdue(&self, today: NaiveDate, interval_days: u32) -> bool {
+        if interval_days == 0 {
+            return false;
+        }
+        let since = if self.last_checked.is_empty() {
+            &self.bagging_date
+        } else {
+            &self.last_checked
+        };
+        match since.parse::<NaiveDate>() {
+            Ok(since) => (today - since).num_days() >= i64::from(interval_days),
+            Err(_) => true,
+        }
+    }
+}
+
+/// Every bag baggie has made on this machine.
+///
+/// Stored next to the settings as blocks of `Key: Value` lines, one block per bag,
+/// separated by blank lines.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Inventory {
+    pub entries: Vec<InventoryEntry>,
+}
+
+pub fn inventory_path() -> Option<PathBuf> {
+    config_dir().map(|d| d.join("baggie").join("inventory.txt"))
+}
+
+impl Inventory {
+    pub fn load() -> Self {
+        inventory_path()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|content| Self::parse(&content))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = inventory_path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.serialize())
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut entries = Vec::new();
+        for block in content.split("\n\n") {
+            let mut entry = InventoryEntry::default();
+            for line in block.lines() {
+                let Some((key, value)) = line.split_once(':') else {
+                    continue;
+                };
+                let value = value.trim();
+                match key.trim() {
+                    "Path" => entry.path = PathBuf::from(value),
+                    "Identifier" => entry.identifier = value.to_string(),
+                    "Sender-Id" => entry.sender_id = value.to_string(),
+                    "Bagging-Date" => entry.bagging_date = value.to_string(),
+                    "Files" => entry.files = value.parse().unwrap_or(0),
+                    "Bytes" => entry.bytes = value.parse().unwrap_or(0),
+                    "Profile" => entry.profile = value.to_string(),
+                    "Project" => entry.project = value.to_string(),
+                    "Last-Checked" => entry.last_checked = value.to_string(),
+                    // `Damaged: data/file: note`, one line per file
+                    "Damaged" => {
+                        if let Some((file, note)) = value.split_once(':') {
+                            entry
+                                .damaged
+                                .push((file.trim().to_string(), note.trim().to_string()));
+                        }
+                    }
+                    // `Audit: <time>\t<status>\t...`, one line per check
+                    "Audit" => entry.audits.extend(AuditRecord::parse(value)),
+                    "Status" => entry.status = FixityStatus::parse(value),
+                    _ => {}
+                }
+            }
+            if !entry.path.as_os_str().is_empty() {
+                entries.push(entry);
+            }
+        }
+        Self { entries }
+    }
+
+    pub fn serialize(&self) -> String {
+        self.entries
+            .iter()
+            .map(|e| {
+                let mut block = format!(
+                    "Path: {}\nIdentifier: {}\nSender-Id: {}\nBagging-Date: {}\nFiles: {}\nBytes: {}\nProfile: {}\nProject: {}\nLast-Checked: {}\nStatus: {}\n",
+                    e.path.display(),
+                    e.identifier,
+                    e.sender_id,
+                    e.bagging_date,
+                    e.files,
+                    e.bytes,
+                    e.profile,
+                    e.project,
+                    e.last_checked,
+                    e.status.name()
+                );
+                for (file, note) in &e.damaged {
+                    // Notes are kept to one line so the record stays parseable
+                    block.push_str(&format!("Damaged: {}: {}\n", file, note.replace('\n', " ")));
+                }
+                for audit in &e.audits {
+                    block.push_str(&format!("Audit: {}\n", audit.serialize()));
+                }
+                block
+            })
+            .collect::<Vec<_>>()
+            .join("\n")
+    }
+
+    /// Records the bag at `path`, replacing any earlier record for the same folder.
+    pub fn register(&mut self, path: &Path) -> Result<(), BagError> {
+        let entry = InventoryEntry::read(path)?;
+        match self.entries.iter_mut().find(|e| e.path == entry.path) {
+            Some(existing) => *existing = entry,
+            None => self.entries.push(entry),
+        }
+        Ok(())
+    }
+
+    /// Drops the record of the bag at `path`, e.g. once it's been unbagged.
+    pub fn forget(&mut self, path: &Path) {
+        let path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+        self.entries.retain(|e| e.path != path);
+    }
+
+    /// The bag with `id` as its Internal-Sender-Identifier, or failing that as its
+    /// identifier.
+    pub fn find(&self, id: &str) -> Option<&InventoryEntry> {
+        self.entries
+            .iter()
+            .find(|e| e.sender_id == id)
+            .or_else(|| self.entries.iter().find(|e| e.identifier == id))
+    }
+
+    /// The number after the highest `<prefix><number>` Internal-Sender-Identifier
+    /// recorded, or 1 if there's none.
+    pub fn next_in_sequence(&self, prefix: &str) -> u64 {
+        self.entries
+            .iter()
+            .filter_map(|e| e.sender_id.strip_prefix(prefix)?.parse::<u64>().ok())
+            .max()
+            .map_or(1, |n| n + 1)
+    }
+
+    /// Bags due for a fixity check, the one that has waited longest first.
+    pub fn due(&self, today: NaiveDate, interval_days: u32) -> Vec<&InventoryEntry> {
+        let since = |e: &InventoryEntry| {
+            if e.last_checked.is_empty() {
+                e.bagging_date.clone()
+            } else {
+                e.last_checked.clone()
+            }
+        };
+        let mut due: Vec<&InventoryEntry> = self
+            .entries
+            .iter()
+            .filter(|e| e.is_overdue(today, interval_days))
+            .collect();
+        due.sort_by_key(|e| since(e));
+        due
+    }
+
+    /// Records `file` in the bag at `bag` as known to be damaged, with a note. Bags
+    /// received from elsewhere are added to the inventory so the note has a home.
+    pub fn mark_damaged(&mut self, bag: &Path, file: &str, note: &str) {
+        let bag = std::path::absolute(bag).unwrap_or_else(|_| bag.to_path_buf());
+        if !self.entries.iter().any(|e| e.path == bag) && self.register(&bag).is_err() {
+            self.entries.push(InventoryEntry {
+                path: bag.clone(),
+                ..InventoryEntry::default()
+            });
+        }
+        let entry = self.entries.iter_mut().find(|e| e.path == bag).unwrap();
+        entry.damaged.retain(|(f, _)| f != file);
+        entry.damaged.push((file.to_string(), note.to_string()));
+    }
+
+    /// Forgets a damaged-file note, e.g. once the file has been restored.
+    pub fn clear_damaged(&mut self, bag: &Path, file: &str) {
+        let bag = std::path::absolute(bag).unwrap_or_else(|_| bag.to_path_buf());
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.path == bag) {
+            entry.damaged.retain(|(f, _)| f != file);
+        }
+    }
+
+    /// Stores the outcome of a fixity check of the bag at `path` and adds it to the
+    /// bag's audit history.
+    pub fn record_check(&mut self, path: &Path, audit: AuditRecord) {
+        if let Some(entry) = self.entries.iter_mut().find(|e| e.path == path) {
+            entry.status = audit.status;
+            entry.last_checked = audit.date().to_string();
+            entry.audits.push(audit);
+            let excess = entry.audits.len().saturating_sub(AUDIT_HISTORY);
+            entry.audits.drain(..excess);
+        }
+    }
+
+    /// Every recorded audit of every bag, newest first, with the bag it was of.
+    pub fn audits(&self) -> Vec<(&InventoryEntry, &AuditRecord)> {
+        let mut audits: Vec<_> = self
+            .entries
+            .iter()
+            .flat_map(|e| e.audits.iter().map(move |a| (e, a)))
+            .collect();
+        audits.sort_by(|a, b| b.1.time.cmp(&a.1.time));
+        audits
+    }
+
+    /// The audit history as CSV: `time,bag,identifier,status,run,files,failures`, with
+    /// the failures of a check joined by `; `.
+    pub fn audits_csv(&self) -> String {
+        let mut csv = String::from("time,bag,identifier,status,run,files,failures\n");
+        for (entry, audit) in self.audits() {
+            csv.push_str(&format!(
+                "{},{},{},{},{},{},{}\n",
+                audit.time,
+                csv_field(&entry.path.to_string_lossy()),
+                csv_field(&entry.identifier),
+                audit.status.name(),
+                if audit.scheduled {
+                    "scheduled"
+                } else {
+                    "manual"
+                },
+                audit.files_checked,
+                csv_field(&audit.failures.join("; "))
+            ));
+        }
+        csv
+    }
+
+    pub fn stats(&self) -> InventoryStats {
+        let mut stats = InventoryStats::default();
+        let mut by_month: BTreeMap<String, Tally> = BTreeMap::new();
+        let mut by_profile: BTreeMap<String, Tally> = BTreeMap::new();
+        let mut by_project: BTreeMap<String, Tally> = BTreeMap::new();
+        for entry in &self.entries {
+            stats.total.add(entry);
+            let month = entry.bagging_date.get(..7).unwrap_or("unknown");
+            by_month.entry(month.to_string()).or_default().add(entry);
+            by_profile
+                .entry(entry.profile.clone())
+                .or_default()
+                .add(entry);
+            by_project
+                .entry(entry.project.clone())
+                .or_default()
+                .add(entry);
+        }
+        stats.by_month = by_month.into_iter().collect();
+        stats.by_profile = by_profile.into_iter().collect();
+        stats.by_project = by_project.into_iter().collect();
+        stats
+    }
+}
+
+/// Bags, files and bytes added up over some set of inventory entries.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Tally {
+    pub bags: u64,
+    pub files: u64,
+    pub bytes: u64,
+}
+
+impl Tally {
+    fn add(&mut self, entry: &InventoryEntry) {
+        self.bags += 1;
+        self.files += entry.files;
+        self.bytes += entry.bytes;
+    }
+}
+
+/// Totals across the inventory, for preservation reporting.
+#[derive(Debug, Clone, Default)]
+pub struct InventoryStats {
+    pub total: Tally,
+    /// Bags made per `YYYY-MM`, oldest first.
+    pub by_month: Vec<(String, Tally)>,
+    /// Keyed by profile identifier; bags without one are under "".
+    pub by_profile: Vec<(String, Tally)>,
+    /// Keyed by group identifier; bags without one are under "".
+    pub by_project: Vec<(String, Tally)>,
+}
+
+impl InventoryStats {
+    /// Growth over time: the running total at the end of each month.
+    pub fn cumulative_by_month(&self) -> Vec<(String, Tally)> {
+        let mut running = Tally::default();
+        self.by_month
+            .iter()
+            .map(|(month, tally)| {
+                running.bags += tally.bags;
+                running.files += tally.files;
+                running.bytes += tally.bytes;
+                (month.clone(), running)
+            })
+            .collect()
+    }
+
+    /// One row per figure: `category,key,bags,files,bytes`.
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("category,key,bags,files,bytes\n");
+        let mut row = |category: &str, key: &str, t: &Tally| {
+            csv.push_str(&format!(
+                "{},{},{},{},{}\n",
+                category,
+                csv_field(key),
+                t.bags,
+                t.files,
+                t.bytes
+            ));
+        };
+        row("total", "", &self.total);
+        for (month, tally) in &self.by_month {
+            row("month", month, tally);
+        }
+        for (month, tally) in self.cumulative_by_month() {
+            row("cumulative", &month, &tally);
+        }
+        for (profile, tally) in &self.by_profile {
+            row("profile", profile, tally);
+        }
+        for (project, tally) in &self.by_project {
+            row("project", project, tally);
+        }
+        csv
+    }
+}
+
+/// Quotes a CSV field when it needs it.
+pub fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(path: &str, date: &str, files: u64, bytes: u64, project: &str) -> InventoryEntry {
+        InventoryEntry {
+            path: PathBuf::from(path),
+            identifier: path.to_string(),
+            sender_id: format!("ACC-{}", files),
+            bagging_date: date.to_string(),
+            files,
+            bytes,
+            profile: String::new(),
+            project: project.to_string(),
+            last_checked: String::new(),
+            status: FixityStatus::Unchecked,
+            damaged: Vec::new(),
+            audits: Vec::new(),
+        }
+    }
+
+    fn audit(time: &str, status: FixityStatus, failures: &[&str]) -> AuditRecord {
+        AuditRecord {
+            time: time.to_string(),
+            status,
+            scheduled: true,
+            files_checked: 3,
+            failures: failures.iter().map(|f| f.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn test_inventory_round_trip() {
+        let inventory = Inventory {
+            entries: vec![
+                entry("/bags/one", "2026-01-05", 3, 300, "Oral history"),
+                entry("/bags/two", "2026-02-10", 1, 50, ""),
+            ],
+        };
+        assert_eq!(Inventory::parse(&inventory.serialize()), inventory);
+    }
+
+    #[test]
+    fn test_find_by_sender_id() {
+        let inventory = Inventory {
+            entries: vec![
+                entry("/bags/one", "2026-01-05", 3, 300, ""),
+                entry("/bags/two", "2026-02-10", 41, 50, ""),
+            ],
+        };
+        assert_eq!(
+            inventory.find("ACC-41").unwrap().path,
+            PathBuf::from("/bags/two")
+        );
+        assert_eq!(inventory.find("/bags/one").unwrap().files, 3);
+        assert!(inventory.find("ACC-5").is_none());
+        assert_eq!(inventory.next_in_sequence("ACC-"), 42);
+        assert_eq!(inventory.next_in_sequence("LIB-"), 1);
+    }
+
+    #[test]
+    fn test_fixity_schedule() {
+        let today: NaiveDate = "2026-06-30".parse().unwrap();
+        let mut inventory = Inventory {
+            entries: vec![
+                entry("/bags/old", "2026-01-05", 1, 1, ""),
+                entry("/bags/new", "2026-06-01", 1, 1, ""),
+                entry("/bags/older", "2025-12-01", 1, 1, ""),
+            ],
+        };
+        assert!(inventory.entries[0].is_overdue(today, 90));
+        assert!(!inventory.entries[1].is_overdue(today, 90));
+        assert!(!inventory.entries[0].is_overdue(today, 0));
+        assert_eq!(
+            inventory.due(today, 90)[0].path,
+            PathBuf::from("/bags/older")
+        );
+
+        let failed = audit(
+            "2026-06-30 02:00:00",
+            FixityStatus::Failed,
+            &["data/a.tif is missing"],
+        );
+        inventory.record_check(Path::new("/bags/older"), failed);
+        let ok = audit("2026-06-30 02:05:00", FixityStatus::Ok, &[]);
+        inventory.record_check(Path::new("/bags/old"), ok);
+        assert!(inventory.due(today, 90).is_empty());
+        assert_eq!(inventory.entries[2].last_checked, "2026-06-30");
+
+        let reloaded = Inventory::parse(&inventory.serialize());
+        assert_eq!(reloaded.entries[2].status, FixityStatus::Failed);
+        assert_eq!(reloaded, inventory);
+    }
+
+    #[test]
+    fn test_audit_history() {
+        let mut inventory = Inventory {
+            entries: vec![entry("/bags/one", "2026-01-05", 3, 300, "")],
+        };
+        let bag = Path::new("/bags/one");
+        for day in 1..=AUDIT_HISTORY + 2 {
+            let time = format!("2026-{:02}-{:02} 03:00:00", 1 + day / 28, 1 + day % 28);
+            inventory.record_check(bag, audit(&time, FixityStatus::Ok, &[]));
+        }
+        let failures = ["data/a.tif: checksum mismatch", "tab\there, \"quoted\""];
+        inventory.record_check(
+            bag,
+            audit("2026-12-01 03:00:00", FixityStatus::Failed, &failures),
+        );
+        let audits = &inventory.entries[0].audits;
+        assert_eq!(audits.len(), AUDIT_HISTORY);
+        assert_eq!(inventory.entries[0].last_checked, "2026-12-01");
+
+        let reloaded = Inventory::parse(&inventory.serialize());
+        let last = reloaded.entries[0].audits.last().unwrap();
+        assert_eq!(last.status, FixityStatus::Failed);
+        assert_eq!(
+            last.failures,
+            vec!["data/a.tif: checksum mismatch", "tab here, \"quoted\""]
+        );
+        assert_eq!(reloaded.entries[0].audits.len(), AUDIT_HISTORY);
+
+        let csv = reloaded.audits_csv();
+        assert!(csv.starts_with(
+            "time,bag,identifier,status,run,files,failures\n\
+             2026-12-01 03:00:00,/bags/one,/bags/one,failed,scheduled,3,\
+             \"data/a.tif: checksum mismatch; tab here, \"\"quoted\"\"\"\n"
+        ));
+    }
+
+    #[test]
+    fn test_damaged_notes() {
+        let mut inventory = Inventory {
+            entries: vec![entry("/bags/one", "2026-01-05", 2, 2, "")],
+        };
+        let bag = Path::new("/bags/one");
+        inventory.mark_damaged(bag, "data/a.tif", "scratched disc: 2 copies bad");
+        inventory.mark_damaged(bag, "data/b.tif", "first note");
+        inventory.mark_damaged(bag, "data/b.tif", "second\nnote");
+        assert_eq!(inventory.entries[0].damaged.len(), 2);
+
+        let reloaded = Inventory::parse(&inventory.serialize());
+        assert_eq!(
+            reloaded.entries[0].damaged,
+            vec![
+                (
+                    "data/a.tif".to_string(),
+                    "scratched disc: 2 copies bad".to_string()
+                ),
+                ("data/b.tif".to_string(), "second note".to_string()),
+            ]
+        );
+
+        inventory.clear_damaged(bag, "data/a.tif");
+        assert_eq!(inventory.entries[0].damaged.len(), 1);
+
+        // A bag that was never inventoried still gets a record
+        inventory.mark_damaged(Path::new("/received/bag"), "data/x", "late");
+        assert_eq!(inventory.entries.len(), 2);
+    }
+
+    #[test]
+    fn test_stats_totals_and_growth() {
+        let inventory = Inventory {
+            entries: vec![
+                entry("/bags/one", "2026-01-05", 3, 300, "Oral history"),
+                entry("/bags/two", "2026-01-20", 2, 200, "Oral history"),
+                entry("/bags/three", "2026-03-01", 1, 50, "Maps, large"),
+            ],
+        };
+        let stats = inventory.stats();
+        assert_eq!(
+            stats.total,
+            Tally {
+                bags: 3,
+                files: 6,
+                bytes: 550
+            }
+        );
+        assert_eq!(stats.by_month.len(), 2);
+        let growth = stats.cumulative_by_month();
+        assert_eq!(growth[0].1.bags, 2);
+        assert_eq!(growth[1].1.bytes, 550);
+        assert_eq!(stats.by_project.len(), 2);
+
+        let csv = stats.to_csv();
+        assert!(csv.starts_with("category,key,bags,files,bytes\ntotal,,3,6,550\n"));
+        assert!(csv.contains("month,2026-01,2,5,500\n"));
+        assert!(csv.contains("project,\"Maps, large\",1,1,50\n"));
+    }
+
+    #[test]
+    fn test_register_replaces_same_path() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_inventory");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+        crate::bagit::bag_directory(&temp_dir, &Default::default(), None).unwrap();
+
+        let mut inventory = Inventory::default();
+        inventory.register(&temp_dir).unwrap();
+        inventory.register(&temp_dir).unwrap();
+        assert_eq!(inventory.entries.len(), 1);
+        assert_eq!(inventory.entries[0].files, 1);
+        assert_eq!(inventory.entries[0].bytes, 7);
+        assert_eq!(inventory.entries[0].bagging_date.len(), 10);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
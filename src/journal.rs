@@ -0,0 +1,257 @@
+use crate::bagit::{decode_manifest_path, encode_manifest_path, ChecksumAlgorithm};
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// File at the top of a folder being bagged in place, recording the files moved and
+/// checksummed so far, so a run that was interrupted can pick up where it stopped.
+/// It's removed once the bag is sealed.
+pub const JOURNAL_FILE: &str = ".baggie-journal";
+
+/// A file's size and modification time (in nanoseconds), so a checksum is only reused
+/// for a file that hasn't changed since.
+type Stamp = (u64, u128);
+
+/// The journal of an in-place bagging run. Each line is written as soon as the step it
+/// records is done; paths are relative to the bag and percent-encoded like a BagIt 1.0
+/// manifest's. A last line cut short by a crash is ignored.
+#[derive(Debug)]
+pub struct Journal {
+    path: PathBuf,
+    writer: Mutex<Writer>,
+    resumed: bool,
+    moved: usize,
+    moves_done: bool,
+    digests: HashMap<String, (Stamp, Vec<String>)>,
+}
+
+/// The journal file, opened on the first line written so a run refused before doing
+/// anything leaves none, and what has to go before that line.
+#[derive(Debug, Default)]
+struct Writer {
+    file: Option<File>,
+    pending: String,
+}
+
+impl Journal {
+    /// Starts the journal in `bag`, or continues the one an interrupted run left.
+    /// Checksums it recorded in other algorithms than `algorithms` aren't reused.
+    pub fn open(bag: &Path, algorithms: &[ChecksumAlgorithm]) -> io::Result<Self> {
+        let names: Vec<&str> = algorithms.iter().map(|a| a.name()).collect();
+        let names = names.join(",");
+        let content = match fs::read_to_string(bag.join(JOURNAL_FILE)) {
+            Ok(content) => Some(content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => None,
+            Err(e) => return Err(e),
+        };
+        let mut journal = Self {
+            path: bag.join(JOURNAL_FILE),
+            writer: Mutex::new(Writer::default()),
+            resumed: content.is_some(),
+            moved: 0,
+            moves_done: false,
+            digests: HashMap::new(),
+        };
+        let content = content.unwrap_or_default();
+        let mut same_algorithms = false;
+        for line in content
+            .split_inclusive('\n')
+            .filter(|line| line.ends_with('\n'))
+        {
+            let mut fields = line.trim_end_matches('\n').split('\t');
+            match fields.next() {
+                Some("algorithms") => same_algorithms = fields.next() == Some(names.as_str()),
+                Some("moved") => journal.moved += 1,
+                Some("moves done") => journal.moves_done = true,
+                Some("hashed") if same_algorithms => {
+                    let (Some(size), Some(modified), Some(digests), Some(file)) =
+                        (fields.next(), fields.next(), fields.next(), fields.next())
+                    else {
+                        continue;
+                    };
+                    let (Ok(size), Ok(modified)) = (size.parse(), modified.parse()) else {
+                        continue;
+                    };
+                    let digests = digests.split(',').map(String::from).collect();
+                    journal
+                        .digests
+                        .insert(decode_manifest_path(file), ((size, modified), digests));
+                }
+                _ => {}
+            }
+        }
+        let pending = &mut journal.writer.get_mut().unwrap().pending;
+        if !content.is_empty() && !content.ends_with('\n') {
+            pending.push('\n');
+        }
+        if !same_algorithms {
+            pending.push_str(&format!("algorithms\t{}\n", names));
+        }
+        Ok(journal)
+    }
+
+    /// Whether an interrupted run's journal was found.
+    pub fn resumed(&self) -> bool {
+        self.resumed
+    }
+
+    /// Files an interrupted run moved into `data/`.
+    pub fn moved(&self) -> usize {
+        self.moved
+    }
+
+    /// Checksums an interrupted run recorded that can still be used.
+    pub fn hashed(&self) -> usize {
+        self.digests.len()
+    }
+
+    /// Whether every file had been moved into `data/`.
+    pub fn moves_done(&self) -> bool {
+        self.moves_done
+    }
+
+    /// Records that `file` (`data/...`) was moved into place.
+    pub fn record_move(&self, file: &str) -> io::Result<()> {
+        self.write(&format!("moved\t{}", encode_manifest_path(file)))
+    }
+
+    /// Records that the whole payload is in `data/`.
+    pub fn record_moves_done(&self) -> io::Result<()> {
+        self.write("moves done")
+    }
+
+    /// Records the checksums of `file`, as it was when `metadata` was read.
+    pub fn record_digests(
+        &self,
+        file: &str,
+        metadata: &fs::Metadata,
+        digests: &[String],
+    ) -> io::Result<()> {
+        let (size, modified) = stamp(metadata);
+        self.write(&format!(
+            "hashed\t{}\t{}\t{}\t{}",
+            size,
+            modified,
+            digests.join(","),
+            encode_manifest_path(file)
+        ))
+    }
+
+    /// The checksums an interrupted run recorded for `file`, if it hasn't changed since.
+    pub fn digests(&self, file: &str, metadata: &fs::Metadata) -> Option<Vec<String>> {
+        let (recorded, digests) = self.digests.get(file)?;
+        (*recorded == stamp(metadata)).then(|| digests.clone())
+    }
+
+    fn write(&self, line: &str) -> io::Result<()> {
+        let mut writer = self.writer.lock().unwrap();
+        if writer.file.is_none() {
+            let file = OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(&self.path)?;
+            writer.file = Some(file);
+        }
+        let text = std::mem::take(&mut writer.pending) + line + "\n";
+        let file = writer.file.as_mut().unwrap();
+        file.write_all(text.as_bytes())?;
+        file.flush()
+    }
+}
+
+fn stamp(metadata: &fs::Metadata) -> Stamp {
+    let modified = metadata
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map_or(0, |d| d.as_nanos());
+    (metadata.len(), modified)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{
+        bag_directory, bag_like_reason, calculate_sha256, validate_bag, BagOptions,
+        ValidationOptions,
+    };
+
+    #[test]
+    fn test_journal_lines() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_journal_lines");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(temp_dir.join("data")).unwrap();
+        fs::write(temp_dir.join("data/x"), "x").unwrap();
+        let metadata = fs::metadata(temp_dir.join("data/x")).unwrap();
+        let (size, modified) = stamp(&metadata);
+        // The last line was cut short by the crash
+        let lines = format!(
+            "algorithms\tsha256\nmoved\tdata/x\nhashed\t{size}\t{modified}\tabc\tdata/x\nhashed\t1"
+        );
+        fs::write(temp_dir.join(JOURNAL_FILE), lines).unwrap();
+
+        let journal = Journal::open(&temp_dir, &[ChecksumAlgorithm::Sha256]).unwrap();
+        assert!(journal.resumed());
+        assert_eq!((journal.moved(), journal.hashed()), (1, 1));
+        assert_eq!(
+            journal.digests("data/x", &metadata),
+            Some(vec!["abc".to_string()])
+        );
+        fs::write(temp_dir.join("data/x"), "changed").unwrap();
+        let changed = fs::metadata(temp_dir.join("data/x")).unwrap();
+        assert_eq!(journal.digests("data/x", &changed), None);
+        journal.record_moves_done().unwrap();
+        let content = fs::read_to_string(temp_dir.join(JOURNAL_FILE)).unwrap();
+        assert!(content.ends_with("\nhashed\t1\nmoves done\n"));
+
+        // Checksums in other algorithms aren't reused
+        let journal = Journal::open(&temp_dir, &[ChecksumAlgorithm::Md5]).unwrap();
+        assert!(journal.moves_done());
+        assert_eq!(journal.hashed(), 0);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_resume_interrupted_bagging() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_journal_resume");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(temp_dir.join("data")).unwrap();
+        fs::write(temp_dir.join("b.txt"), "second").unwrap();
+        // As a run interrupted after moving and checksumming a.txt leaves the folder
+        fs::write(temp_dir.join("data/a.txt"), "first").unwrap();
+        let journal = Journal::open(&temp_dir, &[ChecksumAlgorithm::Sha256]).unwrap();
+        journal.record_move("data/a.txt").unwrap();
+        let metadata = fs::metadata(temp_dir.join("data/a.txt")).unwrap();
+        let digests = vec![calculate_sha256(&temp_dir.join("data/a.txt")).unwrap()];
+        journal
+            .record_digests("data/a.txt", &metadata, &digests)
+            .unwrap();
+        drop(journal);
+        assert_eq!(bag_like_reason(&temp_dir), None);
+
+        let report = bag_directory(&temp_dir, &BagOptions::default(), None).unwrap();
+        assert!(
+            report.warnings[0].starts_with("Resumed"),
+            "{:?}",
+            report.warnings
+        );
+        assert!(!temp_dir.join(JOURNAL_FILE).exists());
+        let manifest = fs::read_to_string(temp_dir.join("manifest-sha256.txt")).unwrap();
+        assert!(manifest.contains(&format!("{}  data/a.txt", digests[0])));
+        assert!(manifest.contains("  data/b.txt"));
+        let bag_info = fs::read_to_string(temp_dir.join("bag-info.txt")).unwrap();
+        assert!(bag_info.contains("Payload-Oxum: 11.2"));
+        let report = validate_bag(&temp_dir, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,319 @@
+/// Arrays and objects nested deeper than this aren't parsed, as each level takes a
+/// stack frame and profiles come from anywhere on the web.
+const MAX_DEPTH: usize = 128;
+
+/// A parsed JSON value. Objects keep their members in file order.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+impl Json {
+    /// Parses `text`, which must hold exactly one value. The error says what was
+    /// expected and where.
+    pub fn parse(text: &str) -> Result<Json, String> {
+        let mut parser = Parser {
+            chars: text.chars().collect(),
+            at: 0,
+            depth: 0,
+        };
+        let value = parser.value()?;
+        parser.skip_space();
+        match parser.at < parser.chars.len() {
+            true => Err(parser.error("the end of the text")),
+            false => Ok(value),
+        }
+    }
+
+    /// The member `key` of an object.
+    pub fn get(&self, key: &str) -> Option<&Json> {
+        self.as_object()?
+            .iter()
+            .find(|(k, _)| k == key)
+            .map(|(_, v)| v)
+    }
+
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Json::String(s) => Some(s),
+            _ => None,
+        }
+    }
+
+    pub fn as_bool(&self) -> Option<bool> {
+        match self {
+            Json::Bool(b) => Some(*b),
+            _ => None,
+        }
+    }
+
+    pub fn as_array(&self) -> Option<&[Json]> {
+        match self {
+            Json::Array(items) => Some(items),
+            _ => None,
+        }
+    }
+
+    pub fn as_object(&self) -> Option<&[(String, Json)]> {
+        match self {
+            Json::Object(members) => Some(members),
+            _ => None,
+        }
+    }
+}
+
+struct Parser {
+    chars: Vec<char>,
+    at: usize,
+    /// Arrays and objects open around the current value.
+    depth: usize,
+}
+
+impl Parser {
+    fn error(&self, expected: &str) -> String {
+        let line = self.chars[..self.at].iter().filter(|&&c| c == '\n').count() + 1;
+        format!("expected {} on line {}", expected, line)
+    }
+
+    fn skip_space(&mut self) {
+        while self.chars.get(self.at).is_some_and(|c| c.is_whitespace()) {
+            self.at += 1;
+        }
+    }
+
+    /// Consumes `c` after any whitespace, if it's next.
+    fn eat(&mut self, c: char) -> bool {
+        self.skip_space();
+        let next = self.chars.get(self.at) == Some(&c);
+        if next {
+            self.at += 1;
+        }
+        next
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_space();
+        match self.chars.get(self.at) {
+            Some('{' | '[') if self.depth == MAX_DEPTH => {
+                Err(self.error(&format!("at most {} nested arrays and objects", MAX_DEPTH)))
+            }
+            Some('{' | '[') => {
+                self.depth += 1;
+                let value = self.nested();
+                self.depth -= 1;
+                value
+            }
+            Some('"') => Ok(Json::String(self.string()?)),
+            Some('t') => self.word("true", Json::Bool(true)),
+            Some('f') => self.word("false", Json::Bool(false)),
+            Some('n') => self.word("null", Json::Null),
+            Some(c) if *c == '-' || c.is_ascii_digit() => {
+                let start = self.at;
+                while self
+                    .chars
+                    .get(self.at)
+                    .is_some_and(|c| c.is_ascii_digit() || "+-.eE".contains(*c))
+                {
+                    self.at += 1;
+                }
+                let number: String = self.chars[start..self.at].iter().collect();
+                number
+                    .parse()
+                    .map(Json::Number)
+                    .map_err(|_| self.error("a number"))
+            }
+            _ => Err(self.error("a value")),
+        }
+    }
+
+    /// The object or array starting here.
+    fn nested(&mut self) -> Result<Json, String> {
+        match self.chars.get(self.at) {
+            Some('{') => {
+                self.at += 1;
+                let mut members = Vec::new();
+                if self.eat('}') {
+                    return Ok(Json::Object(members));
+                }
+                loop {
+                    self.skip_space();
+                    let key = self.string()?;
+                    if !self.eat(':') {
+                        return Err(self.error("\":\""));
+                    }
+                    members.push((key, self.value()?));
+                    if self.eat('}') {
+                        return Ok(Json::Object(members));
+                    }
+                    if !self.eat(',') {
+                        return Err(self.error("\",\" or \"}\""));
+                    }
+                }
+            }
+            Some('[') => {
+                self.at += 1;
+                let mut items = Vec::new();
+                if self.eat(']') {
+                    return Ok(Json::Array(items));
+                }
+                loop {
+                    items.push(self.value()?);
+                    if self.eat(']') {
+                        return Ok(Json::Array(items));
+                    }
+                    if !self.eat(',') {
+                        return Err(self.error("\",\" or \"]\""));
+                    }
+                }
+            }
+            _ => Err(self.error("an object or array")),
+        }
+    }
+
+    fn word(&mut self, word: &str, value: Json) -> Result<Json, String> {
+        let end = self.at + word.len();
+        if self
+            .chars
+            .get(self.at..end)
+            .is_some_and(|w| w.iter().copied().eq(word.chars()))
+        {
+            self.at = end;
+            Ok(value)
+        } else {
+            Err(self.error("a value"))
+        }
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        if self.chars.get(self.at) != Some(&'"') {
+            return Err(self.error("a string"));
+        }
+        self.at += 1;
+        let mut string = String::new();
+        loop {
+            let Some(&c) = self.chars.get(self.at) else {
+                return Err(self.error("the end of a string"));
+            };
+            self.at += 1;
+            match c {
+                '"' => return Ok(string),
+                '\\' => {
+                    let escaped = self.chars.get(self.at).copied();
+                    self.at += 1;
+                    match escaped {
+                        Some('n') => string.push('\n'),
+                        Some('r') => string.push('\r'),
+                        Some('t') => string.push('\t'),
+                        Some('b') => string.push('\u{8}'),
+                        Some('f') => string.push('\u{c}'),
+                        Some('u') => {
+                            let unit = self.hex4()?;
+                            // A surrogate pair is two escapes
+                            let code = if (0xD800..0xDC00).contains(&unit) && self.eat_str("\\u") {
+                                let low = self.hex4()?;
+                                0x10000
+                                    + ((unit - 0xD800) << 10)
+                                    + (low.wrapping_sub(0xDC00) & 0x3FF)
+                            } else {
+                                unit
+                            };
+                            string.push(char::from_u32(code).unwrap_or('\u{FFFD}'));
+                        }
+                        Some(c @ ('"' | '\\' | '/')) => string.push(c),
+                        _ => return Err(self.error("an escape like \\n or \\u00e9")),
+                    }
+                }
+                c => string.push(c),
+            }
+        }
+    }
+
+    fn eat_str(&mut self, s: &str) -> bool {
+        let end = self.at + s.chars().count();
+        let next = self
+            .chars
+            .get(self.at..end)
+            .is_some_and(|w| w.iter().copied().eq(s.chars()));
+        if next {
+            self.at = end;
+        }
+        next
+    }
+
+    fn hex4(&mut self) -> Result<u32, String> {
+        let digits: String = self
+            .chars
+            .get(self.at..self.at + 4)
+            .unwrap_or_default()
+            .iter()
+            .collect();
+        let unit = u32::from_str_radix(&digits, 16).map_err(|_| self.error("four hex digits"))?;
+        self.at += 4;
+        Ok(unit)
+    }
+}
+
+/// `value` as a quoted JSON string.
+pub fn quote(value: &str) -> String {
+    let mut json = String::with_capacity(value.len() + 2);
+    json.push('"');
+    for c in value.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => json.push_str(&format!("\\u{:04x}", c as u32)),
+            c => json.push(c),
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_json() {
+        let text = r#"{"name": "Café \"bags\"", "n": -1.5e2, "ok": true,
+                       "list": [null, false, {}, []], "emoji": "📦"}"#;
+        let json = Json::parse(text).unwrap();
+        assert_eq!(json.get("name").unwrap().as_str(), Some("Café \"bags\""));
+        assert_eq!(json.get("n"), Some(&Json::Number(-150.0)));
+        assert_eq!(json.get("ok").unwrap().as_bool(), Some(true));
+        assert_eq!(json.get("list").unwrap().as_array().unwrap().len(), 4);
+        assert_eq!(json.get("emoji").unwrap().as_str(), Some("📦"));
+        assert!(json.get("missing").is_none());
+
+        assert_eq!(
+            Json::parse("{\"a\": 1,\n}").unwrap_err(),
+            "expected a string on line 2"
+        );
+        assert!(Json::parse("[1] 2").is_err());
+        assert!(Json::parse("\"open").is_err());
+
+        // Deep nesting is refused rather than overflowing the stack
+        let deep = |levels: usize| format!("{}{}", "[".repeat(levels), "]".repeat(levels));
+        assert!(Json::parse(&deep(MAX_DEPTH)).is_ok());
+        assert_eq!(
+            Json::parse(&deep(MAX_DEPTH + 1)).unwrap_err(),
+            "expected at most 128 nested arrays and objects on line 1"
+        );
+        assert!(Json::parse(&"{\"a\":".repeat(100_000)).is_err());
+
+        let quoted = quote("say \"hi\"\\\n\u{1}");
+        assert_eq!(quoted, "\"say \\\"hi\\\"\\\\\\n\\u0001\"");
+        assert_eq!(
+            Json::parse(&quoted).unwrap().as_str(),
+            Some("say \"hi\"\\\n\u{1}")
+        );
+    }
+}
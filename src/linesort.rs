@@ -0,0 +1,143 @@
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+
+/// Bytes of lines held in memory before they're sorted and spilled to a run file.
+const RUN_BYTES: usize = 64 * 1024 * 1024;
+
+/// Sorts more lines than are worth holding in memory, such as the manifest of a
+/// million-file payload: they're sorted in runs, each spilled to a file, and the runs
+/// merged as the output is written. Lines must not contain line breaks.
+#[derive(Debug)]
+pub struct LineSorter {
+    dir: PathBuf,
+    name: String,
+    lines: Vec<String>,
+    bytes: usize,
+    limit: usize,
+    runs: Vec<PathBuf>,
+}
+
+impl LineSorter {
+    /// A sorter spilling runs to `<dir>/<name>.<n>`.
+    pub fn new(dir: &Path, name: &str) -> Self {
+        Self::with_limit(dir, name, RUN_BYTES)
+    }
+
+    fn with_limit(dir: &Path, name: &str, limit: usize) -> Self {
+        Self {
+            dir: dir.to_path_buf(),
+            name: name.to_string(),
+            lines: Vec::new(),
+            bytes: 0,
+            limit,
+            runs: Vec::new(),
+        }
+    }
+
+    pub fn push(&mut self, line: String) -> io::Result<()> {
+        self.bytes += line.len();
+        self.lines.push(line);
+        if self.bytes >= self.limit {
+            self.spill()?;
+        }
+        Ok(())
+    }
+
+    fn spill(&mut self) -> io::Result<()> {
+        self.lines.sort();
+        let run = self.dir.join(format!("{}.{}", self.name, self.runs.len()));
+        let mut output = BufWriter::new(File::create(&run)?);
+        for line in self.lines.drain(..) {
+            writeln!(output, "{}", line)?;
+        }
+        output.flush()?;
+        self.runs.push(run);
+        self.bytes = 0;
+        Ok(())
+    }
+
+    /// Writes every line pushed to `dest`, sorted and each ending in a newline, and
+    /// removes the run files.
+    pub fn finish(mut self, dest: &Path) -> io::Result<()> {
+        let mut output = BufWriter::new(File::create(dest)?);
+        if self.runs.is_empty() {
+            self.lines.sort();
+            for line in &self.lines {
+                writeln!(output, "{}", line)?;
+            }
+            return output.flush();
+        }
+        if !self.lines.is_empty() {
+            self.spill()?;
+        }
+        let mut runs = Vec::new();
+        for run in &self.runs {
+            runs.push(BufReader::new(File::open(run)?).lines());
+        }
+        let mut next = BinaryHeap::new();
+        for (i, run) in runs.iter_mut().enumerate() {
+            if let Some(line) = run.next() {
+                next.push(Reverse((line?, i)));
+            }
+        }
+        while let Some(Reverse((line, i))) = next.pop() {
+            writeln!(output, "{}", line)?;
+            if let Some(line) = runs[i].next() {
+                next.push(Reverse((line?, i)));
+            }
+        }
+        output.flush()?;
+        for run in &self.runs {
+            fs::remove_file(run)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sort_in_runs() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_linesort");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(&temp_dir).unwrap();
+        let mut lines: Vec<String> = (0..100)
+            .map(|i| format!("{:x}  data/{}", i * 37 % 101, i))
+            .collect();
+
+        // Small enough runs that there are several to merge
+        let mut sorter = LineSorter::with_limit(&temp_dir, "manifest", 200);
+        for line in &lines {
+            sorter.push(line.clone()).unwrap();
+        }
+        assert!(sorter.runs.len() > 2);
+        sorter.finish(&temp_dir.join("sorted.txt")).unwrap();
+        lines.sort();
+        let expected = lines.join("\n") + "\n";
+        assert_eq!(
+            fs::read_to_string(temp_dir.join("sorted.txt")).unwrap(),
+            expected
+        );
+        assert_eq!(fs::read_dir(&temp_dir).unwrap().count(), 1);
+
+        // And the same when everything fits in memory
+        let mut sorter = LineSorter::new(&temp_dir, "manifest");
+        for line in lines.iter().rev() {
+            sorter.push(line.clone()).unwrap();
+        }
+        sorter.finish(&temp_dir.join("sorted.txt")).unwrap();
+        assert_eq!(
+            fs::read_to_string(temp_dir.join("sorted.txt")).unwrap(),
+            expected
+        );
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
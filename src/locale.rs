@@ -0,0 +1,189 @@
+/// How numbers, sizes and dates are written for people to read. Tag files and CSV
+/// exports never go through here; they keep the formats the spec asks for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Locale {
+    /// Whatever the environment says (`LC_ALL`, then `LANG`).
+    #[default]
+    Auto,
+    EnUs,
+    EnGb,
+    De,
+    Fr,
+}
+
+impl Locale {
+    pub const ALL: [Locale; 5] = [
+        Locale::Auto,
+        Locale::EnUs,
+        Locale::EnGb,
+        Locale::De,
+        Locale::Fr,
+    ];
+
+    /// The name used in the settings file.
+    pub fn code(self) -> &'static str {
+        match self {
+            Locale::Auto => "auto",
+            Locale::EnUs => "en-US",
+            Locale::EnGb => "en-GB",
+            Locale::De => "de-DE",
+            Locale::Fr => "fr-FR",
+        }
+    }
+
+    pub fn from_code(code: &str) -> Self {
+        Self::ALL
+            .into_iter()
+            .find(|l| l.code().eq_ignore_ascii_case(code))
+            .unwrap_or_default()
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            Locale::Auto => "System default",
+            Locale::EnUs => "English (US)",
+            Locale::EnGb => "English (UK)",
+            Locale::De => "Deutsch",
+            Locale::Fr => "Français",
+        }
+    }
+
+    /// Turns `Auto` into a concrete locale from the environment.
+    pub fn resolve(self) -> Self {
+        if self != Locale::Auto {
+            return self;
+        }
+        let tag = ["LC_ALL", "LANG"]
+            .iter()
+            .filter_map(|v| std::env::var(v).ok())
+            .find(|v| !v.is_empty())
+            .unwrap_or_default();
+        Self::from_posix(&tag)
+    }
+
+    /// Maps a POSIX locale name such as `de_AT.UTF-8`.
+    fn from_posix(tag: &str) -> Self {
+        let tag = tag.split('.').next().unwrap_or("").replace('-', "_");
+        match tag.split('_').next().unwrap_or("") {
+            "de" => Locale::De,
+            "fr" => Locale::Fr,
+            "en" if tag != "en_US" => Locale::EnGb,
+            _ => Locale::EnUs,
+        }
+    }
+
+    /// (thousands separator, decimal separator)
+    fn separators(self) -> (char, char) {
+        match self.resolve() {
+            Locale::De => ('.', ','),
+            // no-break space, so a number never wraps across lines
+            Locale::Fr => ('\u{a0}', ','),
+            _ => (',', '.'),
+        }
+    }
+
+    pub fn number(self, value: f64, decimals: usize) -> String {
+        let (group, point) = self.separators();
+        let text = format!("{:.*}", decimals, value.abs());
+        let (int, frac) = text.split_once('.').unwrap_or((&text, ""));
+        let mut out = String::new();
+        if value < 0.0 {
+            out.push('-');
+        }
+        for (i, c) in int.chars().enumerate() {
+            if i > 0 && (int.len() - i) % 3 == 0 {
+                out.push(group);
+            }
+            out.push(c);
+        }
+        if !frac.is_empty() {
+            out.push(point);
+            out.push_str(frac);
+        }
+        out
+    }
+
+    pub fn count(self, value: u64) -> String {
+        self.number(value as f64, 0)
+    }
+
+    pub fn bytes(self, bytes: u64) -> String {
+        const UNITS: [&str; 5] = ["bytes", "KiB", "MiB", "GiB", "TiB"];
+        let mut value = bytes as f64;
+        let mut unit = 0;
+        while value >= 1024.0 && unit < UNITS.len() - 1 {
+            value /= 1024.0;
+            unit += 1;
+        }
+        if unit == 0 {
+            format!("{} bytes", self.count(bytes))
+        } else {
+            format!("{} {}", self.number(value, 1), UNITS[unit])
+        }
+    }
+
+    /// `fraction` runs from 0 to 1.
+    pub fn percent(self, fraction: f32) -> String {
+        let value = self.number(f64::from(fraction) * 100.0, 0);
+        match self.resolve() {
+            Locale::De | Locale::Fr => format!("{}\u{a0}%", value),
+            _ => format!("{}%", value),
+        }
+    }
+
+    /// Rewrites an ISO `YYYY-MM-DD` date (or `YYYY-MM` month) in local order. Anything
+    /// else comes back unchanged.
+    pub fn date(self, iso: &str) -> String {
+        let parts: Vec<&str> = iso.split('-').collect();
+        if parts.len() < 2 || parts.len() > 3 || !parts.iter().all(|p| p.parse::<u32>().is_ok()) {
+            return iso.to_string();
+        }
+        let locale = self.resolve();
+        let sep = if locale == Locale::De { "." } else { "/" };
+        match (locale, parts.as_slice()) {
+            (Locale::EnUs, [y, m, d]) => format!("{m}/{d}/{y}"),
+            (_, [y, m, d]) => format!("{d}{sep}{m}{sep}{y}"),
+            (_, [y, m]) => format!("{m}{sep}{y}"),
+            _ => iso.to_string(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_numbers_and_sizes() {
+        assert_eq!(Locale::EnUs.number(1234.5, 1), "1,234.5");
+        assert_eq!(Locale::De.number(1234.5, 1), "1.234,5");
+        assert_eq!(Locale::Fr.number(1234.5, 1), "1\u{a0}234,5");
+        assert_eq!(Locale::EnGb.count(1_000_000), "1,000,000");
+        assert_eq!(Locale::EnUs.count(999), "999");
+
+        assert_eq!(Locale::EnUs.bytes(512), "512 bytes");
+        assert_eq!(Locale::EnUs.bytes(1536), "1.5 KiB");
+        assert_eq!(Locale::De.bytes(5 * 1024 * 1024 * 1024), "5,0 GiB");
+        assert_eq!(Locale::EnUs.percent(0.5), "50%");
+        assert_eq!(Locale::Fr.percent(0.5), "50\u{a0}%");
+    }
+
+    #[test]
+    fn test_dates() {
+        assert_eq!(Locale::EnUs.date("2026-03-09"), "03/09/2026");
+        assert_eq!(Locale::EnGb.date("2026-03-09"), "09/03/2026");
+        assert_eq!(Locale::De.date("2026-03-09"), "09.03.2026");
+        assert_eq!(Locale::De.date("2026-03"), "03.2026");
+        assert_eq!(Locale::De.date("never"), "never");
+    }
+
+    #[test]
+    fn test_codes() {
+        assert_eq!(Locale::from_code("de-DE"), Locale::De);
+        assert_eq!(Locale::from_code("xx"), Locale::Auto);
+        assert_eq!(Locale::from_posix("de_AT.UTF-8"), Locale::De);
+        assert_eq!(Locale::from_posix("en_US.UTF-8"), Locale::EnUs);
+        assert_eq!(Locale::from_posix("en_GB"), Locale::EnGb);
+        assert_eq!(Locale::from_posix("C"), Locale::EnUs);
+    }
+}
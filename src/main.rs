@@ -1,10 +1,47 @@
+mod agent;
 mod app;
+mod archive;
+mod bagignore;
 mod bagit;
+mod cli;
+mod clone;
+mod dedup;
+mod diff;
+mod download;
+mod encrypt;
+mod events;
+mod filelist;
+mod fixtures;
+mod group;
+mod intake;
+mod inventory;
+mod journal;
+mod json;
+mod linesort;
+mod locale;
+mod optical;
+mod plugin;
+mod profile;
+mod provenance;
+mod receipt;
+mod s3;
+mod settings;
+mod signature;
+mod split;
+mod store;
+mod tagfile;
+mod tape;
+mod triage;
 
 use app::BagItApp;
 use eframe::icon_data::from_png_bytes;
 
 fn main() -> eframe::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(code) = cli::run(&args) {
+        std::process::exit(code);
+    }
+
     let icon = from_png_bytes(include_bytes!("../icon.png")).expect("Failed to load icon");
 
     let options = eframe::NativeOptions {
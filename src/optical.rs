@@ -0,0 +1,228 @@
+use crate::bagit::{
+    bag_algorithms, bag_volume, calculate_digest, copy_file, plan_volumes, read_payload_manifest,
+    BagError, BagOptions, BagSummary, DedupMode, Progress, VolumePlan,
+};
+use crate::inventory::csv_field;
+use crate::tagfile::{read_tag_file, BagInfo};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+/// File mapping every payload file to its disc, written next to the discs.
+pub const DISC_LABELS_FILE: &str = "disc-labels.csv";
+
+/// Recordable Blu-ray sizes, by name, in bytes (whole 2048-byte sectors).
+pub const DISC_MEDIA: [(&str, u64); 3] = [
+    ("bd25", 12_219_392 * 2048),
+    ("bd50", 24_438_784 * 2048),
+    ("bd100", 48_878_592 * 2048),
+];
+
+/// What goes on each disc.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum DiscLayout {
+    /// A sub-bag per disc, with `Bag-Group-Identifier` and `Bag-Count`.
+    #[default]
+    SubBags,
+    /// The payload files alone, in their folders, with a copy of the label file.
+    Folders,
+}
+
+/// Splits the payload of the finished bag at `bag` across discs holding `capacity`
+/// bytes each.
+pub fn plan_discs(bag: &Path, capacity: u64) -> Result<Vec<VolumePlan>, BagError> {
+    if !bag.join("bagit.txt").is_file() {
+        return Err(BagError::NotABag);
+    }
+    let options = BagOptions {
+        skip_names: Vec::new(),
+        ..BagOptions::default()
+    };
+    Ok(plan_volumes(&bag.join("data"), &options, capacity)?.0)
+}
+
+/// Writes the discs for `plan` into `disc-01`, `disc-02`, ... under `dest`, checks
+/// every copied file against the bag's manifest, and writes `disc-labels.csv`.
+/// Returns the disc folders.
+pub fn write_discs(
+    bag: &Path,
+    dest: &Path,
+    plan: &[VolumePlan],
+    layout: DiscLayout,
+    options: &BagOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<Vec<PathBuf>, BagError> {
+    let (algorithm, manifest) = read_payload_manifest(bag)?;
+    let identifier = BagSummary::read(bag)?.identifier;
+    let (bag_info, _) = read_tag_file(&bag.join("bag-info.txt"))?;
+
+    // Sub-bags carry the bag's own fields. Nothing is skipped (the payload was
+    // filtered when it was bagged) and nothing is deduplicated, since a disc must
+    // stand on its own. They're checksummed the way the bag is.
+    let mut options = options.clone();
+    options.metadata = BagInfo::parse(&bag_info);
+    options.skip_names = Vec::new();
+    options.dedup = DedupMode::Off;
+    options.algorithms = bag_algorithms(bag);
+
+    let mut labels = format!("disc,label,file,bytes,{}\n", algorithm.name());
+    let mut discs = Vec::new();
+    for (index, volume) in plan.iter().enumerate() {
+        let disc = dest.join(format!("disc-{:02}", index + 1));
+        let label = format!("{} disc {} of {}", identifier, index + 1, plan.len());
+        match layout {
+            DiscLayout::SubBags => {
+                bag_volume(
+                    &bag.join("data"),
+                    &disc,
+                    plan,
+                    index,
+                    &identifier,
+                    &options,
+                    progress_tx.clone(),
+                )?;
+            }
+            DiscLayout::Folders => {
+                if disc.exists() && fs::read_dir(&disc)?.next().is_some() {
+                    return Err(BagError::DestinationNotEmpty(disc));
+                }
+                if let Some(ref tx) = progress_tx {
+                    let _ = tx.send(Progress::Started {
+                        total_files: volume.files.len(),
+                    });
+                }
+                for (i, relative) in volume.files.iter().enumerate() {
+                    if let Some(ref tx) = progress_tx {
+                        let _ = tx.send(Progress::Copying {
+                            current: i + 1,
+                            filename: relative.to_string_lossy().to_string(),
+                        });
+                    }
+                    let target = disc.join(relative);
+                    if let Some(parent) = target.parent() {
+                        fs::create_dir_all(parent)?;
+                    }
+                    copy_file(&bag.join("data").join(relative), &target)?;
+                }
+            }
+        }
+
+        let payload_dir = match layout {
+            DiscLayout::SubBags => disc.join("data"),
+            DiscLayout::Folders => disc.clone(),
+        };
+        for relative in &volume.files {
+            let file = format!("data/{}", relative.to_string_lossy().replace('\\', "/"));
+            let copy = payload_dir.join(relative);
+            let expected = manifest.get(&file).cloned().unwrap_or_default();
+            if calculate_digest(&copy, algorithm)? != expected {
+                return Err(BagError::CopyMismatch(copy));
+            }
+            labels.push_str(&format!(
+                "{},{},{},{},{}\n",
+                index + 1,
+                csv_field(&label),
+                csv_field(&file),
+                fs::metadata(&copy)?.len(),
+                expected
+            ));
+        }
+        discs.push(disc);
+    }
+
+    fs::write(dest.join(DISC_LABELS_FILE), &labels)?;
+    if layout == DiscLayout::Folders {
+        for disc in &discs {
+            fs::write(disc.join(DISC_LABELS_FILE), &labels)?;
+        }
+    }
+    Ok(discs)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{bag_directory, validate_bag, ValidationOptions};
+
+    fn make_bag(name: &str) -> PathBuf {
+        let temp_dir = std::env::temp_dir().join(name);
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("films");
+        fs::create_dir_all(bag.join("reel-2")).unwrap();
+        fs::write(bag.join("reel-1.mkv"), vec![1u8; 600 * 1024]).unwrap();
+        fs::write(bag.join("reel-2/part-a.mkv"), vec![2u8; 600 * 1024]).unwrap();
+        fs::write(bag.join("reel-2/part-b.mkv"), vec![3u8; 100 * 1024]).unwrap();
+        let options = BagOptions {
+            metadata: {
+                let mut info = BagInfo::default();
+                info.push("External-Identifier", "FILM-7");
+                info
+            },
+            ..BagOptions::default()
+        };
+        bag_directory(&bag, &options, None).unwrap();
+        bag
+    }
+
+    #[test]
+    fn test_sub_bags_per_disc() {
+        let bag = make_bag("bagit_test_optical");
+        let temp_dir = bag.parent().unwrap().to_path_buf();
+
+        // Room for about 1 MiB of payload per disc
+        let plan = plan_discs(&bag, 2 * 1024 * 1024).unwrap();
+        assert_eq!(plan.len(), 2);
+        let out = temp_dir.join("discs");
+        let discs = write_discs(
+            &bag,
+            &out,
+            &plan,
+            DiscLayout::SubBags,
+            &BagOptions::default(),
+            None,
+        )
+        .unwrap();
+        assert_eq!(discs.len(), 2);
+        for disc in &discs {
+            let report = validate_bag(disc, &ValidationOptions::default(), None).unwrap();
+            assert!(report.is_valid(), "{:?}", report.errors);
+        }
+        let info = fs::read_to_string(discs[1].join("bag-info.txt")).unwrap();
+        assert!(info.contains("Bag-Group-Identifier: FILM-7"));
+        assert!(info.contains("Bag-Count: 2 of 2"));
+
+        let labels = fs::read_to_string(out.join(DISC_LABELS_FILE)).unwrap();
+        assert_eq!(labels.lines().count(), 4);
+        assert!(labels.contains(",data/reel-1.mkv,614400,"));
+        assert!(labels.contains("2,FILM-7 disc 2 of 2,data/"));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_folder_layout() {
+        let bag = make_bag("bagit_test_optical_folders");
+        let temp_dir = bag.parent().unwrap().to_path_buf();
+
+        let capacity = DISC_MEDIA[0].1;
+        let plan = plan_discs(&bag, capacity).unwrap();
+        assert_eq!(plan.len(), 1);
+        let out = temp_dir.join("discs");
+        let discs = write_discs(
+            &bag,
+            &out,
+            &plan,
+            DiscLayout::Folders,
+            &BagOptions::default(),
+            None,
+        )
+        .unwrap();
+        assert!(discs[0].join("reel-2/part-b.mkv").is_file());
+        assert!(!discs[0].join("bagit.txt").exists());
+        assert!(discs[0].join(DISC_LABELS_FILE).is_file());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,254 @@
+use crate::bagit::{BagError, BagReport, EMPTY_DIRS_FILE, STAGING_DIR};
+use crate::dedup::{DEDUP_REPORT_FILE, FETCH_FILE};
+use crate::download::SOURCE_URLS_FILE;
+use crate::events::EVENT_LOG_FILE;
+use crate::journal::JOURNAL_FILE;
+use crate::provenance::{FILE_METADATA_FILE, FILE_PERMISSIONS_FILE, PROVENANCE_FILE, XATTRS_FILE};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+use walkdir::WalkDir;
+
+/// Points in writing a bag where plugins run.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PluginStage {
+    /// The payload is in `data/`; stdin lists its files.
+    AfterEnumeration,
+    /// Every payload file is hashed; stdin is the payload manifest.
+    AfterHashing,
+    /// bag-info.txt and the manifest are written, in the staging folder until the bag
+    /// is sealed; the tag manifest and bagit.txt are still to come, so tag files added
+    /// here are covered by it.
+    BeforeSealing,
+}
+
+impl PluginStage {
+    pub const ALL: [PluginStage; 3] = [
+        PluginStage::AfterEnumeration,
+        PluginStage::AfterHashing,
+        PluginStage::BeforeSealing,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            PluginStage::AfterEnumeration => "after-enumeration",
+            PluginStage::AfterHashing => "after-hashing",
+            PluginStage::BeforeSealing => "before-sealing",
+        }
+    }
+}
+
+/// An external program run while bagging, for characterization or policy checks.
+///
+/// The contract: the command runs with `BAGGIE_STAGE` (the stage name), `BAGGIE_BAG`
+/// (the bag root) and `BAGGIE_TAG_DIR` (an empty folder) set, plus `BAGGIE_STAGED`
+/// once tag files are being written (the folder holding the new ones until the bag is
+/// sealed, as those in the bag stay as they were till then), and the entry list for
+/// its stage on stdin, one `data/...` path or manifest line per line. It may read the
+/// bag but must not change it. Files it writes to `BAGGIE_TAG_DIR` are added to the
+/// bag as tag files, keeping their relative paths; they may not replace baggie's own
+/// tag files or go under `data/`. Each line it prints becomes a warning in the report,
+/// and a non-zero exit stops the bag with what it wrote to stderr.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Plugin {
+    pub stage: PluginStage,
+    /// Program and arguments, split on whitespace.
+    pub command: String,
+}
+
+impl Plugin {
+    /// Reads a `stage: command` line, as stored in the settings file.
+    pub fn parse(line: &str) -> Option<Plugin> {
+        let (stage, command) = line.split_once(':')?;
+        let stage = PluginStage::ALL
+            .into_iter()
+            .find(|s| s.name().eq_ignore_ascii_case(stage.trim()))?;
+        let command = command.trim();
+        if command.is_empty() {
+            return None;
+        }
+        Some(Plugin {
+            stage,
+            command: command.to_string(),
+        })
+    }
+
+    pub fn to_line(&self) -> String {
+        format!("{}: {}", self.stage.name(), self.command)
+    }
+
+    fn program(&self) -> &str {
+        self.command.split_whitespace().next().unwrap_or("")
+    }
+}
+
+/// Tag files a plugin may not write: baggie's own, its working files, and anything in
+/// the payload. fetch.txt is among them, as one would make the bag holey.
+fn reserved_tag_file(relative: &str) -> bool {
+    let first = relative.split('/').next().unwrap_or(relative);
+    relative == "bagit.txt"
+        || relative == "bag-info.txt"
+        || relative.starts_with("manifest-")
+        || relative.starts_with("tagmanifest-")
+        || [
+            PROVENANCE_FILE,
+            FILE_METADATA_FILE,
+            FILE_PERMISSIONS_FILE,
+            XATTRS_FILE,
+            EVENT_LOG_FILE,
+            FETCH_FILE,
+            DEDUP_REPORT_FILE,
+            SOURCE_URLS_FILE,
+            EMPTY_DIRS_FILE,
+        ]
+        .contains(&relative)
+        || ["data", STAGING_DIR, JOURNAL_FILE].contains(&first)
+}
+
+/// Runs the plugins registered for `stage` against the bag at `bag`, feeding each
+/// `input` on stdin. The tag files they add go in `staged` if it's given, to be moved
+/// into the bag as it's sealed, or straight into the bag if not. Returns their names,
+/// relative to the bag root.
+pub fn run_plugins(
+    plugins: &[Plugin],
+    stage: PluginStage,
+    bag: &Path,
+    staged: Option<&Path>,
+    input: &str,
+    report: &mut BagReport,
+) -> Result<Vec<String>, BagError> {
+    let mut added = Vec::new();
+    for plugin in plugins.iter().filter(|p| p.stage == stage) {
+        let nanos = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |d| d.as_nanos());
+        let tag_dir =
+            std::env::temp_dir().join(format!("baggie-plugin-{}-{}", std::process::id(), nanos));
+        fs::create_dir_all(&tag_dir)?;
+        let result = run_plugin(plugin, bag, staged, &tag_dir, input, report, &mut added);
+        let _ = fs::remove_dir_all(&tag_dir);
+        result?;
+    }
+    Ok(added)
+}
+
+fn run_plugin(
+    plugin: &Plugin,
+    bag: &Path,
+    staged: Option<&Path>,
+    tag_dir: &Path,
+    input: &str,
+    report: &mut BagReport,
+    added: &mut Vec<String>,
+) -> Result<(), BagError> {
+    let failed = |message: String| BagError::PluginFailed {
+        command: plugin.command.clone(),
+        message,
+    };
+
+    let mut args = plugin.command.split_whitespace();
+    let mut command = Command::new(args.next().unwrap_or(""));
+    command
+        .args(args)
+        .env("BAGGIE_STAGE", plugin.stage.name())
+        .env("BAGGIE_BAG", bag)
+        .env("BAGGIE_TAG_DIR", tag_dir);
+    if let Some(staged) = staged {
+        command.env("BAGGIE_STAGED", staged);
+    }
+    let mut child = command
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| failed(e.to_string()))?;
+
+    // Written from another thread so a plugin that prints before it has read all of
+    // stdin can't deadlock against us.
+    let mut stdin = child.stdin.take().unwrap();
+    let input = input.to_string();
+    let writer = std::thread::spawn(move || {
+        // A plugin that doesn't care about the entry list may exit without reading it.
+        let _ = stdin.write_all(input.as_bytes());
+    });
+    let output = child
+        .wait_with_output()
+        .map_err(|e| failed(e.to_string()))?;
+    let _ = writer.join();
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+        return Err(failed(if stderr.is_empty() {
+            output.status.to_string()
+        } else {
+            stderr
+        }));
+    }
+    for line in String::from_utf8_lossy(&output.stdout).lines() {
+        if !line.trim().is_empty() {
+            report
+                .warnings
+                .push(format!("{}: {}", plugin.program(), line.trim()));
+        }
+    }
+
+    let files: Vec<PathBuf> = WalkDir::new(tag_dir)
+        .into_iter()
+        .filter_map(|e| e.ok())
+        .filter(|e| e.file_type().is_file())
+        .map(|e| e.path().to_path_buf())
+        .collect();
+    for file in files {
+        let relative = file
+            .strip_prefix(tag_dir)
+            .unwrap()
+            .to_string_lossy()
+            .replace('\\', "/");
+        if reserved_tag_file(&relative) {
+            return Err(failed(format!("may not write the tag file {}", relative)));
+        }
+        let target = staged.unwrap_or(bag).join(&relative);
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::copy(&file, target)?;
+        if !added.contains(&relative) {
+            added.push(relative);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_plugin_line() {
+        let plugin = Plugin::parse("before-sealing: /opt/tools/siegfried --json").unwrap();
+        assert_eq!(plugin.stage, PluginStage::BeforeSealing);
+        assert_eq!(plugin.command, "/opt/tools/siegfried --json");
+        assert_eq!(plugin.program(), "/opt/tools/siegfried");
+        assert_eq!(Plugin::parse(&plugin.to_line()), Some(plugin));
+
+        assert!(Plugin::parse("on-a-whim: tool").is_none());
+        assert!(Plugin::parse("after-hashing:").is_none());
+    }
+
+    #[test]
+    fn test_reserved_tag_files() {
+        assert!(reserved_tag_file("bagit.txt"));
+        assert!(reserved_tag_file("manifest-md5.txt"));
+        assert!(reserved_tag_file("data/extra.txt"));
+        assert!(reserved_tag_file("provenance.csv"));
+        assert!(reserved_tag_file("fetch.txt"));
+        assert!(reserved_tag_file("source-urls.txt"));
+        assert!(reserved_tag_file(".baggie-journal"));
+        assert!(reserved_tag_file(".baggie-staging/bag-info.txt"));
+        assert!(!reserved_tag_file("metadata/fetch.txt"));
+        assert!(!reserved_tag_file("metadata/formats.csv"));
+        assert!(!reserved_tag_file("database.txt"));
+    }
+}
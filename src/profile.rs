@@ -0,0 +1,763 @@
+use crate::bagit::{
+    glob_match, BagError, BagFiles, BagItVersion, BagOptions, ChecksumAlgorithm,
+    GENERATED_BAG_INFO_LABELS,
+};
+use crate::dedup::FETCH_FILE;
+use crate::download::http_agent;
+use crate::json::Json;
+use crate::settings::config_dir;
+use crate::tagfile::{decode_tag_bytes, tag_value, BagInfo};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Whether a profile wants bags serialized.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Serialization {
+    Forbidden,
+    Required,
+    Optional,
+}
+
+/// What a profile says about one bag-info.txt field.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProfileField {
+    pub label: String,
+    pub required: bool,
+    /// Whether the field may appear more than once.
+    pub repeatable: bool,
+    /// The values allowed; empty if any is.
+    pub values: Vec<String>,
+    pub description: String,
+}
+
+/// A BagIt Profile, as described by the bagit-profiles JSON specification: the rules
+/// a repository sets for the bags it accepts.
+///
+/// Algorithms are named as the profile gives them (`sha256`, `md5`, ...).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Profile {
+    /// `BagIt-Profile-Identifier`, the URL the profile is published at.
+    pub identifier: String,
+    pub source_organization: String,
+    pub description: String,
+    pub version: String,
+    pub bag_info: Vec<ProfileField>,
+    pub manifests_required: Vec<String>,
+    /// `None` if any algorithm is allowed.
+    pub manifests_allowed: Option<Vec<String>>,
+    pub tag_manifests_required: Vec<String>,
+    /// `None` if any algorithm is allowed.
+    pub tag_manifests_allowed: Option<Vec<String>>,
+    pub allow_fetch: bool,
+    pub serialization: Serialization,
+    /// MIME types a serialized bag may have; empty if any is accepted.
+    pub accept_serialization: Vec<String>,
+    /// `BagIt-Version`s accepted; empty if any is.
+    pub accept_bagit_version: Vec<String>,
+    /// Tag files that must be in the bag, as paths relative to it.
+    pub tag_files_required: Vec<String>,
+    /// Glob patterns the bag's other tag files must match; `None` if any is allowed.
+    pub tag_files_allowed: Option<Vec<String>>,
+}
+
+/// bag-info.txt fields baggie fills in itself when it makes a bag.
+const FILLED_IN_LABELS: &[&str] = &["Internal-Sender-Identifier", "BagIt-Profile-Identifier"];
+
+fn bad(message: impl Into<String>) -> BagError {
+    BagError::BadProfile(message.into())
+}
+
+fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// The text of the profile at `source`, a URL or a file.
+fn fetch(source: &str) -> Result<String, BagError> {
+    let text = if is_url(source) {
+        http_agent()
+            .get(source)
+            .call()
+            .map_err(|e| e.to_string())
+            .and_then(|response| response.into_string().map_err(|e| e.to_string()))
+    } else {
+        fs::read_to_string(source).map_err(|e| e.to_string())
+    };
+    text.map_err(|e| bad(format!("{} can't be read: {}", source, e)))
+}
+
+/// Parses `text`, read from `source`, saying where it came from if it's no good.
+fn parse_from(source: &str, text: &str) -> Result<Profile, BagError> {
+    Profile::parse(text).map_err(|e| match e {
+        BagError::BadProfile(message) => bad(format!("{}: {}", source, message)),
+        e => e,
+    })
+}
+
+/// Where saved profiles are kept, one `<name>.json` each.
+fn profiles_dir() -> Option<PathBuf> {
+    config_dir().map(|d| d.join("baggie").join("profiles"))
+}
+
+fn saved_path(name: &str) -> Option<PathBuf> {
+    profiles_dir().map(|d| d.join(format!("{}.json", name)))
+}
+
+/// What a profile from `source` is saved as: the last part of its path, less
+/// `.json`, with anything but letters, digits, `-`, `_` and `.` made `_`.
+fn profile_name(source: &str) -> String {
+    let path = source.split(['?', '#']).next().unwrap_or_default();
+    let last = path
+        .trim_end_matches(['/', '\\'])
+        .rsplit(['/', '\\'])
+        .next()
+        .unwrap_or_default();
+    let stem = last.strip_suffix(".json").unwrap_or(last);
+    let name: String = stem
+        .chars()
+        .map(|c| match c.is_alphanumeric() || "-_.".contains(c) {
+            true => c,
+            false => '_',
+        })
+        .collect();
+    match name.trim_matches('.').is_empty() {
+        true => "profile".to_string(),
+        false => name,
+    }
+}
+
+/// Reads the profile at `source` (a URL or a file) and keeps a copy, so it can be
+/// picked for new bags by name without fetching it again. Returns the name, which
+/// replaces any profile saved under it before.
+pub fn save_profile(source: &str) -> Result<String, BagError> {
+    let text = fetch(source)?;
+    parse_from(source, &text)?;
+    let name = profile_name(source);
+    let path = saved_path(&name).ok_or_else(|| bad("there's no settings folder to save it in"))?;
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    fs::write(path, text)?;
+    Ok(name)
+}
+
+/// The names of the saved profiles, in order.
+pub fn saved_profiles() -> Vec<String> {
+    let mut names: Vec<String> = profiles_dir()
+        .and_then(|dir| fs::read_dir(dir).ok())
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let name = entry.ok()?.file_name().to_string_lossy().to_string();
+            name.strip_suffix(".json").map(String::from)
+        })
+        .collect();
+    names.sort();
+    names
+}
+
+pub fn remove_profile(name: &str) -> std::io::Result<()> {
+    match saved_path(name) {
+        Some(path) => fs::remove_file(path),
+        None => Ok(()),
+    }
+}
+
+/// The list of strings under `key`, `None` if it's not there.
+fn strings(json: &Json, key: &str) -> Result<Option<Vec<String>>, BagError> {
+    let Some(value) = json.get(key) else {
+        return Ok(None);
+    };
+    let items = value
+        .as_array()
+        .ok_or_else(|| bad(format!("{} isn't a list", key)))?;
+    items
+        .iter()
+        .map(|item| {
+            item.as_str()
+                .map(String::from)
+                .ok_or_else(|| bad(format!("{} should list only strings", key)))
+        })
+        .collect::<Result<_, _>>()
+        .map(Some)
+}
+
+fn flag(json: &Json, key: &str, default: bool) -> Result<bool, BagError> {
+    match json.get(key) {
+        None => Ok(default),
+        Some(value) => value
+            .as_bool()
+            .ok_or_else(|| bad(format!("{} should be true or false", key))),
+    }
+}
+
+/// Whether `name` is among `names`, ignoring case, as profiles differ on it.
+fn listed(names: &[String], name: &str) -> bool {
+    names.iter().any(|n| n.eq_ignore_ascii_case(name))
+}
+
+impl Profile {
+    pub fn parse(text: &str) -> Result<Profile, BagError> {
+        let json = Json::parse(text).map_err(|e| bad(format!("not JSON: {}", e)))?;
+        let info = json
+            .get("BagIt-Profile-Info")
+            .filter(|info| info.as_object().is_some())
+            .ok_or_else(|| bad("there's no BagIt-Profile-Info"))?;
+        let text = |key| {
+            info.get(key)
+                .and_then(Json::as_str)
+                .unwrap_or_default()
+                .to_string()
+        };
+        let identifier = text("BagIt-Profile-Identifier");
+        if identifier.is_empty() {
+            return Err(bad("BagIt-Profile-Info has no BagIt-Profile-Identifier"));
+        }
+
+        let mut bag_info = Vec::new();
+        if let Some(fields) = json.get("Bag-Info") {
+            let fields = fields
+                .as_object()
+                .ok_or_else(|| bad("Bag-Info isn't an object"))?;
+            for (label, rule) in fields {
+                if rule.as_object().is_none() {
+                    return Err(bad(format!(
+                        "the Bag-Info rule for {} isn't an object",
+                        label
+                    )));
+                }
+                bag_info.push(ProfileField {
+                    label: label.clone(),
+                    required: flag(rule, "required", false)?,
+                    repeatable: flag(rule, "repeatable", true)?,
+                    values: strings(rule, "values")?.unwrap_or_default(),
+                    description: rule
+                        .get("description")
+                        .and_then(Json::as_str)
+                        .unwrap_or_default()
+                        .to_string(),
+                });
+            }
+        }
+
+        let serialization = match json.get("Serialization").map(|s| s.as_str()) {
+            None | Some(Some("optional")) => Serialization::Optional,
+            Some(Some("required")) => Serialization::Required,
+            Some(Some("forbidden")) => Serialization::Forbidden,
+            Some(_) => {
+                return Err(bad(
+                    "Serialization should be \"forbidden\", \"required\" or \"optional\"",
+                ))
+            }
+        };
+        Ok(Profile {
+            identifier,
+            source_organization: text("Source-Organization"),
+            description: text("External-Description"),
+            version: text("Version"),
+            bag_info,
+            manifests_required: strings(&json, "Manifests-Required")?.unwrap_or_default(),
+            manifests_allowed: strings(&json, "Manifests-Allowed")?,
+            tag_manifests_required: strings(&json, "Tag-Manifests-Required")?.unwrap_or_default(),
+            tag_manifests_allowed: strings(&json, "Tag-Manifests-Allowed")?,
+            allow_fetch: flag(&json, "Allow-Fetch.txt", true)?,
+            serialization,
+            accept_serialization: strings(&json, "Accept-Serialization")?.unwrap_or_default(),
+            accept_bagit_version: strings(&json, "Accept-BagIt-Version")?.unwrap_or_default(),
+            tag_files_required: strings(&json, "Tag-Files-Required")?.unwrap_or_default(),
+            tag_files_allowed: strings(&json, "Tag-Files-Allowed")?,
+        })
+    }
+
+    /// Reads the profile at `source`: an `http://` or `https://` URL, a file, or the
+    /// name of a saved profile.
+    pub fn load(source: &str) -> Result<Profile, BagError> {
+        let saved = saved_path(source).filter(|path| path.is_file());
+        match saved {
+            Some(_) if !is_url(source) && !Path::new(source).exists() => Profile::named(source),
+            _ => parse_from(source, &fetch(source)?),
+        }
+    }
+
+    /// The profile saved as `name` with [`save_profile`].
+    pub fn named(name: &str) -> Result<Profile, BagError> {
+        let path = saved_path(name).ok_or_else(|| bad("there's no settings folder"))?;
+        parse_from(name, &fetch(&path.to_string_lossy())?)
+    }
+
+    /// Adds the required bag-info fields `metadata` lacks: with their value if the
+    /// profile allows only one, blank otherwise. Returns the labels left blank, for
+    /// the user to fill in.
+    pub fn prefill(&self, metadata: &mut BagInfo) -> Vec<String> {
+        let mut blank = Vec::new();
+        for rule in self.bag_info.iter().filter(|rule| rule.required) {
+            let filled_in = GENERATED_BAG_INFO_LABELS
+                .iter()
+                .chain(FILLED_IN_LABELS)
+                .any(|l| l.eq_ignore_ascii_case(&rule.label));
+            let present = metadata
+                .fields()
+                .iter()
+                .any(|(label, _)| label.eq_ignore_ascii_case(&rule.label));
+            if filled_in || present {
+                continue;
+            }
+            match rule.values.as_slice() {
+                [value] => metadata.push(&rule.label, value),
+                _ => {
+                    metadata.push(&rule.label, "");
+                    blank.push(rule.label.clone());
+                }
+            }
+        }
+        blank
+    }
+
+    /// Makes `options` make bags under the profile: required bag-info fields are
+    /// pre-filled as by [`Profile::prefill`], the required checksum algorithms are
+    /// added and the disallowed ones dropped, and a BagIt version it doesn't accept is
+    /// swapped for one it does. Anything still amiss, like a field left blank, is
+    /// refused by [`crate::bagit::check_profile`] before the bag is made.
+    pub fn preset(&self, options: &mut BagOptions) {
+        self.prefill(&mut options.metadata);
+
+        let known = |names: &[String]| -> Vec<ChecksumAlgorithm> {
+            names
+                .iter()
+                .filter_map(|name| ChecksumAlgorithm::from_name(&name.to_ascii_lowercase()))
+                .collect()
+        };
+        // Baggie writes a tag manifest in each algorithm it writes a manifest in
+        let allowed = |algorithm: &ChecksumAlgorithm| {
+            [&self.manifests_allowed, &self.tag_manifests_allowed]
+                .iter()
+                .all(|list| {
+                    list.as_ref()
+                        .is_none_or(|list| listed(list, algorithm.name()))
+                })
+        };
+        let mut algorithms = known(&self.manifests_required);
+        algorithms.extend(known(&self.tag_manifests_required));
+        algorithms.extend(options.algorithms.iter().filter(|a| allowed(a)));
+        if algorithms.is_empty() {
+            algorithms.extend(ChecksumAlgorithm::ALL.iter().find(|a| allowed(a)));
+        }
+        let mut unique = Vec::new();
+        for algorithm in algorithms {
+            if !unique.contains(&algorithm) {
+                unique.push(algorithm);
+            }
+        }
+        if !unique.is_empty() {
+            options.algorithms = unique;
+        }
+
+        if self.check_version(options.bagit_version.as_str()).is_some() {
+            if let Some(version) = self
+                .accept_bagit_version
+                .iter()
+                .find_map(|v| BagItVersion::parse(v))
+            {
+                options.bagit_version = version;
+            }
+        }
+        options.profile = Some(self.clone());
+    }
+
+    /// What's wrong with `bag_info` under the profile's Bag-Info rules. Fields in
+    /// `filled_in` count as present, since they'll be added later.
+    fn check_bag_info(&self, bag_info: &BagInfo, filled_in: &[&str]) -> Vec<String> {
+        let mut problems = Vec::new();
+        for rule in &self.bag_info {
+            let values: Vec<&str> = bag_info
+                .fields()
+                .iter()
+                .filter(|(label, _)| label.eq_ignore_ascii_case(&rule.label))
+                .map(|(_, value)| value.as_str())
+                .collect();
+            let filled = filled_in
+                .iter()
+                .any(|l| l.eq_ignore_ascii_case(&rule.label));
+            if rule.required && values.is_empty() && !filled {
+                problems.push(format!("bag-info.txt needs a {} field", rule.label));
+            } else if rule.required && values.iter().all(|v| v.trim().is_empty()) && !filled {
+                problems.push(format!("bag-info.txt's {} field is blank", rule.label));
+            }
+            if !rule.repeatable && values.len() > 1 {
+                problems.push(format!(
+                    "bag-info.txt may have only one {} field",
+                    rule.label
+                ));
+            }
+            for value in values {
+                // A blank required field is reported above
+                if rule.required && value.trim().is_empty() {
+                    continue;
+                }
+                if !rule.values.is_empty() && !rule.values.iter().any(|v| v == value) {
+                    problems.push(format!(
+                        "{} is \"{}\", but must be one of: {}",
+                        rule.label,
+                        value,
+                        rule.values.join(", ")
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    /// What's wrong with having manifests (or, with `tag`, tag manifests) in
+    /// `algorithms`.
+    fn check_algorithms(&self, algorithms: &[&str], tag: bool) -> Vec<String> {
+        let (required, allowed, kind) = match tag {
+            false => (
+                &self.manifests_required,
+                &self.manifests_allowed,
+                "manifest",
+            ),
+            true => (
+                &self.tag_manifests_required,
+                &self.tag_manifests_allowed,
+                "tag manifest",
+            ),
+        };
+        let mut problems = Vec::new();
+        for algorithm in required {
+            if !algorithms.iter().any(|a| a.eq_ignore_ascii_case(algorithm)) {
+                problems.push(format!("a {} {} is required", algorithm, kind));
+            }
+        }
+        if let Some(allowed) = allowed {
+            for algorithm in algorithms {
+                if !listed(allowed, algorithm) {
+                    problems.push(format!(
+                        "{} {}s aren't allowed (only {})",
+                        algorithm,
+                        kind,
+                        allowed.join(", ")
+                    ));
+                }
+            }
+        }
+        problems
+    }
+
+    fn check_version(&self, version: &str) -> Option<String> {
+        (!self.accept_bagit_version.is_empty() && !listed(&self.accept_bagit_version, version))
+            .then(|| {
+                format!(
+                    "BagIt {} isn't accepted (only {})",
+                    version,
+                    self.accept_bagit_version.join(", ")
+                )
+            })
+    }
+
+    /// What's wrong with a bag serialized as `mime_type`, or left as a folder if
+    /// `None`.
+    pub fn check_serialization(&self, mime_type: Option<&str>) -> Option<String> {
+        match (self.serialization, mime_type) {
+            (Serialization::Required, None) => {
+                Some("the bag must be serialized (baggie serialize)".to_string())
+            }
+            (Serialization::Forbidden, Some(_)) => {
+                Some("the bag mustn't be serialized".to_string())
+            }
+            (_, Some(mime_type))
+                if !self.accept_serialization.is_empty()
+                    && !listed(&self.accept_serialization, mime_type) =>
+            {
+                Some(format!(
+                    "{} isn't an accepted serialization (only {})",
+                    mime_type,
+                    self.accept_serialization.join(", ")
+                ))
+            }
+            _ => None,
+        }
+    }
+
+    /// What would make a bag made with `options` fail the profile, for refusing
+    /// before anything is moved. Tag files and serialization are left to validation.
+    pub fn check_options(&self, options: &BagOptions) -> Vec<String> {
+        let mut filled_in = GENERATED_BAG_INFO_LABELS.to_vec();
+        filled_in.extend(FILLED_IN_LABELS);
+        let mut problems = self.check_bag_info(&options.metadata, &filled_in);
+        if let Some(declared) = options.metadata.get("BagIt-Profile-Identifier") {
+            if declared != self.identifier {
+                problems.push(format!(
+                    "bag-info.txt already names the profile {}",
+                    declared
+                ));
+            }
+        }
+        // Baggie writes a tag manifest in each algorithm it writes a manifest in
+        let algorithms: Vec<&str> = options
+            .manifest_algorithms()
+            .iter()
+            .map(|a| a.name())
+            .collect();
+        problems.extend(self.check_algorithms(&algorithms, false));
+        problems.extend(self.check_algorithms(&algorithms, true));
+        problems.extend(self.check_version(options.bagit_version.as_str()));
+        if !self.allow_fetch && options.dedup == crate::bagit::DedupMode::Fetch {
+            problems.push(
+                "fetch.txt isn't allowed, so files can't be deduplicated into it".to_string(),
+            );
+        }
+        problems
+    }
+
+    /// What keeps the bag in `files` from conforming to the profile.
+    pub fn check_bag(&self, files: &dyn BagFiles) -> Vec<String> {
+        let read = |name: &str| {
+            files
+                .read(name)
+                .and_then(Result::ok)
+                .map(|bytes| decode_tag_bytes(&bytes, name).0)
+        };
+        let mut problems = Vec::new();
+        if let Some(version) = read("bagit.txt")
+            .as_deref()
+            .and_then(|b| tag_value(b, "BagIt-Version"))
+        {
+            problems.extend(self.check_version(version));
+        }
+
+        let bag_info = BagInfo::parse(&read("bag-info.txt").unwrap_or_default());
+        problems.extend(self.check_bag_info(&bag_info, &[]));
+        if let Some(declared) = bag_info.get("BagIt-Profile-Identifier") {
+            if declared != self.identifier {
+                problems.push(format!("bag-info.txt names another profile, {}", declared));
+            }
+        }
+
+        let top_level = files.top_level();
+        let algorithms = |prefix: &str| -> Vec<String> {
+            top_level
+                .iter()
+                .filter_map(|name| name.strip_prefix(prefix)?.strip_suffix(".txt"))
+                .map(String::from)
+                .collect()
+        };
+        for (prefix, tag) in [("manifest-", false), ("tagmanifest-", true)] {
+            let found = algorithms(prefix);
+            let found: Vec<&str> = found.iter().map(String::as_str).collect();
+            problems.extend(self.check_algorithms(&found, tag));
+        }
+
+        if !self.allow_fetch && files.exists(FETCH_FILE) {
+            problems.push("fetch.txt isn't allowed".to_string());
+        }
+        problems.extend(self.check_serialization(files.serialization()));
+
+        let tag_files = files.tag_files();
+        for required in &self.tag_files_required {
+            if !tag_files.contains(required) {
+                problems.push(format!("the tag file {} is required", required));
+            }
+        }
+        if let Some(allowed) = &self.tag_files_allowed {
+            let standard = |name: &str| {
+                ["bagit.txt", "bag-info.txt", FETCH_FILE].contains(&name)
+                    || (!name.contains('/')
+                        && (name.starts_with("manifest-") || name.starts_with("tagmanifest-")))
+            };
+            let mut extra: Vec<&String> = tag_files
+                .iter()
+                .filter(|name| !standard(name))
+                .filter(|name| !allowed.iter().any(|pattern| glob_match(pattern, name)))
+                .collect();
+            extra.sort();
+            for name in extra {
+                problems.push(format!("the tag file {} isn't allowed", name));
+            }
+        }
+        problems
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{
+        bag_directory, validate_bag, BagItVersion, ChecksumAlgorithm, FindingKind,
+        ValidationOptions,
+    };
+
+    const PROFILE: &str = r#"{
+        "BagIt-Profile-Info": {
+            "BagIt-Profile-Identifier": "https://example.org/profiles/grant.json",
+            "Source-Organization": "Example Foundation",
+            "External-Description": "Bags for grant deposits",
+            "Version": "1.2"
+        },
+        "Bag-Info": {
+            "Source-Organization": {"required": true, "values": ["Example Archive"]},
+            "Contact-Email": {"required": true, "repeatable": false},
+            "Bagging-Date": {"required": true}
+        },
+        "Manifests-Required": ["sha256"],
+        "Manifests-Allowed": ["sha256", "sha512"],
+        "Allow-Fetch.txt": false,
+        "Serialization": "optional",
+        "Accept-Serialization": ["application/x-tar"],
+        "Accept-BagIt-Version": ["1.0"],
+        "Tag-Files-Allowed": ["metadata/*"]
+    }"#;
+
+    #[test]
+    fn test_parse_profile() {
+        let profile = Profile::parse(PROFILE).unwrap();
+        assert_eq!(
+            profile.identifier,
+            "https://example.org/profiles/grant.json"
+        );
+        assert_eq!(profile.bag_info.len(), 3);
+        assert!(!profile.bag_info[1].repeatable);
+        assert_eq!(profile.manifests_allowed.as_ref().unwrap().len(), 2);
+        assert!(profile.tag_manifests_allowed.is_none());
+        assert!(!profile.allow_fetch);
+        assert_eq!(profile.serialization, Serialization::Optional);
+        assert_eq!(
+            profile
+                .check_serialization(Some("application/zip"))
+                .unwrap(),
+            "application/zip isn't an accepted serialization (only application/x-tar)"
+        );
+
+        assert!(matches!(Profile::parse("{}"), Err(BagError::BadProfile(_))));
+        let bad_list = PROFILE.replace("[\"sha256\"],", "\"sha256\",");
+        let result = Profile::parse(&bad_list);
+        assert!(matches!(result, Err(BagError::BadProfile(m)) if m.contains("Manifests-Required")));
+    }
+
+    #[test]
+    fn test_profile_constrains_bagging_and_validation() {
+        let profile = Profile::parse(PROFILE).unwrap();
+        let temp_dir = std::env::temp_dir().join("bagit_test_profile");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+
+        // Missing fields and a disallowed algorithm are refused before anything moves
+        let mut options = BagOptions {
+            algorithms: vec![ChecksumAlgorithm::Md5],
+            profile: Some(profile.clone()),
+            ..BagOptions::default()
+        };
+        options.metadata.push("Source-Organization", "Elsewhere");
+        let result = bag_directory(&temp_dir, &options, None);
+        let Err(BagError::ProfileViolation(problems)) = result else {
+            panic!("expected a profile violation, got {:?}", result);
+        };
+        assert_eq!(
+            problems,
+            vec![
+                "Source-Organization is \"Elsewhere\", but must be one of: Example Archive",
+                "bag-info.txt needs a Contact-Email field",
+                "a sha256 manifest is required",
+                "md5 manifests aren't allowed (only sha256, sha512)",
+            ]
+        );
+        assert!(temp_dir.join("file.txt").exists());
+
+        options.algorithms = vec![ChecksumAlgorithm::Sha256];
+        options.metadata = BagInfo::default();
+        options
+            .metadata
+            .push("Source-Organization", "Example Archive");
+        options
+            .metadata
+            .push("Contact-Email", "archivist@example.org");
+        bag_directory(&temp_dir, &options, None).unwrap();
+        let bag_info = fs::read_to_string(temp_dir.join("bag-info.txt")).unwrap();
+        assert!(
+            bag_info.contains("BagIt-Profile-Identifier: https://example.org/profiles/grant.json")
+        );
+
+        let validation = ValidationOptions {
+            profile: Some(profile.clone()),
+            ..ValidationOptions::default()
+        };
+        let report = validate_bag(&temp_dir, &validation, None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // A stray tag file and a second contact both break the profile
+        fs::create_dir(temp_dir.join("notes")).unwrap();
+        fs::write(temp_dir.join("notes/readme.txt"), "hi").unwrap();
+        let bag_info = bag_info + "Contact-Email: other@example.org\n";
+        fs::write(temp_dir.join("bag-info.txt"), bag_info).unwrap();
+        let report = validate_bag(&temp_dir, &validation, None).unwrap();
+        let problems: Vec<&str> = report
+            .errors_of(FindingKind::Other)
+            .filter(|e| e.rule == crate::bagit::RULE_PROFILE)
+            .map(|e| e.message.as_str())
+            .collect();
+        assert_eq!(
+            problems,
+            vec![
+                "bag-info.txt may have only one Contact-Email field",
+                "the tag file notes/readme.txt isn't allowed",
+            ]
+        );
+
+        // BagIt 0.97 isn't accepted
+        let old = BagOptions {
+            bagit_version: BagItVersion::V0_97,
+            ..options
+        };
+        let problems = profile.check_options(&old);
+        assert!(problems.contains(&"BagIt 0.97 isn't accepted (only 1.0)".to_string()));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_preset_from_profile() {
+        let profile = Profile::parse(PROFILE).unwrap();
+        let mut options = BagOptions {
+            algorithms: vec![ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha512],
+            bagit_version: BagItVersion::V0_97,
+            ..BagOptions::default()
+        };
+        options.metadata.push("Contact-Name", "A. Archivist");
+        profile.preset(&mut options);
+
+        // The one allowed value is filled in, the other required field left blank,
+        // and Bagging-Date left to baggie
+        assert_eq!(
+            options.metadata.get("Source-Organization"),
+            Some("Example Archive")
+        );
+        assert_eq!(options.metadata.get("Contact-Email"), Some(""));
+        assert_eq!(options.metadata.get("Bagging-Date"), None);
+        assert_eq!(
+            options.algorithms,
+            [ChecksumAlgorithm::Sha256, ChecksumAlgorithm::Sha512]
+        );
+        assert_eq!(options.bagit_version, BagItVersion::V1_0);
+        assert_eq!(
+            profile.check_options(&options),
+            vec!["bag-info.txt's Contact-Email field is blank"]
+        );
+
+        let mut metadata = options.metadata.clone();
+        metadata
+            .fields_mut()
+            .retain(|(label, _)| label != "Contact-Email");
+        metadata.push("Contact-Email", "archivist@example.org");
+        assert!(profile.prefill(&mut metadata).is_empty());
+        options.metadata = metadata;
+        assert!(profile.check_options(&options).is_empty());
+
+        assert_eq!(
+            profile_name("https://example.org/profiles/grant.json?v=2"),
+            "grant"
+        );
+        assert_eq!(profile_name("C:\\Profiles\\my profile.json"), "my_profile");
+        assert_eq!(profile_name("https://example.org/"), "example.org");
+    }
+}
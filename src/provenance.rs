@@ -0,0 +1,581 @@
+use crate::bagit::{decode_manifest_path, encode_manifest_path, unsafe_manifest_path, BagOptions};
+use crate::inventory::csv_field;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use walkdir::WalkDir;
+
+/// Tag file recording where each payload file came from.
+pub const PROVENANCE_FILE: &str = "provenance.csv";
+
+/// Tag file recording each payload file's timestamps as they were before bagging.
+pub const FILE_METADATA_FILE: &str = "file-metadata.tsv";
+
+/// Tag file recording each payload file's extended attributes, Finder tags and labels
+/// among them on macOS, which bagging otherwise loses.
+pub const XATTRS_FILE: &str = "extended-attributes.tsv";
+
+/// Tag file recording each payload file's Unix mode, owner and group, which BagIt
+/// doesn't carry, so they can be put back with `restore_permissions`.
+pub const FILE_PERMISSIONS_FILE: &str = "file-permissions.tsv";
+
+/// A payload file's attributes where it was found, before bagging moved or copied it.
+#[derive(Debug, Clone)]
+pub struct Provenance {
+    /// Path within the source folder, and so under `data/`.
+    pub relative: PathBuf,
+    pub source_path: PathBuf,
+    pub bytes: u64,
+    /// Times the filesystem keeps; creation time isn't kept by every one.
+    pub modified: Option<SystemTime>,
+    pub accessed: Option<SystemTime>,
+    pub created: Option<SystemTime>,
+    /// Owning user's name, or numeric ID when it has no name; empty where there's no
+    /// notion of one.
+    pub owner: String,
+    /// Permission bits, user ID and group ID, on Unix.
+    pub permissions: Option<(u32, u32, u32)>,
+    /// Extended attributes by name, if they were asked for.
+    pub xattrs: Vec<(String, Vec<u8>)>,
+}
+
+/// Provenance gathered as the payload of `source` is walked, before each file is moved
+/// or copied.
+#[derive(Debug)]
+pub struct ProvenanceLog {
+    source: PathBuf,
+    root: PathBuf,
+    users: Vec<(u32, String)>,
+    /// Whether extended attributes are read, which takes a call per attribute.
+    read_xattrs: bool,
+    pub records: Vec<Provenance>,
+}
+
+impl ProvenanceLog {
+    /// A log for bagging `source`, if `options` asks for a tag file written from one.
+    pub fn wanted(source: &Path, options: &BagOptions) -> Option<Self> {
+        let wanted = options.record_provenance
+            || options.record_file_times
+            || options.record_permissions
+            || options.record_xattrs;
+        wanted.then(|| Self::new(source, options.record_xattrs))
+    }
+
+    fn new(source: &Path, read_xattrs: bool) -> Self {
+        Self {
+            source: source.to_path_buf(),
+            root: std::path::absolute(source).unwrap_or_else(|_| source.to_path_buf()),
+            users: user_names(),
+            read_xattrs,
+            records: Vec::new(),
+        }
+    }
+
+    /// Records `entry` if it's a file. Call before it's moved.
+    pub fn record(&mut self, entry: &walkdir::DirEntry) {
+        if entry.file_type().is_dir() {
+            return;
+        }
+        let (Ok(metadata), Ok(relative)) =
+            (entry.metadata(), entry.path().strip_prefix(&self.source))
+        else {
+            return;
+        };
+        let source_path = self.root.join(relative);
+        self.push(entry.path(), source_path, relative, &metadata);
+    }
+
+    /// Records the file at `path`, wherever it is, as going to `relative` under
+    /// `data/`.
+    pub fn record_file(&mut self, path: &Path, relative: &Path) {
+        if let Ok(metadata) = fs::metadata(path) {
+            let source_path = std::path::absolute(path).unwrap_or_else(|_| path.to_path_buf());
+            self.push(path, source_path, relative, &metadata);
+        }
+    }
+
+    fn push(
+        &mut self,
+        path: &Path,
+        source_path: PathBuf,
+        relative: &Path,
+        metadata: &fs::Metadata,
+    ) {
+        self.records.push(Provenance {
+            source_path,
+            relative: relative.to_path_buf(),
+            bytes: metadata.len(),
+            modified: metadata.modified().ok(),
+            accessed: metadata.accessed().ok(),
+            created: metadata.created().ok(),
+            owner: owner(metadata, &self.users),
+            permissions: permissions(metadata),
+            xattrs: match self.read_xattrs {
+                true => xattrs(path),
+                false => Vec::new(),
+            },
+        });
+    }
+
+    /// Records the files an interrupted run already moved into `moved_into`, which a
+    /// move leaves with the size, times and owner they had.
+    pub fn record_moved(&mut self, moved_into: &Path) {
+        let mut moved = ProvenanceLog::new(moved_into, self.read_xattrs);
+        for entry in WalkDir::new(moved_into)
+            .min_depth(1)
+            .into_iter()
+            .filter_map(|e| e.ok())
+        {
+            moved.record(&entry);
+        }
+        for record in moved.records {
+            self.records.push(Provenance {
+                source_path: self.root.join(&record.relative),
+                ..record
+            });
+        }
+    }
+}
+
+/// Writes the tag files `options` asks for from `records` into `bag`, and returns
+/// them.
+pub fn write_records(
+    bag: &Path,
+    records: &[Provenance],
+    options: &BagOptions,
+) -> io::Result<Vec<String>> {
+    let mut written = Vec::new();
+    if options.record_provenance {
+        written.push(write_provenance(bag, records)?);
+    }
+    if options.record_file_times {
+        written.push(write_file_metadata(bag, records)?);
+    }
+    if options.record_permissions {
+        written.push(write_file_permissions(bag, records)?);
+    }
+    if options.record_xattrs {
+        written.push(write_xattrs(bag, records)?);
+    }
+    Ok(written)
+}
+
+/// The records of files that made it into `data/`, with their `data/...` paths.
+fn bagged<'a>(
+    bag: &'a Path,
+    records: &'a [Provenance],
+) -> impl Iterator<Item = (String, &'a Provenance)> {
+    records
+        .iter()
+        .filter(|record| bag.join("data").join(&record.relative).is_file())
+        .map(|record| {
+            let file = record.relative.to_string_lossy().replace('\\', "/");
+            (format!("data/{}", file), record)
+        })
+}
+
+/// A time in UTC, RFC 3339, to `precision` (a chrono format such as `%S%.9f`); empty
+/// if there isn't one.
+fn timestamp(time: Option<SystemTime>, precision: &str) -> String {
+    time.map(|t| {
+        chrono::DateTime::<chrono::Utc>::from(t)
+            .format(&format!("%Y-%m-%dT%H:%M:{}Z", precision))
+            .to_string()
+    })
+    .unwrap_or_default()
+}
+
+/// Writes provenance.csv into `bag` for the files that made it into `data/`.
+fn write_provenance(bag: &Path, records: &[Provenance]) -> io::Result<String> {
+    let mut csv = String::from("file,source_path,bytes,modified,owner\n");
+    for (file, record) in bagged(bag, records) {
+        csv.push_str(&format!(
+            "{},{},{},{},{}\n",
+            csv_field(&file),
+            csv_field(&record.source_path.to_string_lossy()),
+            record.bytes,
+            timestamp(record.modified, "%S"),
+            csv_field(&record.owner)
+        ));
+    }
+    fs::write(bag.join(PROVENANCE_FILE), csv)?;
+    Ok(PROVENANCE_FILE.to_string())
+}
+
+/// A path as the .tsv tag files write it: percent-encoded like a BagIt 1.0 manifest's,
+/// and tabs as `%09`.
+fn encode_tsv_path(file: &str) -> String {
+    encode_manifest_path(file).replace('\t', "%09")
+}
+
+/// Reverses `encode_tsv_path`. A `%` of the name itself is `%25`, so any `%09` is a tab.
+fn decode_tsv_path(file: &str) -> String {
+    decode_manifest_path(&file.replace("%09", "\t"))
+}
+
+/// Writes file-metadata.tsv into `bag`: the modification, access and creation times of
+/// the files that made it into `data/`, to the nanosecond.
+fn write_file_metadata(bag: &Path, records: &[Provenance]) -> io::Result<String> {
+    let mut tsv = String::from("file\tmodified\taccessed\tcreated\n");
+    for (file, record) in bagged(bag, records) {
+        tsv.push_str(&format!(
+            "{}\t{}\t{}\t{}\n",
+            encode_tsv_path(&file),
+            timestamp(record.modified, "%S%.9f"),
+            timestamp(record.accessed, "%S%.9f"),
+            timestamp(record.created, "%S%.9f")
+        ));
+    }
+    fs::write(bag.join(FILE_METADATA_FILE), tsv)?;
+    Ok(FILE_METADATA_FILE.to_string())
+}
+
+/// Writes file-permissions.tsv into `bag`: the mode (in octal), user ID and group ID of
+/// the files that made it into `data/`. Where there are none to record, it's only the
+/// header.
+fn write_file_permissions(bag: &Path, records: &[Provenance]) -> io::Result<String> {
+    let mut tsv = String::from("file\tmode\tuid\tgid\n");
+    for (file, record) in bagged(bag, records) {
+        if let Some((mode, uid, gid)) = record.permissions {
+            tsv.push_str(&format!(
+                "{}\t{:04o}\t{}\t{}\n",
+                encode_tsv_path(&file),
+                mode,
+                uid,
+                gid
+            ));
+        }
+    }
+    fs::write(bag.join(FILE_PERMISSIONS_FILE), tsv)?;
+    Ok(FILE_PERMISSIONS_FILE.to_string())
+}
+
+/// Writes extended-attributes.tsv into `bag`: a line per extended attribute of the
+/// files that made it into `data/`, its value in hex as it may be binary (Finder tags
+/// are a binary property list).
+fn write_xattrs(bag: &Path, records: &[Provenance]) -> io::Result<String> {
+    let mut tsv = String::from("file\tattribute\tvalue\n");
+    for (file, record) in bagged(bag, records) {
+        for (name, value) in &record.xattrs {
+            let hex: String = value.iter().map(|b| format!("{:02x}", b)).collect();
+            tsv.push_str(&format!(
+                "{}\t{}\t{}\n",
+                encode_tsv_path(&file),
+                encode_tsv_path(name),
+                hex
+            ));
+        }
+    }
+    fs::write(bag.join(XATTRS_FILE), tsv)?;
+    Ok(XATTRS_FILE.to_string())
+}
+
+/// What `restore_permissions` put back.
+#[derive(Debug, Default, PartialEq, Eq)]
+pub struct Restored {
+    /// Files given their recorded mode.
+    pub files: usize,
+    /// Files whose owner or group couldn't be set back, as only root can give a file
+    /// away.
+    pub owners_kept: usize,
+}
+
+/// Gives the payload files of `bag` the mode, owner and group its file-permissions.tsv
+/// recorded, as after the bag is copied to another server or extracted from an archive.
+/// Lines naming files that aren't there, or paths outside `data/`, are passed over.
+pub fn restore_permissions(bag: &Path) -> io::Result<Restored> {
+    let mut restored = Restored::default();
+    // Windows has no modes or numeric owners to put back
+    if cfg!(not(unix)) {
+        return Ok(restored);
+    }
+    let tsv = match fs::read_to_string(bag.join(FILE_PERMISSIONS_FILE)) {
+        Ok(tsv) => tsv,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(restored),
+        Err(e) => return Err(e),
+    };
+    for line in tsv.lines().skip(1) {
+        let fields: Vec<&str> = line.split('\t').collect();
+        let [file, mode, uid, gid] = fields[..] else {
+            continue;
+        };
+        let file = decode_tsv_path(file);
+        let (Ok(mode), Ok(uid), Ok(gid)) = (u32::from_str_radix(mode, 8), uid.parse(), gid.parse())
+        else {
+            continue;
+        };
+        let path = bag.join(&file);
+        if unsafe_manifest_path(&file, true).is_some() || !path.is_file() {
+            continue;
+        }
+        match set_owner(&path, uid, gid) {
+            Err(e) if e.kind() == io::ErrorKind::PermissionDenied => restored.owners_kept += 1,
+            result => result?,
+        }
+        // After the owner, as changing it can clear the setuid and setgid bits
+        set_mode(&path, mode)?;
+        restored.files += 1;
+    }
+    Ok(restored)
+}
+
+/// User names by ID, from /etc/passwd.
+#[cfg(unix)]
+fn user_names() -> Vec<(u32, String)> {
+    fs::read_to_string("/etc/passwd")
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split(':');
+            let name = fields.next()?;
+            let uid = fields.nth(1)?.parse().ok()?;
+            Some((uid, name.to_string()))
+        })
+        .collect()
+}
+
+#[cfg(not(unix))]
+fn user_names() -> Vec<(u32, String)> {
+    Vec::new()
+}
+
+#[cfg(unix)]
+fn owner(metadata: &fs::Metadata, users: &[(u32, String)]) -> String {
+    use std::os::unix::fs::MetadataExt;
+    let uid = metadata.uid();
+    users
+        .iter()
+        .find(|(id, _)| *id == uid)
+        .map_or_else(|| uid.to_string(), |(_, name)| name.clone())
+}
+
+#[cfg(not(unix))]
+fn owner(_metadata: &fs::Metadata, _users: &[(u32, String)]) -> String {
+    String::new()
+}
+
+#[cfg(unix)]
+fn permissions(metadata: &fs::Metadata) -> Option<(u32, u32, u32)> {
+    use std::os::unix::fs::MetadataExt;
+    Some((metadata.mode() & 0o7777, metadata.uid(), metadata.gid()))
+}
+
+#[cfg(not(unix))]
+fn permissions(_metadata: &fs::Metadata) -> Option<(u32, u32, u32)> {
+    None
+}
+
+/// A file's extended attributes, by name. One that can't be read is left out, as is
+/// everything on a filesystem without them.
+#[cfg(unix)]
+fn xattrs(path: &Path) -> Vec<(String, Vec<u8>)> {
+    let Ok(names) = xattr::list(path) else {
+        return Vec::new();
+    };
+    let mut xattrs: Vec<(String, Vec<u8>)> = names
+        .filter_map(|name| {
+            let value = xattr::get(path, &name).ok()??;
+            Some((name.to_string_lossy().to_string(), value))
+        })
+        .collect();
+    xattrs.sort();
+    xattrs
+}
+
+#[cfg(not(unix))]
+fn xattrs(_path: &Path) -> Vec<(String, Vec<u8>)> {
+    Vec::new()
+}
+
+#[cfg(unix)]
+fn set_owner(path: &Path, uid: u32, gid: u32) -> io::Result<()> {
+    std::os::unix::fs::chown(path, Some(uid), Some(gid))
+}
+
+#[cfg(not(unix))]
+fn set_owner(_path: &Path, _uid: u32, _gid: u32) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(unix)]
+fn set_mode(path: &Path, mode: u32) -> io::Result<()> {
+    use std::os::unix::fs::PermissionsExt;
+    fs::set_permissions(path, fs::Permissions::from_mode(mode))
+}
+
+#[cfg(not(unix))]
+fn set_mode(_path: &Path, _mode: u32) -> io::Result<()> {
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{
+        bag_directory, bag_into, unbag_directory, validate_bag, BagOptions, ValidationOptions,
+    };
+
+    #[test]
+    fn test_provenance_file() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_provenance");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("donation");
+        fs::create_dir_all(source.join("photos")).unwrap();
+        fs::write(source.join("photos/beach, 1972.jpg"), "jpeg").unwrap();
+        fs::write(source.join("Thumbs.db"), "skipped").unwrap();
+        let options = BagOptions {
+            record_provenance: true,
+            ..BagOptions::default()
+        };
+
+        let copy = temp_dir.join("copy");
+        bag_into(&source, &copy, &options, None).unwrap();
+        let report = validate_bag(&copy, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        let tagmanifest = fs::read_to_string(copy.join("tagmanifest-sha256.txt")).unwrap();
+        assert!(tagmanifest.contains("  provenance.csv"));
+
+        let csv = fs::read_to_string(copy.join(PROVENANCE_FILE)).unwrap();
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        let original = std::path::absolute(source.join("photos/beach, 1972.jpg")).unwrap();
+        assert!(lines[1].starts_with(&format!(
+            "\"data/photos/beach, 1972.jpg\",\"{}\",4,",
+            original.display()
+        )));
+        assert!(lines[1].contains("Z,"));
+
+        // Bagging in place records where the files were before they moved
+        bag_directory(&source, &options, None).unwrap();
+        let csv = fs::read_to_string(source.join(PROVENANCE_FILE)).unwrap();
+        assert!(csv.contains(&original.display().to_string()));
+        assert!(!csv.contains("Thumbs.db"));
+
+        // Bags made without it don't get one
+        let plain = temp_dir.join("plain");
+        bag_into(&copy.join("data"), &plain, &BagOptions::default(), None).unwrap();
+        assert!(!plain.join(PROVENANCE_FILE).exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_restore_permissions() {
+        use std::os::unix::fs::{MetadataExt, PermissionsExt};
+        let temp_dir = std::env::temp_dir().join("bagit_test_permissions");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("backup");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("secret.key"), "key").unwrap();
+        let mode = |path: &Path| fs::metadata(path).unwrap().mode() & 0o7777;
+        let set_mode = |path: &Path, mode| {
+            fs::set_permissions(path, fs::Permissions::from_mode(mode)).unwrap()
+        };
+        set_mode(&source.join("secret.key"), 0o640);
+        let uid = fs::metadata(source.join("secret.key")).unwrap().uid();
+        let options = BagOptions {
+            record_permissions: true,
+            ..BagOptions::default()
+        };
+
+        // A copy whose files lost their modes gets them back
+        let copy = temp_dir.join("copy");
+        bag_into(&source, &copy, &options, None).unwrap();
+        let tsv = fs::read_to_string(copy.join(FILE_PERMISSIONS_FILE)).unwrap();
+        assert!(tsv.contains(&format!("data/secret.key\t0640\t{}\t", uid)));
+        set_mode(&copy.join("data/secret.key"), 0o666);
+        let restored = restore_permissions(&copy).unwrap();
+        assert_eq!(restored.files, 1);
+        assert_eq!(mode(&copy.join("data/secret.key")), 0o640);
+        let report = validate_bag(&copy, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        // Unbagging puts them back too, and removes the record
+        bag_directory(&source, &options, None).unwrap();
+        set_mode(&source.join("data/secret.key"), 0o600);
+        unbag_directory(&source).unwrap();
+        assert_eq!(mode(&source.join("secret.key")), 0o640);
+        assert!(!source.join(FILE_PERMISSIONS_FILE).exists());
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_xattrs_file() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_xattrs");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("collection");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("tagged.txt"), "text").unwrap();
+        fs::write(source.join("plain.txt"), "text").unwrap();
+        if xattr::set(source.join("tagged.txt"), "user.xdg.tags", b"red").is_err() {
+            // Nothing to record where the filesystem has no extended attributes
+            fs::remove_dir_all(&temp_dir).unwrap();
+            return;
+        }
+        let options = BagOptions {
+            record_xattrs: true,
+            ..BagOptions::default()
+        };
+
+        let copy = temp_dir.join("copy");
+        bag_into(&source, &copy, &options, None).unwrap();
+        let tsv = fs::read_to_string(copy.join(XATTRS_FILE)).unwrap();
+        assert_eq!(
+            tsv,
+            "file\tattribute\tvalue\ndata/tagged.txt\tuser.xdg.tags\t726564\n"
+        );
+        let report = validate_bag(&copy, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_metadata() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_file_metadata");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let source = temp_dir.join("donation");
+        fs::create_dir_all(&source).unwrap();
+        fs::write(source.join("tab\there.txt"), "text").unwrap();
+        let modified = SystemTime::UNIX_EPOCH + std::time::Duration::new(86_400, 123_456_789);
+        let file = fs::File::options()
+            .write(true)
+            .open(source.join("tab\there.txt"))
+            .unwrap();
+        file.set_modified(modified).unwrap();
+        drop(file);
+        let options = BagOptions {
+            record_file_times: true,
+            ..BagOptions::default()
+        };
+
+        let copy = temp_dir.join("copy");
+        bag_into(&source, &copy, &options, None).unwrap();
+        assert!(!copy.join(PROVENANCE_FILE).exists());
+        let tsv = fs::read_to_string(copy.join(FILE_METADATA_FILE)).unwrap();
+        let lines: Vec<&str> = tsv.lines().collect();
+        assert_eq!(lines[0], "file\tmodified\taccessed\tcreated");
+        assert!(lines[1].starts_with("data/tab%09here.txt\t1970-01-02T00:00:00.123456789Z\t"));
+        // The copy keeps the original's modification time
+        let copied = fs::metadata(copy.join("data/tab\there.txt")).unwrap();
+        assert_eq!(copied.modified().unwrap(), modified);
+        let tagmanifest = fs::read_to_string(copy.join("tagmanifest-sha256.txt")).unwrap();
+        assert!(tagmanifest.contains("  file-metadata.tsv"));
+        let report = validate_bag(&copy, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,142 @@
+use crate::bagit::{bag_algorithm, calculate_digest, BagError, BagReport, BagSummary};
+use crate::tagfile::{read_tag_file, BagInfo};
+use std::path::Path;
+
+/// Escapes text for HTML element content and attribute values.
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Who is handing the bag over: `Contact-Name` from bag-info.txt, else the OS user.
+fn operator(bag_info: &BagInfo) -> String {
+    bag_info
+        .get("Contact-Name")
+        .map(String::from)
+        .or_else(|| std::env::var("USER").ok())
+        .or_else(|| std::env::var("USERNAME").ok())
+        .unwrap_or_default()
+}
+
+/// Renders a transfer receipt for the bag at `path` as a standalone HTML page.
+///
+/// HTML rather than PDF so it prints from any browser (and "Save as PDF" covers the
+/// rest) without a PDF library. The signature lines are for when media changes hands.
+pub fn receipt_html(path: &Path, report: &BagReport) -> Result<String, BagError> {
+    let summary = BagSummary::read(path)?;
+    let (bag_info, _) = read_tag_file(&path.join("bag-info.txt"))?;
+    let bag_info = BagInfo::parse(&bag_info);
+    let algorithm = bag_algorithm(path).unwrap_or_default();
+    let tagmanifest_name = algorithm.tagmanifest_name();
+    let (tagmanifest, _) = read_tag_file(&path.join(&tagmanifest_name))?;
+
+    let date = match (bag_info.get("Bagging-Date"), bag_info.get("Bagging-Time")) {
+        (Some(date), Some(time)) => format!("{} {}", date, time),
+        (Some(date), None) => date.to_string(),
+        _ => String::new(),
+    };
+    let mut rows = vec![
+        ("Identifier", summary.identifier.clone()),
+        ("Bagging date", date),
+        ("Operator", operator(&bag_info)),
+        ("Payload-Oxum", summary.payload_oxum.clone()),
+        ("Algorithm", algorithm.label().to_string()),
+        ("Location", path.display().to_string()),
+    ];
+    if let Some(count) = bag_info.get("Bag-Count") {
+        rows.push(("Bag-Count", count.to_string()));
+    }
+
+    let mut checksums: Vec<(String, String)> = tagmanifest
+        .lines()
+        .filter_map(|line| line.split_once(char::is_whitespace))
+        .map(|(digest, file)| (file.trim_start().to_string(), digest.to_string()))
+        .collect();
+    let tagmanifest_checksum = calculate_digest(&path.join(&tagmanifest_name), algorithm)?;
+    checksums.push((tagmanifest_name, tagmanifest_checksum));
+
+    let mut warnings = report.warnings.clone();
+    if !report.skipped.is_empty() {
+        warnings.push(format!(
+            "{} system files were left out of the payload",
+            report.skipped.len()
+        ));
+    }
+    for vanished in &report.vanished {
+        warnings.push(format!("Vanished during bagging: {}", vanished.display()));
+    }
+
+    let mut html = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n<title>Transfer receipt: {}</title>\n",
+        escape(&summary.identifier)
+    );
+    html.push_str(
+        "<style>\nbody { font-family: sans-serif; max-width: 48em; margin: 2em auto; }\n\
+         th { text-align: left; padding-right: 1em; }\n\
+         td.digest { font-family: monospace; word-break: break-all; }\n\
+         .signature { margin-top: 3em; border-top: 1px solid #000; width: 20em; }\n\
+         </style>\n</head>\n<body>\n<h1>Transfer receipt</h1>\n<table>\n",
+    );
+    for (label, value) in &rows {
+        html.push_str(&format!(
+            "<tr><th>{}</th><td>{}</td></tr>\n",
+            label,
+            escape(value)
+        ));
+    }
+    html.push_str("</table>\n<h2>Top-level checksums (SHA-256)</h2>\n<table>\n");
+    for (file, digest) in &checksums {
+        html.push_str(&format!(
+            "<tr><th>{}</th><td class=\"digest\">{}</td></tr>\n",
+            escape(file),
+            escape(digest)
+        ));
+    }
+    html.push_str("</table>\n");
+    if !warnings.is_empty() {
+        html.push_str("<h2>Warnings</h2>\n<ul>\n");
+        for warning in &warnings {
+            html.push_str(&format!("<li>{}</li>\n", escape(warning)));
+        }
+        html.push_str("</ul>\n");
+    }
+    html.push_str(
+        "<p class=\"signature\">Handed over by (name, signature, date)</p>\n\
+         <p class=\"signature\">Received by (name, signature, date)</p>\n</body>\n</html>\n",
+    );
+    Ok(html)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{bag_directory, BagOptions};
+    use std::fs;
+
+    #[test]
+    fn test_receipt_lists_bag_facts() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_receipt");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir(&temp_dir).unwrap();
+        fs::write(temp_dir.join("file.txt"), "content").unwrap();
+        let mut options = BagOptions::default();
+        options.metadata.push("Contact-Name", "Ada <Archivist>");
+        let mut report = bag_directory(&temp_dir, &options, None).unwrap();
+        report.warnings.push("Something odd".to_string());
+
+        let html = receipt_html(&temp_dir, &report).unwrap();
+        let summary = BagSummary::read(&temp_dir).unwrap();
+        assert!(html.contains("<td>bagit_test_receipt</td>"));
+        assert!(html.contains("<td>7.1</td>"));
+        assert!(html.contains("Ada &lt;Archivist&gt;"));
+        assert!(html.contains(&summary.tagmanifest_sha256));
+        assert!(html.contains("<th>manifest-sha256.txt</th>"));
+        assert!(html.contains("<li>Something odd</li>"));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,664 @@
+use crate::archive::{SerialFormat, SerialOptions};
+use crate::bagit::{
+    bag_name, copy_destination, default_skip_names, BagItVersion, BagOptions, CaseCollisionPolicy,
+    ChecksumAlgorithm, DateMode, DedupMode, EmptyDirPolicy, EmptySourcePolicy, ErrorPolicy,
+    IdScheme, ValidationMode, ValidationOptions, DEFAULT_MAX_DEPTH, DEFAULT_NAME_TEMPLATE,
+};
+use crate::encrypt::{Encryption, EncryptionTool};
+use crate::inventory::Inventory;
+use crate::locale::Locale;
+use crate::plugin::Plugin;
+use crate::profile::Profile;
+use crate::signature::{Signing, SigningTool};
+use crate::tagfile::BagInfo;
+use crate::tape::parse_size;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// User preferences, persisted as `Key: Value` lines (the same shape as bag-info.txt).
+#[derive(Debug, Clone)]
+pub struct Settings {
+    pub date_mode: DateMode,
+    pub include_bagging_time: bool,
+    pub skip_names: Vec<String>,
+    pub skip_appledouble: bool,
+    pub strip_quarantine: bool,
+    /// Whether copies are made as copy-on-write clones where the filesystem can.
+    pub clone_files: bool,
+    /// Whether copies bag what symlinks point at.
+    pub follow_symlinks: bool,
+    /// Folders deep a payload may nest.
+    pub max_depth: usize,
+    pub error_policy: ErrorPolicy,
+    pub empty_source: EmptySourcePolicy,
+    pub empty_dirs: EmptyDirPolicy,
+    pub case_collisions: CaseCollisionPolicy,
+    pub validation_mode: ValidationMode,
+    /// Whether new bags are checked against the inventoried ones for files already stored.
+    pub dedup: DedupMode,
+    /// How new bags get their `Internal-Sender-Identifier`.
+    pub id_scheme: IdScheme,
+    /// Whether new bags get provenance.csv.
+    pub record_provenance: bool,
+    /// Whether new bags get file-metadata.tsv.
+    pub record_file_times: bool,
+    /// Whether new bags get file-permissions.tsv.
+    pub record_permissions: bool,
+    /// Whether new bags get extended-attributes.tsv.
+    pub record_xattrs: bool,
+    /// Whether new bags keep events.csv.
+    pub event_log: bool,
+    /// Algorithms new bags' manifests are written in.
+    pub algorithms: Vec<ChecksumAlgorithm>,
+    /// Version new bags declare.
+    pub bagit_version: BagItVersion,
+    /// Saved BagIt Profile new bags are made under; empty for none.
+    pub bag_profile: String,
+    /// Format bags are serialized in.
+    pub serial_format: SerialFormat,
+    /// zstd level for `.tar.zst` bags, 1 to 22.
+    pub zstd_level: i32,
+    /// Threads compressing a `.tar.zst` bag.
+    pub compression_threads: usize,
+    /// Whether serialized bags are byte-identical from one run to the next.
+    pub reproducible_archives: bool,
+    /// What serialized bags are encrypted with, if anything.
+    pub encryption_tool: Option<EncryptionTool>,
+    /// Who serialized bags are encrypted for: age public keys or GnuPG key IDs.
+    pub encryption_recipients: Vec<String>,
+    /// What new bags' tag manifests are signed with, if anything.
+    pub signing_tool: Option<SigningTool>,
+    /// See `Signing::key`.
+    pub signing_key: String,
+    /// minisign public key file signatures are checked against.
+    pub minisign_public_key: Option<PathBuf>,
+    /// Bytes per volume serialized bags are split into; `None` keeps them whole.
+    pub volume_size: Option<u64>,
+    /// Whether folders are bagged as a copy next to them rather than in place.
+    pub bag_copy: bool,
+    /// What copied bags are called; see `bag_name`.
+    pub name_template: String,
+    /// Where copied bags go; next to the folder when unset.
+    pub output_dir: Option<PathBuf>,
+    /// Days between background fixity checks of inventoried bags; 0 turns them off.
+    pub audit_interval_days: u32,
+    /// Percent of payload files background fixity checks hash; 0 hashes them all.
+    pub audit_sample_percent: u32,
+    /// Jobs (the user's own plus background fixity checks) allowed to run at once.
+    pub max_jobs: usize,
+    /// Files each job hashes at once.
+    pub hash_threads: usize,
+    /// Whether new bags are read back and checked against their manifests before
+    /// they're sealed.
+    pub verify_after: bool,
+    /// How the UI writes numbers and dates.
+    pub locale: Locale,
+    /// bag-info.txt fields added to every bag; labels may repeat.
+    pub metadata: BagInfo,
+    pub plugins: Vec<Plugin>,
+    /// `host:port` of the agent that does remote jobs; empty until one is used.
+    pub agent_address: String,
+    /// Shared secret the agent expects. Never exported in settings bundles.
+    pub agent_token: String,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            date_mode: DateMode::default(),
+            include_bagging_time: false,
+            skip_names: default_skip_names(),
+            skip_appledouble: true,
+            strip_quarantine: false,
+            clone_files: true,
+            follow_symlinks: false,
+            max_depth: DEFAULT_MAX_DEPTH,
+            error_policy: ErrorPolicy::default(),
+            empty_source: EmptySourcePolicy::default(),
+            empty_dirs: EmptyDirPolicy::default(),
+            case_collisions: CaseCollisionPolicy::default(),
+            validation_mode: ValidationMode::default(),
+            dedup: DedupMode::default(),
+            id_scheme: IdScheme::default(),
+            record_provenance: false,
+            record_file_times: false,
+            record_permissions: false,
+            record_xattrs: false,
+            event_log: false,
+            algorithms: vec![ChecksumAlgorithm::default()],
+            bagit_version: BagItVersion::default(),
+            bag_profile: String::new(),
+            serial_format: SerialFormat::Tar,
+            zstd_level: zstd::DEFAULT_COMPRESSION_LEVEL,
+            compression_threads: default_compression_threads(),
+            reproducible_archives: false,
+            encryption_tool: None,
+            encryption_recipients: Vec::new(),
+            signing_tool: None,
+            signing_key: String::new(),
+            minisign_public_key: None,
+            volume_size: None,
+            bag_copy: false,
+            name_template: DEFAULT_NAME_TEMPLATE.to_string(),
+            output_dir: None,
+            audit_interval_days: 90,
+            audit_sample_percent: 0,
+            max_jobs: 2,
+            hash_threads: default_hash_threads(),
+            verify_after: false,
+            locale: Locale::default(),
+            metadata: BagInfo::default(),
+            plugins: Vec::new(),
+            agent_address: String::new(),
+            agent_token: String::new(),
+        }
+    }
+}
+
+pub fn config_dir() -> Option<PathBuf> {
+    if cfg!(windows) {
+        std::env::var_os("APPDATA").map(PathBuf::from)
+    } else if cfg!(target_os = "macos") {
+        std::env::var_os("HOME").map(|h| PathBuf::from(h).join("Library/Application Support"))
+    } else {
+        std::env::var_os("XDG_CONFIG_HOME")
+            .map(PathBuf::from)
+            .or_else(|| std::env::var_os("HOME").map(|h| PathBuf::from(h).join(".config")))
+    }
+}
+
+/// One thread per core, up to 4: past that, disks rather than CPUs are the limit.
+fn default_hash_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get().min(4))
+        .unwrap_or(1)
+}
+
+/// One thread per core: compressing is bound by the CPU, not the disk.
+fn default_compression_threads() -> usize {
+    std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1)
+}
+
+/// First line of an exported bundle, so importing some other file fails loudly.
+const BUNDLE_HEADER: &str = "Baggie-Settings-Bundle: 1";
+
+/// Keys a bundle leaves out: they suit one machine or one person, not a whole
+/// institution, name something saved only on this machine (the bag profile), or (the
+/// token) are secret.
+const WORKSTATION_KEYS: [&str; 9] = [
+    "Max-Jobs",
+    "Hash-Threads",
+    "Compression-Threads",
+    "Locale",
+    "Output-Folder",
+    "Agent-Token",
+    "Bag-Profile",
+    "Signing-Key",
+    "Minisign-Public-Key",
+];
+
+pub fn settings_path() -> Option<PathBuf> {
+    config_dir().map(|d| d.join("baggie").join("settings.txt"))
+}
+
+impl Settings {
+    /// Loads saved settings, falling back to defaults for anything missing or unreadable.
+    pub fn load() -> Self {
+        settings_path()
+            .and_then(|p| fs::read_to_string(p).ok())
+            .map(|content| Self::parse(&content))
+            .unwrap_or_default()
+    }
+
+    pub fn save(&self) -> std::io::Result<()> {
+        let Some(path) = settings_path() else {
+            return Ok(());
+        };
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(path, self.serialize())
+    }
+
+    pub fn parse(content: &str) -> Self {
+        let mut settings = Settings::default();
+        for line in content.lines() {
+            let Some((key, value)) = line.split_once(':') else {
+                continue;
+            };
+            let value = value.trim();
+            match key.trim() {
+                "Date-Mode" => {
+                    settings.date_mode = match value {
+                        "utc" => DateMode::Utc,
+                        _ => DateMode::Local,
+                    }
+                }
+                "Bagging-Time" => settings.include_bagging_time = value == "true",
+                // `/` can't appear in a file name on any OS, so it separates the list
+                "Skip-Names" => {
+                    settings.skip_names = value
+                        .split('/')
+                        .map(str::trim)
+                        .filter(|s| !s.is_empty())
+                        .map(String::from)
+                        .collect()
+                }
+                "Skip-AppleDouble" => settings.skip_appledouble = value == "true",
+                "Strip-Quarantine" => settings.strip_quarantine = value == "true",
+                "Clone-Files" => settings.clone_files = value == "true",
+                "Follow-Symlinks" => settings.follow_symlinks = value == "true",
+                "Max-Depth" => {
+                    settings.max_depth = value.parse().unwrap_or(DEFAULT_MAX_DEPTH).max(1)
+                }
+                "On-Error" => {
+                    settings.error_policy = match value {
+                        "skip" => ErrorPolicy::Skip,
+                        "retry" => ErrorPolicy::Retry,
+                        _ => ErrorPolicy::Abort,
+                    }
+                }
+                "Empty-Source" => {
+                    settings.empty_source = match value {
+                        "allow" => EmptySourcePolicy::Allow,
+                        _ => EmptySourcePolicy::Refuse,
+                    }
+                }
+                "Empty-Folders" => {
+                    settings.empty_dirs = match value {
+                        "keep-file" => EmptyDirPolicy::Placeholder,
+                        "record" => EmptyDirPolicy::Record,
+                        _ => EmptyDirPolicy::Warn,
+                    }
+                }
+                "Case-Collisions" => {
+                    settings.case_collisions = match value {
+                        "refuse" => CaseCollisionPolicy::Refuse,
+                        _ => CaseCollisionPolicy::Warn,
+                    }
+                }
+                "Validation-Mode" => {
+                    settings.validation_mode = match value {
+                        "strict" => ValidationMode::Strict,
+                        _ => ValidationMode::Lenient,
+                    }
+                }
+                "Dedup" => {
+                    settings.dedup = match value {
+                        "report" => DedupMode::Report,
+                        "fetch" => DedupMode::Fetch,
+                        _ => DedupMode::Off,
+                    }
+                }
+                "Id-Scheme" => {
+                    settings.id_scheme = match value {
+                        "ulid" => IdScheme::Ulid,
+                        "sequence" => IdScheme::Sequence(String::new()),
+                        _ => IdScheme::Uuid,
+                    }
+                }
+                // Written after Id-Scheme, so the scheme is already a sequence
+                "Id-Prefix" => {
+                    if let IdScheme::Sequence(prefix) = &mut settings.id_scheme {
+                        *prefix = value.to_string();
+                    }
+                }
+                "Provenance" => settings.record_provenance = value == "true",
+                "File-Times" => settings.record_file_times = value == "true",
+                "Permissions" => settings.record_permissions = value == "true",
+                "Extended-Attributes" => settings.record_xattrs = value == "true",
+                "Event-Log" => settings.event_log = value == "true",
+                "Checksum-Algorithm" => {
+                    settings.algorithms = value
+                        .split('/')
+                        .filter_map(ChecksumAlgorithm::from_name)
+                        .collect()
+                }
+                "BagIt-Version" => {
+                    settings.bagit_version = BagItVersion::parse(value).unwrap_or_default()
+                }
+                "Bag-Profile" => settings.bag_profile = value.to_string(),
+                "Serial-Format" => {
+                    settings.serial_format = SerialFormat::parse(value).unwrap_or(SerialFormat::Tar)
+                }
+                "Zstd-Level" => {
+                    settings.zstd_level = value
+                        .parse()
+                        .unwrap_or(zstd::DEFAULT_COMPRESSION_LEVEL)
+                        .clamp(1, 22)
+                }
+                "Compression-Threads" => {
+                    settings.compression_threads = value
+                        .parse()
+                        .unwrap_or_else(|_| default_compression_threads())
+                        .max(1)
+                }
+                "Reproducible-Archives" => settings.reproducible_archives = value == "true",
+                "Encryption" => settings.encryption_tool = EncryptionTool::parse(value),
+                "Signing" => settings.signing_tool = SigningTool::parse(value),
+                "Signing-Key" => settings.signing_key = value.to_string(),
+                "Minisign-Public-Key" if !value.is_empty() => {
+                    settings.minisign_public_key = Some(PathBuf::from(value))
+                }
+                "Volume-Size" => settings.volume_size = parse_size(value).filter(|&s| s > 0),
+                "Encryption-Recipient" if !value.is_empty() => {
+                    settings.encryption_recipients.push(value.to_string())
+                }
+                "Bag-Copy" => settings.bag_copy = value == "true",
+                "Name-Template" if !value.is_empty() => settings.name_template = value.to_string(),
+                "Output-Folder" if !value.is_empty() => {
+                    settings.output_dir = Some(PathBuf::from(value))
+                }
+                "Audit-Interval-Days" => settings.audit_interval_days = value.parse().unwrap_or(90),
+                "Audit-Sample-Percent" => {
+                    settings.audit_sample_percent = value.parse().unwrap_or(0).min(100)
+                }
+                "Max-Jobs" => settings.max_jobs = value.parse().unwrap_or(2).max(1),
+                "Verify-After-Bagging" => settings.verify_after = value == "true",
+                "Hash-Threads" => {
+                    settings.hash_threads = value
+                        .parse()
+                        .unwrap_or_else(|_| default_hash_threads())
+                        .max(1)
+                }
+                "Locale" => settings.locale = Locale::from_code(value),
+                "Agent-Address" => settings.agent_address = value.to_string(),
+                "Agent-Token" => settings.agent_token = value.to_string(),
+                // Stored as `Metadata: Label: value`, one line per field, in order
+                "Metadata" => {
+                    if let Some((label, field_value)) = value.split_once(':') {
+                        settings.metadata.push(label.trim(), field_value.trim());
+                    }
+                }
+                // Stored as `Plugin: stage: command`, one line per plugin, in run order
+                "Plugin" => settings.plugins.extend(Plugin::parse(value)),
+                _ => {}
+            }
+        }
+        settings
+    }
+
+    pub fn serialize(&self) -> String {
+        let date_mode = match self.date_mode {
+            DateMode::Local => "local",
+            DateMode::Utc => "utc",
+        };
+        let error_policy = match self.error_policy {
+            ErrorPolicy::Abort => "abort",
+            ErrorPolicy::Skip => "skip",
+            ErrorPolicy::Retry => "retry",
+        };
+        let empty_source = match self.empty_source {
+            EmptySourcePolicy::Refuse => "refuse",
+            EmptySourcePolicy::Allow => "allow",
+        };
+        let empty_dirs = match self.empty_dirs {
+            EmptyDirPolicy::Warn => "warn",
+            EmptyDirPolicy::Placeholder => "keep-file",
+            EmptyDirPolicy::Record => "record",
+        };
+        let case_collisions = match self.case_collisions {
+            CaseCollisionPolicy::Warn => "warn",
+            CaseCollisionPolicy::Refuse => "refuse",
+        };
+        let validation_mode = match self.validation_mode {
+            ValidationMode::Strict => "strict",
+            ValidationMode::Lenient => "lenient",
+        };
+        let dedup = match self.dedup {
+            DedupMode::Off => "off",
+            DedupMode::Report => "report",
+            DedupMode::Fetch => "fetch",
+        };
+        let (id_scheme, id_prefix) = match &self.id_scheme {
+            IdScheme::Uuid => ("uuid", ""),
+            IdScheme::Ulid => ("ulid", ""),
+            IdScheme::Sequence(prefix) => ("sequence", prefix.as_str()),
+        };
+        let mut content = format!(
+            "Date-Mode: {}\nBagging-Time: {}\nSkip-Names: {}\nSkip-AppleDouble: {}\nStrip-Quarantine: {}\nClone-Files: {}\nFollow-Symlinks: {}\nMax-Depth: {}\nOn-Error: {}\nEmpty-Source: {}\nEmpty-Folders: {}\nCase-Collisions: {}\nValidation-Mode: {}\nDedup: {}\nId-Scheme: {}\nId-Prefix: {}\nProvenance: {}\nFile-Times: {}\nPermissions: {}\nExtended-Attributes: {}\nEvent-Log: {}\nChecksum-Algorithm: {}\nBagIt-Version: {}\nBag-Profile: {}\nSerial-Format: {}\nZstd-Level: {}\nCompression-Threads: {}\nReproducible-Archives: {}\nEncryption: {}\nSigning: {}\nSigning-Key: {}\nMinisign-Public-Key: {}\nVolume-Size: {}\nBag-Copy: {}\nName-Template: {}\nOutput-Folder: {}\nAudit-Interval-Days: {}\nAudit-Sample-Percent: {}\nMax-Jobs: {}\nHash-Threads: {}\nVerify-After-Bagging: {}\nLocale: {}\nAgent-Address: {}\nAgent-Token: {}\n",
+            date_mode,
+            self.include_bagging_time,
+            self.skip_names.join("/"),
+            self.skip_appledouble,
+            self.strip_quarantine,
+            self.clone_files,
+            self.follow_symlinks,
+            self.max_depth,
+            error_policy,
+            empty_source,
+            empty_dirs,
+            case_collisions,
+            validation_mode,
+            dedup,
+            id_scheme,
+            id_prefix,
+            self.record_provenance,
+            self.record_file_times,
+            self.record_permissions,
+            self.record_xattrs,
+            self.event_log,
+            self.algorithms
+                .iter()
+                .map(|a| a.name())
+                .collect::<Vec<_>>()
+                .join("/"),
+            self.bagit_version.as_str(),
+            self.bag_profile,
+            self.serial_format.extension(),
+            self.zstd_level,
+            self.compression_threads,
+            self.reproducible_archives,
+            self.encryption_tool.map_or("off", EncryptionTool::name),
+            self.signing_tool.map_or("off", SigningTool::name),
+            self.signing_key,
+            self.minisign_public_key.as_ref().map(|k| k.display().to_string()).unwrap_or_default(),
+            self.volume_size.map(|s| s.to_string()).unwrap_or_default(),
+            self.bag_copy,
+            self.name_template,
+            self.output_dir.as_ref().map(|d| d.display().to_string()).unwrap_or_default(),
+            self.audit_interval_days,
+            self.audit_sample_percent,
+            self.max_jobs,
+            self.hash_threads,
+            self.verify_after,
+            self.locale.code(),
+            self.agent_address,
+            self.agent_token
+        );
+        for (label, value) in self.metadata.fields() {
+            content.push_str(&format!("Metadata: {}: {}\n", label, value));
+        }
+        for plugin in &self.plugins {
+            content.push_str(&format!("Plugin: {}\n", plugin.to_line()));
+        }
+        for recipient in &self.encryption_recipients {
+            content.push_str(&format!("Encryption-Recipient: {}\n", recipient));
+        }
+        content
+    }
+
+    /// Options for one run; `skip_system_files` is the per-run override of the skip list.
+    /// Under a bag profile, they're preset from it (see `Profile::preset`).
+    pub fn bag_options(&self, skip_system_files: bool) -> BagOptions {
+        let mut options = BagOptions {
+            date_mode: self.date_mode,
+            include_bagging_time: self.include_bagging_time,
+            skip_names: if skip_system_files {
+                self.skip_names.clone()
+            } else {
+                Vec::new()
+            },
+            skip_appledouble: self.skip_appledouble && skip_system_files,
+            strip_quarantine: self.strip_quarantine,
+            clone_files: self.clone_files,
+            follow_symlinks: self.follow_symlinks,
+            max_depth: self.max_depth,
+            error_policy: self.error_policy,
+            empty_source: self.empty_source,
+            empty_dirs: self.empty_dirs,
+            case_collisions: self.case_collisions,
+            hash_threads: self.hash_threads,
+            verify_after: self.verify_after,
+            metadata: self.metadata.clone(),
+            plugins: self.plugins.clone(),
+            dedup: self.dedup,
+            dedup_against: if self.dedup == DedupMode::Off {
+                Vec::new()
+            } else {
+                Inventory::load()
+                    .entries
+                    .into_iter()
+                    .map(|e| e.path)
+                    .collect()
+            },
+            tag_directories: Vec::new(),
+            id_scheme: self.id_scheme.clone(),
+            id_sequence_next: match &self.id_scheme {
+                IdScheme::Sequence(prefix) => Inventory::load().next_in_sequence(prefix),
+                _ => 1,
+            },
+            record_provenance: self.record_provenance,
+            record_file_times: self.record_file_times,
+            record_permissions: self.record_permissions,
+            record_xattrs: self.record_xattrs,
+            event_log: self.event_log,
+            algorithms: self.algorithms.clone(),
+            bagit_version: self.bagit_version,
+            profile: None,
+            signing: self.signing(),
+        };
+        if !self.bag_profile.is_empty() {
+            if let Ok(profile) = Profile::named(&self.bag_profile) {
+                profile.preset(&mut options);
+            }
+        }
+        options
+    }
+
+    /// Where a copy of `source` bagged with `options` goes when no destination is given.
+    pub fn copy_destination(&self, source: &Path, options: &BagOptions) -> PathBuf {
+        let name = bag_name(&self.name_template, source, options);
+        copy_destination(source, &name, self.output_dir.as_deref())
+    }
+
+    pub fn validation_options(&self) -> ValidationOptions {
+        ValidationOptions {
+            mode: self.validation_mode,
+            hash_threads: self.hash_threads,
+            completeness_only: false,
+            sample: None,
+            profile: None,
+            minisign_key: self.minisign_public_key.clone(),
+            gnupg_home: None,
+        }
+    }
+
+    /// How new bags are signed, if they are.
+    pub fn signing(&self) -> Option<Signing> {
+        self.signing_tool.map(|tool| Signing {
+            tool,
+            key: self.signing_key.clone(),
+            gnupg_home: None,
+        })
+    }
+
+    pub fn serial_options(&self) -> SerialOptions {
+        SerialOptions {
+            format: self.serial_format,
+            zstd_level: self.zstd_level,
+            threads: self.compression_threads as u32,
+            reproducible: self.reproducible_archives,
+            signing: self.signing(),
+            volume_size: self.volume_size,
+            encryption: self.encryption_tool.map(|tool| Encryption {
+                tool,
+                recipients: self.encryption_recipients.clone(),
+            }),
+        }
+    }
+
+    /// The settings to share across workstations, as one file, leaving out the
+    /// workstation-specific keys and the agent token.
+    pub fn to_bundle(&self) -> String {
+        let mut bundle = format!("{}\n", BUNDLE_HEADER);
+        for line in self.serialize().lines() {
+            let key = line.split_once(':').map_or("", |(k, _)| k);
+            if !WORKSTATION_KEYS.contains(&key) {
+                bundle.push_str(line);
+                bundle.push('\n');
+            }
+        }
+        bundle
+    }
+
+    /// These settings with everything but the workstation-specific keys replaced from
+    /// `bundle`, or `None` if it isn't a settings bundle.
+    pub fn with_bundle(&self, bundle: &str) -> Option<Settings> {
+        if bundle.lines().next()?.trim() != BUNDLE_HEADER {
+            return None;
+        }
+        Some(Settings {
+            max_jobs: self.max_jobs,
+            hash_threads: self.hash_threads,
+            compression_threads: self.compression_threads,
+            locale: self.locale,
+            agent_token: self.agent_token.clone(),
+            bag_profile: self.bag_profile.clone(),
+            signing_key: self.signing_key.clone(),
+            minisign_public_key: self.minisign_public_key.clone(),
+            ..Settings::parse(bundle)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_keeps_workstation_settings() {
+        let mut shared = Settings {
+            validation_mode: ValidationMode::Strict,
+            skip_names: vec!["Thumbs.db".to_string()],
+            algorithms: vec![ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha512],
+            empty_dirs: EmptyDirPolicy::Placeholder,
+            case_collisions: CaseCollisionPolicy::Refuse,
+            record_file_times: true,
+            max_jobs: 8,
+            ..Settings::default()
+        };
+        shared
+            .metadata
+            .push("Source-Organization", "Example Archive");
+        let bundle = shared.to_bundle();
+        assert!(bundle.starts_with(BUNDLE_HEADER));
+        assert!(!bundle.contains("Max-Jobs"));
+
+        let local = Settings {
+            max_jobs: 3,
+            locale: Locale::De,
+            ..Settings::default()
+        };
+        let imported = local.with_bundle(&bundle).unwrap();
+        assert_eq!(imported.validation_mode, ValidationMode::Strict);
+        assert_eq!(
+            imported.algorithms,
+            [ChecksumAlgorithm::Md5, ChecksumAlgorithm::Sha512]
+        );
+        assert_eq!(imported.skip_names, vec!["Thumbs.db".to_string()]);
+        assert_eq!(imported.empty_dirs, EmptyDirPolicy::Placeholder);
+        assert_eq!(imported.case_collisions, CaseCollisionPolicy::Refuse);
+        assert!(imported.record_file_times);
+        assert_eq!(
+            imported.metadata.get("Source-Organization"),
+            Some("Example Archive")
+        );
+        assert_eq!(imported.max_jobs, 3);
+        assert_eq!(imported.locale, Locale::De);
+
+        assert!(local.with_bundle("Date-Mode: utc\n").is_none());
+    }
+}
@@ -0,0 +1,398 @@
+use crate::bagit::{BagError, BagFiles};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output, Stdio};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// The tag manifest a detached signature covers. It lists every other tag file, and
+/// the payload manifests list the payload, so signing it vouches for the whole bag.
+pub const SIGNED_MANIFEST: &str = "tagmanifest-sha256.txt";
+
+/// Programs a bag's tag manifest can be signed with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SigningTool {
+    /// GnuPG, with a key from the user's keyring; writes an armored `.asc`.
+    Gpg,
+    /// minisign, with a secret key file; writes a `.minisig`.
+    Minisign,
+}
+
+impl SigningTool {
+    pub const ALL: [SigningTool; 2] = [SigningTool::Gpg, SigningTool::Minisign];
+
+    pub fn parse(text: &str) -> Option<Self> {
+        SigningTool::ALL.into_iter().find(|t| t.name() == text)
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            SigningTool::Gpg => "gpg",
+            SigningTool::Minisign => "minisign",
+        }
+    }
+
+    /// The signature's file name, beside the tag manifest it signs.
+    pub fn signature_name(self) -> String {
+        match self {
+            SigningTool::Gpg => format!("{}.asc", SIGNED_MANIFEST),
+            SigningTool::Minisign => format!("{}.minisig", SIGNED_MANIFEST),
+        }
+    }
+}
+
+/// How new bags are signed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Signing {
+    pub tool: SigningTool,
+    /// For gpg, the key ID, fingerprint or email address to sign with, or blank for
+    /// the default key. For minisign, the path of the secret key file, which must
+    /// not be password-protected (`minisign -G -W`), as nobody is there to type it.
+    pub key: String,
+    /// GnuPG home folder whose keyring is used instead of the user's.
+    pub gnupg_home: Option<PathBuf>,
+}
+
+/// How a bag's detached signature checked out.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignatureCheck {
+    /// There's no signature.
+    Unsigned,
+    /// The signature is good; who made it, as the tool names them.
+    Good(String),
+    /// The signature doesn't match the tag manifest, so one or the other changed.
+    Bad(String),
+    /// There's a signature, but it couldn't be checked, and why.
+    Unchecked(String),
+}
+
+fn failed(tool: SigningTool, message: impl Into<String>) -> BagError {
+    BagError::SigningFailed {
+        tool: tool.name().to_string(),
+        message: message.into(),
+    }
+}
+
+/// stderr of a failed run, or its exit status if it printed nothing.
+fn complaint(output: &Output) -> String {
+    let stderr = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    match stderr.is_empty() {
+        true => output.status.to_string(),
+        false => stderr,
+    }
+}
+
+/// Whether the bag at `bag` has a detached signature.
+pub fn is_signed(bag: &Path) -> bool {
+    SigningTool::ALL
+        .iter()
+        .any(|t| bag.join(t.signature_name()).is_file())
+}
+
+/// Removes any detached signature from the bag at `bag`, as it no longer matches
+/// once the tag manifest is rewritten.
+pub fn remove_signatures(bag: &Path) -> io::Result<()> {
+    for tool in SigningTool::ALL {
+        let signature = bag.join(tool.signature_name());
+        if signature.is_file() {
+            fs::remove_file(signature)?;
+        }
+    }
+    Ok(())
+}
+
+/// Signs `tagmanifest-sha256.txt` of the bag at `bag` as `signing` says, replacing any
+/// earlier signature, and returns the signature's path.
+pub fn sign_bag(bag: &Path, signing: &Signing) -> Result<PathBuf, BagError> {
+    let manifest = bag.join(SIGNED_MANIFEST);
+    if !manifest.is_file() {
+        return Err(failed(
+            signing.tool,
+            format!(
+                "the bag has no {} to sign; add SHA-256 to its algorithms",
+                SIGNED_MANIFEST
+            ),
+        ));
+    }
+    remove_signatures(bag)?;
+    let signature = bag.join(signing.tool.signature_name());
+    let mut command = Command::new(signing.tool.name());
+    match signing.tool {
+        SigningTool::Gpg => {
+            if let Some(home) = &signing.gnupg_home {
+                command.arg("--homedir").arg(home);
+            }
+            command.args(["--batch", "--yes", "--armor", "--detach-sign"]);
+            if !signing.key.is_empty() {
+                command.arg("--local-user").arg(&signing.key);
+            }
+            command.arg("--output").arg(&signature).arg(&manifest);
+        }
+        SigningTool::Minisign => {
+            command
+                .arg("-S")
+                .arg("-s")
+                .arg(&signing.key)
+                .arg("-m")
+                .arg(&manifest)
+                .arg("-x")
+                .arg(&signature);
+        }
+    }
+    let output = command
+        .stdin(Stdio::null())
+        .output()
+        .map_err(|e| failed(signing.tool, e.to_string()))?;
+    if !output.status.success() {
+        let _ = fs::remove_file(&signature);
+        return Err(failed(signing.tool, complaint(&output)));
+    }
+    Ok(signature)
+}
+
+/// Checks the detached signature over `tagmanifest-sha256.txt` in `files`, if it has
+/// one: GnuPG signatures against the keyring in `gnupg_home` (the user's if `None`),
+/// minisign ones against the public key file `minisign_key`. The two files are copied
+/// out first, so a serialized bag is checked the same way as a folder.
+pub fn check_signature(
+    files: &dyn BagFiles,
+    minisign_key: Option<&Path>,
+    gnupg_home: Option<&Path>,
+) -> SignatureCheck {
+    let Some((tool, signature)) = SigningTool::ALL
+        .into_iter()
+        .find_map(|tool| Some((tool, files.read(&tool.signature_name())?)))
+    else {
+        return SignatureCheck::Unsigned;
+    };
+    let Some(Ok(manifest)) = files.read(SIGNED_MANIFEST) else {
+        return SignatureCheck::Bad(format!(
+            "{} is signed, but there's no {}",
+            tool.signature_name(),
+            SIGNED_MANIFEST
+        ));
+    };
+    let signature = match signature {
+        Ok(signature) => signature,
+        Err(e) => return SignatureCheck::Unchecked(format!("{}: {}", tool.signature_name(), e)),
+    };
+    if tool == SigningTool::Minisign && minisign_key.is_none() {
+        return SignatureCheck::Unchecked(
+            "The bag is signed with minisign, but no public key is set to check it against"
+                .to_string(),
+        );
+    }
+
+    let nanos = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_nanos());
+    let dir =
+        std::env::temp_dir().join(format!("baggie-signature-{}-{}", std::process::id(), nanos));
+    let result = fs::create_dir_all(&dir)
+        .and_then(|_| fs::write(dir.join(SIGNED_MANIFEST), manifest))
+        .and_then(|_| fs::write(dir.join(tool.signature_name()), signature))
+        .and_then(|_| verify(tool, &dir, minisign_key, gnupg_home));
+    let _ = fs::remove_dir_all(&dir);
+    match result {
+        Ok(check) => check,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => SignatureCheck::Unchecked(format!(
+            "The bag is signed with {}, which isn't installed to check it",
+            tool.name()
+        )),
+        Err(e) => SignatureCheck::Unchecked(e.to_string()),
+    }
+}
+
+/// Runs the tool's verifier over the manifest and signature copied into `dir`.
+fn verify(
+    tool: SigningTool,
+    dir: &Path,
+    minisign_key: Option<&Path>,
+    gnupg_home: Option<&Path>,
+) -> io::Result<SignatureCheck> {
+    let manifest = dir.join(SIGNED_MANIFEST);
+    let signature = dir.join(tool.signature_name());
+    match tool {
+        SigningTool::Gpg => {
+            let mut command = Command::new("gpg");
+            if let Some(home) = gnupg_home {
+                command.arg("--homedir").arg(home);
+            }
+            let output = command
+                .args(["--batch", "--status-fd", "1", "--verify"])
+                .arg(&signature)
+                .arg(&manifest)
+                .stdin(Stdio::null())
+                .output()?;
+            // Machine-readable status lines: `[GNUPG:] GOODSIG <key ID> <user ID>`
+            let status = String::from_utf8_lossy(&output.stdout);
+            let line = |keyword: &str| {
+                status.lines().find_map(|line| {
+                    let rest = line.strip_prefix("[GNUPG:] ")?.strip_prefix(keyword)?;
+                    Some(rest.trim().to_string())
+                })
+            };
+            Ok(
+                if let Some(signer) = line("GOODSIG ").filter(|_| output.status.success()) {
+                    let signer = signer
+                        .split_once(' ')
+                        .map_or(signer.as_str(), |(_, uid)| uid);
+                    SignatureCheck::Good(signer.to_string())
+                } else if let Some(key) = line("NO_PUBKEY ") {
+                    SignatureCheck::Unchecked(format!(
+                        "The bag is signed with GnuPG key {}, which isn't in your keyring",
+                        key
+                    ))
+                } else {
+                    SignatureCheck::Bad(format!(
+                        "{} doesn't match {}: {}",
+                        tool.signature_name(),
+                        SIGNED_MANIFEST,
+                        complaint(&output)
+                    ))
+                },
+            )
+        }
+        SigningTool::Minisign => {
+            let output = Command::new("minisign")
+                .arg("-V")
+                .arg("-p")
+                .arg(minisign_key.unwrap_or(Path::new("")))
+                .arg("-m")
+                .arg(&manifest)
+                .arg("-x")
+                .arg(&signature)
+                .stdin(Stdio::null())
+                .output()?;
+            if !output.status.success() {
+                return Ok(SignatureCheck::Bad(format!(
+                    "{} doesn't match {}: {}",
+                    tool.signature_name(),
+                    SIGNED_MANIFEST,
+                    complaint(&output)
+                )));
+            }
+            let stdout = String::from_utf8_lossy(&output.stdout);
+            let comment = stdout
+                .lines()
+                .find_map(|line| line.strip_prefix("Trusted comment: "))
+                .unwrap_or("the minisign key");
+            Ok(SignatureCheck::Good(comment.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::archive::{serialize_bag, validate_archive, SerialOptions};
+    use crate::bagit::{bag_directory, validate_bag, BagOptions, ValidationOptions};
+
+    /// Needs gpg to make a key, so isn't run by default; run with
+    /// `cargo test -- --ignored`.
+    #[test]
+    #[ignore]
+    fn test_sign_and_verify() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_signature");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("bag");
+        fs::create_dir_all(&bag).unwrap();
+        fs::write(bag.join("letter.txt"), "Dear all,").unwrap();
+
+        // A key of its own, in a keyring of its own
+        let home = temp_dir.join("gnupg");
+        fs::create_dir_all(&home).unwrap();
+        let made = Command::new("gpg")
+            .arg("--homedir")
+            .arg(&home)
+            .args([
+                "--batch",
+                "--passphrase",
+                "",
+                "--quick-gen-key",
+                "archivist@example.org",
+            ])
+            .output()
+            .expect("gpg isn't installed");
+        assert!(
+            made.status.success(),
+            "gpg can't make a key: {}",
+            complaint(&made)
+        );
+
+        let signing = Signing {
+            tool: SigningTool::Gpg,
+            key: "archivist@example.org".to_string(),
+            gnupg_home: Some(home.clone()),
+        };
+        let options = BagOptions {
+            signing: Some(signing.clone()),
+            event_log: true,
+            ..BagOptions::default()
+        };
+        bag_directory(&bag, &options, None).unwrap();
+        assert!(bag.join("tagmanifest-sha256.txt.asc").is_file());
+        let validation = ValidationOptions {
+            gnupg_home: Some(home.clone()),
+            ..ValidationOptions::default()
+        };
+        let report = validate_bag(&bag, &validation, None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert_eq!(report.signed_by.as_deref(), Some("archivist@example.org"));
+
+        // Checked inside a serialized bag too; serializing without signing set up
+        // leaves the signature as it was, in the archive and the bag
+        let log = fs::read(bag.join(crate::events::EVENT_LOG_FILE)).unwrap();
+        let archive =
+            serialize_bag(&bag, &temp_dir.join("out"), &SerialOptions::default(), None).unwrap();
+        let report = validate_archive(&archive, &validation, None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+        assert_eq!(report.signed_by.as_deref(), Some("archivist@example.org"));
+        assert_eq!(
+            fs::read(bag.join(crate::events::EVENT_LOG_FILE)).unwrap(),
+            log
+        );
+        let report = validate_bag(&bag, &validation, None).unwrap();
+        assert_eq!(report.signed_by.as_deref(), Some("archivist@example.org"));
+
+        // Checked against another keyring, the key isn't known
+        let stranger = temp_dir.join("stranger");
+        fs::create_dir_all(&stranger).unwrap();
+        let elsewhere = ValidationOptions {
+            gnupg_home: Some(stranger),
+            ..ValidationOptions::default()
+        };
+        let report = validate_bag(&bag, &elsewhere, None).unwrap();
+        assert_eq!(report.signed_by, None);
+        assert!(report
+            .warnings
+            .iter()
+            .any(|w| w.rule == crate::bagit::RULE_SIGNATURE));
+
+        // A tag manifest changed after signing fails
+        let manifest = bag.join(SIGNED_MANIFEST);
+        let text = fs::read_to_string(&manifest).unwrap();
+        fs::write(&manifest, text.replace("bagit.txt", "bagit.txt ")).unwrap();
+        let report = validate_bag(&bag, &validation, None).unwrap();
+        assert!(report
+            .errors
+            .iter()
+            .any(|e| e.rule == crate::bagit::RULE_SIGNATURE));
+
+        // Without a SHA-256 tag manifest there's nothing to sign
+        fs::remove_file(&manifest).unwrap();
+        assert!(matches!(
+            sign_bag(&bag, &signing),
+            Err(BagError::SigningFailed { .. })
+        ));
+
+        let _ = Command::new("gpgconf")
+            .arg("--homedir")
+            .arg(&home)
+            .args(["--kill", "gpg-agent"])
+            .output();
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,252 @@
+use crate::bagit::{BagError, ChecksumAlgorithm, Digester};
+use std::fs::{self, File};
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+
+/// Volume size offered until one is chosen: the most many upload services take in
+/// one file.
+pub const DEFAULT_VOLUME_SIZE: u64 = 5_000_000_000;
+
+/// Extension of the list written beside the volumes, giving each one's SHA-256 in
+/// the format `sha256sum -c` reads.
+pub const VOLUME_LIST_EXTENSION: &str = "sha256";
+
+/// `path` with `.<suffix>` added to its name.
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(".");
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Path of volume `number` (from 1) of the file at `path`: `bag.tar.zst.001` and on.
+pub fn volume_path(path: &Path, number: usize) -> PathBuf {
+    with_suffix(path, &format!("{:03}", number))
+}
+
+/// Path of the list of volumes of the file at `path`.
+pub fn volume_list_path(path: &Path) -> PathBuf {
+    with_suffix(path, VOLUME_LIST_EXTENSION)
+}
+
+fn bad(message: impl Into<String>) -> BagError {
+    BagError::BadVolumes(message.into())
+}
+
+/// Copies up to `limit` bytes from `reader` to `out`, returning how many there were
+/// and their SHA-256.
+fn copy_hashed(
+    reader: &mut impl Read,
+    limit: u64,
+    out: &mut impl Write,
+) -> io::Result<(u64, String)> {
+    let mut reader = reader.take(limit);
+    let mut digester = Digester::new(ChecksumAlgorithm::Sha256);
+    let mut buffer = vec![0u8; 1 << 16];
+    let mut copied = 0;
+    loop {
+        let read = reader.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        digester.update(&buffer[..read]);
+        out.write_all(&buffer[..read])?;
+        copied += read as u64;
+    }
+    Ok((copied, digester.finish()))
+}
+
+/// Splits the file at `path` into numbered volumes of `volume_size` bytes beside it
+/// (the last one shorter), writes the list of their checksums, and removes the file.
+/// A file that fits in one volume still becomes `.001`, so whoever receives it
+/// always reassembles the same way. Returns the list's path. If a volume or the list
+/// can't be written, those already written are removed and the file is kept.
+pub fn split_file(path: &Path, volume_size: u64) -> Result<PathBuf, BagError> {
+    if volume_size == 0 {
+        return Err(bad("the volume size must be more than 0 bytes"));
+    }
+    let file_name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .ok_or_else(|| bad(format!("{} doesn't name a file", path.display())))?;
+    let mut written = Vec::new();
+    let list_path = volume_list_path(path);
+    let result = write_volumes(path, &file_name, volume_size, &mut written).and_then(|list| {
+        written.push(list_path.clone());
+        Ok(fs::write(&list_path, list)?)
+    });
+    if let Err(e) = result {
+        for written in &written {
+            let _ = fs::remove_file(written);
+        }
+        return Err(e);
+    }
+    fs::remove_file(path)?;
+    Ok(list_path)
+}
+
+/// Writes the volumes of the file at `path`, adding each one's path to `written` as
+/// it's created, and returns the list of their checksums.
+fn write_volumes(
+    path: &Path,
+    file_name: &str,
+    volume_size: u64,
+    written: &mut Vec<PathBuf>,
+) -> Result<String, BagError> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let mut list = String::new();
+    for number in 1.. {
+        let volume = volume_path(path, number);
+        let file = File::create(&volume)?;
+        written.push(volume.clone());
+        let mut out = BufWriter::new(file);
+        let (copied, digest) = copy_hashed(&mut reader, volume_size, &mut out)?;
+        out.flush()?;
+        if copied == 0 && number > 1 {
+            // The file ended exactly at the end of the last volume
+            fs::remove_file(&volume)?;
+            written.pop();
+            break;
+        }
+        list.push_str(&format!("{}  {}.{:03}\n", digest, file_name, number));
+        if copied < volume_size {
+            break;
+        }
+    }
+    Ok(list)
+}
+
+/// The file the volumes of `path` make up: `path` is the list of volumes or any one
+/// of them.
+fn joined_path(path: &Path) -> Option<PathBuf> {
+    let extension = path.extension()?.to_str()?;
+    let is_volume = !extension.is_empty() && extension.bytes().all(|b| b.is_ascii_digit());
+    (is_volume || extension == VOLUME_LIST_EXTENSION).then(|| path.with_extension(""))
+}
+
+/// Reassembles the file split by `split_file`, given its list of volumes or any one
+/// of them, into `dest` (the volumes' own folder if `None`), and returns its path.
+/// Each volume is checked against the list as it's copied; if one is missing or
+/// doesn't match, the partial file is removed and the error names the volume.
+pub fn join_volumes(path: &Path, dest: Option<&Path>) -> Result<PathBuf, BagError> {
+    let joined = joined_path(path).ok_or_else(|| {
+        bad(format!(
+            "{} isn't a volume or a list of volumes",
+            path.display()
+        ))
+    })?;
+    let list_path = volume_list_path(&joined);
+    let list = fs::read_to_string(&list_path)
+        .map_err(|e| bad(format!("{}: {}", list_path.display(), e)))?;
+    let folder = joined.parent().unwrap_or(Path::new(""));
+
+    let mut volumes = Vec::new();
+    for line in list.lines().filter(|l| !l.trim().is_empty()) {
+        // `sha256sum` marks files read in binary mode with `*`
+        let (digest, name) = line
+            .split_once(' ')
+            .map(|(d, n)| (d, n.trim_start_matches([' ', '*'])))
+            .filter(|(_, n)| !n.is_empty() && !n.contains(['/', '\\']))
+            .ok_or_else(|| {
+                bad(format!(
+                    "{} has a line that isn't a checksum and a volume",
+                    list_path.display()
+                ))
+            })?;
+        volumes.push((digest.to_ascii_lowercase(), folder.join(name)));
+    }
+    if volumes.is_empty() {
+        return Err(bad(format!("{} lists no volumes", list_path.display())));
+    }
+
+    let out_path = match (dest, joined.file_name()) {
+        (Some(dest), Some(name)) => dest.join(name),
+        _ => joined.clone(),
+    };
+    if out_path.exists() {
+        return Err(bad(format!("{} already exists", out_path.display())));
+    }
+    let mut out = BufWriter::new(File::create(&out_path)?);
+    let result = volumes.iter().try_for_each(|(expected, volume)| {
+        let mut reader = BufReader::new(
+            File::open(volume).map_err(|e| bad(format!("{}: {}", volume.display(), e)))?,
+        );
+        let (_, digest) = copy_hashed(&mut reader, u64::MAX, &mut out)?;
+        match &digest == expected {
+            true => Ok(()),
+            false => Err(bad(format!(
+                "{} doesn't match its checksum; copy it again",
+                volume.display()
+            ))),
+        }
+    });
+    if let Err(e) = result.and_then(|_| Ok(out.flush()?)) {
+        drop(out);
+        let _ = fs::remove_file(&out_path);
+        return Err(e);
+    }
+    Ok(out_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_and_join() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_split");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        fs::create_dir_all(&temp_dir).unwrap();
+        let archive = temp_dir.join("bag.tar");
+        let content: Vec<u8> = (0..2500u32).map(|i| (i % 251) as u8).collect();
+        fs::write(&archive, &content).unwrap();
+
+        let list = split_file(&archive, 1000).unwrap();
+        assert_eq!(list, temp_dir.join("bag.tar.sha256"));
+        assert!(!archive.exists());
+        assert_eq!(fs::metadata(volume_path(&archive, 1)).unwrap().len(), 1000);
+        assert_eq!(fs::metadata(volume_path(&archive, 3)).unwrap().len(), 500);
+        assert!(!volume_path(&archive, 4).exists());
+        assert_eq!(fs::read_to_string(&list).unwrap().lines().count(), 3);
+
+        // From any volume, into another folder
+        let elsewhere = temp_dir.join("elsewhere");
+        fs::create_dir_all(&elsewhere).unwrap();
+        let joined = join_volumes(&volume_path(&archive, 2), Some(&elsewhere)).unwrap();
+        assert_eq!(joined, elsewhere.join("bag.tar"));
+        assert_eq!(fs::read(&joined).unwrap(), content);
+        assert!(join_volumes(&list, Some(&elsewhere)).is_err());
+
+        // A damaged volume is named, and nothing is left behind
+        fs::write(volume_path(&archive, 2), vec![0u8; 1000]).unwrap();
+        match join_volumes(&list, None) {
+            Err(BagError::BadVolumes(message)) => assert!(message.contains("bag.tar.002")),
+            other => panic!("{:?}", other),
+        }
+        assert!(!archive.exists());
+
+        // Exactly two volumes' worth leaves no empty third
+        let even = temp_dir.join("even.tar");
+        fs::write(&even, &content[..2000]).unwrap();
+        split_file(&even, 1000).unwrap();
+        assert!(!volume_path(&even, 3).exists());
+        assert_eq!(
+            fs::read(join_volumes(&volume_path(&even, 1), None).unwrap()).unwrap(),
+            &content[..2000]
+        );
+
+        assert!(join_volumes(&temp_dir.join("bag.tar"), None).is_err());
+
+        // A volume that can't be created takes the ones before it with it
+        let blocked = temp_dir.join("blocked.tar");
+        fs::write(&blocked, &content).unwrap();
+        fs::create_dir(volume_path(&blocked, 2)).unwrap();
+        assert!(split_file(&blocked, 1000).is_err());
+        assert_eq!(fs::read(&blocked).unwrap(), content);
+        assert!(!volume_path(&blocked, 1).exists());
+        assert!(!volume_list_path(&blocked).exists());
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,169 @@
+use crate::bagit::{
+    calculate_sha256, read_payload_manifest, BagError, ChecksumAlgorithm, Progress,
+};
+use crate::clone::copy_hashing;
+use crate::inventory::csv_field;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+
+/// Folder under the store root holding the objects.
+pub const OBJECTS_DIR: &str = "objects";
+
+/// Suffix of the file mapping a bag's payload paths to objects, written to the store
+/// root as `<bag name>.store-map.csv`.
+pub const STORE_MAP_FILE: &str = "store-map.csv";
+
+/// What an export added to the store.
+#[derive(Debug, Clone, Default)]
+pub struct StoreReport {
+    pub objects_written: usize,
+    /// Payload files whose content was already in the store.
+    pub objects_present: usize,
+    pub bytes_written: u64,
+    pub map: PathBuf,
+}
+
+/// Where the object with SHA-256 `digest` lives in the store: `objects/ab/cd/<digest>`.
+pub fn object_path(store: &Path, digest: &str) -> PathBuf {
+    store
+        .join(OBJECTS_DIR)
+        .join(&digest[..2])
+        .join(&digest[2..4])
+        .join(digest)
+}
+
+/// Copies the payload of the finished bag at `bag` into `store`, one object per distinct
+/// digest, and writes the map from the bag's paths to them. Objects already in the
+/// store are left alone, so a store shared by many bags keeps one copy of each file.
+/// The bag itself is untouched and stays the form it's sent in. Objects are named by
+/// SHA-256 whatever the bag's algorithm; files of bags in other algorithms are
+/// checked against their own manifest as they're copied.
+pub fn export_to_store(
+    bag: &Path,
+    store: &Path,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<StoreReport, BagError> {
+    if !bag.join("bagit.txt").is_file() {
+        return Err(BagError::NotABag);
+    }
+    let (algorithm, manifest) = read_payload_manifest(bag)?;
+    let mut manifest: Vec<_> = manifest.into_iter().collect();
+    manifest.sort();
+    fs::create_dir_all(store)?;
+
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started {
+            total_files: manifest.len(),
+        });
+    }
+    let mut report = StoreReport::default();
+    let mut map = String::from("file,bytes,sha256,object\n");
+    for (i, (file, expected)) in manifest.iter().enumerate() {
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Copying {
+                current: i + 1,
+                filename: file.clone(),
+            });
+        }
+        let source = bag.join(file);
+        let digest = match algorithm {
+            ChecksumAlgorithm::Sha256 => expected.clone(),
+            _ => calculate_sha256(&source)?,
+        };
+        if digest.len() != 64 || !digest.bytes().all(|b| b.is_ascii_hexdigit()) {
+            return Err(BagError::CopyMismatch(source));
+        }
+        let bytes = fs::metadata(&source)?.len();
+        let object = object_path(store, &digest);
+        if object.is_file() {
+            report.objects_present += 1;
+        } else {
+            // Written under a temporary name, so an interrupted export never leaves a
+            // partial object behind that a later one would take as present
+            fs::create_dir_all(object.parent().unwrap())?;
+            let partial = object.with_extension("partial");
+            if copy_hashing(&source, &partial, algorithm)? != *expected {
+                let _ = fs::remove_file(&partial);
+                return Err(BagError::CopyMismatch(source));
+            }
+            fs::rename(&partial, &object)?;
+            report.objects_written += 1;
+            report.bytes_written += bytes;
+        }
+        let relative = object.strip_prefix(store).unwrap();
+        map.push_str(&format!(
+            "{},{},{},{}\n",
+            csv_field(file),
+            bytes,
+            digest,
+            relative.to_string_lossy().replace('\\', "/")
+        ));
+    }
+
+    let name = bag
+        .file_name()
+        .map(|n| n.to_string_lossy())
+        .unwrap_or_default();
+    report.map = store.join(format!("{}.{}", name, STORE_MAP_FILE));
+    fs::write(&report.map, map)?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{bag_directory, calculate_sha256, BagOptions, DedupMode};
+
+    #[test]
+    fn test_export_to_store() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_store");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("letters");
+        fs::create_dir_all(bag.join("copies")).unwrap();
+        fs::write(bag.join("a.txt"), "dear sir").unwrap();
+        fs::write(bag.join("copies/a.txt"), "dear sir").unwrap();
+        fs::write(bag.join("b.txt"), "yours faithfully").unwrap();
+        let options = BagOptions {
+            dedup: DedupMode::Off,
+            ..BagOptions::default()
+        };
+        bag_directory(&bag, &options, None).unwrap();
+
+        let store = temp_dir.join("store");
+        let report = export_to_store(&bag, &store, None).unwrap();
+        assert_eq!(report.objects_written, 2);
+        assert_eq!(report.objects_present, 1);
+        assert_eq!(report.bytes_written, 24);
+
+        let digest = calculate_sha256(&bag.join("data/a.txt")).unwrap();
+        let object = object_path(&store, &digest);
+        assert!(object.ends_with(format!(
+            "objects/{}/{}/{}",
+            &digest[..2],
+            &digest[2..4],
+            digest
+        )));
+        assert_eq!(fs::read_to_string(&object).unwrap(), "dear sir");
+        let map = fs::read_to_string(store.join("letters.store-map.csv")).unwrap();
+        assert_eq!(map.lines().count(), 4);
+        assert!(map.contains(&format!("data/copies/a.txt,8,{},objects/", digest)));
+
+        // A second export of the same bag adds nothing
+        let again = export_to_store(&bag, &store, None).unwrap();
+        assert_eq!(again.objects_written, 0);
+        assert_eq!(again.objects_present, 3);
+
+        // A damaged payload file is caught, not stored under the wrong digest
+        fs::write(bag.join("data/b.txt"), "yours faithfullY").unwrap();
+        fs::remove_dir_all(&store).unwrap();
+        assert!(matches!(
+            export_to_store(&bag, &store, None),
+            Err(BagError::CopyMismatch(_))
+        ));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
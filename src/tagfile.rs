@@ -0,0 +1,319 @@
+use std::fs;
+use std::io;
+use std::path::Path;
+
+const UTF8_BOM: &[u8] = &[0xEF, 0xBB, 0xBF];
+const UTF16_LE_BOM: &[u8] = &[0xFF, 0xFE];
+const UTF16_BE_BOM: &[u8] = &[0xFE, 0xFF];
+
+/// Longest bag-info.txt line written where a value can be folded (RFC 8493 §2.2.2).
+const MAX_LINE_LENGTH: usize = 79;
+
+/// Indent marking a folded continuation line.
+const CONTINUATION: &str = "  ";
+
+/// A `Tag-File-Character-Encoding` baggie can check tag files against.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TagEncoding {
+    Utf8,
+    Latin1,
+}
+
+impl TagEncoding {
+    /// Reads the IANA name in bagit.txt, or a common alias of it, ignoring case.
+    pub fn parse(name: &str) -> Option<TagEncoding> {
+        match name.trim().to_ascii_lowercase().as_str() {
+            "utf-8" | "utf8" => Some(TagEncoding::Utf8),
+            "iso-8859-1" | "iso_8859-1" | "iso8859-1" | "latin1" | "latin-1" | "l1" => {
+                Some(TagEncoding::Latin1)
+            }
+            _ => None,
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TagEncoding::Utf8 => "UTF-8",
+            TagEncoding::Latin1 => "ISO-8859-1",
+        }
+    }
+}
+
+/// Decodes tag file bytes leniently, returning the text plus any encoding warnings.
+///
+/// Baggie always writes UTF-8 without a BOM, but bags from older tools sometimes carry
+/// a BOM or were saved as Latin-1. Neither should make validation fall over, so a BOM is
+/// stripped and invalid UTF-8 is decoded as Latin-1 (every byte maps to a code point).
+pub fn decode_tag_bytes(bytes: &[u8], name: &str) -> (String, Vec<String>) {
+    decode_tag_bytes_as(bytes, name, TagEncoding::Utf8)
+}
+
+/// [`decode_tag_bytes`] for a tag file declared to be in `encoding`. Any byte order
+/// mark is reported, since the spec's encodings have none, and so is UTF-8 that
+/// doesn't decode, with the line it's on.
+pub fn decode_tag_bytes_as(
+    bytes: &[u8],
+    name: &str,
+    encoding: TagEncoding,
+) -> (String, Vec<String>) {
+    let mut warnings = Vec::new();
+    if bytes.starts_with(UTF16_LE_BOM) || bytes.starts_with(UTF16_BE_BOM) {
+        warnings.push(format!(
+            "{} starts with a UTF-16 byte order mark, but tag files are declared {}",
+            name,
+            encoding.name()
+        ));
+    }
+    let bytes = match bytes.strip_prefix(UTF8_BOM) {
+        Some(rest) => {
+            warnings.push(format!("{} starts with a UTF-8 byte order mark", name));
+            rest
+        }
+        None => bytes,
+    };
+
+    let latin1 = |bytes: &[u8]| bytes.iter().map(|&b| b as char).collect();
+    let content = match encoding {
+        TagEncoding::Latin1 => latin1(bytes),
+        TagEncoding::Utf8 => match std::str::from_utf8(bytes) {
+            Ok(s) => s.to_string(),
+            Err(e) => {
+                let offset = e.valid_up_to();
+                let line = bytes[..offset].iter().filter(|&&b| b == b'\n').count() + 1;
+                let lines = bytes
+                    .split(|&b| b == b'\n')
+                    .filter(|l| std::str::from_utf8(l).is_err())
+                    .count();
+                warnings.push(format!(
+                    "{} is not valid UTF-8 (first bad byte at offset {}, on line {}; {} bad lines); read as Latin-1",
+                    name, offset, line, lines
+                ));
+                latin1(bytes)
+            }
+        },
+    };
+
+    (content, warnings)
+}
+
+/// Reads a tag file with [`decode_tag_bytes`], naming it by its file name in warnings.
+pub fn read_tag_file(path: &Path) -> io::Result<(String, Vec<String>)> {
+    let bytes = fs::read(path)?;
+    let name = path
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    Ok(decode_tag_bytes(&bytes, &name))
+}
+
+/// Writes a tag file as UTF-8 with no BOM, as declared in bagit.txt.
+///
+/// Returns the bytes written so callers can checksum exactly what is on disk.
+pub fn write_tag_file(path: &Path, content: &str) -> io::Result<String> {
+    let content = content.trim_start_matches('\u{FEFF}').to_string();
+    fs::write(path, &content)?;
+    Ok(content)
+}
+
+/// The fields of a bag-info.txt file, in file order.
+///
+/// Labels may repeat (several `Contact-Email` lines are fine per the spec), so this is
+/// a list rather than a map. Label lookups ignore ASCII case.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BagInfo {
+    fields: Vec<(String, String)>,
+}
+
+impl BagInfo {
+    /// Reads `Label: value` lines. A line starting with a space or tab continues the
+    /// value above it, and is joined onto it with a single space.
+    pub fn parse(content: &str) -> Self {
+        let mut fields: Vec<(String, String)> = Vec::new();
+        for line in content.lines() {
+            if line.starts_with([' ', '\t']) {
+                let more = line.trim();
+                if let Some((_, value)) = fields.last_mut().filter(|_| !more.is_empty()) {
+                    if !value.is_empty() {
+                        value.push(' ');
+                    }
+                    value.push_str(more);
+                }
+            } else if let Some((label, value)) = line.split_once(':') {
+                fields.push((label.trim().to_string(), value.trim().to_string()));
+            }
+        }
+        Self { fields }
+    }
+
+    pub fn fields(&self) -> &[(String, String)] {
+        &self.fields
+    }
+
+    pub fn fields_mut(&mut self) -> &mut Vec<(String, String)> {
+        &mut self.fields
+    }
+
+    /// First value for `label`.
+    pub fn get(&self, label: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(l, _)| l.eq_ignore_ascii_case(label))
+            .map(|(_, v)| v.as_str())
+    }
+
+    /// Appends a field, keeping any existing ones with the same label.
+    pub fn push(&mut self, label: impl Into<String>, value: impl Into<String>) {
+        self.fields.push((label.into(), value.into()));
+    }
+
+    /// One line per field, with long values folded onto indented continuation lines.
+    pub fn serialize(&self) -> String {
+        self.fields
+            .iter()
+            .map(|(label, value)| fold(&format!("{}: {}", label, value)) + "\n")
+            .collect()
+    }
+}
+
+/// Breaks `line` at spaces so that no line runs past `MAX_LINE_LENGTH` characters.
+/// A word too long to fit stays whole on a line of its own.
+fn fold(line: &str) -> String {
+    let mut folded = String::new();
+    let mut width = 0;
+    for (i, word) in line.split(' ').enumerate() {
+        let length = word.chars().count();
+        if i > 0 {
+            let fits = width + 1 + length <= MAX_LINE_LENGTH;
+            if fits || word.is_empty() || width == CONTINUATION.len() {
+                folded.push(' ');
+                width += 1;
+            } else {
+                folded.push('\n');
+                folded.push_str(CONTINUATION);
+                width = CONTINUATION.len();
+            }
+        }
+        folded.push_str(word);
+        width += length;
+    }
+    folded
+}
+
+/// Returns the value of the first `Label: value` line with the given label.
+pub fn tag_value<'a>(content: &'a str, label: &str) -> Option<&'a str> {
+    content.lines().find_map(|line| {
+        let (key, value) = line.split_once(':')?;
+        (key.trim() == label).then(|| value.trim())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_decode_plain_utf8() {
+        let (content, warnings) =
+            decode_tag_bytes("Source-Organization: Café\n".as_bytes(), "bag-info.txt");
+        assert_eq!(content, "Source-Organization: Café\n");
+        assert!(warnings.is_empty());
+    }
+
+    #[test]
+    fn test_decode_strips_bom() {
+        let mut bytes = UTF8_BOM.to_vec();
+        bytes.extend_from_slice(b"BagIt-Version: 0.97\n");
+        let (content, warnings) = decode_tag_bytes(&bytes, "bagit.txt");
+        assert_eq!(content, "BagIt-Version: 0.97\n");
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("byte order mark"));
+    }
+
+    #[test]
+    fn test_decode_latin1_fallback() {
+        // "Café" in Latin-1: 0xE9 is not valid UTF-8 on its own
+        let bytes = b"Source-Organization: Caf\xE9\n";
+        let (content, warnings) = decode_tag_bytes(bytes, "bag-info.txt");
+        assert_eq!(content, "Source-Organization: Café\n");
+        assert_eq!(warnings.len(), 1);
+        assert!(warnings[0].contains("Latin-1"));
+    }
+
+    #[test]
+    fn test_decode_declared_encoding() {
+        let bytes =
+            b"Source-Organization: Archive\nContact-Name: Caf\xE9\nExternal-Description: \xFF\n";
+        let (_, warnings) = decode_tag_bytes(bytes, "bag-info.txt");
+        assert!(
+            warnings[0].contains("offset 46, on line 2; 2 bad lines"),
+            "{:?}",
+            warnings
+        );
+
+        // The same bytes are fine in a bag that declares Latin-1
+        let (content, warnings) = decode_tag_bytes_as(bytes, "bag-info.txt", TagEncoding::Latin1);
+        assert!(content.contains("Contact-Name: Café\n"));
+        assert!(warnings.is_empty());
+
+        let utf16 = [&[0xFF, 0xFE][..], "B\0a\0".as_bytes()].concat();
+        let (_, warnings) = decode_tag_bytes(&utf16, "bag-info.txt");
+        assert!(warnings[0].contains("UTF-16 byte order mark"));
+
+        assert_eq!(TagEncoding::parse(" utf-8"), Some(TagEncoding::Utf8));
+        assert_eq!(TagEncoding::parse("ISO-8859-1"), Some(TagEncoding::Latin1));
+        assert_eq!(TagEncoding::parse("Shift_JIS"), None);
+    }
+
+    #[test]
+    fn test_bag_info_keeps_repeated_labels_in_order() {
+        let content = "Contact-Email: a@example.org\nSource-Organization: Archive\nContact-Email: b@example.org\n";
+        let info = BagInfo::parse(content);
+        assert_eq!(info.fields().len(), 3);
+        let emails: Vec<_> = info
+            .fields()
+            .iter()
+            .filter(|(label, _)| label == "Contact-Email")
+            .map(|(_, value)| value.as_str())
+            .collect();
+        assert_eq!(emails, vec!["a@example.org", "b@example.org"]);
+        assert_eq!(info.get("Source-Organization"), Some("Archive"));
+        assert_eq!(info.serialize(), content);
+    }
+
+    #[test]
+    fn test_bag_info_folds_long_values() {
+        let description = "Correspondence, minutes and annual reports of the society, \
+                           together with photographs of its members and premises, \
+                           deposited by the last secretary in 1998";
+        let long_word = "x".repeat(100);
+        let mut info = BagInfo::default();
+        info.push("External-Description", description);
+        info.push("Source-Organization", "Archive");
+        info.push("External-Identifier", long_word.as_str());
+        let content = info.serialize();
+        let lines: Vec<&str> = content.lines().collect();
+        assert!(lines.len() > 4);
+        assert!(lines[0].starts_with("External-Description: Correspondence"));
+        assert!(lines[1].starts_with("  "));
+        assert!(lines[..lines.len() - 2]
+            .iter()
+            .all(|l| l.chars().count() <= 79));
+        assert_eq!(lines[lines.len() - 1], format!("  {}", long_word));
+        assert_eq!(BagInfo::parse(&content), info);
+
+        // Tabs continue a value too, as some tools write them
+        let info = BagInfo::parse("External-Description: first\n\tsecond\n   \nBag-Size: 1 KB\n");
+        assert_eq!(info.get("External-Description"), Some("first second"));
+        assert_eq!(info.get("Bag-Size"), Some("1 KB"));
+        assert_eq!(info.fields().len(), 2);
+    }
+
+    #[test]
+    fn test_write_never_emits_bom() {
+        let path = std::env::temp_dir().join("bagit_test_tagfile_bom.txt");
+        write_tag_file(&path, "\u{FEFF}Contact-Name: Someone\n").unwrap();
+        let bytes = fs::read(&path).unwrap();
+        assert!(!bytes.starts_with(UTF8_BOM));
+        fs::remove_file(&path).unwrap();
+    }
+}
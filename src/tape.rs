@@ -0,0 +1,575 @@
+use crate::bagit::{BagError, Progress};
+use crate::events::{append_events, Event, EventType};
+use std::fs::{self, File};
+use std::io::{self, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::Sender;
+use std::time::UNIX_EPOCH;
+use walkdir::WalkDir;
+
+/// Tag file, added as the last member of the last segment, listing where every member's
+/// data starts. A copy is also written next to the segments for the tape catalogue.
+pub const TAPE_INDEX_FILE: &str = "tape-index.txt";
+
+/// Tar's record size: every header and every member's data is padded to it.
+const RECORD: u64 = 512;
+
+/// 256 KiB, a common LTO block size.
+pub const DEFAULT_TAPE_BLOCK_SIZE: u64 = 256 * 1024;
+
+/// How a bag is written out for tape.
+#[derive(Debug, Clone)]
+pub struct TapeOptions {
+    /// Bytes per write; each segment is padded to a whole number of blocks. A
+    /// multiple of 512.
+    pub block_size: u64,
+    /// Largest size of one segment (tar file); `None` writes a single one.
+    pub segment_size: Option<u64>,
+}
+
+impl Default for TapeOptions {
+    fn default() -> Self {
+        Self {
+            block_size: DEFAULT_TAPE_BLOCK_SIZE,
+            segment_size: None,
+        }
+    }
+}
+
+/// Reads a size such as `262144`, `256KiB` or `18T`. `K`, `M`, `G` and `T` are powers
+/// of 1000, as tape capacities are quoted; `KiB` and the rest are powers of 1024.
+pub fn parse_size(text: &str) -> Option<u64> {
+    let text = text.trim();
+    let split = text
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(text.len());
+    let (number, unit) = text.split_at(split);
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" => 1000,
+        "M" | "MB" => 1000u64.pow(2),
+        "G" | "GB" => 1000u64.pow(3),
+        "T" | "TB" => 1000u64.pow(4),
+        "KIB" => 1 << 10,
+        "MIB" => 1 << 20,
+        "GIB" => 1 << 30,
+        "TIB" => 1 << 40,
+        _ => return None,
+    };
+    number.parse::<u64>().ok()?.checked_mul(multiplier)
+}
+
+/// A file going into the archive.
+pub struct Member {
+    pub source: PathBuf,
+    /// Path inside the archive: the bag's folder name, then the path in the bag.
+    pub name: String,
+    pub size: u64,
+    pub mtime: u64,
+}
+
+/// Bytes a member takes: its header(s), then its data padded to whole records.
+fn member_length(member: &Member) -> u64 {
+    header_bytes(&member.name, member.size, member.mtime).len() as u64
+        + member.size.div_ceil(RECORD) * RECORD
+}
+
+/// The fixed order members are written in: bagit.txt, the other tag files, then
+/// the payload, each sorted by path.
+pub fn collect_members(bag: &Path) -> Result<Vec<Member>, BagError> {
+    let bag_name = bag
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_else(|| "bag".to_string());
+    let mut members = Vec::new();
+    for entry in WalkDir::new(bag).into_iter() {
+        let entry = entry.map_err(io::Error::from)?;
+        if !entry.file_type().is_file() {
+            continue;
+        }
+        let relative = entry.path().strip_prefix(bag).unwrap();
+        let relative = relative.to_string_lossy().replace('\\', "/");
+        let metadata = entry.metadata().map_err(io::Error::from)?;
+        let mtime = metadata
+            .modified()
+            .ok()
+            .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+            .map_or(0, |d| d.as_secs());
+        members.push(Member {
+            source: entry.path().to_path_buf(),
+            name: format!("{}/{}", bag_name, relative),
+            size: metadata.len(),
+            mtime,
+        });
+    }
+    let rank = |m: &Member| {
+        let relative = m.name.split_once('/').map_or("", |(_, r)| r);
+        if relative == "bagit.txt" {
+            0
+        } else if relative.starts_with("data/") {
+            2
+        } else {
+            1
+        }
+    };
+    members.sort_by(|a, b| rank(a).cmp(&rank(b)).then_with(|| a.name.cmp(&b.name)));
+    Ok(members)
+}
+
+/// Writes `value` as a zero-padded octal field of `width` bytes ending in NUL.
+fn octal(field: &mut [u8], value: u64) {
+    let width = field.len() - 1;
+    let text = format!("{:0width$o}", value, width = width);
+    field[..width].copy_from_slice(text.as_bytes());
+    field[width] = 0;
+}
+
+/// A ustar header block for `name`.
+fn ustar_header(name: &[u8], prefix: &[u8], size: u64, mtime: u64, kind: u8) -> [u8; 512] {
+    let mut header = [0u8; 512];
+    header[..name.len()].copy_from_slice(name);
+    octal(&mut header[100..108], 0o644);
+    octal(&mut header[108..116], 0);
+    octal(&mut header[116..124], 0);
+    octal(&mut header[124..136], size);
+    octal(&mut header[136..148], mtime);
+    header[156] = kind;
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+    header[345..345 + prefix.len()].copy_from_slice(prefix);
+    // The checksum is summed with its own field as spaces
+    header[148..156].copy_from_slice(b"        ");
+    let sum: u64 = header.iter().map(|&b| u64::from(b)).sum();
+    header[148..154].copy_from_slice(format!("{:06o}", sum).as_bytes());
+    header[154] = 0;
+    header
+}
+
+/// One `<length> <key>=<value>\n` pax record; the length counts itself.
+fn pax_record(key: &str, value: &str) -> String {
+    let body = format!(" {}={}\n", key, value);
+    let mut length = body.len() + 1;
+    while length.to_string().len() + body.len() != length {
+        length += 1;
+    }
+    format!("{}{}", length, body)
+}
+
+/// Splits `name` into ustar's prefix and name fields, if it fits.
+fn split_name(name: &str) -> Option<(&str, &str)> {
+    if name.len() <= 100 {
+        return Some(("", name));
+    }
+    name.char_indices()
+        .filter(|&(i, c)| c == '/' && i <= 155 && name.len() - i - 1 <= 100)
+        .map(|(i, _)| (&name[..i], &name[i + 1..]))
+        .next()
+}
+
+/// The header blocks for a file: a plain ustar header, preceded by a pax extended
+/// header when the name or size doesn't fit ustar's fields.
+fn header_bytes(name: &str, size: u64, mtime: u64) -> Vec<u8> {
+    const MAX_USTAR_SIZE: u64 = 0o77777777777;
+    let split = split_name(name);
+    let mut records = String::new();
+    if split.is_none() {
+        records.push_str(&pax_record("path", name));
+    }
+    if size > MAX_USTAR_SIZE {
+        records.push_str(&pax_record("size", &size.to_string()));
+    }
+
+    let mut bytes = Vec::new();
+    if !records.is_empty() {
+        let pax_name = format!("PaxHeaders/{}", name.rsplit('/').next().unwrap_or(name));
+        let pax_name = &pax_name.as_bytes()[..pax_name.len().min(100)];
+        bytes.extend(ustar_header(
+            pax_name,
+            b"",
+            records.len() as u64,
+            mtime,
+            b'x',
+        ));
+        bytes.extend(records.as_bytes());
+        bytes.resize(bytes.len().div_ceil(RECORD as usize) * RECORD as usize, 0);
+    }
+    // With a pax path, the ustar name is only a fallback for old readers
+    let (prefix, short) = match split {
+        Some((prefix, short)) => (prefix.as_bytes(), short.as_bytes()),
+        None => (&b""[..], &name.as_bytes()[name.len() - 100..]),
+    };
+    bytes.extend(ustar_header(
+        short,
+        prefix,
+        size.min(MAX_USTAR_SIZE),
+        mtime,
+        b'0',
+    ));
+    bytes
+}
+
+/// Writes in whole blocks and counts what went out.
+struct BlockWriter {
+    out: BufWriter<File>,
+    block_size: u64,
+    written: u64,
+}
+
+impl BlockWriter {
+    fn create(path: &Path, block_size: u64) -> io::Result<Self> {
+        Ok(Self {
+            out: BufWriter::with_capacity(block_size as usize, File::create(path)?),
+            block_size,
+            written: 0,
+        })
+    }
+
+    fn write(&mut self, bytes: &[u8]) -> io::Result<()> {
+        self.out.write_all(bytes)?;
+        self.written += bytes.len() as u64;
+        Ok(())
+    }
+
+    fn pad_to(&mut self, multiple: u64) -> io::Result<()> {
+        let padding = self.written.div_ceil(multiple) * multiple - self.written;
+        self.write(&vec![0u8; padding as usize])
+    }
+
+    /// Ends the archive with two zero records, padded to a whole block.
+    fn finish(mut self) -> io::Result<()> {
+        self.write(&[0u8; 2 * RECORD as usize])?;
+        self.pad_to(self.block_size)?;
+        self.out.flush()
+    }
+}
+
+/// Bytes an archive ends with: two zero records, then padding to a whole block.
+fn closing_length(written: u64, block_size: u64) -> u64 {
+    (written + 2 * RECORD).div_ceil(block_size) * block_size - written
+}
+
+/// Bytes `write_tar` writes for `members`.
+pub fn tar_length(members: &[Member]) -> u64 {
+    let written = members.iter().map(member_length).sum();
+    written + closing_length(written, 20 * RECORD)
+}
+
+/// Writes `members`, from [`collect_members`], to `out` as a single tar stream, padded
+/// to tar's usual 10 KiB.
+pub fn write_tar(
+    members: &[Member],
+    out: &mut impl Write,
+    progress_tx: &Option<Sender<Progress>>,
+) -> Result<(), BagError> {
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started {
+            total_files: members.len(),
+        });
+    }
+    let mut written = 0;
+    for (i, member) in members.iter().enumerate() {
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Copying {
+                current: i + 1,
+                filename: member.name.clone(),
+            });
+        }
+        let header = header_bytes(&member.name, member.size, member.mtime);
+        out.write_all(&header)?;
+        let copied = io::copy(&mut File::open(&member.source)?.take(member.size), out)?;
+        if copied != member.size {
+            return Err(BagError::FileVanished(member.source.clone()));
+        }
+        let padding = member.size.div_ceil(RECORD) * RECORD - member.size;
+        out.write_all(&vec![0u8; padding as usize])?;
+        written += header.len() as u64 + copied + padding;
+    }
+    out.write_all(&vec![0u8; closing_length(written, 20 * RECORD) as usize])?;
+    Ok(())
+}
+
+/// Writes the bag at `bag` into `dest` as uncompressed tar segments for tape, and
+/// returns their paths. Members are written in a fixed order and never split across
+/// segments; the last segment ends with `tape-index.txt`, which gives the segment,
+/// data offset and size of every member.
+pub fn write_tape(
+    bag: &Path,
+    dest: &Path,
+    options: &TapeOptions,
+    progress_tx: Option<Sender<Progress>>,
+) -> Result<Vec<PathBuf>, BagError> {
+    if !bag.join("bagit.txt").is_file() {
+        return Err(BagError::NotABag);
+    }
+    if options.block_size == 0 || !options.block_size.is_multiple_of(RECORD) {
+        return Err(BagError::BadTapeBlockSize(options.block_size));
+    }
+    append_events(
+        bag,
+        &[Event::now(
+            EventType::Packing,
+            format!(
+                "Written as tar for tape to {}, in {}-byte blocks",
+                dest.display(),
+                options.block_size
+            ),
+        )],
+    )?;
+    let members = collect_members(bag)?;
+    let bag_name = members
+        .first()
+        .and_then(|m| m.name.split('/').next())
+        .unwrap_or("bag")
+        .to_string();
+
+    // Plan the segments: member i goes in segments[i] at offsets[i]
+    let capacity = options.segment_size.unwrap_or(u64::MAX);
+    let fits = |used: u64, length: u64| {
+        used.checked_add(length)
+            .map(|end| end + closing_length(end, options.block_size) <= capacity)
+            .unwrap_or(false)
+    };
+    let mut placement = Vec::new();
+    let mut segment = 0;
+    let mut used = 0;
+    for member in &members {
+        let length = member_length(member);
+        if !fits(0, length) {
+            return Err(BagError::FileTooLargeForVolume(PathBuf::from(&member.name)));
+        }
+        if !fits(used, length) {
+            segment += 1;
+            used = 0;
+        }
+        let data_offset = used + header_bytes(&member.name, member.size, member.mtime).len() as u64;
+        placement.push((segment, data_offset));
+        used += length;
+    }
+
+    // The index names segments by file, so their count must be known first; adding the
+    // index itself may need one more segment.
+    let index_name = format!("{}/{}", bag_name, TAPE_INDEX_FILE);
+    let segment_name = |i: usize, count: usize| {
+        if count == 1 {
+            format!("{}.tar", bag_name)
+        } else {
+            format!("{}.part{:03}.tar", bag_name, i + 1)
+        }
+    };
+    let index_text = |count: usize| {
+        let mut text = format!(
+            "# segment  data offset  size  member (block size {})\n",
+            options.block_size
+        );
+        for (member, (segment, offset)) in members.iter().zip(&placement) {
+            text.push_str(&format!(
+                "{}  {}  {}  {}\n",
+                segment_name(*segment, count),
+                offset,
+                member.size,
+                member.name
+            ));
+        }
+        text
+    };
+    let mut count = segment + 1;
+    let index_length = |count: usize| {
+        let size = index_text(count).len() as u64;
+        header_bytes(&index_name, size, 0).len() as u64 + size.div_ceil(RECORD) * RECORD
+    };
+    if !fits(used, index_length(count)) {
+        count += 1;
+        if !fits(0, index_length(count)) {
+            return Err(BagError::FileTooLargeForVolume(PathBuf::from(&index_name)));
+        }
+    }
+    let index = index_text(count);
+
+    fs::create_dir_all(dest)?;
+    if let Some(ref tx) = progress_tx {
+        let _ = tx.send(Progress::Started {
+            total_files: members.len(),
+        });
+    }
+    let paths: Vec<PathBuf> = (0..count)
+        .map(|i| dest.join(segment_name(i, count)))
+        .collect();
+    let mut writer = BlockWriter::create(&paths[0], options.block_size)?;
+    let mut current = 0;
+    for (i, (member, (segment, _))) in members.iter().zip(&placement).enumerate() {
+        if *segment != current {
+            writer.finish()?;
+            current = *segment;
+            writer = BlockWriter::create(&paths[current], options.block_size)?;
+        }
+        if let Some(ref tx) = progress_tx {
+            let _ = tx.send(Progress::Copying {
+                current: i + 1,
+                filename: member.name.clone(),
+            });
+        }
+        writer.write(&header_bytes(&member.name, member.size, member.mtime))?;
+        let mut file = File::open(&member.source)?.take(member.size);
+        let copied = io::copy(&mut file, &mut writer.out)?;
+        writer.written += copied;
+        if copied != member.size {
+            return Err(BagError::FileVanished(member.source.clone()));
+        }
+        writer.pad_to(RECORD)?;
+    }
+    if current + 1 < count {
+        writer.finish()?;
+        writer = BlockWriter::create(&paths[count - 1], options.block_size)?;
+    }
+    writer.write(&header_bytes(&index_name, index.len() as u64, 0))?;
+    writer.write(index.as_bytes())?;
+    writer.pad_to(RECORD)?;
+    writer.finish()?;
+
+    fs::write(
+        dest.join(format!("{}.{}", bag_name, TAPE_INDEX_FILE)),
+        &index,
+    )?;
+    Ok(paths)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{bag_directory, BagOptions};
+
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("262144"), Some(262144));
+        assert_eq!(parse_size("256KiB"), Some(262144));
+        assert_eq!(parse_size("18T"), Some(18_000_000_000_000));
+        assert_eq!(parse_size("2 GB"), Some(2_000_000_000));
+        assert_eq!(parse_size("lots"), None);
+    }
+
+    #[test]
+    fn test_headers() {
+        let header = header_bytes("bag/data/file.txt", 13, 0);
+        assert_eq!(header.len(), 512);
+        assert_eq!(&header[..17], b"bag/data/file.txt");
+        assert_eq!(&header[124..136], b"00000000015\0");
+
+        let deep = format!("bag/data/{}/file.txt", "d".repeat(120));
+        let (prefix, name) = split_name(&deep).unwrap();
+        assert_eq!(format!("{}/{}", prefix, name), deep);
+
+        // Too long for ustar: a pax header carries the full path
+        let long = format!("bag/data/{}", "n".repeat(300));
+        let header = header_bytes(&long, 1, 0);
+        assert_eq!(header.len(), 3 * 512);
+        assert_eq!(header[156], b'x');
+        let record = pax_record("path", &long);
+        assert!(record.starts_with(&format!("{} path=", record.len())));
+    }
+
+    /// Reads back every member of a tar file as (name, data offset, contents).
+    fn read_tar(bytes: &[u8]) -> Vec<(String, u64, Vec<u8>)> {
+        let mut members = Vec::new();
+        let mut offset = 0;
+        let mut long_name = None;
+        while offset + 512 <= bytes.len() && bytes[offset] != 0 {
+            let header = &bytes[offset..offset + 512];
+            let field = |range: std::ops::Range<usize>| {
+                String::from_utf8_lossy(&header[range])
+                    .trim_end_matches('\0')
+                    .to_string()
+            };
+            let size = u64::from_str_radix(&field(124..135), 8).unwrap();
+            let start = offset + 512;
+            let data = bytes[start..start + size as usize].to_vec();
+            offset = start + (size as usize).div_ceil(512) * 512;
+            if header[156] == b'x' {
+                let text = String::from_utf8(data).unwrap();
+                long_name = text
+                    .split_once(" path=")
+                    .map(|(_, rest)| rest.trim_end().to_string());
+                continue;
+            }
+            let prefix = field(345..500);
+            let name = long_name.take().unwrap_or_else(|| {
+                if prefix.is_empty() {
+                    field(0..100)
+                } else {
+                    format!("{}/{}", prefix, field(0..100))
+                }
+            });
+            members.push((name, start as u64, data));
+        }
+        members
+    }
+
+    #[test]
+    fn test_write_tape_segments() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_tape");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("reel");
+        fs::create_dir_all(bag.join("b")).unwrap();
+        fs::write(bag.join("a.bin"), vec![7u8; 3000]).unwrap();
+        fs::write(bag.join("b/c.bin"), vec![9u8; 3000]).unwrap();
+        fs::write(bag.join(format!("{}.txt", "long".repeat(40))), "x").unwrap();
+        bag_directory(&bag, &BagOptions::default(), None).unwrap();
+
+        // One segment, checked member by member against the index
+        let out = temp_dir.join("one");
+        let options = TapeOptions {
+            block_size: 10240,
+            segment_size: None,
+        };
+        let paths = write_tape(&bag, &out, &options, None).unwrap();
+        assert_eq!(paths, vec![out.join("reel.tar")]);
+        let bytes = fs::read(&paths[0]).unwrap();
+        assert_eq!(bytes.len() % 10240, 0);
+        let members = read_tar(&bytes);
+        assert_eq!(members[0].0, "reel/bagit.txt");
+        assert!(members[1].0.ends_with(".txt") && !members[1].0.contains("/data/"));
+        assert_eq!(members.last().unwrap().0, "reel/tape-index.txt");
+        let index = String::from_utf8(members.last().unwrap().2.clone()).unwrap();
+        assert_eq!(
+            index,
+            fs::read_to_string(out.join("reel.tape-index.txt")).unwrap()
+        );
+        for line in index.lines().skip(1) {
+            let parts: Vec<&str> = line.splitn(4, "  ").collect();
+            let member = members.iter().find(|m| m.0 == parts[3]).unwrap();
+            assert_eq!(parts[1], member.1.to_string());
+            assert_eq!(parts[2], member.2.len().to_string());
+            assert_eq!(
+                member.2,
+                fs::read(bag.join(parts[3].strip_prefix("reel/").unwrap())).unwrap()
+            );
+        }
+
+        // Segments small enough that each payload file needs its own
+        let out = temp_dir.join("split");
+        let options = TapeOptions {
+            block_size: 512,
+            segment_size: Some(6 * 1024),
+        };
+        let paths = write_tape(&bag, &out, &options, None).unwrap();
+        assert!(paths.len() > 2);
+        assert!(paths[0].ends_with("reel.part001.tar"));
+        for path in &paths {
+            assert!(fs::metadata(path).unwrap().len() <= 6 * 1024);
+        }
+        let index = fs::read_to_string(out.join("reel.tape-index.txt")).unwrap();
+        assert!(index.contains("reel.part002.tar"));
+
+        let options = TapeOptions {
+            block_size: 1000,
+            segment_size: None,
+        };
+        assert!(matches!(
+            write_tape(&bag, &temp_dir.join("bad"), &options, None),
+            Err(BagError::BadTapeBlockSize(1000))
+        ));
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}
@@ -0,0 +1,106 @@
+use crate::bagit::{calculate_digest, BagError, Mismatch};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+/// What another copy of a mismatched file says about it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CopyComparison {
+    /// The copy has the checksum the manifest expects, so it can be restored from.
+    Matches(PathBuf),
+    /// The copy exists but is damaged too (or was changed the same way).
+    Differs { path: PathBuf, actual: String },
+    /// Neither layout of the copy has the file.
+    Missing,
+}
+
+/// Hashes the file again; `true` if it now matches (a transient read error, most likely).
+pub fn rehash(bag: &Path, mismatch: &Mismatch) -> io::Result<bool> {
+    Ok(calculate_digest(&bag.join(&mismatch.file), mismatch.algorithm)? == mismatch.expected)
+}
+
+/// Looks the file up in another copy: either a copy of the bag (same path) or a copy
+/// of the original folder (path without the leading `data/`).
+pub fn compare_with_copy(copy: &Path, mismatch: &Mismatch) -> io::Result<CopyComparison> {
+    let candidates = [
+        Some(copy.join(&mismatch.file)),
+        mismatch
+            .file
+            .strip_prefix("data/")
+            .map(|rest| copy.join(rest)),
+    ];
+    for candidate in candidates.into_iter().flatten() {
+        if candidate.is_file() {
+            let actual = calculate_digest(&candidate, mismatch.algorithm)?;
+            return Ok(if actual == mismatch.expected {
+                CopyComparison::Matches(candidate)
+            } else {
+                CopyComparison::Differs {
+                    path: candidate,
+                    actual,
+                }
+            });
+        }
+    }
+    Ok(CopyComparison::Missing)
+}
+
+/// Replaces the damaged file with `replacement`, but only if the replacement has the
+/// checksum the manifest expects; the bag's tag files stay as they are.
+pub fn restore(bag: &Path, mismatch: &Mismatch, replacement: &Path) -> Result<(), BagError> {
+    if calculate_digest(replacement, mismatch.algorithm)? != mismatch.expected {
+        return Err(BagError::ReplacementMismatch(replacement.to_path_buf()));
+    }
+    fs::copy(replacement, bag.join(&mismatch.file))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bagit::{bag_directory, validate_bag, BagOptions, ValidationOptions};
+
+    #[test]
+    fn test_triage_compare_and_restore() {
+        let temp_dir = std::env::temp_dir().join("bagit_test_triage");
+        if temp_dir.exists() {
+            fs::remove_dir_all(&temp_dir).unwrap();
+        }
+        let bag = temp_dir.join("bag");
+        let backup = temp_dir.join("backup");
+        fs::create_dir_all(&bag).unwrap();
+        fs::create_dir_all(&backup).unwrap();
+        fs::write(bag.join("photo.tif"), "original").unwrap();
+        fs::write(backup.join("photo.tif"), "original").unwrap();
+        bag_directory(&bag, &BagOptions::default(), None).unwrap();
+
+        fs::write(bag.join("data/photo.tif"), "bit rot").unwrap();
+        let report = validate_bag(&bag, &ValidationOptions::default(), None).unwrap();
+        assert_eq!(report.mismatches.len(), 1);
+        let mismatch = &report.mismatches[0];
+        assert_eq!(mismatch.file, "data/photo.tif");
+        assert!(!rehash(&bag, mismatch).unwrap());
+
+        // The backup is a copy of the original folder, so the file is found without data/
+        let comparison = compare_with_copy(&backup, mismatch).unwrap();
+        assert_eq!(
+            comparison,
+            CopyComparison::Matches(backup.join("photo.tif"))
+        );
+        assert_eq!(
+            compare_with_copy(&temp_dir, mismatch).unwrap(),
+            CopyComparison::Missing
+        );
+
+        // A wrong replacement is refused; the right one fixes the bag
+        fs::write(temp_dir.join("wrong.tif"), "something else").unwrap();
+        let result = restore(&bag, mismatch, &temp_dir.join("wrong.tif"));
+        assert!(matches!(result, Err(BagError::ReplacementMismatch(_))));
+        restore(&bag, mismatch, &backup.join("photo.tif")).unwrap();
+        assert!(rehash(&bag, mismatch).unwrap());
+        let report = validate_bag(&bag, &ValidationOptions::default(), None).unwrap();
+        assert!(report.is_valid(), "{:?}", report.errors);
+
+        fs::remove_dir_all(&temp_dir).unwrap();
+    }
+}